@@ -51,6 +51,7 @@ pub enum Code {
     IndexAlreadyExists,
     IndexNotFound,
     InvalidIndexUid,
+    IndexUidTooLong,
     OpenIndex,
 
     // invalid state error
@@ -69,6 +70,7 @@ pub enum Code {
     DocumentNotFound,
     Internal,
     InvalidToken,
+    IpNotAllowed,
     MissingAuthorizationHeader,
     NotFound,
     PayloadTooLarge,
@@ -78,6 +80,18 @@ pub enum Code {
 
     DumpAlreadyInProgress,
     DumpProcessFailed,
+
+    ReplicationDisabled,
+    ReplicationLogTruncated,
+    ReadOnlyReplica,
+
+    UpdateNotFound,
+    TaskNotFound,
+    ShuttingDown,
+    Unavailable,
+    TooManyPendingUpdates,
+    NotEnoughDiskSpace,
+    TooManySearchRequests,
 }
 
 impl Code {
@@ -93,6 +107,7 @@ impl Code {
             // thrown when requesting an unexisting index
             IndexNotFound => ErrCode::invalid("index_not_found", StatusCode::NOT_FOUND),
             InvalidIndexUid => ErrCode::invalid("invalid_index_uid", StatusCode::BAD_REQUEST),
+            IndexUidTooLong => ErrCode::invalid("index_uid_too_long", StatusCode::BAD_REQUEST),
             OpenIndex => {
                 ErrCode::internal("index_not_accessible", StatusCode::INTERNAL_SERVER_ERROR)
             }
@@ -122,6 +137,8 @@ impl Code {
             DocumentNotFound => ErrCode::invalid("document_not_found", StatusCode::NOT_FOUND),
             Internal => ErrCode::internal("internal", StatusCode::INTERNAL_SERVER_ERROR),
             InvalidToken => ErrCode::authentication("invalid_token", StatusCode::FORBIDDEN),
+            // thrown when an administrative route is called from outside the IP allowlist
+            IpNotAllowed => ErrCode::authentication("ip_not_allowed", StatusCode::FORBIDDEN),
             MissingAuthorizationHeader => {
                 ErrCode::authentication("missing_authorization_header", StatusCode::UNAUTHORIZED)
             }
@@ -142,6 +159,33 @@ impl Code {
             DumpProcessFailed => {
                 ErrCode::internal("dump_process_failed", StatusCode::INTERNAL_SERVER_ERROR)
             }
+
+            // error related to replication
+            ReplicationDisabled => ErrCode::invalid("replication_disabled", StatusCode::NOT_FOUND),
+            // thrown when a replica asks for log entries that were removed by the primary
+            ReplicationLogTruncated => {
+                ErrCode::invalid("replication_log_truncated", StatusCode::GONE)
+            }
+            // thrown when an update is sent to a read replica rather than to its primary
+            ReadOnlyReplica => ErrCode::invalid("read_only_replica", StatusCode::FORBIDDEN),
+
+            // error related to updates
+            UpdateNotFound => ErrCode::invalid("update_not_found", StatusCode::NOT_FOUND),
+            TaskNotFound => ErrCode::invalid("task_not_found", StatusCode::NOT_FOUND),
+            TooManyPendingUpdates => {
+                ErrCode::invalid("too_many_pending_updates", StatusCode::TOO_MANY_REQUESTS)
+            }
+            // thrown when the free disk space is below `--min-free-disk-space`
+            NotEnoughDiskSpace => {
+                ErrCode::internal("not_enough_disk_space", StatusCode::INSUFFICIENT_STORAGE)
+            }
+            ShuttingDown => ErrCode::internal("shutting_down", StatusCode::SERVICE_UNAVAILABLE),
+            // thrown when `--search-queue-size` searches are already waiting to be executed
+            TooManySearchRequests => {
+                ErrCode::internal("too_many_search_requests", StatusCode::SERVICE_UNAVAILABLE)
+            }
+            // thrown when an actor crashed, the request can be retried once it is restarted
+            Unavailable => ErrCode::internal("unavailable", StatusCode::SERVICE_UNAVAILABLE),
         }
     }
 
@@ -1,137 +1,343 @@
-use std::hash::{Hash, Hasher};
-use std::{error, thread};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use log::error;
+use log::{debug, error};
 use serde::Serialize;
-use serde_qs as qs;
-use siphasher::sip::SipHasher;
-use walkdir::WalkDir;
+use serde_json::json;
+use tokio::sync::mpsc;
+use uuid::Uuid;
 
-use crate::Data;
-use crate::Opt;
+use crate::index_controller::IndexController;
+use crate::option::Opt;
 
 const AMPLITUDE_API_KEY: &str = "f7fba398780e06d8fe6666a9be7e3d47";
+/// The default analytics endpoint, the only one the API key is sent to.
+const AMPLITUDE_ENDPOINT: &str = "https://api2.amplitude.com/2/httpapi";
+/// The file, in the database directory, holding the anonymous identifier of the instance.
+const INSTANCE_UID_FILE: &str = "instance-uid";
+const REPORT_INTERVAL: Duration = Duration::from_secs(3600); // one hour
+/// Route usage events are dropped rather than waited on when that many are still to be handled.
+const EVENT_QUEUE_SIZE: usize = 1024;
+
+pub(crate) enum AnalyticsMsg {
+    RouteUsed { method: String, route: String },
+}
+
+/// A handle to the analytics actor.
+///
+/// Sending an event never waits: when analytics are disabled the event is ignored, and when the
+/// actor lags behind, it is dropped.
+#[derive(Clone)]
+pub struct Analytics {
+    sender: Option<mpsc::Sender<AnalyticsMsg>>,
+}
+
+impl Analytics {
+    pub fn disabled() -> Self {
+        Self { sender: None }
+    }
+
+    /// Records a call to `route`, the pattern of the matched route rather than the requested
+    /// path, so that no index name or document id is ever reported.
+    pub fn route_used(&self, method: &str, route: &str) {
+        if let Some(ref sender) = self.sender {
+            let msg = AnalyticsMsg::RouteUsed {
+                method: method.to_string(),
+                route: route.to_string(),
+            };
+            let _ = sender.try_send(msg);
+        }
+    }
+}
 
 #[derive(Debug, Serialize)]
 struct EventProperties {
     database_size: u64,
-    last_update_timestamp: Option<i64>, //timestamp
+    last_update_timestamp: Option<i64>,
+    number_of_indexes: usize,
     number_of_documents: Vec<u64>,
+    route_usage: BTreeMap<String, u64>,
 }
 
-impl EventProperties {
-    fn from(data: Data) -> Result<EventProperties, Box<dyn error::Error>> {
-        let mut index_list = Vec::new();
+#[derive(Debug, Serialize)]
+struct UserProperties {
+    env: String,
+    start_since_days: u64,
+}
 
-        let reader = data.db.main_read_txn()?;
+#[derive(Debug, Serialize)]
+struct Event {
+    user_id: String,
+    event_type: &'static str,
+    time: u64,
+    app_version: &'static str,
+    user_properties: UserProperties,
+    event_properties: Option<EventProperties>,
+}
+
+/// The actor only holds the index controller to gather the stats, and not the `Data` holding
+/// the sender of its events, so that it stops once the `Data` is dropped.
+struct AnalyticsActor {
+    receiver: mpsc::Receiver<AnalyticsMsg>,
+    index_controller: IndexController,
+    env: String,
+    endpoint: String,
+    instance_uid: String,
+    first_start: Instant,
+    /// The number of calls to each route since the last report.
+    route_usage: BTreeMap<String, u64>,
+}
 
-        for index_uid in data.db.indexes_uids() {
-            if let Some(index) = data.db.open_index(&index_uid) {
-                let number_of_documents = index.main.number_of_documents(&reader)?;
-                index_list.push(number_of_documents);
+impl AnalyticsActor {
+    async fn run(mut self) {
+        let mut interval = tokio::time::interval(REPORT_INTERVAL);
+        loop {
+            tokio::select! {
+                msg = self.receiver.recv() => match msg {
+                    Some(msg) => self.handle_message(msg),
+                    None => break,
+                },
+                _ = interval.tick() => self.report().await,
             }
         }
+    }
 
-        let database_size = WalkDir::new(&data.db_path)
-            .into_iter()
-            .filter_map(|entry| entry.ok())
-            .filter_map(|entry| entry.metadata().ok())
-            .filter(|metadata| metadata.is_file())
-            .fold(0, |acc, m| acc + m.len());
+    fn handle_message(&mut self, msg: AnalyticsMsg) {
+        match msg {
+            AnalyticsMsg::RouteUsed { method, route } => {
+                *self
+                    .route_usage
+                    .entry(format!("{} {}", method, route))
+                    .or_default() += 1;
+            }
+        }
+    }
 
-        let last_update_timestamp = data.db.last_update(&reader)?.map(|u| u.timestamp());
+    async fn report(&mut self) {
+        let route_usage = std::mem::take(&mut self.route_usage);
+        let event_properties = match self.index_controller.get_all_stats(None).await {
+            Ok(stats) => Some(EventProperties {
+                database_size: stats.database_size,
+                last_update_timestamp: stats.last_update.map(|u| u.timestamp()),
+                number_of_indexes: stats.indexes.len(),
+                number_of_documents: stats
+                    .indexes
+                    .values()
+                    .map(|index| index.number_of_documents)
+                    .collect(),
+                route_usage,
+            }),
+            Err(e) => {
+                debug!("Could not gather the analytics stats: {}", e);
+                None
+            }
+        };
+
+        let event = Event {
+            user_id: self.instance_uid.clone(),
+            event_type: "runtime_tick",
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            app_version: env!("CARGO_PKG_VERSION"),
+            user_properties: UserProperties {
+                env: self.env.clone(),
+                start_since_days: self.first_start.elapsed().as_secs() / 86_400, // one day
+            },
+            event_properties,
+        };
 
-        Ok(EventProperties {
-            database_size,
-            last_update_timestamp,
-            number_of_documents: index_list,
-        })
+        let mut body = json!({ "events": [event] });
+        if self.endpoint == AMPLITUDE_ENDPOINT {
+            body["api_key"] = json!(AMPLITUDE_API_KEY);
+        }
+
+        // The request is sent on the blocking pool and never awaited, so that a slow endpoint
+        // can't delay the handling of the events.
+        let endpoint = self.endpoint.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = ureq::post(&endpoint).send_json(body) {
+                error!("Unsuccessful call to the analytics endpoint: {}", e);
+            }
+        });
     }
 }
 
-#[derive(Debug, Serialize)]
-struct UserProperties<'a> {
-    env: &'a str,
-    start_since_days: u64,
-    user_email: Option<String>,
-    server_provider: Option<String>,
+/// Reads the anonymous identifier of the instance, generating it on the first run.
+fn instance_uid(db_path: &Path) -> String {
+    let path = db_path.join(INSTANCE_UID_FILE);
+    match fs::read_to_string(&path) {
+        Ok(uid) if Uuid::parse_str(uid.trim()).is_ok() => uid.trim().to_string(),
+        _ => {
+            let uid = Uuid::new_v4().to_string();
+            if let Err(e) = fs::write(&path, &uid) {
+                debug!("Could not persist the instance uid: {}", e);
+            }
+            uid
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct Event<'a> {
-    user_id: &'a str,
-    event_type: &'a str,
-    device_id: &'a str,
-    time: u64,
-    app_version: &'a str,
-    user_properties: UserProperties<'a>,
-    event_properties: Option<EventProperties>,
+/// Creates the analytics handle for the given options. The actor itself is only started by
+/// `spawn`, once the index controller it reports on exists.
+pub(crate) fn create(no_analytics: bool) -> (Analytics, Option<mpsc::Receiver<AnalyticsMsg>>) {
+    if no_analytics {
+        (Analytics::disabled(), None)
+    } else {
+        let (sender, receiver) = mpsc::channel(EVENT_QUEUE_SIZE);
+        let analytics = Analytics {
+            sender: Some(sender),
+        };
+        (analytics, Some(receiver))
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct AmplitudeRequest<'a> {
-    api_key: &'a str,
-    event: &'a str,
+/// Starts the analytics actor, it stops once all the `Analytics` handles are dropped.
+pub(crate) fn spawn(
+    receiver: mpsc::Receiver<AnalyticsMsg>,
+    index_controller: IndexController,
+    options: &Opt,
+) {
+    let actor = AnalyticsActor {
+        receiver,
+        index_controller,
+        env: options.env.clone(),
+        endpoint: options.analytics_endpoint.clone(),
+        instance_uid: instance_uid(&options.db_path),
+        first_start: Instant::now(),
+        route_usage: BTreeMap::new(),
+    };
+    tokio::task::spawn(actor.run());
 }
 
-pub fn analytics_sender(data: Data, opt: Opt) {
-    let username = whoami::username();
-    let hostname = whoami::hostname();
-    let platform = whoami::platform();
-
-    let uid = username + &hostname + &platform.to_string();
-
-    let mut hasher = SipHasher::new();
-    uid.hash(&mut hasher);
-    let hash = hasher.finish();
-
-    let uid = format!("{:X}", hash);
-    let platform = platform.to_string();
-    let first_start = Instant::now();
-
-    loop {
-        let n = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        let user_id = &uid;
-        let device_id = &platform;
-        let time = n.as_secs();
-        let event_type = "runtime_tick";
-        let elapsed_since_start = first_start.elapsed().as_secs() / 86_400; // One day
-        let event_properties = EventProperties::from(data.clone()).ok();
-        let app_version = env!("CARGO_PKG_VERSION").to_string();
-        let app_version = app_version.as_str();
-        let user_email = std::env::var("MEILI_USER_EMAIL").ok();
-        let server_provider = std::env::var("MEILI_SERVER_PROVIDER").ok();
-        let user_properties = UserProperties {
-            env: &opt.env,
-            start_since_days: elapsed_since_start,
-            user_email,
-            server_provider,
-        };
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
 
-        let event = Event {
-            user_id,
-            event_type,
-            device_id,
-            time,
-            app_version,
-            user_properties,
-            event_properties
+    use structopt::StructOpt;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn actor(dir: &Path, endpoint: &str) -> (Analytics, AnalyticsActor) {
+        let db_path = dir.join("data.ms");
+        let args = [
+            "meilisearch",
+            "--db-path",
+            db_path.to_str().unwrap(),
+            "--analytics-endpoint",
+            endpoint,
+        ];
+        let options = Opt::from_iter_safe(&args).unwrap();
+        fs::create_dir_all(&options.db_path).unwrap();
+        let index_controller = IndexController::new(&options.db_path, &options).unwrap();
+        let (analytics, receiver) = create(false);
+        let actor = AnalyticsActor {
+            receiver: receiver.unwrap(),
+            index_controller,
+            env: options.env.clone(),
+            endpoint: options.analytics_endpoint.clone(),
+            instance_uid: instance_uid(&options.db_path),
+            first_start: Instant::now(),
+            route_usage: BTreeMap::new(),
         };
-        let event = serde_json::to_string(&event).unwrap();
+        (analytics, actor)
+    }
 
-        let request = AmplitudeRequest {
-            api_key: AMPLITUDE_API_KEY,
-            event: &event,
+    fn route_used(actor: &mut AnalyticsActor, route: &str) {
+        let msg = AnalyticsMsg::RouteUsed {
+            method: "GET".to_string(),
+            route: route.to_string(),
         };
+        actor.handle_message(msg);
+    }
 
-        let body = qs::to_string(&request).unwrap();
-        let response = ureq::post("https://api.amplitude.com/httpapi").send_string(&body);
-        if !response.ok() {
-            let body = response.into_string().unwrap();
-            error!("Unsuccessful call to Amplitude: {}", body);
-        }
+    #[test]
+    fn instance_uid_is_persisted() {
+        let dir = TempDir::new().unwrap();
+        let uid = instance_uid(dir.path());
+        assert!(Uuid::parse_str(&uid).is_ok());
+        assert_eq!(instance_uid(dir.path()), uid);
+
+        fs::write(dir.path().join(INSTANCE_UID_FILE), "not a uuid").unwrap();
+        let regenerated = instance_uid(dir.path());
+        assert_ne!(regenerated, uid);
+        assert!(Uuid::parse_str(&regenerated).is_ok());
+    }
+
+    #[test]
+    fn disabled_analytics_have_no_actor() {
+        let (analytics, receiver) = create(true);
+        assert!(receiver.is_none());
+        // The events are ignored.
+        analytics.route_used("GET", "/indexes");
+    }
+
+    #[actix_rt::test]
+    async fn route_usage_is_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            while !String::from_utf8_lossy(&request).ends_with("]}") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buffer[..n]),
+                }
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let dir = TempDir::new().unwrap();
+        let (_analytics, mut actor) = actor(dir.path(), &endpoint);
+        route_used(&mut actor, "/indexes");
+        route_used(&mut actor, "/indexes");
+        route_used(&mut actor, "/indexes/{index_uid}");
+        assert_eq!(actor.route_usage["GET /indexes"], 2);
+        assert_eq!(actor.route_usage["GET /indexes/{index_uid}"], 1);
+
+        actor.report().await;
+        assert!(actor.route_usage.is_empty());
+
+        let request = server.join().unwrap();
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        // The API key is only sent to Amplitude.
+        assert!(body.get("api_key").is_none());
+        let event = &body["events"][0];
+        assert_eq!(event["event_type"], "runtime_tick");
+        assert_eq!(event["user_id"], actor.instance_uid.as_str());
+        assert_eq!(event["event_properties"]["number_of_indexes"], 0);
+        assert_eq!(event["event_properties"]["route_usage"]["GET /indexes"], 2);
+    }
+
+    #[actix_rt::test]
+    async fn actor_stops_with_its_handles() {
+        let dir = TempDir::new().unwrap();
+        // Nothing listens on the discard port, the reports fail right away.
+        let (analytics, actor) = actor(dir.path(), "http://127.0.0.1:9/");
+        let actor = tokio::task::spawn(actor.run());
+
+        analytics.route_used("GET", "/indexes");
+        let clone = analytics.clone();
+        drop(analytics);
+        drop(clone);
 
-        thread::sleep(Duration::from_secs(3600)) // one hour
+        tokio::time::timeout(Duration::from_secs(5), actor)
+            .await
+            .expect("the actor didn't stop")
+            .unwrap();
     }
 }
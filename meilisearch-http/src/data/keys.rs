@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+/// The file, in the database directory, holding the master key set by the last rotation.
+const ROTATED_KEYS_FILE: &str = "rotated-master-key.json";
+
+use super::ApiKeys;
+
+/// The master key set by the last rotation, and the previous master keys still accepted, so that
+/// a rotation survives a restart.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotatedKeys {
+    /// The hash of the master key of the options the keys were rotated from. The rotated keys are
+    /// ignored once another master key is set in the options.
+    pub configured_key_hash: String,
+    /// The private and public keys are derived from it on restart, the file is only readable by
+    /// its owner.
+    pub master_key: String,
+    /// The hashes of the previous keys, with the end of their grace period.
+    pub previous_keys: Vec<(KeyHashes, DateTime<Utc>)>,
+}
+
+/// The hashes of the master, private and public keys. The requests are authenticated by hashing
+/// their key, so that the previous keys don't need to be kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyHashes {
+    pub master: String,
+    pub private: String,
+    pub public: String,
+}
+
+impl KeyHashes {
+    /// Returns the hashes of the keys, `None` if no master key is set.
+    pub fn new(keys: &ApiKeys) -> Option<Self> {
+        Some(KeyHashes {
+            master: key_hash(keys.master.as_deref()?),
+            private: key_hash(keys.private.as_deref()?),
+            public: key_hash(keys.public.as_deref()?),
+        })
+    }
+}
+
+pub fn key_hash(key: &str) -> String {
+    format!("{:x}", sha2::Sha256::digest(key.as_bytes()))
+}
+
+impl RotatedKeys {
+    /// Reads the keys rotated from `configured_key`, the master key of the options, if any.
+    pub fn load(db_path: &Path, configured_key: &str) -> anyhow::Result<Option<Self>> {
+        let path = db_path.join(ROTATED_KEYS_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let keys: Self = serde_json::from_slice(&fs::read(&path)?)?;
+        if keys.configured_key_hash == key_hash(configured_key) {
+            Ok(Some(keys))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Writes the keys in the database directory, replacing the previous ones atomically. The
+    /// file is only readable by its owner.
+    pub fn persist(&self, db_path: &Path) -> anyhow::Result<()> {
+        let file = tempfile::NamedTempFile::new_in(db_path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.as_file()
+                .set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        serde_json::to_writer(&file, self)?;
+        file.as_file().sync_all()?;
+        file.persist(db_path.join(ROTATED_KEYS_FILE))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn rotated_keys() -> RotatedKeys {
+        let previous = |key: &str| {
+            let keys = ApiKeys::from_master_key(Some(key.to_string()));
+            (KeyHashes::new(&keys).unwrap(), Utc::now())
+        };
+        RotatedKeys {
+            configured_key_hash: key_hash("master"),
+            master_key: "second".to_string(),
+            previous_keys: vec![previous("master"), previous("first")],
+        }
+    }
+
+    #[test]
+    fn no_rotated_keys() {
+        let dir = TempDir::new().unwrap();
+        assert!(RotatedKeys::load(dir.path(), "master").unwrap().is_none());
+    }
+
+    #[test]
+    fn rotated_keys_are_persisted() {
+        let dir = TempDir::new().unwrap();
+        rotated_keys().persist(dir.path()).unwrap();
+
+        let keys = RotatedKeys::load(dir.path(), "master").unwrap().unwrap();
+        assert_eq!(keys.master_key, "second");
+        let previous: Vec<_> = keys
+            .previous_keys
+            .iter()
+            .map(|(k, _)| k.master.as_str())
+            .collect();
+        assert_eq!(previous, [key_hash("master"), key_hash("first")]);
+    }
+
+    #[test]
+    fn previous_keys_are_not_persisted() {
+        let dir = TempDir::new().unwrap();
+        rotated_keys().persist(dir.path()).unwrap();
+
+        let path = dir.path().join(ROTATED_KEYS_FILE);
+        let persisted = fs::read_to_string(&path).unwrap();
+        assert!(!persisted.contains("first"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn rotated_keys_are_ignored_with_another_configured_key() {
+        let dir = TempDir::new().unwrap();
+        rotated_keys().persist(dir.path()).unwrap();
+        assert!(RotatedKeys::load(dir.path(), "other").unwrap().is_none());
+    }
+}
@@ -1,16 +1,32 @@
+mod keys;
 pub mod search;
 mod updates;
 
+pub use self::keys::{key_hash, KeyHashes};
+
 use std::fs::create_dir_all;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
+use log::info;
+use parking_lot::RwLock;
 use sha2::Digest;
+use tokio::time::sleep;
 
+use self::keys::RotatedKeys;
+use crate::analytics::{self, Analytics};
+use crate::error::Error;
 use crate::index::Settings;
-use crate::index_controller::IndexController;
-use crate::index_controller::{IndexMetadata, IndexSettings};
-use crate::option::Opt;
+use crate::index_controller::{
+    dump_path, load_snapshot, snapshot_path, spawn_replica, spawn_snapshots, DumpError, DumpInfo,
+    DumpStatus, LogEntry,
+};
+use crate::index_controller::{ConsistencyReport, IndexMetadata, IndexSettings, IndexStats, Stats};
+use crate::index_controller::{IndexController, Task, TaskFilter, UpdateStatus};
+use crate::option::{Opt, OrphanRepair};
 
 #[derive(Clone)]
 pub struct Data {
@@ -27,7 +43,11 @@ impl Deref for Data {
 
 pub struct DataInner {
     pub index_controller: IndexController,
-    pub api_keys: ApiKeys,
+    api_keys: RwLock<ApiKeys>,
+    /// The hashes of the keys in use before the rotations of the master key, and the date until
+    /// which they are still accepted.
+    previous_api_keys: RwLock<Vec<(KeyHashes, DateTime<Utc>)>>,
+    analytics: Analytics,
     options: Opt,
 }
 
@@ -39,6 +59,16 @@ pub struct ApiKeys {
 }
 
 impl ApiKeys {
+    fn from_master_key(master: Option<String>) -> ApiKeys {
+        let mut keys = ApiKeys {
+            master,
+            private: None,
+            public: None,
+        };
+        keys.generate_missing_api_keys();
+        keys
+    }
+
     pub fn generate_missing_api_keys(&mut self) {
         if let Some(master_key) = &self.master {
             if self.private.is_none() {
@@ -59,35 +89,92 @@ impl Data {
     pub fn new(options: Opt) -> anyhow::Result<Data> {
         let path = options.db_path.clone();
 
+        if options.replication_primary && options.replicate_from.is_some() {
+            anyhow::bail!("An instance can't be both a replication primary and a replica");
+        }
+
+        if let Some(ref snapshot_path) = options.import_snapshot {
+            load_snapshot(
+                &path,
+                snapshot_path,
+                options.ignore_snapshot_if_db_exists,
+                options.ignore_missing_snapshot,
+            )?;
+        }
+
         create_dir_all(&path)?;
-        let index_size = options.max_mdb_size.get_bytes() as usize;
-        let update_store_size = options.max_udb_size.get_bytes() as usize;
-        let index_controller = IndexController::new(&path, index_size, update_store_size)?;
+        let index_controller = IndexController::new(&path, &options)?;
 
-        let mut api_keys = ApiKeys {
-            master: options.clone().master_key,
-            private: None,
-            public: None,
+        // The master key set by the last rotation replaces the one of the options.
+        let rotated = match options.master_key {
+            Some(ref master_key) => RotatedKeys::load(&path, master_key)?,
+            None => None,
+        };
+        let (api_keys, previous_api_keys) = match rotated {
+            Some(rotated) => {
+                info!("Using the master key set by the last rotation.");
+                (
+                    ApiKeys::from_master_key(Some(rotated.master_key)),
+                    rotated.previous_keys,
+                )
+            }
+            None => (
+                ApiKeys::from_master_key(options.master_key.clone()),
+                Vec::new(),
+            ),
         };
 
-        api_keys.generate_missing_api_keys();
+        let (analytics, analytics_receiver) = analytics::create(options.no_analytics);
 
         let inner = DataInner {
             index_controller,
             options,
-            api_keys,
+            api_keys: RwLock::new(api_keys),
+            previous_api_keys: RwLock::new(previous_api_keys),
+            analytics,
         };
         let inner = Arc::new(inner);
+        let data = Data { inner };
+
+        if let Some(receiver) = analytics_receiver {
+            analytics::spawn(receiver, data.index_controller.clone(), &data.options);
+        }
+
+        if let Some(ref primary) = data.options.replicate_from {
+            spawn_replica(
+                data.index_controller.clone(),
+                &data.options.db_path,
+                primary,
+                data.options.replication_api_key.clone(),
+                Duration::from_secs(data.options.replication_poll_interval_sec),
+            );
+        }
 
-        Ok(Data { inner })
+        if data.options.schedule_snapshot {
+            let interval = data.options.snapshot_interval_sec.unwrap_or(86400);
+            spawn_snapshots(
+                data.index_controller.clone(),
+                snapshot_path(&data.options.snapshot_dir, &data.options.db_path),
+                Duration::from_secs(interval),
+            );
+        }
+
+        Ok(data)
     }
 
     pub async fn settings(&self, uid: String) -> anyhow::Result<Settings> {
         self.index_controller.settings(uid).await
     }
 
-    pub async fn list_indexes(&self) -> anyhow::Result<Vec<IndexMetadata>> {
-        self.index_controller.list_indexes().await
+    pub async fn list_indexes(
+        &self,
+        prefix: Option<String>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<IndexMetadata>> {
+        self.index_controller
+            .list_indexes(prefix, offset, limit)
+            .await
     }
 
     pub async fn index(&self, uid: String) -> anyhow::Result<IndexMetadata> {
@@ -108,13 +195,246 @@ impl Data {
         Ok(meta)
     }
 
+    pub async fn get_index_stats(&self, uid: String) -> anyhow::Result<IndexStats> {
+        self.index_controller.get_stats(uid).await
+    }
+
+    pub async fn ready(&self, deadline: Duration) -> anyhow::Result<()> {
+        self.index_controller.ready(deadline).await
+    }
+
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        self.index_controller.shutdown().await
+    }
+
+    pub async fn get_stats(&self, index_uids: Option<Vec<String>>) -> anyhow::Result<Stats> {
+        self.index_controller.get_all_stats(index_uids).await
+    }
+
+    /// Checks that the indexes and their data on the disk match, see
+    /// `IndexController::check_consistency`.
+    pub async fn check_consistency(
+        &self,
+        repair: OrphanRepair,
+    ) -> anyhow::Result<ConsistencyReport> {
+        self.index_controller.check_consistency(repair).await
+    }
+
+    /// Starts creating a dump in the dumps directory, see `IndexController::create_dump`.
+    pub fn create_dump(&self) -> anyhow::Result<DumpInfo> {
+        let dumps_dir = self.options.dumps_dir.clone();
+        self.index_controller
+            .create_dump(dumps_dir, self.options.dump_batch_size)
+    }
+
+    /// Creates a snapshot of the database in the snapshots directory, see
+    /// `IndexController::create_snapshot`, and returns the path of the snapshot to import: the
+    /// full snapshot, which is followed by the increments when the snapshots are incremental.
+    pub async fn create_snapshot(&self) -> anyhow::Result<PathBuf> {
+        let path = snapshot_path(&self.options.snapshot_dir, &self.options.db_path);
+        self.index_controller.create_snapshot(&path).await?;
+        Ok(path)
+    }
+
+    /// Creates a dump like `create_dump`, and waits for it to be created.
+    pub async fn create_dump_and_wait(&self) -> anyhow::Result<DumpInfo> {
+        let uid = self.create_dump()?.uid;
+        loop {
+            let info = self.dump_info(uid.clone())?;
+            match info.status {
+                DumpStatus::InProgress => sleep(Duration::from_millis(100)).await,
+                DumpStatus::Done => return Ok(info),
+                DumpStatus::Failed => anyhow::bail!(
+                    "The dump {} failed: {}",
+                    uid,
+                    info.error.unwrap_or_default()
+                ),
+            }
+        }
+    }
+
+    /// Lists the most recent tasks matching `filter`, see `IndexController::list_tasks`.
+    pub async fn list_tasks(
+        &self,
+        filter: &TaskFilter,
+        from: Option<u64>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Task>> {
+        self.index_controller.list_tasks(filter, from, limit).await
+    }
+
+    /// Deletes the finished tasks matching `filter`, see `IndexController::delete_tasks`.
+    pub async fn delete_tasks(&self, filter: &TaskFilter) -> anyhow::Result<Vec<u64>> {
+        self.index_controller.delete_tasks(filter).await
+    }
+
+    /// Cancels the enqueued tasks matching `filter`, see `IndexController::cancel_tasks`.
+    pub async fn cancel_tasks(&self, filter: &TaskFilter) -> anyhow::Result<Vec<u64>> {
+        self.index_controller.cancel_tasks(filter).await
+    }
+
+    pub async fn task(&self, uid: u64) -> anyhow::Result<Task> {
+        self.index_controller.task(uid).await
+    }
+
+    pub fn dump_info(&self, uid: String) -> anyhow::Result<DumpInfo> {
+        self.index_controller
+            .dump_info(&self.options.dumps_dir, uid)
+    }
+
+    pub async fn import_dump(&self, path: &Path) -> anyhow::Result<()> {
+        self.index_controller
+            .import_dump(path, self.options.dump_batch_size)
+            .await
+    }
+
+    /// Waits for the pending updates of all the indexes to be processed.
+    pub async fn wait_pending_updates(&self) -> anyhow::Result<()> {
+        self.index_controller.wait_pending_updates().await
+    }
+
+    /// Rebuilds the index `index_uid` from its documents and its current settings, and waits for
+    /// the index to be replaced.
+    pub async fn reindex_and_wait(&self, index_uid: String) -> anyhow::Result<()> {
+        let update_id = self
+            .reindex(index_uid.clone(), Settings::default())
+            .await?
+            .id();
+        loop {
+            match self.get_update_status(index_uid.clone(), update_id).await? {
+                UpdateStatus::Pending(_) | UpdateStatus::Processing(_) => {
+                    sleep(Duration::from_millis(100)).await
+                }
+                UpdateStatus::Processed(_) => return Ok(()),
+                UpdateStatus::Failed(failed) => anyhow::bail!(
+                    "The index {} couldn't be rebuilt: {}",
+                    index_uid,
+                    failed.error()
+                ),
+                UpdateStatus::Aborted(_) => {
+                    anyhow::bail!("The rebuild of the index {} was aborted.", index_uid)
+                }
+            }
+        }
+    }
+
+    /// Restores the index `index_uid` of the dump `dump_uid`, found in the dumps directory, under
+    /// the uid `target_uid`, or under its own uid when it is missing.
+    pub async fn restore_index(
+        &self,
+        dump_uid: String,
+        index_uid: String,
+        target_uid: Option<String>,
+    ) -> anyhow::Result<IndexMetadata> {
+        let path = dump_path(&self.options.dumps_dir, &dump_uid);
+        if !path.exists() {
+            return Err(DumpError::DumpNotFound(dump_uid).into());
+        }
+
+        let target_uid = target_uid.unwrap_or_else(|| index_uid.clone());
+        self.index_controller
+            .restore_index(&path, index_uid, target_uid, self.options.dump_batch_size)
+            .await
+    }
+
+    pub fn replication_log(&self, after: u64, limit: usize) -> anyhow::Result<Vec<LogEntry>> {
+        self.index_controller.replication_log_entries(after, limit)
+    }
+
+    pub fn replication_payload(&self, seq: u64) -> anyhow::Result<Vec<u8>> {
+        self.index_controller.replication_log_payload(seq)
+    }
+
     #[inline]
     pub fn http_payload_size_limit(&self) -> usize {
         self.options.http_payload_size_limit.get_bytes() as usize
     }
 
+    pub fn api_keys(&self) -> ApiKeys {
+        self.api_keys.read().clone()
+    }
+
+    /// Returns the hashes of the keys a request can be authenticated with: the current ones, and
+    /// the ones in use before the rotations of the master key until the end of their grace period.
+    pub fn accepted_api_keys(&self) -> Vec<KeyHashes> {
+        let now = Utc::now();
+        let mut keys: Vec<_> = KeyHashes::new(&self.api_keys()).into_iter().collect();
+        let previous = self.previous_api_keys.read();
+        keys.extend(
+            previous
+                .iter()
+                .filter(|(_, until)| now < *until)
+                .map(|(keys, _)| keys.clone()),
+        );
+        keys
+    }
+
+    /// Replaces the master key, and the keys derived from it, without restarting the server.
+    /// The previous keys are still accepted during the configured grace period. The new key is
+    /// persisted in the database directory, and replaces the one of the options on restart.
+    pub fn rotate_master_key(&self, master_key: String) -> anyhow::Result<ApiKeys> {
+        if master_key.is_empty() {
+            anyhow::bail!(Error::bad_request("The master key can't be empty"));
+        }
+
+        let mut api_keys = self.api_keys.write();
+        let mut previous_api_keys = self.previous_api_keys.write();
+        let configured_key = match self.options.master_key {
+            Some(ref key) => key,
+            None => anyhow::bail!(Error::bad_request(
+                "No master key is set, there is no key to rotate"
+            )),
+        };
+
+        let now = Utc::now();
+        let grace_period =
+            chrono::Duration::seconds(self.options.master_key_grace_period_sec as i64);
+        let mut previous: Vec<_> = previous_api_keys
+            .iter()
+            .filter(|(_, until)| now < *until)
+            .cloned()
+            .collect();
+        if let Some(hashes) = KeyHashes::new(&api_keys) {
+            previous.push((hashes, now + grace_period));
+        }
+
+        let rotated = RotatedKeys {
+            configured_key_hash: key_hash(configured_key),
+            master_key: master_key.clone(),
+            previous_keys: previous.clone(),
+        };
+        rotated.persist(&self.options.db_path)?;
+
+        let new_keys = ApiKeys::from_master_key(Some(master_key));
+        *api_keys = new_keys.clone();
+        *previous_api_keys = previous;
+
+        Ok(new_keys)
+    }
+
+    /// The origins allowed to make cross-origin requests, `None` meaning that any origin is.
+    pub fn allowed_origins(&self) -> Option<&[String]> {
+        let origins = &self.options.http_allowed_origins;
+        if origins.is_empty() && self.options.env != "production" {
+            None
+        } else {
+            Some(origins)
+        }
+    }
+
+    /// Whether the message of the internal errors is returned to the client, it is only logged
+    /// in production.
+    pub fn verbose_internal_errors(&self) -> bool {
+        self.options.env != "production"
+    }
+
+    #[inline]
+    pub fn analytics(&self) -> &Analytics {
+        &self.analytics
+    }
+
     #[inline]
-    pub fn api_keys(&self) -> &ApiKeys {
-        &self.api_keys
+    pub fn options(&self) -> &Opt {
+        &self.options
     }
 }
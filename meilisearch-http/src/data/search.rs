@@ -1,17 +1,30 @@
+use actix_web::web::Bytes;
+use futures::Stream;
 use serde_json::{Map, Value};
 
 use super::Data;
-use crate::index::{SearchQuery, SearchResult};
+use crate::index::{FacetSearchQuery, FacetSearchResult, SearchQuery, SearchResult};
 
 impl Data {
     pub async fn search(
         &self,
         index: String,
-        search_query: SearchQuery,
+        mut search_query: SearchQuery,
     ) -> anyhow::Result<SearchResult> {
+        if search_query.timeout_ms.is_none() {
+            search_query.timeout_ms = self.options.search_timeout_ms;
+        }
         self.index_controller.search(index, search_query).await
     }
 
+    pub async fn facet_search(
+        &self,
+        index: String,
+        query: FacetSearchQuery,
+    ) -> anyhow::Result<FacetSearchResult> {
+        self.index_controller.facet_search(index, query).await
+    }
+
     pub async fn retrieve_documents(
         &self,
         index: String,
@@ -24,6 +37,21 @@ impl Data {
             .await
     }
 
+    pub async fn export_documents(
+        &self,
+        index: String,
+        chunk_size: usize,
+        attributes_to_retrieve: Option<Vec<String>>,
+    ) -> anyhow::Result<impl Stream<Item = anyhow::Result<Bytes>>> {
+        self.index_controller
+            .export_documents(index, chunk_size, attributes_to_retrieve)
+            .await
+    }
+
+    pub async fn number_of_documents(&self, index: String) -> anyhow::Result<u64> {
+        self.index_controller.number_of_documents(index).await
+    }
+
     pub async fn retrieve_document(
         &self,
         index: String,
@@ -34,4 +62,15 @@ impl Data {
             .document(index, document_id, attributes_to_retrieve)
             .await
     }
+
+    pub async fn retrieve_documents_by_ids(
+        &self,
+        index: String,
+        document_ids: Vec<String>,
+        attributes_to_retrieve: Option<Vec<String>>,
+    ) -> anyhow::Result<Vec<Map<String, Value>>> {
+        self.index_controller
+            .documents_by_ids(index, document_ids, attributes_to_retrieve)
+            .await
+    }
 }
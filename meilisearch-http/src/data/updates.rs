@@ -1,9 +1,12 @@
+use std::time::Duration;
+
 use actix_web::web::Payload;
+use futures::Stream;
 use milli::update::{IndexDocumentsMethod, UpdateFormat};
 
 use super::Data;
-use crate::index::Settings;
-use crate::index_controller::{IndexMetadata, IndexSettings, UpdateStatus};
+use crate::index::{Settings, ValidationReport};
+use crate::index_controller::{IndexMetadata, IndexSettings, Priority, UpdateStatus, WaitFor};
 
 impl Data {
     pub async fn add_documents(
@@ -13,29 +16,55 @@ impl Data {
         format: UpdateFormat,
         stream: Payload,
         primary_key: Option<String>,
+        skip_invalid_documents: bool,
+        priority: Priority,
     ) -> anyhow::Result<UpdateStatus> {
         let update_status = self
             .index_controller
-            .add_documents(index, method, format, stream, primary_key)
+            .add_documents(
+                index,
+                method,
+                format,
+                stream,
+                primary_key,
+                skip_invalid_documents,
+                priority,
+            )
             .await?;
         Ok(update_status)
     }
 
+    pub async fn validate_documents(
+        &self,
+        index: String,
+        stream: Payload,
+        primary_key: Option<String>,
+    ) -> anyhow::Result<ValidationReport> {
+        self.index_controller
+            .validate_documents(index, stream, primary_key)
+            .await
+    }
+
     pub async fn update_settings(
         &self,
         index: String,
         settings: Settings,
         create: bool,
+        priority: Priority,
     ) -> anyhow::Result<UpdateStatus> {
         let update = self
             .index_controller
-            .update_settings(index, settings, create)
+            .update_settings(index, settings, create, priority)
             .await?;
         Ok(update)
     }
 
-    pub async fn clear_documents(&self, index: String) -> anyhow::Result<UpdateStatus> {
-        let update = self.index_controller.clear_documents(index).await?;
+    pub async fn clear_documents(
+        &self,
+        index: String,
+        priority: Priority,
+    ) -> anyhow::Result<UpdateStatus> {
+        let update = self.index_controller.clear_documents(index, priority).await?;
         Ok(update)
     }
 
@@ -43,14 +72,23 @@ impl Data {
         &self,
         index: String,
         document_ids: Vec<String>,
+        priority: Priority,
     ) -> anyhow::Result<UpdateStatus> {
         let update = self
             .index_controller
-            .delete_documents(index, document_ids)
+            .delete_documents(index, document_ids, priority)
             .await?;
         Ok(update)
     }
 
+    pub async fn clone_index(&self, index: String, new_uid: String) -> anyhow::Result<UpdateStatus> {
+        self.index_controller.clone_index(index, new_uid).await
+    }
+
+    pub async fn reindex(&self, index: String, settings: Settings) -> anyhow::Result<UpdateStatus> {
+        self.index_controller.reindex(index, settings).await
+    }
+
     pub async fn delete_index(&self, index: String) -> anyhow::Result<()> {
         self.index_controller.delete_index(index).await?;
         Ok(())
@@ -60,10 +98,33 @@ impl Data {
         self.index_controller.update_status(index, uid).await
     }
 
+    /// Returns the status of the update once it reached `wait_for`, or its current status if it
+    /// didn't within `duration`.
+    pub async fn wait_update_status(
+        &self,
+        index: String,
+        uid: u64,
+        wait_for: WaitFor,
+        duration: Duration,
+    ) -> anyhow::Result<UpdateStatus> {
+        self.index_controller
+            .wait_update_status(index, uid, wait_for, duration)
+            .await
+    }
+
     pub async fn get_updates_status(&self, index: String) -> anyhow::Result<Vec<UpdateStatus>> {
         self.index_controller.all_update_status(index).await
     }
 
+    /// Streams the new status of the updates of the index, see
+    /// `IndexController::update_events`.
+    pub async fn update_events(
+        &self,
+        index: String,
+    ) -> anyhow::Result<impl Stream<Item = UpdateStatus>> {
+        self.index_controller.update_events(index).await
+    }
+
     pub async fn update_index(
         &self,
         uid: String,
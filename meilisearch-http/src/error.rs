@@ -1,5 +1,6 @@
 use std::error;
 use std::fmt;
+use std::future::Future;
 
 use actix_web as aweb;
 use actix_web::dev::HttpResponseBuilder;
@@ -9,6 +10,25 @@ use actix_web::http::StatusCode;
 use meilisearch_error::{Code, ErrorCode};
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 
+use crate::index::FilterError;
+use crate::index_controller::{
+    DumpError, IndexError, ReplicationError, SearchLimitError, TaskError, UpdateError, UuidError,
+};
+
+tokio::task_local! {
+    static VERBOSE_INTERNAL_ERRORS: bool;
+}
+
+/// Runs `fut`, the handling of a request, returning the message of the internal errors to the
+/// client if `verbose`. When it is not, the message is only logged, and the client receives a
+/// generic one. The messages are returned outside of a request.
+pub fn with_verbose_internal_errors<F: Future>(
+    verbose: bool,
+    fut: F,
+) -> impl Future<Output = F::Output> {
+    VERBOSE_INTERNAL_ERRORS.scope(verbose, fut)
+}
+
 #[derive(Debug)]
 pub struct ResponseError {
     inner: Box<dyn ErrorCode>,
@@ -28,15 +48,114 @@ impl fmt::Display for ResponseError {
     }
 }
 
-// TODO: remove this when implementing actual error handling
 impl From<anyhow::Error> for ResponseError {
-    fn from(other: anyhow::Error) -> ResponseError {
+    fn from(error: anyhow::Error) -> ResponseError {
+        let error = match error.downcast::<UuidError>() {
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        let error = match error.downcast::<IndexError>() {
+            Ok(IndexError::Error(error)) => return index_error(error),
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        let error = match error.downcast::<UpdateError>() {
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        let error = match error.downcast::<DumpError>() {
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        let error = match error.downcast::<ReplicationError>() {
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        let error = match error.downcast::<SearchLimitError>() {
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        let error = match error.downcast::<TaskError>() {
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        let error = match error.downcast::<FilterError>() {
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        let error = match error.downcast::<Error>() {
+            Ok(error) => {
+                return ResponseError {
+                    inner: Box::new(error),
+                }
+            }
+            Err(error) => error,
+        };
+        // The invalid requests are reported with one of the errors above, any other error is an
+        // internal failure, e.g. a database or io error.
         ResponseError {
-            inner: Box::new(Error::NotFound(other.to_string())),
+            inner: Box::new(Error::Internal(error.to_string())),
         }
     }
 }
 
+/// Reports an error returned by an index with its own code when it is typed, e.g. an invalid
+/// filter, as an invalid request otherwise: the untyped errors of the indexes are raised on the
+/// invalid queries and updates.
+fn index_error(error: anyhow::Error) -> ResponseError {
+    let error = match error.downcast::<FilterError>() {
+        Ok(error) => {
+            return ResponseError {
+                inner: Box::new(error),
+            }
+        }
+        Err(error) => error,
+    };
+    let error = match error.downcast::<Error>() {
+        Ok(error) => {
+            return ResponseError {
+                inner: Box::new(error),
+            }
+        }
+        Err(error) => error,
+    };
+    ResponseError {
+        inner: Box::new(IndexError::Error(error)),
+    }
+}
+
 impl From<Error> for ResponseError {
     fn from(error: Error) -> ResponseError {
         ResponseError {
@@ -61,17 +180,27 @@ impl Serialize for ResponseError {
         let struct_name = "ResponseError";
         let field_count = 4;
 
+        let verbose = VERBOSE_INTERNAL_ERRORS.try_with(|verbose| *verbose);
+        let message = if self.http_status().is_server_error() && verbose == Ok(false) {
+            String::from("An internal error occurred")
+        } else {
+            self.to_string()
+        };
+
         let mut state = serializer.serialize_struct(struct_name, field_count)?;
-        state.serialize_field("message", &self.to_string())?;
-        state.serialize_field("errorCode", &self.error_name())?;
-        state.serialize_field("errorType", &self.error_type())?;
-        state.serialize_field("errorLink", &self.error_url())?;
+        state.serialize_field("message", &message)?;
+        state.serialize_field("code", &self.error_name())?;
+        state.serialize_field("type", &self.error_type())?;
+        state.serialize_field("link", &self.error_url())?;
         state.end()
     }
 }
 
 impl aweb::error::ResponseError for ResponseError {
     fn error_response(&self) -> aweb::HttpResponse {
+        if self.http_status().is_server_error() {
+            log::error!("{}", self);
+        }
         HttpResponseBuilder::new(self.status_code()).json(&self)
     }
 
@@ -91,13 +220,16 @@ pub enum Error {
     Internal(String),
     InvalidIndexUid,
     InvalidToken(String),
+    IpNotAllowed(Option<String>),
     MissingAuthorizationHeader,
     NotFound(String),
     OpenIndex(String),
     RetrieveDocument(u32, String),
     SearchDocuments(String),
     PayloadTooLarge,
+    ReadOnlyReplica,
     UnsupportedMediaType,
+    UnsupportedContentEncoding(String),
     DumpAlreadyInProgress,
     DumpProcessFailed(String),
 }
@@ -117,16 +249,18 @@ impl ErrorCode for Error {
             Internal(_) => Code::Internal,
             InvalidIndexUid => Code::InvalidIndexUid,
             InvalidToken(_) => Code::InvalidToken,
+            IpNotAllowed(_) => Code::IpNotAllowed,
             MissingAuthorizationHeader => Code::MissingAuthorizationHeader,
             NotFound(_) => Code::NotFound,
             OpenIndex(_) => Code::OpenIndex,
             RetrieveDocument(_, _) => Code::RetrieveDocument,
             SearchDocuments(_) => Code::SearchDocuments,
             PayloadTooLarge => Code::PayloadTooLarge,
+            ReadOnlyReplica => Code::ReadOnlyReplica,
             UnsupportedMediaType => Code::UnsupportedMediaType,
-            _ => unreachable!()
-            //DumpAlreadyInProgress => Code::DumpAlreadyInProgress,
-            //DumpProcessFailed(_) => Code::DumpProcessFailed,
+            UnsupportedContentEncoding(_) => Code::UnsupportedMediaType,
+            DumpAlreadyInProgress => Code::DumpAlreadyInProgress,
+            DumpProcessFailed(_) => Code::DumpProcessFailed,
         }
     }
 }
@@ -255,13 +389,17 @@ impl fmt::Display for Error {
             Self::Internal(err) => f.write_str(err),
             Self::InvalidIndexUid => f.write_str("Index must have a valid uid; Index uid can be of type integer or string only composed of alphanumeric characters, hyphens (-) and underscores (_)."),
             Self::InvalidToken(err) => write!(f, "Invalid API key: {}", err),
+            Self::IpNotAllowed(Some(ip)) => write!(f, "The address {} is not allowed to call this route", ip),
+            Self::IpNotAllowed(None) => f.write_str("The address of the client is unknown and can't be allowed to call this route"),
             Self::MissingAuthorizationHeader => f.write_str("You must have an authorization token"),
             Self::NotFound(err) => write!(f, "{} not found", err),
             Self::OpenIndex(err) => write!(f, "Impossible to open index; {}", err),
             Self::RetrieveDocument(id, err) => write!(f, "Impossible to retrieve the document with id: {}; {}", id, err),
             Self::SearchDocuments(err) => write!(f, "Impossible to search documents; {}", err),
             Self::PayloadTooLarge => f.write_str("Payload too large"),
+            Self::ReadOnlyReplica => f.write_str("This instance is a read-only replica, the updates must be sent to its primary"),
             Self::UnsupportedMediaType => f.write_str("Unsupported media type"),
+            Self::UnsupportedContentEncoding(encoding) => write!(f, "Unsupported content encoding `{}`, the supported encodings are `gzip`, `br` and `deflate`", encoding),
             Self::DumpAlreadyInProgress => f.write_str("Another dump is already in progress"),
             Self::DumpProcessFailed(message) => write!(f, "Dump process failed: {}", message),
         }
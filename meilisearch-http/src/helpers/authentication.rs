@@ -7,6 +7,7 @@ use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
 use actix_web::web;
 use futures::future::{err, ok, Future, Ready};
 
+use crate::data::key_hash;
 use crate::error::{Error, ResponseError};
 use crate::Data;
 
@@ -83,18 +84,14 @@ where
             }
         };
 
-        let authenticated = match self.acl {
-            Authentication::Admin => data.api_keys().master.as_deref() == Some(auth_header),
-            Authentication::Private => {
-                data.api_keys().master.as_deref() == Some(auth_header)
-                    || data.api_keys().private.as_deref() == Some(auth_header)
-            }
+        let hash = key_hash(auth_header);
+        let authenticated = data.accepted_api_keys().iter().any(|keys| match self.acl {
+            Authentication::Admin => keys.master == hash,
+            Authentication::Private => keys.master == hash || keys.private == hash,
             Authentication::Public => {
-                data.api_keys().master.as_deref() == Some(auth_header)
-                    || data.api_keys().private.as_deref() == Some(auth_header)
-                    || data.api_keys().public.as_deref() == Some(auth_header)
+                keys.master == hash || keys.private == hash || keys.public == hash
             }
-        };
+        });
 
         if authenticated {
             Box::pin(svc.call(req))
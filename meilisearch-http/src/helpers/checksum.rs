@@ -0,0 +1,168 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_web::error::PayloadError;
+use actix_web::http::header::HeaderMap;
+use actix_web::web::Bytes;
+use futures::Stream;
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// The header holding the base64 encoded MD5 digest of the payload, as defined by RFC 1864.
+const CONTENT_MD5: &str = "content-md5";
+/// The header holding the hex encoded SHA-256 digest of the payload.
+const PAYLOAD_CHECKSUM: &str = "x-payload-checksum";
+
+/// The checksum a client sent along with its payload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Checksum {
+    Md5(String),
+    Sha256(String),
+}
+
+impl Checksum {
+    /// Reads the checksum of the payload from the `Content-MD5` or `X-Payload-Checksum` header.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, Error> {
+        let header = |name: &str| match headers.get(name) {
+            Some(value) => value
+                .to_str()
+                .map(|value| Some(value.trim().to_string()))
+                .map_err(|_| Error::BadRequest(format!("Invalid `{}` header.", name))),
+            None => Ok(None),
+        };
+
+        if let Some(digest) = header(PAYLOAD_CHECKSUM)? {
+            return Ok(Some(Checksum::Sha256(digest.to_lowercase())));
+        }
+        Ok(header(CONTENT_MD5)?.map(Checksum::Md5))
+    }
+
+    fn hasher(&self) -> Hasher {
+        match self {
+            Checksum::Md5(_) => Hasher::Md5(md5::Md5::new()),
+            Checksum::Sha256(_) => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    fn expected(&self) -> &str {
+        match self {
+            Checksum::Md5(digest) | Checksum::Sha256(digest) => digest,
+        }
+    }
+}
+
+enum Hasher {
+    Md5(md5::Md5),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(bytes),
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// Returns the digest encoded as it is sent in its header.
+    fn finish(self) -> String {
+        match self {
+            Hasher::Md5(hasher) => base64::encode(hasher.finalize()),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+/// A payload whose bytes are checked against a checksum. An error is returned at the end of the
+/// payload if they don't match, so that a truncated payload is never registered as an update.
+pub struct VerifiedPayload<S> {
+    inner: S,
+    checksum: Checksum,
+    hasher: Option<Hasher>,
+}
+
+impl<S> VerifiedPayload<S> {
+    pub fn new(inner: S, checksum: Checksum) -> Self {
+        let hasher = Some(checksum.hasher());
+        Self {
+            inner,
+            checksum,
+            hasher,
+        }
+    }
+}
+
+impl<S> Stream for VerifiedPayload<S>
+where
+    S: Stream<Item = Result<Bytes, PayloadError>> + Unpin,
+{
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match Pin::new(&mut self.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(bytes))) => {
+                if let Some(ref mut hasher) = self.hasher {
+                    hasher.update(&bytes);
+                }
+                Poll::Ready(Some(Ok(bytes)))
+            }
+            Poll::Ready(None) => match self.hasher.take() {
+                Some(hasher) => {
+                    let digest = hasher.finish();
+                    if digest == self.checksum.expected() {
+                        Poll::Ready(None)
+                    } else {
+                        let message = format!(
+                            "The payload doesn't match its checksum, it may have been truncated: \
+                            expected `{}`, computed `{}`.",
+                            self.checksum.expected(),
+                            digest
+                        );
+                        let error = io::Error::new(io::ErrorKind::InvalidData, message);
+                        Poll::Ready(Some(Err(PayloadError::Io(error))))
+                    }
+                }
+                None => Poll::Ready(None),
+            },
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream::{self, StreamExt};
+
+    use super::*;
+
+    async fn verify(chunks: &[&'static str], checksum: Checksum) -> Result<String, PayloadError> {
+        let chunks = chunks
+            .iter()
+            .map(|chunk| Ok(Bytes::from_static(chunk.as_bytes())));
+        let mut payload = VerifiedPayload::new(stream::iter(chunks), checksum);
+        let mut body = String::new();
+        while let Some(bytes) = payload.next().await {
+            body.push_str(std::str::from_utf8(&bytes?).unwrap());
+        }
+        Ok(body)
+    }
+
+    #[actix_rt::test]
+    async fn payload_matching_its_checksum() {
+        let md5 = Checksum::Md5("XUFAKrxLKna5cZ2REBfFkg==".to_string());
+        assert_eq!(verify(&["hel", "lo"], md5).await.unwrap(), "hello");
+
+        let sha256 = Checksum::Sha256(
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".to_string(),
+        );
+        assert_eq!(verify(&["hello"], sha256).await.unwrap(), "hello");
+    }
+
+    #[actix_rt::test]
+    async fn truncated_payload() {
+        let md5 = Checksum::Md5("XUFAKrxLKna5cZ2REBfFkg==".to_string());
+        assert!(verify(&["hel"], md5).await.is_err());
+    }
+}
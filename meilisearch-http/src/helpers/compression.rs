@@ -1,27 +1,174 @@
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
+use std::fmt;
 use std::fs::{create_dir_all, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 use std::path::Path;
+use std::str::FromStr;
 use tar::{Archive, Builder};
 
 use crate::error::Error;
 
-pub fn to_tar_gz(src: &Path, dest: &Path) -> Result<(), Error> {
-    let f = File::create(dest)?;
-    let gz_encoder = GzEncoder::new(f, Compression::default());
-    let mut tar_encoder = Builder::new(gz_encoder);
-    tar_encoder.append_dir_all(".", src)?;
-    let gz_encoder = tar_encoder.into_inner()?;
-    gz_encoder.finish()?;
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression algorithm of the snapshots and the dumps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveCodec {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Default for ArchiveCodec {
+    fn default() -> Self {
+        ArchiveCodec::Gzip
+    }
+}
+
+impl FromStr for ArchiveCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(ArchiveCodec::None),
+            "gzip" => Ok(ArchiveCodec::Gzip),
+            "zstd" => Ok(ArchiveCodec::Zstd),
+            _ => Err(format!(
+                "Invalid archive compression `{}`, expected `none`, `gzip` or `zstd`.",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveCodec::None => f.write_str("none"),
+            ArchiveCodec::Gzip => f.write_str("gzip"),
+            ArchiveCodec::Zstd => f.write_str("zstd"),
+        }
+    }
+}
+
+/// The compression of the archives written by `to_tar`: the algorithm and its level, the default
+/// level of the algorithm when `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveCompression {
+    codec: ArchiveCodec,
+    level: Option<u32>,
+}
+
+impl ArchiveCompression {
+    /// Fails if `level` isn't a level of `codec`: from 0 to 9 for gzip and from 1 to 22 for zstd.
+    /// The level is ignored without compression.
+    pub fn new(codec: ArchiveCodec, level: Option<u32>) -> anyhow::Result<Self> {
+        let max_level = match codec {
+            ArchiveCodec::None => return Ok(Self { codec, level: None }),
+            ArchiveCodec::Gzip => 9,
+            ArchiveCodec::Zstd => 22,
+        };
+        let min_level = if codec == ArchiveCodec::Zstd { 1 } else { 0 };
+        match level {
+            Some(level) if level < min_level || level > max_level => anyhow::bail!(
+                "Invalid {} compression level {}, expected a level from {} to {}.",
+                codec,
+                level,
+                min_level,
+                max_level
+            ),
+            _ => Ok(Self { codec, level }),
+        }
+    }
+}
+
+/// Archives the content of `src` as `dest`, compressed while it is written.
+pub fn to_tar(src: &Path, dest: &Path, compression: ArchiveCompression) -> Result<(), Error> {
+    let f = BufWriter::new(File::create(dest)?);
+    let mut f = match compression.codec {
+        ArchiveCodec::None => append_dir_all(src, f)?,
+        ArchiveCodec::Gzip => {
+            let level = compression
+                .level
+                .map_or_else(Compression::default, Compression::new);
+            append_dir_all(src, GzEncoder::new(f, level))?.finish()?
+        }
+        ArchiveCodec::Zstd => {
+            // The level 0 is the default level of zstd.
+            let level = compression.level.unwrap_or(0) as i32;
+            append_dir_all(src, zstd::Encoder::new(f, level)?)?.finish()?
+        }
+    };
+    f.flush()?;
+    f.get_ref().sync_all()?;
     Ok(())
 }
 
-pub fn from_tar_gz(src: &Path, dest: &Path) -> Result<(), Error> {
-    let f = File::open(src)?;
-    let gz = GzDecoder::new(f);
-    let mut ar = Archive::new(gz);
+fn append_dir_all<W: Write>(src: &Path, writer: W) -> io::Result<W> {
+    let mut tar_encoder = Builder::new(writer);
+    tar_encoder.append_dir_all(".", src)?;
+    tar_encoder.into_inner()
+}
+
+/// Extracts the archive `src` in `dest`, whatever the compression it was written with.
+pub fn from_tar(src: &Path, dest: &Path) -> Result<(), Error> {
+    let mut f = BufReader::new(File::open(src)?);
+    let header = f.fill_buf()?;
+    let is_gzip = header.starts_with(GZIP_MAGIC);
+    let is_zstd = header.starts_with(ZSTD_MAGIC);
+    let reader: Box<dyn Read> = if is_gzip {
+        Box::new(GzDecoder::new(f))
+    } else if is_zstd {
+        Box::new(zstd::Decoder::with_buffer(f)?)
+    } else {
+        Box::new(f)
+    };
+    let mut ar = Archive::new(reader);
     create_dir_all(dest)?;
     ar.unpack(dest)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn archive_with_each_codec() {
+        let dir = TempDir::new().unwrap();
+        let src = dir.path().join("src");
+        create_dir_all(src.join("subdir")).unwrap();
+        fs::write(src.join("subdir/file.txt"), b"Hello").unwrap();
+
+        for &(codec, level, magic) in &[
+            (ArchiveCodec::None, None, &b""[..]),
+            (ArchiveCodec::Gzip, Some(1), GZIP_MAGIC),
+            (ArchiveCodec::Zstd, None, ZSTD_MAGIC),
+            (ArchiveCodec::Zstd, Some(19), ZSTD_MAGIC),
+        ] {
+            let archive = dir.path().join(format!("{}-{:?}.tar", codec, level));
+            let dest = dir.path().join(format!("{}-{:?}", codec, level));
+            let compression = ArchiveCompression::new(codec, level).unwrap();
+
+            to_tar(&src, &archive, compression).unwrap();
+            assert!(fs::read(&archive).unwrap().starts_with(magic));
+            from_tar(&archive, &dest).unwrap();
+            let content = fs::read_to_string(dest.join("subdir/file.txt")).unwrap();
+            assert_eq!(content, "Hello");
+        }
+    }
+
+    #[test]
+    fn invalid_levels() {
+        assert!(ArchiveCompression::new(ArchiveCodec::Gzip, Some(10)).is_err());
+        assert!(ArchiveCompression::new(ArchiveCodec::Zstd, Some(0)).is_err());
+        assert!(ArchiveCompression::new(ArchiveCodec::Zstd, Some(23)).is_err());
+        assert!(ArchiveCompression::new(ArchiveCodec::None, Some(42)).is_ok());
+    }
+}
@@ -0,0 +1,187 @@
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::web;
+use futures::future::{err, ok, Future, Ready};
+
+use crate::error::{Error, ResponseError};
+use crate::Data;
+
+/// A range of IP addresses in the CIDR notation, e.g. `192.168.0.0/16`. A single address is a
+/// range containing only itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_matches(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V4(net), IpAddr::V6(ip)) => match ip.to_ipv4() {
+                Some(ip) => prefix_matches(&net.octets(), &ip.octets(), self.prefix_len),
+                None => false,
+            },
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+fn prefix_matches(net: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let full_bytes = (prefix_len / 8) as usize;
+    let remaining_bits = prefix_len % 8;
+
+    if net[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+
+    if remaining_bits == 0 {
+        true
+    } else {
+        let mask = !0u8 << (8 - remaining_bits);
+        net[full_bytes] & mask == ip[full_bytes] & mask
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.find('/') {
+            Some(pos) => (&s[..pos], Some(&s[pos + 1..])),
+            None => (s, None),
+        };
+
+        let addr: IpAddr = addr
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid address in `{}`: {}", s, e))?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_prefix_len)
+                .ok_or_else(|| format!("invalid prefix length in `{}`", s))?,
+            None => max_prefix_len,
+        };
+
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+/// Restricts the administrative routes to the clients whose address is in the
+/// `--admin-allowed-ips` allowlist, whatever API key they use. No restriction applies when the
+/// allowlist is empty.
+///
+/// The administrative routes are the ones wrapped with this middleware: deleting an index or
+/// its documents, updating its settings, cloning or reindexing it, and anything related to the
+/// keys, the dumps, the replication, the consistency repairs or the deletion and cancellation of
+/// tasks.
+///
+/// The address is the one of the peer of the connection, the `X-Forwarded-For` header is not
+/// trusted.
+#[derive(Clone, Copy)]
+pub struct AdminIpAllowlist;
+
+impl<S: 'static, B> Transform<S, ServiceRequest> for AdminIpAllowlist
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = AdminIpAllowlistMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(AdminIpAllowlistMiddleware { service })
+    }
+}
+
+pub struct AdminIpAllowlistMiddleware<S> {
+    service: S,
+}
+
+#[allow(clippy::type_complexity)]
+impl<S, B> Service<ServiceRequest> for AdminIpAllowlistMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // This unwrap is left because this error should never appear. If that's the case, then
+        // it means that actix-web has an issue or someone changes the type `Data`.
+        let data = req.app_data::<web::Data<Data>>().unwrap().clone();
+        let allowlist = &data.options().admin_allowed_ips;
+
+        if allowlist.is_empty() {
+            return Box::pin(self.service.call(req));
+        }
+
+        let ip = req.peer_addr().map(|addr| addr.ip());
+        match ip {
+            Some(ip) if allowlist.iter().any(|network| network.contains(ip)) => {
+                Box::pin(self.service.call(req))
+            }
+            _ => Box::pin(err(ResponseError::from(Error::IpNotAllowed(
+                ip.map(|ip| ip.to_string()),
+            ))
+            .into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ip_network_contains() {
+        let network: IpNetwork = "192.168.0.0/16".parse().unwrap();
+        assert!(network.contains("192.168.12.1".parse().unwrap()));
+        assert!(!network.contains("192.169.0.1".parse().unwrap()));
+        assert!(network.contains("::ffff:192.168.0.1".parse().unwrap()));
+
+        let network: IpNetwork = "10.0.0.128/25".parse().unwrap();
+        assert!(network.contains("10.0.0.200".parse().unwrap()));
+        assert!(!network.contains("10.0.0.127".parse().unwrap()));
+
+        let network: IpNetwork = "127.0.0.1".parse().unwrap();
+        assert!(network.contains("127.0.0.1".parse().unwrap()));
+        assert!(!network.contains("127.0.0.2".parse().unwrap()));
+
+        let network: IpNetwork = "fd00::/8".parse().unwrap();
+        assert!(network.contains("fd12::1".parse().unwrap()));
+        assert!(!network.contains("fe80::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_ip_network() {
+        assert!("192.168.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("192.168.0/16".parse::<IpNetwork>().is_err());
+        assert!("::1/129".parse::<IpNetwork>().is_err());
+    }
+}
@@ -1,4 +1,11 @@
 pub mod authentication;
+pub mod checksum;
 pub mod compression;
+pub mod ip_allowlist;
+pub mod read_only;
+pub mod request_id;
 
 pub use authentication::Authentication;
+pub use ip_allowlist::AdminIpAllowlist;
+pub use read_only::ReadOnlyReplica;
+pub use request_id::RequestId;
@@ -0,0 +1,93 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::web;
+use futures::future::{err, ok, Future, Ready};
+
+use crate::error::{Error, ResponseError};
+use crate::Data;
+
+/// The routes called with a method other than `GET` that leave the indexes untouched. The
+/// searches and the document fetches are sent with a `POST` request but only read the indexes,
+/// creating a dump only reads the indexes too, and the keys are not replicated.
+///
+/// The routes are listed by their exact pattern, a new route is rejected on a replica until it
+/// is added here.
+const READ_ONLY_ROUTES: &[(Method, &str)] = &[
+    (Method::POST, "/indexes/{index_uid}/search"),
+    (Method::POST, "/indexes/{index_uid}/facet-search"),
+    (Method::POST, "/indexes/{index_uid}/documents/fetch"),
+    (Method::POST, "/dumps"),
+    (Method::POST, "/keys/rotate"),
+];
+
+/// Whether the request leaves the indexes untouched.
+fn is_allowed_on_replica(req: &ServiceRequest) -> bool {
+    let method = req.method();
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return true;
+    }
+
+    match req.match_pattern() {
+        Some(pattern) => READ_ONLY_ROUTES
+            .iter()
+            .any(|(m, p)| m == method && *p == pattern),
+        None => false,
+    }
+}
+
+/// Rejects the requests that would modify the indexes of a replica, started with
+/// `--replicate-from`, as its indexes are only updated by replicating its primary.
+#[derive(Clone, Copy)]
+pub struct ReadOnlyReplica;
+
+impl<S: 'static, B> Transform<S, ServiceRequest> for ReadOnlyReplica
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = ReadOnlyReplicaMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ReadOnlyReplicaMiddleware { service })
+    }
+}
+
+pub struct ReadOnlyReplicaMiddleware<S> {
+    service: S,
+}
+
+#[allow(clippy::type_complexity)]
+impl<S, B> Service<ServiceRequest> for ReadOnlyReplicaMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // This unwrap is left because this error should never appear. If that's the case, then
+        // it means that actix-web has an issue or someone changes the type `Data`.
+        let data = req.app_data::<web::Data<Data>>().unwrap().clone();
+
+        if data.options().replicate_from.is_none() || is_allowed_on_replica(&req) {
+            Box::pin(self.service.call(req))
+        } else {
+            Box::pin(err(ResponseError::from(Error::ReadOnlyReplica).into()))
+        }
+    }
+}
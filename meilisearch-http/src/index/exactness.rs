@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+
+use anyhow::bail;
+use heed::RoTxn;
+use meilisearch_tokenizer::{Analyzer, AnalyzerConfig};
+use serde_json::Value;
+
+use super::{Index, EXACT_ATTRIBUTES_KEY, EXACT_WORDS_KEY};
+
+/// A word of the query, normalized by the tokenizer.
+struct QueryWord {
+    text: String,
+    /// Whether the word is one of the exact words of the index.
+    exact: bool,
+    /// Whether the word can match as a prefix, i.e. it is the last word of the query.
+    prefix: bool,
+}
+
+/// The exactness rules of an index that concern a query: the exact words must match exactly,
+/// wherever they are, and the words of the exact attributes only match exactly. The words of the
+/// query can also be restricted to match in some of the searchable attributes only.
+pub struct Exactness {
+    words: Vec<QueryWord>,
+    exact_attributes: HashSet<String>,
+    searchable_attributes: Option<HashSet<String>>,
+    /// The attributes the words are allowed to match in, all the searchable ones when `None`.
+    attributes_to_search_on: Option<HashSet<String>>,
+}
+
+#[derive(PartialEq)]
+enum Match {
+    Exact,
+    /// A match with typos, or as a prefix.
+    Fuzzy,
+}
+
+/// The number of typos allowed for a word, as milli does.
+pub(super) fn allowed_typos(word: &str) -> usize {
+    match word.chars().count() {
+        0..=4 => 0,
+        5..=8 => 1,
+        _ => 2,
+    }
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + (ca != cb) as usize;
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl QueryWord {
+    /// Returns the number of typos of the match of the word on `token`, `None` if it doesn't
+    /// match. A match as a prefix has no typos.
+    fn typos(&self, token: &str) -> Option<usize> {
+        if token == self.text || (self.prefix && token.starts_with(&self.text)) {
+            return Some(0);
+        }
+
+        let typos = allowed_typos(&self.text);
+        if typos == 0 {
+            return None;
+        }
+        let word: Vec<_> = self.text.chars().collect();
+        let token: Vec<_> = token.chars().collect();
+        let distance = if self.prefix && token.len() > word.len() {
+            levenshtein(&word, &token[..word.len()])
+        } else {
+            levenshtein(&word, &token)
+        };
+        if distance <= typos {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    fn matches(&self, token: &str) -> Option<Match> {
+        if token == self.text {
+            Some(Match::Exact)
+        } else {
+            self.typos(token).map(|_| Match::Fuzzy)
+        }
+    }
+}
+
+/// Returns the number of typos of the match of the query `word` on `token`, as a prefix if
+/// `prefix`, `None` if it doesn't match.
+pub(super) fn match_typos(word: &str, prefix: bool, token: &str) -> Option<usize> {
+    let word = QueryWord {
+        text: word.to_string(),
+        exact: false,
+        prefix,
+    };
+    word.typos(token)
+}
+
+/// Returns whether the query `word` matches `token`, with typos, and as a prefix if `prefix`.
+pub(super) fn fuzzy_matches(word: &str, prefix: bool, token: &str) -> bool {
+    match_typos(word, prefix, token).is_some()
+}
+
+/// Returns the normalized words of `text`.
+pub(super) fn words<A: AsRef<[u8]>>(analyzer: &Analyzer<A>, text: &str) -> Vec<String> {
+    analyzer
+        .analyze(text)
+        .tokens()
+        .filter(|token| token.is_word())
+        .map(|token| token.text().to_string())
+        .collect()
+}
+
+/// Calls `f` on all the strings of a JSON value, converting the numbers to strings.
+pub(super) fn for_each_string(value: &Value, f: &mut impl FnMut(&str)) {
+    match value {
+        Value::String(s) => f(s),
+        Value::Number(n) => f(&n.to_string()),
+        Value::Array(values) => values.iter().for_each(|v| for_each_string(v, f)),
+        Value::Object(object) => object.values().for_each(|v| for_each_string(v, f)),
+        Value::Null | Value::Bool(_) => (),
+    }
+}
+
+impl Index {
+    /// Returns the exactness rules concerning the query `q`, searched on the
+    /// `attributes_to_search_on`, `None` when none of them does.
+    pub(super) fn exactness(
+        &self,
+        txn: &RoTxn,
+        q: &str,
+        attributes_to_search_on: Option<&HashSet<String>>,
+    ) -> anyhow::Result<Option<Exactness>> {
+        let exact_attributes: HashSet<_> = self
+            .string_list(txn, EXACT_ATTRIBUTES_KEY)?
+            .into_iter()
+            .collect();
+
+        let stop_words = fst::Set::default();
+        let analyzer = Analyzer::new(AnalyzerConfig::default_with_stopwords(&stop_words));
+        let exact_words: HashSet<_> = self
+            .string_list(txn, EXACT_WORDS_KEY)?
+            .iter()
+            .flat_map(|word| words(&analyzer, word))
+            .collect();
+
+        // The last word is a prefix, unless the query ends with a separator.
+        let ends_with_word = q.chars().last().map_or(false, char::is_alphanumeric);
+        let texts = words(&analyzer, q);
+        let last = texts.len().saturating_sub(1);
+        let query_words: Vec<_> = texts
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| QueryWord {
+                exact: exact_words.contains(&text),
+                prefix: ends_with_word && i == last,
+                text,
+            })
+            .collect();
+
+        if exact_attributes.is_empty()
+            && query_words.iter().all(|w| !w.exact)
+            && attributes_to_search_on.is_none()
+        {
+            return Ok(None);
+        }
+
+        let searchable_attributes: Option<HashSet<_>> = self
+            .searchable_fields(txn)?
+            .map(|fields| fields.into_iter().map(String::from).collect());
+
+        if let (Some(searchable), Some(attributes)) =
+            (&searchable_attributes, attributes_to_search_on)
+        {
+            if let Some(attribute) = attributes.iter().find(|a| !searchable.contains(*a)) {
+                bail!(
+                    "Attribute `{}` is not searchable, it can't be used in `attributesToSearchOn`.",
+                    attribute
+                );
+            }
+        }
+
+        Ok(Some(Exactness {
+            words: query_words,
+            exact_attributes,
+            searchable_attributes,
+            attributes_to_search_on: attributes_to_search_on.cloned(),
+        }))
+    }
+}
+
+impl Exactness {
+    /// Whether a hit, given the JSON values of its fields, respects the exactness rules: none of
+    /// the words of the query matches only in a way they forbid, i.e. an exact word matching
+    /// with a typo or as a prefix, a word matching with a typo or as a prefix in an exact
+    /// attribute, or a word matching outside of the attributes to search on.
+    ///
+    /// The words that the hit doesn't match at all are ignored, as the search may drop some
+    /// words of the query.
+    pub(super) fn allows(&self, fields: &[(&str, &[u8])]) -> anyhow::Result<bool> {
+        let stop_words = fst::Set::default();
+        let analyzer = Analyzer::new(AnalyzerConfig::default_with_stopwords(&stop_words));
+
+        // For each word of the query, whether the hit matches it, and whether one of these
+        // matches is allowed.
+        let mut matches = vec![(false, false); self.words.len()];
+
+        for (name, value) in fields.iter().copied() {
+            if let Some(ref searchable) = self.searchable_attributes {
+                if !searchable.contains(name) {
+                    continue;
+                }
+            }
+            let exact_attribute = self.exact_attributes.contains(name);
+            let searched_attribute = self
+                .attributes_to_search_on
+                .as_ref()
+                .map_or(true, |attributes| attributes.contains(name));
+
+            let value: Value = serde_json::from_slice(value)?;
+            for_each_string(&value, &mut |text| {
+                for token in words(&analyzer, text) {
+                    for (word, (found, allowed)) in self.words.iter().zip(&mut matches) {
+                        match word.matches(&token) {
+                            Some(_) if !searched_attribute => (),
+                            Some(Match::Exact) => *allowed = true,
+                            Some(Match::Fuzzy) if !word.exact && !exact_attribute => {
+                                *allowed = true
+                            }
+                            Some(Match::Fuzzy) => (),
+                            None => continue,
+                        }
+                        *found = true;
+                    }
+                }
+            });
+        }
+
+        Ok(matches.iter().all(|(found, allowed)| !found || *allowed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn word(text: &str, prefix: bool) -> QueryWord {
+        QueryWord {
+            text: text.to_string(),
+            exact: false,
+            prefix,
+        }
+    }
+
+    #[test]
+    fn test_query_word_matches() {
+        assert!(word("cat", false).matches("cat") == Some(Match::Exact));
+        assert!(word("cat", false).matches("bat").is_none());
+        assert!(word("cat", true).matches("catalog") == Some(Match::Fuzzy));
+        assert!(word("kitten", false).matches("kiten") == Some(Match::Fuzzy));
+        assert!(word("kitten", false).matches("mitton").is_none());
+        assert!(word("kitten", true).matches("kittens") == Some(Match::Fuzzy));
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(levenshtein(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(levenshtein(&chars(""), &chars("abc")), 3);
+        assert_eq!(levenshtein(&chars("abc"), &chars("abc")), 0);
+    }
+}
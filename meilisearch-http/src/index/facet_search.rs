@@ -0,0 +1,124 @@
+use std::time::Instant;
+
+use anyhow::bail;
+use meilisearch_tokenizer::{Analyzer, AnalyzerConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::exactness::{for_each_string, fuzzy_matches, words};
+use super::search::parse_facets;
+use super::Index;
+
+const DEFAULT_FACET_SEARCH_LIMIT: usize = 10;
+
+const fn default_facet_search_limit() -> usize {
+    DEFAULT_FACET_SEARCH_LIMIT
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FacetSearchQuery {
+    /// The faceted attribute whose values are searched.
+    pub facet_name: String,
+    /// The words searched in the values, with typos, the last one as a prefix. All the values
+    /// are returned when `None`.
+    pub facet_query: Option<String>,
+    /// The search whose hits the values are counted on, all the documents when `None`.
+    pub q: Option<String>,
+    pub facet_filters: Option<Value>,
+    #[serde(default = "default_facet_search_limit")]
+    pub limit: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FacetHit {
+    pub value: Value,
+    pub count: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FacetSearchResult {
+    /// The matching values, from the most to the least frequent.
+    pub facet_hits: Vec<FacetHit>,
+    pub facet_query: Option<String>,
+    pub processing_time_ms: u128,
+}
+
+impl Index {
+    /// Searches the values of a faceted attribute among the hits of a search, and returns them
+    /// with the number of hits having them.
+    ///
+    /// The values are those of the facet distribution of milli, which is bounded: for the
+    /// attributes with a lot of values, a query or a filter narrows the searched values down.
+    pub fn perform_facet_search(
+        &self,
+        query: FacetSearchQuery,
+    ) -> anyhow::Result<FacetSearchResult> {
+        let before_search = Instant::now();
+        let rtxn = self.read_txn()?;
+
+        if !self.faceted_fields(&rtxn)?.contains_key(&query.facet_name) {
+            bail!(
+                "Attribute `{}` is not faceted, it can't be used in a facet search.",
+                query.facet_name
+            );
+        }
+
+        let mut search = self.search(&rtxn);
+        if let Some(ref q) = query.q {
+            search.query(q);
+        }
+        if let Some(ref facets) = query.facet_filters {
+            if let Some(facets) = parse_facets(facets, self, &rtxn)? {
+                search.facet_condition(facets);
+            }
+        }
+        // Only the candidates are needed, not the ranked hits.
+        search.limit(0);
+        let candidates = search.execute()?.candidates;
+
+        let mut distribution = self
+            .facets_distribution(&rtxn)
+            .facets(Some(&query.facet_name))
+            .candidates(candidates)
+            .execute()?;
+        let values = distribution.remove(&query.facet_name).unwrap_or_default();
+
+        let stop_words = fst::Set::default();
+        let analyzer = Analyzer::new(AnalyzerConfig::default_with_stopwords(&stop_words));
+        let query_words = match query.facet_query {
+            Some(ref facet_query) => words(&analyzer, facet_query),
+            None => Vec::new(),
+        };
+
+        let mut facet_hits = Vec::new();
+        for (value, count) in values {
+            let value = serde_json::to_value(value)?;
+            let mut value_words = Vec::new();
+            for_each_string(&value, &mut |text| {
+                value_words.extend(words(&analyzer, text))
+            });
+
+            let last = query_words.len().saturating_sub(1);
+            let matches = query_words.iter().enumerate().all(|(i, word)| {
+                value_words
+                    .iter()
+                    .any(|token| fuzzy_matches(word, i == last, token))
+            });
+            if matches {
+                facet_hits.push(FacetHit { value, count });
+            }
+        }
+
+        facet_hits.sort_by(|a, b| b.count.cmp(&a.count));
+        facet_hits.truncate(query.limit);
+
+        Ok(FacetSearchResult {
+            facet_hits,
+            facet_query: query.facet_query,
+            processing_time_ms: before_search.elapsed().as_millis(),
+        })
+    }
+}
@@ -0,0 +1,657 @@
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Bound;
+
+use chrono::DateTime;
+use heed::RoTxn;
+use meilisearch_error::{Code, ErrorCode};
+use roaring::RoaringBitmap;
+use serde_json::Value;
+use thiserror::Error;
+
+use super::filter_docids::{number_bytes, Kind};
+use super::flatten::is_field_or_parent;
+use super::Index;
+
+#[derive(Debug, Error)]
+pub enum FilterError {
+    #[error("Invalid filter, {message} at position {position}.")]
+    Syntax { position: usize, message: String },
+    #[error("Attribute `{attribute}` is not filterable, the filterable attributes are: {}. See the `attributesForFaceting` setting.", list(.filterable))]
+    NotFilterable {
+        attribute: String,
+        filterable: Vec<String>,
+    },
+}
+
+impl ErrorCode for FilterError {
+    fn error_code(&self) -> Code {
+        Code::Filter
+    }
+}
+
+fn list(attributes: &[String]) -> String {
+    if attributes.is_empty() {
+        return String::from("none");
+    }
+    let attributes: Vec<_> = attributes.iter().map(|a| format!("`{}`", a)).collect();
+    attributes.join(", ")
+}
+
+/// A filter of the documents, parsed from the `filter` parameter of a search, e.g.
+/// `genre IN [horror, thriller] AND (year > 2000 OR NOT director.name = "John Carpenter")`.
+///
+/// The attributes of the conditions are the names of the fields, the fields flattened from a
+/// nested object are named after their path, e.g. `director.name`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Condition {
+        attribute: String,
+        operator: Operator,
+    },
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+}
+
+/// The bounds of the comparisons are numbers, or RFC 3339 dates, e.g. `2021-01-01T00:00:00Z`,
+/// compared as timestamps, as are the dates of the documents.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operator {
+    Equal(String),
+    NotEqual(String),
+    Greater(f64),
+    GreaterOrEqual(f64),
+    Lower(f64),
+    LowerOrEqual(f64),
+    Between(f64, f64),
+    In(ValueSet),
+    NotIn(ValueSet),
+    /// The document contains the field, whatever its value.
+    Exists,
+    IsNull,
+    /// The value is an empty string, array or object.
+    IsEmpty,
+}
+
+/// The values of an `IN` condition, normalized to be looked up in the filter databases.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValueSet {
+    strings: HashSet<String>,
+    numbers: HashSet<u64>,
+}
+
+impl ValueSet {
+    fn insert(&mut self, value: &str) {
+        self.strings.insert(value.to_lowercase());
+        if let Some(number) = parse_number(value) {
+            self.numbers.insert(number_key(number));
+        }
+    }
+
+    /// Returns the numbers of the set, the values parsed as numbers or dates.
+    fn numbers(&self) -> impl Iterator<Item = f64> + '_ {
+        self.numbers.iter().map(|key| f64::from_bits(*key))
+    }
+}
+
+/// `0.0` and `-0.0` are equal but don't have the same bits, adding `0.0` turns the latter into
+/// the former.
+fn number_key(number: f64) -> u64 {
+    (number + 0.0).to_bits()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Operator(&'static str),
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+}
+
+/// Splits the filter into tokens, along with their position.
+fn tokenize(filter: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    const OPERATORS: [&str; 6] = [">=", "<=", "!=", "=", ">", "<"];
+
+    let mut tokens = Vec::new();
+    let mut chars = filter.char_indices().peekable();
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "()[],".contains(c) {
+            chars.next();
+            let token = match c {
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '[' => Token::OpenBracket,
+                ']' => Token::CloseBracket,
+                _ => Token::Comma,
+            };
+            tokens.push((position, token));
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, escaped)) => string.push(escaped),
+                        None => break,
+                    },
+                    Some((_, quote)) if quote == c => {
+                        tokens.push((position, Token::Quoted(string)));
+                        break;
+                    }
+                    Some((_, c)) => string.push(c),
+                    None => {
+                        return Err(FilterError::Syntax {
+                            position,
+                            message: String::from("unclosed quote"),
+                        })
+                    }
+                }
+            }
+        } else if let Some(operator) = OPERATORS
+            .iter()
+            .find(|op| filter[position..].starts_with(*op))
+        {
+            for _ in 0..operator.len() {
+                chars.next();
+            }
+            tokens.push((position, Token::Operator(*operator)));
+        } else {
+            let mut word = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() || "()[],\"'=!<>".contains(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if word.is_empty() {
+                return Err(FilterError::Syntax {
+                    position,
+                    message: format!("unexpected `{}`", c),
+                });
+            }
+            tokens.push((position, Token::Word(word)));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(_, token)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == keyword)
+    }
+
+    fn error(&self, message: impl Into<String>) -> FilterError {
+        let position = match self.tokens.get(self.position) {
+            Some((position, _)) => *position,
+            None => self.len,
+        };
+        FilterError::Syntax {
+            position,
+            message: message.into(),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.and()?;
+        while self.is_keyword("OR") {
+            self.next();
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.term()?;
+        while self.is_keyword("AND") {
+            self.next();
+            filter = Filter::And(Box::new(filter), Box::new(self.term()?));
+        }
+        Ok(filter)
+    }
+
+    fn term(&mut self) -> Result<Filter, FilterError> {
+        if self.is_keyword("NOT") {
+            self.next();
+            return Ok(Filter::Not(Box::new(self.term()?)));
+        }
+        if let Some(Token::Open) = self.peek() {
+            self.next();
+            let filter = self.expression()?;
+            return match self.next() {
+                Some(Token::Close) => Ok(filter),
+                _ => {
+                    self.position -= 1;
+                    Err(self.error("expected `)`"))
+                }
+            };
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Filter, FilterError> {
+        let attribute = match self.peek() {
+            Some(Token::Word(word)) | Some(Token::Quoted(word)) => word.clone(),
+            _ => return Err(self.error("expected an attribute")),
+        };
+        self.next();
+
+        // `NOT EXISTS`, `IS NOT NULL` and `IS NOT EMPTY` negate the condition.
+        let mut negated = false;
+        let operator = match self.peek() {
+            Some(Token::Operator(operator)) => {
+                let operator = *operator;
+                self.next();
+                match operator {
+                    "=" => Operator::Equal(self.value()?),
+                    "!=" => Operator::NotEqual(self.value()?),
+                    ">" => Operator::Greater(self.number()?),
+                    ">=" => Operator::GreaterOrEqual(self.number()?),
+                    "<" => Operator::Lower(self.number()?),
+                    _ => Operator::LowerOrEqual(self.number()?),
+                }
+            }
+            Some(Token::Word(word)) if word == "IN" => {
+                self.next();
+                Operator::In(self.values()?)
+            }
+            Some(Token::Word(word)) if word == "NOT" => {
+                self.next();
+                if self.is_keyword("IN") {
+                    self.next();
+                    Operator::NotIn(self.values()?)
+                } else if self.is_keyword("EXISTS") {
+                    self.next();
+                    negated = true;
+                    Operator::Exists
+                } else {
+                    return Err(self.error("expected `IN` or `EXISTS`"));
+                }
+            }
+            Some(Token::Word(word)) if word == "EXISTS" => {
+                self.next();
+                Operator::Exists
+            }
+            Some(Token::Word(word)) if word == "IS" => {
+                self.next();
+                if self.is_keyword("NOT") {
+                    self.next();
+                    negated = true;
+                }
+                if self.is_keyword("NULL") {
+                    self.next();
+                    Operator::IsNull
+                } else if self.is_keyword("EMPTY") {
+                    self.next();
+                    Operator::IsEmpty
+                } else {
+                    return Err(self.error("expected `NULL` or `EMPTY`"));
+                }
+            }
+            Some(Token::Word(_)) | Some(Token::Quoted(_)) => {
+                let from = self.number()?;
+                if !self.is_keyword("TO") {
+                    return Err(self.error("expected `TO`"));
+                }
+                self.next();
+                Operator::Between(from, self.number()?)
+            }
+            _ => return Err(self.error("expected an operator")),
+        };
+
+        let condition = Filter::Condition {
+            attribute,
+            operator,
+        };
+        if negated {
+            Ok(Filter::Not(Box::new(condition)))
+        } else {
+            Ok(condition)
+        }
+    }
+
+    fn value(&mut self) -> Result<String, FilterError> {
+        match self.peek() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => {
+                let value = value.clone();
+                self.next();
+                Ok(value)
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    /// Parses a list of values, e.g. `[horror, "science fiction"]`.
+    fn values(&mut self) -> Result<ValueSet, FilterError> {
+        let mut values = ValueSet::default();
+        match self.peek() {
+            Some(Token::OpenBracket) => self.next(),
+            _ => return Err(self.error("expected `[`")),
+        };
+        if let Some(Token::CloseBracket) = self.peek() {
+            self.next();
+            return Ok(values);
+        }
+        loop {
+            values.insert(&self.value()?);
+            match self.peek() {
+                Some(Token::Comma) => self.next(),
+                Some(Token::CloseBracket) => {
+                    self.next();
+                    return Ok(values);
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            };
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, FilterError> {
+        let value = self.value()?;
+        parse_number(&value).ok_or_else(|| {
+            self.position -= 1;
+            let error = self.error(format!(
+                "expected a number or an RFC 3339 date, found `{}`",
+                value
+            ));
+            self.position += 1;
+            error
+        })
+    }
+}
+
+impl Filter {
+    /// Parses the filter expression. The `AND` operator has precedence over `OR`.
+    pub fn parse(filter: &str) -> Result<Filter, FilterError> {
+        let mut parser = Parser {
+            tokens: tokenize(filter)?,
+            position: 0,
+            len: filter.len(),
+        };
+        let filter = parser.expression()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(_) => Err(parser.error("expected `AND`, `OR` or the end of the filter")),
+        }
+    }
+
+    fn attributes<'a>(&'a self, attributes: &mut Vec<&'a str>) {
+        match self {
+            Filter::Condition { attribute, .. } => attributes.push(attribute),
+            Filter::And(left, right) | Filter::Or(left, right) => {
+                left.attributes(attributes);
+                right.attributes(attributes);
+            }
+            Filter::Not(filter) => filter.attributes(attributes),
+        }
+    }
+}
+
+/// Calls `f` on the values that aren't arrays, the elements of the arrays are matched
+/// independently.
+pub(super) fn for_each_scalar<'a>(value: &'a Value, f: &mut impl FnMut(&'a Value)) {
+    match value {
+        Value::Array(values) => values.iter().for_each(|value| for_each_scalar(value, f)),
+        value => f(value),
+    }
+}
+
+/// Returns the timestamp of an RFC 3339 date, in seconds.
+pub(super) fn timestamp(date: &str) -> Option<f64> {
+    let date = DateTime::parse_from_rfc3339(date.trim()).ok()?;
+    Some(date.timestamp() as f64 + date.timestamp_subsec_nanos() as f64 / 1e9)
+}
+
+/// Parses a number, or the timestamp of an RFC 3339 date.
+pub(super) fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse().ok().or_else(|| timestamp(value))
+}
+
+impl Index {
+    /// Parses the filter, and checks that its attributes are filterable: they are faceted, or
+    /// were flattened from a faceted nested object.
+    pub(super) fn filter(&self, txn: &RoTxn, filter: &str) -> anyhow::Result<Filter> {
+        let filter = Filter::parse(filter)?;
+
+        let faceted: BTreeSet<_> = self
+            .faceted_fields(txn)?
+            .into_iter()
+            .map(|(f, _)| f)
+            .collect();
+        let mut attributes = Vec::new();
+        filter.attributes(&mut attributes);
+        for attribute in attributes {
+            if !faceted.iter().any(|f| is_field_or_parent(f, attribute)) {
+                return Err(FilterError::NotFilterable {
+                    attribute: attribute.to_string(),
+                    filterable: faceted.into_iter().collect(),
+                }
+                .into());
+            }
+        }
+
+        Ok(filter)
+    }
+
+    /// Returns the documents matching the filter, found in the filter databases.
+    pub(super) fn filter_candidates(
+        &self,
+        txn: &RoTxn,
+        filter: &Filter,
+    ) -> anyhow::Result<RoaringBitmap> {
+        let fields = self.filterable_fields(txn)?;
+        let documents_ids = self.documents_ids(txn)?;
+        self.evaluate_filter(txn, filter, &fields, &documents_ids)
+    }
+
+    fn evaluate_filter(
+        &self,
+        txn: &RoTxn,
+        filter: &Filter,
+        fields: &BTreeSet<String>,
+        documents_ids: &RoaringBitmap,
+    ) -> anyhow::Result<RoaringBitmap> {
+        match filter {
+            // The attribute of a condition matches the field it names, and the fields flattened
+            // from it when it names a nested object.
+            Filter::Condition {
+                attribute,
+                operator,
+            } => {
+                let mut docids = RoaringBitmap::new();
+                for field in fields.iter().filter(|f| is_field_or_parent(attribute, f)) {
+                    docids |= self.condition_docids(txn, field, operator)?;
+                }
+                // The documents don't match when one of their values is equal, or in the set.
+                match operator {
+                    Operator::NotEqual(_) | Operator::NotIn(_) => {
+                        Ok(documents_ids.clone() - docids)
+                    }
+                    _ => Ok(docids),
+                }
+            }
+            Filter::And(left, right) => {
+                let left = self.evaluate_filter(txn, left, fields, documents_ids)?;
+                let right = self.evaluate_filter(txn, right, fields, documents_ids)?;
+                Ok(left & right)
+            }
+            Filter::Or(left, right) => {
+                let left = self.evaluate_filter(txn, left, fields, documents_ids)?;
+                let right = self.evaluate_filter(txn, right, fields, documents_ids)?;
+                Ok(left | right)
+            }
+            Filter::Not(filter) => {
+                let docids = self.evaluate_filter(txn, filter, fields, documents_ids)?;
+                Ok(documents_ids.clone() - docids)
+            }
+        }
+    }
+
+    /// Returns the documents whose values of the field match the operator, those with a value
+    /// equal, or in the set, for the `!=` and `NOT IN` operators.
+    fn condition_docids(
+        &self,
+        txn: &RoTxn,
+        field: &str,
+        operator: &Operator,
+    ) -> anyhow::Result<RoaringBitmap> {
+        let range = match *operator {
+            Operator::Equal(ref expected) | Operator::NotEqual(ref expected) => {
+                return self.equal_docids(txn, field, expected)
+            }
+            Operator::In(ref values) | Operator::NotIn(ref values) => {
+                return self.in_docids(txn, field, values)
+            }
+            Operator::Exists => return self.filter_docids(txn, Kind::Exists, field, &[]),
+            Operator::IsNull => return self.filter_docids(txn, Kind::Null, field, &[]),
+            Operator::IsEmpty => return self.filter_docids(txn, Kind::Empty, field, &[]),
+            Operator::Greater(n) => (Bound::Excluded(n), Bound::Unbounded),
+            Operator::GreaterOrEqual(n) => (Bound::Included(n), Bound::Unbounded),
+            Operator::Lower(n) => (Bound::Unbounded, Bound::Excluded(n)),
+            Operator::LowerOrEqual(n) => (Bound::Unbounded, Bound::Included(n)),
+            Operator::Between(from, to) => (Bound::Included(from), Bound::Included(to)),
+        };
+        // No number is compared as greater or lower than `NaN`.
+        let is_nan = |bound: Bound<f64>| match bound {
+            Bound::Included(n) | Bound::Excluded(n) => n.is_nan(),
+            Bound::Unbounded => false,
+        };
+        if is_nan(range.0) || is_nan(range.1) {
+            return Ok(RoaringBitmap::new());
+        }
+        self.filter_range_docids(txn, field, range)
+    }
+
+    /// Returns the documents with a value of the field equal to `expected`. The strings and the
+    /// booleans are compared case insensitively, as the facet values are, and the numbers and the
+    /// dates by value.
+    fn equal_docids(
+        &self,
+        txn: &RoTxn,
+        field: &str,
+        expected: &str,
+    ) -> anyhow::Result<RoaringBitmap> {
+        let lowercase = expected.to_lowercase();
+        let mut docids = self.filter_docids(txn, Kind::String, field, lowercase.as_bytes())?;
+        docids |= self.filter_docids(txn, Kind::Bool, field, lowercase.as_bytes())?;
+        if let Ok(number) = expected.trim().parse::<f64>() {
+            docids |= self.filter_docids(txn, Kind::Number, field, &number_bytes(number))?;
+        }
+        if let Some(timestamp) = timestamp(expected) {
+            docids |= self.filter_docids(txn, Kind::Date, field, &number_bytes(timestamp))?;
+        }
+        Ok(docids)
+    }
+
+    /// Returns the documents with a value of the field in the set, compared as the `=` operator
+    /// compares them.
+    fn in_docids(
+        &self,
+        txn: &RoTxn,
+        field: &str,
+        values: &ValueSet,
+    ) -> anyhow::Result<RoaringBitmap> {
+        let mut docids = RoaringBitmap::new();
+        for string in &values.strings {
+            docids |= self.filter_docids(txn, Kind::String, field, string.as_bytes())?;
+            docids |= self.filter_docids(txn, Kind::Bool, field, string.as_bytes())?;
+        }
+        for number in values.numbers() {
+            docids |= self.filter_docids(txn, Kind::Number, field, &number_bytes(number))?;
+            docids |= self.filter_docids(txn, Kind::Date, field, &number_bytes(number))?;
+        }
+        Ok(docids)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn condition(attribute: &str, operator: Operator) -> Filter {
+        Filter::Condition {
+            attribute: attribute.to_string(),
+            operator,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let filter =
+            Filter::parse(r#"user.address.city = "Paris" AND (age >= 18 OR NOT a 1 TO 2)"#)
+                .unwrap();
+        let expected = Filter::And(
+            Box::new(condition(
+                "user.address.city",
+                Operator::Equal(String::from("Paris")),
+            )),
+            Box::new(Filter::Or(
+                Box::new(condition("age", Operator::GreaterOrEqual(18.0))),
+                Box::new(Filter::Not(Box::new(condition(
+                    "a",
+                    Operator::Between(1.0, 2.0),
+                )))),
+            )),
+        );
+        assert_eq!(filter, expected);
+
+        assert!(Filter::parse("a = ").is_err());
+        assert!(Filter::parse("a > b").is_err());
+        assert!(Filter::parse("(a = b").is_err());
+        assert!(Filter::parse("a = 'b").is_err());
+        assert!(Filter::parse("a = b c = d").is_err());
+        assert!(Filter::parse("a IN b").is_err());
+        assert!(Filter::parse("a IN [b c]").is_err());
+        assert!(Filter::parse("a NOT [b]").is_err());
+        assert!(Filter::parse("a IS").is_err());
+        assert!(Filter::parse("a > 2021-13-01T00:00:00Z").is_err());
+        assert_eq!(
+            Filter::parse(r#"a >= "2021-01-01T00:00:00Z""#).unwrap(),
+            condition("a", Operator::GreaterOrEqual(1609459200.0))
+        );
+        assert!(Filter::parse("a IS NOT b").is_err());
+        assert_eq!(
+            Filter::parse("a IS NOT NULL").unwrap(),
+            Filter::Not(Box::new(condition("a", Operator::IsNull)))
+        );
+        assert_eq!(
+            Filter::parse("a NOT EXISTS OR b IS EMPTY").unwrap(),
+            Filter::Or(
+                Box::new(Filter::Not(Box::new(condition("a", Operator::Exists)))),
+                Box::new(condition("b", Operator::IsEmpty)),
+            )
+        );
+
+        let mut values = ValueSet::default();
+        values.insert("b");
+        values.insert("c d");
+        assert_eq!(
+            Filter::parse(r#"a NOT IN [b, "c d"]"#).unwrap(),
+            condition("a", Operator::NotIn(values))
+        );
+    }
+}
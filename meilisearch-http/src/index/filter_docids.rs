@@ -0,0 +1,305 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+
+use heed::types::ByteSlice;
+use heed::{RoTxn, RwTxn};
+use roaring::RoaringBitmap;
+use serde_json::Value;
+
+use super::filter::{for_each_scalar, parse_number, timestamp};
+use super::flatten::is_field_or_parent;
+use super::{Index, BEU32};
+
+/// The prefix of the keys of the filter databases. milli opens a fixed number of databases, the
+/// documents of each entry of the filter databases are stored in its main database instead, at a
+/// key made of this prefix, the kind of the entry, the field and the value.
+const FILTER_DOCIDS_PREFIX: &[u8] = b"filter-docids\0";
+
+/// The documents of entries of the filter databases, by key.
+pub(super) type FilterEntries = BTreeMap<Vec<u8>, RoaringBitmap>;
+
+/// The kinds of the entries of the filter databases, the documents of each filterable field
+/// are indexed under each kind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Kind {
+    /// The documents containing the field, whatever its value.
+    Exists,
+    /// The documents whose value of the field is `null`.
+    Null,
+    /// The documents whose value of the field is an empty string, array or object.
+    Empty,
+    /// The documents containing a string, lowercased, in the field.
+    String,
+    Bool,
+    /// The documents containing a number in the field, for the equality.
+    Number,
+    /// The documents containing an RFC 3339 date in the field, by timestamp, for the equality.
+    Date,
+    /// The documents containing a number, a string parsed as a number or a date in the field,
+    /// for the comparisons.
+    Range,
+}
+
+impl Kind {
+    fn byte(self) -> u8 {
+        match self {
+            Kind::Exists => b'x',
+            Kind::Null => b'n',
+            Kind::Empty => b'e',
+            Kind::String => b's',
+            Kind::Bool => b'b',
+            Kind::Number => b'f',
+            Kind::Date => b'd',
+            Kind::Range => b'r',
+        }
+    }
+}
+
+fn key(kind: Kind, field: &str, value: &[u8]) -> Vec<u8> {
+    let mut key = FILTER_DOCIDS_PREFIX.to_vec();
+    key.push(kind.byte());
+    key.extend_from_slice(field.as_bytes());
+    key.push(0);
+    key.extend_from_slice(value);
+    key
+}
+
+/// Encodes a number so that the encoded numbers are ordered as the numbers are, for the range
+/// queries.
+pub(super) fn number_bytes(number: f64) -> [u8; 8] {
+    // `0.0` and `-0.0` are equal but don't have the same bits, adding `0.0` turns the latter
+    // into the former.
+    let bits = (number + 0.0).to_bits();
+    let bits = if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits | 1 << 63
+    };
+    bits.to_be_bytes()
+}
+
+/// Calls `f` on the entries of the filter databases of a value of a field. The elements of the
+/// arrays are indexed independently.
+fn for_each_entry(value: &Value, f: &mut impl FnMut(Kind, &[u8])) {
+    f(Kind::Exists, &[]);
+    match value {
+        Value::Null => f(Kind::Null, &[]),
+        Value::String(string) if string.is_empty() => f(Kind::Empty, &[]),
+        Value::Array(values) if values.is_empty() => f(Kind::Empty, &[]),
+        Value::Object(object) if object.is_empty() => f(Kind::Empty, &[]),
+        _ => (),
+    }
+
+    for_each_scalar(value, &mut |value| match value {
+        Value::String(string) => {
+            f(Kind::String, string.to_lowercase().as_bytes());
+            if let Some(timestamp) = timestamp(string) {
+                f(Kind::Date, &number_bytes(timestamp));
+            }
+            if let Some(number) = parse_number(string) {
+                f(Kind::Range, &number_bytes(number));
+            }
+        }
+        Value::Number(number) => {
+            if let Some(number) = number.as_f64() {
+                f(Kind::Number, &number_bytes(number));
+                f(Kind::Range, &number_bytes(number));
+            }
+        }
+        Value::Bool(boolean) => f(Kind::Bool, boolean.to_string().as_bytes()),
+        Value::Null | Value::Array(_) | Value::Object(_) => (),
+    });
+}
+
+fn as_slice(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key),
+        Bound::Excluded(key) => Bound::Excluded(key),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+fn decode(bytes: &[u8]) -> anyhow::Result<RoaringBitmap> {
+    Ok(RoaringBitmap::deserialize_from(bytes)?)
+}
+
+fn encode(docids: &RoaringBitmap) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(docids.serialized_size());
+    docids.serialize_into(&mut bytes)?;
+    Ok(bytes)
+}
+
+impl Index {
+    /// Returns the documents of an entry of the filter databases.
+    pub(super) fn filter_docids(
+        &self,
+        txn: &RoTxn,
+        kind: Kind,
+        field: &str,
+        value: &[u8],
+    ) -> anyhow::Result<RoaringBitmap> {
+        match self
+            .main
+            .get::<_, ByteSlice, ByteSlice>(txn, &key(kind, field, value))?
+        {
+            Some(bytes) => decode(bytes),
+            None => Ok(RoaringBitmap::new()),
+        }
+    }
+
+    /// Returns the documents containing a number, a string parsed as a number or a date, in the
+    /// field, whose value is in the range.
+    pub(super) fn filter_range_docids(
+        &self,
+        txn: &RoTxn,
+        field: &str,
+        (from, to): (Bound<f64>, Bound<f64>),
+    ) -> anyhow::Result<RoaringBitmap> {
+        let bound = |bound: Bound<f64>, unbounded: Vec<u8>| match bound {
+            Bound::Included(number) => {
+                Bound::Included(key(Kind::Range, field, &number_bytes(number)))
+            }
+            Bound::Excluded(number) => {
+                Bound::Excluded(key(Kind::Range, field, &number_bytes(number)))
+            }
+            Bound::Unbounded => Bound::Included(unbounded),
+        };
+        // The keys of the numbers of the field are all longer than the prefix, and lower than
+        // the key of the greatest number of 8 bytes.
+        let from = bound(from, key(Kind::Range, field, &[]));
+        let to = bound(to, key(Kind::Range, field, &[u8::MAX; 8]));
+
+        let mut docids = RoaringBitmap::new();
+        let range = (as_slice(&from), as_slice(&to));
+        for entry in self.main.range::<_, ByteSlice, ByteSlice, _>(txn, &range)? {
+            let (_, bytes) = entry?;
+            docids |= decode(bytes)?;
+        }
+        Ok(docids)
+    }
+
+    /// Returns the names of the filterable fields, the faceted fields and the fields flattened
+    /// from them.
+    pub(super) fn filterable_fields(&self, txn: &RoTxn) -> anyhow::Result<BTreeSet<String>> {
+        let faceted = self.faceted_fields(txn)?;
+        Ok(self
+            .fields_ids_map(txn)?
+            .iter()
+            .map(|(_, name)| name)
+            .filter(|name| faceted.keys().any(|f| is_field_or_parent(f, name)))
+            .map(String::from)
+            .collect())
+    }
+
+    /// Returns the entries of the filter databases of the documents `docids`, computed from their
+    /// values, by key.
+    pub(super) fn filter_entries(
+        &self,
+        txn: &RoTxn,
+        docids: &RoaringBitmap,
+    ) -> anyhow::Result<FilterEntries> {
+        let mut entries = FilterEntries::new();
+        let filterable = self.filterable_fields(txn)?;
+        if filterable.is_empty() {
+            return Ok(entries);
+        }
+        let fields_ids_map = self.fields_ids_map(txn)?;
+
+        for docid in docids.iter() {
+            let document = match self.documents.get(txn, &BEU32::new(docid))? {
+                Some(document) => document,
+                None => continue,
+            };
+            for (fid, value) in document.iter() {
+                let field = match fields_ids_map.name(fid) {
+                    Some(field) if filterable.contains(field) => field,
+                    _ => continue,
+                };
+                let value: Value = serde_json::from_slice(value)?;
+                for_each_entry(&value, &mut |kind, value| {
+                    entries
+                        .entry(key(kind, field, value))
+                        .or_insert_with(RoaringBitmap::new)
+                        .insert(docid);
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Indexes the documents `docids`, that are not in the filter databases, in the filter
+    /// databases.
+    pub(super) fn index_filter_docids(
+        &self,
+        txn: &mut RwTxn,
+        docids: &RoaringBitmap,
+    ) -> anyhow::Result<()> {
+        for (key, docids) in self.filter_entries(txn, docids)? {
+            let docids = match self.main.get::<_, ByteSlice, ByteSlice>(txn, &key)? {
+                Some(bytes) => decode(bytes)? | docids,
+                None => docids,
+            };
+            self.main
+                .put::<_, ByteSlice, ByteSlice>(txn, &key, &encode(&docids)?)?;
+        }
+        Ok(())
+    }
+
+    /// Removes documents from the filter databases. `entries` are their entries, returned by
+    /// `filter_entries` before the documents were replaced or deleted, only these keys are
+    /// updated.
+    pub(super) fn remove_filter_entries(
+        &self,
+        txn: &mut RwTxn,
+        entries: FilterEntries,
+    ) -> anyhow::Result<()> {
+        for (key, docids) in entries {
+            let docids = match self.main.get::<_, ByteSlice, ByteSlice>(txn, &key)? {
+                Some(bytes) => decode(bytes)? - docids,
+                None => continue,
+            };
+            if docids.is_empty() {
+                self.main.delete::<_, ByteSlice>(txn, &key)?;
+            } else {
+                self.main
+                    .put::<_, ByteSlice, ByteSlice>(txn, &key, &encode(&docids)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Indexes all the documents in the filter databases again, once the filterable fields
+    /// changed.
+    pub(super) fn reindex_filter_docids(&self, txn: &mut RwTxn) -> anyhow::Result<()> {
+        self.clear_filter_docids(txn)?;
+        let docids = self.documents_ids(txn)?;
+        self.index_filter_docids(txn, &docids)
+    }
+
+    /// Removes all the documents from the filter databases.
+    pub(super) fn clear_filter_docids(&self, txn: &mut RwTxn) -> anyhow::Result<()> {
+        let keys = self
+            .main
+            .prefix_iter::<_, ByteSlice, ByteSlice>(txn, FILTER_DOCIDS_PREFIX)?
+            .map(|entry| entry.map(|(key, _)| key.to_vec()))
+            .collect::<Result<Vec<_>, _>>()?;
+        for key in keys {
+            self.main.delete::<_, ByteSlice>(txn, &key)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_number_bytes() {
+        let numbers = [f64::NEG_INFINITY, -12.5, -1.0, 0.0, 1e-9, 1.0, 32.0, 1e12];
+        for pair in numbers.windows(2) {
+            assert!(number_bytes(pair[0]) < number_bytes(pair[1]), "{:?}", pair);
+        }
+        assert_eq!(number_bytes(-0.0), number_bytes(0.0));
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Write};
+
+use milli::update::UpdateFormat;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde_json::{Map, Value};
+
+/// The separator between the keys of the path of a flattened field.
+const SEPARATOR: char = '.';
+
+/// Flattens the nested objects of the document into top-level fields named after their path, e.g.
+/// `{ "user": { "name": "kero" } }` into `{ "user.name": "kero" }`, as milli only indexes the
+/// top-level fields. The arrays and the empty objects are kept as is. The names of the flattened
+/// fields are added to `nested`.
+pub fn flatten_document(
+    document: Map<String, Value>,
+    nested: &mut BTreeSet<String>,
+) -> Map<String, Value> {
+    let mut flattened = Map::new();
+    for (key, value) in document {
+        match value {
+            Value::Object(object) if !object.is_empty() => {
+                flatten_into(&key, object, &mut flattened, nested)
+            }
+            value => {
+                flattened.insert(key, value);
+            }
+        }
+    }
+    flattened
+}
+
+fn flatten_into(
+    prefix: &str,
+    object: Map<String, Value>,
+    flattened: &mut Map<String, Value>,
+    nested: &mut BTreeSet<String>,
+) {
+    for (key, value) in object {
+        let key = format!("{}{}{}", prefix, SEPARATOR, key);
+        match value {
+            Value::Object(object) if !object.is_empty() => {
+                flatten_into(&key, object, flattened, nested)
+            }
+            value => {
+                nested.insert(key.clone());
+                flattened.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Restores the nesting of the fields of the document that were flattened, listed in `nested`.
+pub fn unflatten_document(
+    document: Map<String, Value>,
+    nested: &BTreeSet<String>,
+) -> Map<String, Value> {
+    if nested.is_empty() {
+        return document;
+    }
+
+    let mut unflattened = Map::new();
+    for (key, value) in document {
+        if !nested.contains(&key) {
+            unflattened.insert(key, value);
+            continue;
+        }
+        let path: Vec<_> = key.split(SEPARATOR).collect();
+        // A field conflicting with a flattened one keeps its flattened name.
+        if let Err(value) = insert_nested(&mut unflattened, &path, value) {
+            unflattened.insert(key, value);
+        }
+    }
+    unflattened
+}
+
+/// Inserts the value at its path in the object, creating the missing objects. The value is
+/// returned if the path goes through a value that isn't an object.
+fn insert_nested(
+    object: &mut Map<String, Value>,
+    path: &[&str],
+    value: Value,
+) -> Result<(), Value> {
+    match path {
+        [] => Err(value),
+        [last] => {
+            object.insert(last.to_string(), value);
+            Ok(())
+        }
+        [first, rest @ ..] => match object
+            .entry(first.to_string())
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            Value::Object(inner) => insert_nested(inner, rest, value),
+            _ => Err(value),
+        },
+    }
+}
+
+/// Whether `field` is the `attribute` field, or was flattened from the `attribute` object.
+pub fn is_field_or_parent(attribute: &str, field: &str) -> bool {
+    match field.strip_prefix(attribute) {
+        Some(rest) => rest.is_empty() || rest.starts_with(SEPARATOR),
+        None => false,
+    }
+}
+
+/// Flattens the documents of a JSON array or stream of documents into `writer`, in the same
+/// format, adds the names of the flattened fields to `nested`, the names of all the fields of
+/// the flattened documents to `fields`, and their ids, the values of the `primary_key`, to `ids`.
+/// The values that aren't objects are copied as is, for milli to report them.
+pub fn flatten_documents(
+    reader: impl io::Read,
+    writer: impl io::Write,
+    format: UpdateFormat,
+    primary_key: Option<&str>,
+    nested: &mut BTreeSet<String>,
+    fields: &mut BTreeSet<String>,
+    ids: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    let reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+
+    match format {
+        UpdateFormat::JsonStream => {
+            let documents = serde_json::Deserializer::from_reader(reader).into_iter::<Value>();
+            for document in documents {
+                let document = flatten_value(document?, primary_key, nested, fields, ids);
+                serde_json::to_writer(&mut writer, &document)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        _ => {
+            let mut deserializer = serde_json::Deserializer::from_reader(reader);
+            let visitor = FlattenDocuments {
+                writer: &mut writer,
+                primary_key,
+                nested,
+                fields,
+                ids,
+            };
+            deserializer.deserialize_seq(visitor)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn flatten_value(
+    value: Value,
+    primary_key: Option<&str>,
+    nested: &mut BTreeSet<String>,
+    fields: &mut BTreeSet<String>,
+    ids: &mut Vec<String>,
+) -> Value {
+    match value {
+        Value::Object(document) => {
+            let document = flatten_document(document, nested);
+            for key in document.keys() {
+                if !fields.contains(key) {
+                    fields.insert(key.clone());
+                }
+            }
+            match primary_key.and_then(|primary_key| document.get(primary_key)) {
+                Some(Value::String(id)) => ids.push(id.clone()),
+                Some(Value::Number(id)) => ids.push(id.to_string()),
+                _ => (),
+            }
+            Value::Object(document)
+        }
+        value => value,
+    }
+}
+
+struct FlattenDocuments<'a, W> {
+    writer: W,
+    primary_key: Option<&'a str>,
+    nested: &'a mut BTreeSet<String>,
+    fields: &'a mut BTreeSet<String>,
+    ids: &'a mut Vec<String>,
+}
+
+impl<'de, W: io::Write> Visitor<'de> for FlattenDocuments<'_, W> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of documents")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Self::Value, A::Error> {
+        self.writer.write_all(b"[").map_err(de::Error::custom)?;
+        let mut first = true;
+        while let Some(document) = seq.next_element::<Value>()? {
+            if !first {
+                self.writer.write_all(b",").map_err(de::Error::custom)?;
+            }
+            first = false;
+            let document = flatten_value(
+                document,
+                self.primary_key,
+                self.nested,
+                self.fields,
+                self.ids,
+            );
+            serde_json::to_writer(&mut self.writer, &document).map_err(de::Error::custom)?;
+        }
+        self.writer.write_all(b"]").map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(object) => object,
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn flatten_and_unflatten() {
+        let document = object(json!({
+            "id": 1,
+            "user": { "name": "kero", "address": { "city": "Paris" }, "tags": [{ "a": 1 }] },
+            "empty": {},
+        }));
+        let mut nested = BTreeSet::new();
+        let flattened = flatten_document(document.clone(), &mut nested);
+        assert_eq!(
+            Value::Object(flattened.clone()),
+            json!({
+                "id": 1,
+                "user.name": "kero",
+                "user.address.city": "Paris",
+                "user.tags": [{ "a": 1 }],
+                "empty": {},
+            })
+        );
+        assert_eq!(nested.len(), 3);
+        assert_eq!(unflatten_document(flattened, &nested), document);
+    }
+
+    #[test]
+    fn flatten_a_payload() {
+        let payload = br#"[{ "id": 1, "a": { "b": 2 } }, { "id": 2, "c": 3 }]"#;
+        let mut flattened = Vec::new();
+        let mut nested = BTreeSet::new();
+        let mut fields = BTreeSet::new();
+        let mut ids = Vec::new();
+        flatten_documents(
+            &payload[..],
+            &mut flattened,
+            UpdateFormat::Json,
+            Some("id"),
+            &mut nested,
+            &mut fields,
+            &mut ids,
+        )
+        .unwrap();
+        let flattened: Value = serde_json::from_slice(&flattened).unwrap();
+        assert_eq!(
+            flattened,
+            json!([{ "id": 1, "a.b": 2 }, { "id": 2, "c": 3 }])
+        );
+        assert!(nested.contains("a.b"));
+        let fields: Vec<_> = fields.iter().map(String::as_str).collect();
+        assert_eq!(fields, ["a.b", "c", "id"]);
+        assert_eq!(ids, ["1", "2"]);
+    }
+}
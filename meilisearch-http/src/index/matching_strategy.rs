@@ -0,0 +1,62 @@
+use std::str;
+
+use fst::Streamer;
+use heed::RoTxn;
+use meilisearch_tokenizer::{Analyzer, AnalyzerConfig};
+use roaring::RoaringBitmap;
+
+use super::exactness::{fuzzy_matches, words};
+use super::Index;
+
+impl Index {
+    /// Returns the documents matching all the words of the query `q`, `None` when it has no
+    /// words.
+    ///
+    /// The words of the index matching a word of the query, with typos or as a prefix for the
+    /// last one, as milli does, are found in the words FST, and their documents in the database
+    /// of the words.
+    pub(super) fn all_words_docids(
+        &self,
+        txn: &RoTxn,
+        q: &str,
+    ) -> anyhow::Result<Option<RoaringBitmap>> {
+        let stop_words = fst::Set::default();
+        let analyzer = Analyzer::new(AnalyzerConfig::default_with_stopwords(&stop_words));
+        let query_words = words(&analyzer, q);
+        if query_words.is_empty() {
+            return Ok(None);
+        }
+
+        // The last word is a prefix, unless the query ends with a separator.
+        let prefix = q.chars().last().map_or(false, char::is_alphanumeric);
+        let last = query_words.len() - 1;
+
+        let mut words_docids = vec![RoaringBitmap::new(); query_words.len()];
+        let words_fst = self.words_fst(txn)?;
+        let mut stream = words_fst.stream();
+        while let Some(word) = stream.next() {
+            let word = match str::from_utf8(word) {
+                Ok(word) => word,
+                Err(_) => continue,
+            };
+            let matched: Vec<_> = (0..query_words.len())
+                .filter(|&i| fuzzy_matches(&query_words[i], prefix && i == last, word))
+                .collect();
+            if matched.is_empty() {
+                continue;
+            }
+            if let Some(docids) = self.word_docids.get(txn, word)? {
+                for i in matched {
+                    words_docids[i] |= &docids;
+                }
+            }
+        }
+
+        let mut words_docids = words_docids.into_iter();
+        let mut docids = words_docids.next().unwrap_or_else(RoaringBitmap::new);
+        for word_docids in words_docids {
+            docids &= word_docids;
+        }
+        Ok(Some(docids))
+    }
+}
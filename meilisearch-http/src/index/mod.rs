@@ -1,19 +1,54 @@
+mod exactness;
+mod facet_search;
+mod filter;
+mod filter_docids;
+mod flatten;
+mod matching_strategy;
+mod phrase;
+mod ranking_score;
 mod search;
 mod updates;
+mod validation;
+mod vector;
 
-use std::collections::HashSet;
-use std::ops::Deref;
+use std::collections::{BTreeSet, HashSet};
+use std::io::Write;
+use std::ops::{Bound, Deref};
 use std::sync::Arc;
 
 use anyhow::{bail, Context};
+use heed::types::{OwnedType, SerdeJson, Str};
 use milli::obkv_to_json;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json::{Map, Value};
 
-pub use search::{SearchQuery, SearchResult, DEFAULT_SEARCH_LIMIT};
-pub use updates::{Facets, Settings, UpdateResult};
+pub use facet_search::{FacetSearchQuery, FacetSearchResult};
+pub use filter::FilterError;
+use flatten::{is_field_or_parent, unflatten_document};
+pub use search::{MatchingStrategy, SearchQuery, SearchResult, DEFAULT_SEARCH_LIMIT};
+pub use updates::{DocumentsAdditionResult, Facets, PaginationSettings, Settings, UpdateResult};
+pub use updates::{PrefixSearch, ProximityPrecision};
+pub use validation::{document_errors, DocumentError, InvalidDocuments};
+pub use validation::{first_document, infer_primary_key, validate_documents};
+pub use validation::{PrimaryKeyError, ValidationReport};
+pub use vector::VECTORS_FIELD;
 
 pub type Document = Map<String, Value>;
 
+type BEU32 = heed::zerocopy::U32<heed::byteorder::BE>;
+
+const MAX_TOTAL_HITS_KEY: &str = "max-total-hits";
+const LANGUAGES_KEY: &str = "languages";
+const SEPARATOR_TOKENS_KEY: &str = "separator-tokens";
+const NON_SEPARATOR_TOKENS_KEY: &str = "non-separator-tokens";
+const DICTIONARY_KEY: &str = "dictionary";
+const EXACT_WORDS_KEY: &str = "exact-words";
+const EXACT_ATTRIBUTES_KEY: &str = "exact-attributes";
+const PROXIMITY_PRECISION_KEY: &str = "proximity-precision";
+const PREFIX_SEARCH_KEY: &str = "prefix-search";
+const NESTED_FIELDS_KEY: &str = "nested-fields";
+
 #[derive(Clone)]
 pub struct Index(pub Arc<milli::Index>);
 
@@ -34,10 +69,12 @@ impl Index {
             .map(|fields| fields.into_iter().map(String::from).collect())
             .unwrap_or_else(|| vec!["*".to_string()]);
 
-        let searchable_attributes = self
-            .searchable_fields(&txn)?
-            .map(|fields| fields.into_iter().map(String::from).collect())
-            .unwrap_or_else(|| vec!["*".to_string()]);
+        let searchable_attributes = match self.searchable_fields(&txn)? {
+            Some(fields) if !self.is_searchable_wildcard(&txn)? => {
+                fields.into_iter().map(String::from).collect()
+            }
+            _ => vec!["*".to_string()],
+        };
 
         let faceted_attributes = self
             .faceted_fields(&txn)?
@@ -51,14 +88,146 @@ impl Index {
             .map(|c| c.to_string())
             .collect();
 
+        let pagination = PaginationSettings {
+            max_total_hits: self.max_total_hits(&txn)?,
+        };
+
+        let separator_tokens = self
+            .string_list(&txn, SEPARATOR_TOKENS_KEY)?
+            .into_iter()
+            .collect();
+        let non_separator_tokens = self
+            .string_list(&txn, NON_SEPARATOR_TOKENS_KEY)?
+            .into_iter()
+            .collect();
+        let dictionary = self
+            .string_list(&txn, DICTIONARY_KEY)?
+            .into_iter()
+            .collect();
+        let exact_words = self
+            .string_list(&txn, EXACT_WORDS_KEY)?
+            .into_iter()
+            .collect();
+        let exact_attributes = self
+            .string_list(&txn, EXACT_ATTRIBUTES_KEY)?
+            .into_iter()
+            .collect();
+
         Ok(Settings {
             displayed_attributes: Some(Some(displayed_attributes)),
             searchable_attributes: Some(Some(searchable_attributes)),
             attributes_for_faceting: Some(Some(faceted_attributes)),
             ranking_rules: Some(Some(criteria)),
+            pagination: Some(Some(pagination)),
+            languages: Some(Some(self.string_list(&txn, LANGUAGES_KEY)?)),
+            separator_tokens: Some(Some(separator_tokens)),
+            non_separator_tokens: Some(Some(non_separator_tokens)),
+            dictionary: Some(Some(dictionary)),
+            exact_words: Some(Some(exact_words)),
+            exact_attributes: Some(Some(exact_attributes)),
+            proximity_precision: Some(Some(
+                self.json_setting(&txn, PROXIMITY_PRECISION_KEY)?
+                    .unwrap_or_default(),
+            )),
+            prefix_search: Some(Some(self.prefix_search(&txn)?)),
         })
     }
 
+    /// Returns the maximum number of hits that can be reached through pagination, `None` when
+    /// the pagination isn't bounded.
+    pub fn max_total_hits(&self, txn: &heed::RoTxn) -> anyhow::Result<Option<u64>> {
+        Ok(self
+            .main
+            .get::<_, Str, OwnedType<u64>>(txn, MAX_TOTAL_HITS_KEY)?)
+    }
+
+    pub fn put_max_total_hits(&self, txn: &mut heed::RwTxn, value: u64) -> anyhow::Result<()> {
+        self.main
+            .put::<_, Str, OwnedType<u64>>(txn, MAX_TOTAL_HITS_KEY, &value)?;
+        Ok(())
+    }
+
+    pub fn delete_max_total_hits(&self, txn: &mut heed::RwTxn) -> anyhow::Result<bool> {
+        Ok(self.main.delete::<_, Str>(txn, MAX_TOTAL_HITS_KEY)?)
+    }
+
+    /// Returns whether the last word of the queries matches the words it is a prefix of.
+    pub fn prefix_search(&self, txn: &heed::RoTxn) -> anyhow::Result<PrefixSearch> {
+        Ok(self
+            .json_setting(txn, PREFIX_SEARCH_KEY)?
+            .unwrap_or_default())
+    }
+
+    /// Returns the names of the fields flattened from nested objects, to restore the nesting of
+    /// the retrieved documents.
+    pub fn nested_fields(&self, txn: &heed::RoTxn) -> anyhow::Result<BTreeSet<String>> {
+        Ok(self
+            .string_list(txn, NESTED_FIELDS_KEY)?
+            .into_iter()
+            .collect())
+    }
+
+    fn put_nested_fields(
+        &self,
+        txn: &mut heed::RwTxn,
+        nested: &BTreeSet<String>,
+    ) -> anyhow::Result<()> {
+        let nested: Vec<_> = nested.iter().cloned().collect();
+        self.put_string_list(txn, NESTED_FIELDS_KEY, &nested)
+    }
+
+    /// Returns a list of strings stored at `key` in the main database, for the settings not
+    /// handled by milli. The list is empty when it isn't set.
+    fn string_list(&self, txn: &heed::RoTxn, key: &str) -> anyhow::Result<Vec<String>> {
+        let list = self
+            .main
+            .get::<_, Str, SerdeJson<Vec<String>>>(txn, key)?
+            .unwrap_or_default();
+        Ok(list)
+    }
+
+    /// Stores `list` at `key`, or deletes the key when the list is empty.
+    fn put_string_list(
+        &self,
+        txn: &mut heed::RwTxn,
+        key: &str,
+        list: &[String],
+    ) -> anyhow::Result<()> {
+        if list.is_empty() {
+            self.main.delete::<_, Str>(txn, key)?;
+        } else {
+            self.main
+                .put::<_, Str, SerdeJson<&[String]>>(txn, key, &list)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the value stored as JSON at `key` in the main database, for the settings not
+    /// handled by milli.
+    fn json_setting<T: DeserializeOwned + 'static>(
+        &self,
+        txn: &heed::RoTxn,
+        key: &str,
+    ) -> anyhow::Result<Option<T>> {
+        Ok(self.main.get::<_, Str, SerdeJson<T>>(txn, key)?)
+    }
+
+    /// Stores `value` at `key`, or deletes the key when the setting is reset.
+    fn put_json_setting<T: Serialize>(
+        &self,
+        txn: &mut heed::RwTxn,
+        key: &str,
+        value: Option<T>,
+    ) -> anyhow::Result<()> {
+        match value {
+            Some(value) => self.main.put::<_, Str, SerdeJson<T>>(txn, key, &value)?,
+            None => {
+                self.main.delete::<_, Str>(txn, key)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn retrieve_documents<S: AsRef<str>>(
         &self,
         offset: usize,
@@ -70,6 +239,7 @@ impl Index {
         let fields_ids_map = self.fields_ids_map(&txn)?;
         let fields_to_display =
             self.fields_to_display(&txn, attributes_to_retrieve, &fields_ids_map)?;
+        let nested_fields = self.nested_fields(&txn)?;
 
         let iter = self.documents.range(&txn, &(..))?.skip(offset).take(limit);
 
@@ -78,12 +248,69 @@ impl Index {
         for entry in iter {
             let (_id, obkv) = entry?;
             let object = obkv_to_json(&fields_to_display, &fields_ids_map, obkv)?;
-            documents.push(object);
+            documents.push(unflatten_document(object, &nested_fields));
         }
 
         Ok(documents)
     }
 
+    /// Writes all the documents of the index to `writer` as JSON lines, with all their fields,
+    /// whether they are displayed or not.
+    pub fn write_all_documents(&self, mut writer: impl Write) -> anyhow::Result<()> {
+        let txn = self.read_txn()?;
+
+        let fields_ids_map = self.fields_ids_map(&txn)?;
+        let all_fields: Vec<_> = fields_ids_map.iter().map(|(id, _)| id).collect();
+        let nested_fields = self.nested_fields(&txn)?;
+
+        for entry in self.documents.range(&txn, &(..))? {
+            let (_id, obkv) = entry?;
+            let object = obkv_to_json(&all_fields, &fields_ids_map, obkv)?;
+            serde_json::to_writer(&mut writer, &unflatten_document(object, &nested_fields))?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns at most `limit` documents, in the order of their internal ids, starting after the
+    /// internal id `after`. The internal id of the last returned document is returned with them,
+    /// to retrieve the next ones.
+    pub fn retrieve_documents_after<S: AsRef<str>>(
+        &self,
+        after: Option<u32>,
+        limit: usize,
+        attributes_to_retrieve: Option<Vec<S>>,
+    ) -> anyhow::Result<(Vec<Map<String, Value>>, Option<u32>)> {
+        let txn = self.read_txn()?;
+
+        let fields_ids_map = self.fields_ids_map(&txn)?;
+        let fields_to_display =
+            self.fields_to_display(&txn, attributes_to_retrieve, &fields_ids_map)?;
+        let nested_fields = self.nested_fields(&txn)?;
+
+        let start = match after {
+            Some(id) => Bound::Excluded(BEU32::new(id)),
+            None => Bound::Unbounded,
+        };
+        let iter = self
+            .documents
+            .range(&txn, &(start, Bound::Unbounded))?
+            .take(limit);
+
+        let mut documents = Vec::new();
+        let mut last_id = None;
+
+        for entry in iter {
+            let (id, obkv) = entry?;
+            let object = obkv_to_json(&fields_to_display, &fields_ids_map, obkv)?;
+            documents.push(unflatten_document(object, &nested_fields));
+            last_id = Some(id.get());
+        }
+
+        Ok((documents, last_id))
+    }
+
     pub fn retrieve_document<S: AsRef<str>>(
         &self,
         doc_id: String,
@@ -95,6 +322,7 @@ impl Index {
 
         let fields_to_display =
             self.fields_to_display(&txn, attributes_to_retrieve, &fields_ids_map)?;
+        let nested_fields = self.nested_fields(&txn)?;
 
         let internal_id = self
             .external_documents_ids(&txn)?
@@ -108,31 +336,81 @@ impl Index {
             .map(|(_, d)| d);
 
         match document {
-            Some(document) => Ok(obkv_to_json(&fields_to_display, &fields_ids_map, document)?),
+            Some(document) => {
+                let object = obkv_to_json(&fields_to_display, &fields_ids_map, document)?;
+                Ok(unflatten_document(object, &nested_fields))
+            }
             None => bail!("Document with id {} not found", doc_id),
         }
     }
 
+    /// Returns the documents with the given external ids, in the order of `doc_ids`. The ids that
+    /// don't match any document are ignored.
+    pub fn retrieve_documents_by_ids<S: AsRef<str>>(
+        &self,
+        doc_ids: Vec<String>,
+        attributes_to_retrieve: Option<Vec<S>>,
+    ) -> anyhow::Result<Vec<Map<String, Value>>> {
+        let txn = self.read_txn()?;
+
+        let fields_ids_map = self.fields_ids_map(&txn)?;
+        let fields_to_display =
+            self.fields_to_display(&txn, attributes_to_retrieve, &fields_ids_map)?;
+        let nested_fields = self.nested_fields(&txn)?;
+
+        let external_documents_ids = self.external_documents_ids(&txn)?;
+        let internal_ids = doc_ids
+            .iter()
+            .filter_map(|id| external_documents_ids.get(id.as_bytes()));
+
+        let mut documents = Vec::new();
+
+        for (_id, obkv) in self.documents(&txn, internal_ids)? {
+            let object = obkv_to_json(&fields_to_display, &fields_ids_map, obkv)?;
+            documents.push(unflatten_document(object, &nested_fields));
+        }
+
+        Ok(documents)
+    }
+
     fn fields_to_display<S: AsRef<str>>(
         &self,
         txn: &heed::RoTxn,
         attributes_to_retrieve: Option<Vec<S>>,
         fields_ids_map: &milli::FieldsIdsMap,
     ) -> anyhow::Result<Vec<u8>> {
-        let mut displayed_fields_ids = match self.displayed_fields_ids(&txn)? {
-            Some(ids) => ids.into_iter().collect::<Vec<_>>(),
+        // The attributes select the fields flattened from their nested objects too.
+        let mut displayed_fields_ids = match self.displayed_fields(&txn)? {
+            Some(attrs) => fields_ids_map
+                .iter()
+                .filter(|(_, name)| attrs.iter().any(|attr| is_field_or_parent(attr, name)))
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>(),
             None => fields_ids_map.iter().map(|(id, _)| id).collect(),
         };
 
+        // The embeddings are only returned when they are explicitly retrieved, as they are
+        // large and rarely useful to the clients.
+        let vectors_fid = match attributes_to_retrieve {
+            Some(ref attrs) if attrs.iter().any(|attr| attr.as_ref() == VECTORS_FIELD) => None,
+            _ => fields_ids_map.id(VECTORS_FIELD),
+        };
+
         let attributes_to_retrieve_ids = match attributes_to_retrieve {
-            Some(attrs) => attrs
+            Some(attrs) if attrs.iter().all(|attr| attr.as_ref() != "*") => fields_ids_map
                 .iter()
-                .filter_map(|f| fields_ids_map.id(f.as_ref()))
+                .filter(|(_, name)| {
+                    attrs
+                        .iter()
+                        .any(|attr| is_field_or_parent(attr.as_ref(), name))
+                })
+                .map(|(id, _)| id)
                 .collect::<HashSet<_>>(),
-            None => fields_ids_map.iter().map(|(id, _)| id).collect(),
+            _ => fields_ids_map.iter().map(|(id, _)| id).collect(),
         };
 
-        displayed_fields_ids.retain(|fid| attributes_to_retrieve_ids.contains(fid));
+        displayed_fields_ids
+            .retain(|fid| attributes_to_retrieve_ids.contains(fid) && Some(*fid) != vectors_fid);
         Ok(displayed_fields_ids)
     }
 }
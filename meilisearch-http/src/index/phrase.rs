@@ -0,0 +1,191 @@
+use heed::RoTxn;
+use meilisearch_tokenizer::{Analyzer, AnalyzerConfig};
+use roaring::RoaringBitmap;
+
+use super::exactness::words;
+use super::Index;
+
+/// The phrases of a query, the double-quoted parts of `q`, whose words must all match exactly
+/// and next to each other in a searchable attribute, and its negative terms, the words prefixed
+/// with `-` outside of the phrases, that must not match.
+pub struct Phrases {
+    phrases: Vec<Vec<String>>,
+    negative_terms: Vec<Vec<String>>,
+}
+
+/// Returns the parts of `q` separated by double quotes, along with whether they are quoted.
+fn parts(q: &str) -> impl Iterator<Item = (&str, bool)> {
+    let parts: Vec<_> = q.split('"').collect();
+    // The parts at odd positions are quoted, the last one only if its quote is closed.
+    let closed = parts.len() % 2 == 1;
+    let last = parts.len() - 1;
+    parts
+        .into_iter()
+        .enumerate()
+        .map(move |(i, part)| (part, i % 2 == 1 && (closed || i != last)))
+}
+
+/// Returns the double-quoted parts of `q`. A quote that isn't closed is ignored.
+fn quoted_parts(q: &str) -> Vec<&str> {
+    parts(q)
+        .filter(|(_, quoted)| *quoted)
+        .map(|(part, _)| part)
+        .collect()
+}
+
+fn is_negative_term(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('-')
+}
+
+/// Returns the negative terms of `q`, without their `-`.
+fn negative_terms(q: &str) -> Vec<&str> {
+    parts(q)
+        .filter(|(_, quoted)| !quoted)
+        .flat_map(|(part, _)| part.split_whitespace())
+        .filter(|word| is_negative_term(word))
+        .map(|word| &word[1..])
+        .collect()
+}
+
+/// Returns `q` without its negative terms, the words that milli must search for.
+pub(super) fn without_negative_terms(q: &str) -> String {
+    let mut text = String::with_capacity(q.len());
+    for (i, (part, quoted)) in parts(q).enumerate() {
+        if i != 0 {
+            text.push('"');
+        }
+        if quoted {
+            text.push_str(part);
+            continue;
+        }
+        // The words are kept with the whitespaces preceding them, the trailing whitespaces tell
+        // milli that the last word isn't a prefix.
+        let mut rest = part;
+        while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+            let end = rest[start..]
+                .find(char::is_whitespace)
+                .map_or(rest.len(), |end| start + end);
+            if !is_negative_term(&rest[start..end]) {
+                text.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+        }
+        text.push_str(rest);
+    }
+    text
+}
+
+impl Phrases {
+    /// Returns the phrases and the negative terms of the query `q`, `None` when it has none.
+    pub(super) fn parse(q: &str) -> Option<Phrases> {
+        let stop_words = fst::Set::default();
+        let analyzer = Analyzer::new(AnalyzerConfig::default_with_stopwords(&stop_words));
+        let phrases: Vec<_> = quoted_parts(q)
+            .into_iter()
+            .map(|part| words(&analyzer, part))
+            .filter(|phrase| !phrase.is_empty())
+            .collect();
+        let negative_terms: Vec<_> = negative_terms(q)
+            .into_iter()
+            .map(|term| words(&analyzer, term))
+            .filter(|term| !term.is_empty())
+            .collect();
+
+        if phrases.is_empty() && negative_terms.is_empty() {
+            return None;
+        }
+
+        Some(Phrases {
+            phrases,
+            negative_terms,
+        })
+    }
+}
+
+impl Index {
+    /// Returns the documents containing all the phrases of the query, `None` when it has none.
+    ///
+    /// The documents containing the words of a phrase next to each other are found in the
+    /// proximity database of the word pairs, those of a single word phrase in the database of
+    /// the words. Only the searchable attributes are indexed in these databases.
+    pub(super) fn phrases_docids(
+        &self,
+        txn: &RoTxn,
+        phrases: &Phrases,
+    ) -> anyhow::Result<Option<RoaringBitmap>> {
+        let mut docids: Option<RoaringBitmap> = None;
+        for phrase in &phrases.phrases {
+            let phrase_docids = self.phrase_docids(txn, phrase)?;
+            docids = Some(match docids {
+                Some(docids) => docids & phrase_docids,
+                None => phrase_docids,
+            });
+        }
+        Ok(docids)
+    }
+
+    /// Returns the documents containing one of the negative terms of the query, in the same
+    /// databases as the phrases, as a negative term is split into several words when it contains
+    /// separators, e.g. `-new-york`.
+    pub(super) fn negative_terms_docids(
+        &self,
+        txn: &RoTxn,
+        phrases: &Phrases,
+    ) -> anyhow::Result<RoaringBitmap> {
+        let mut docids = RoaringBitmap::new();
+        for term in &phrases.negative_terms {
+            docids |= self.phrase_docids(txn, term)?;
+        }
+        Ok(docids)
+    }
+
+    /// Returns the documents containing the words of `phrase` next to each other. The phrases of
+    /// more than two words are matched pair by pair.
+    fn phrase_docids(&self, txn: &RoTxn, phrase: &[String]) -> anyhow::Result<RoaringBitmap> {
+        if let [word] = phrase {
+            let docids = self.word_docids.get(txn, word)?;
+            return Ok(docids.unwrap_or_else(RoaringBitmap::new));
+        }
+
+        let mut docids: Option<RoaringBitmap> = None;
+        for pair in phrase.windows(2) {
+            let pair_docids = self
+                .word_pair_proximity_docids
+                .get(txn, &(pair[0].as_str(), pair[1].as_str(), 1))?
+                .unwrap_or_else(RoaringBitmap::new);
+            docids = Some(match docids {
+                Some(docids) => docids & pair_docids,
+                None => pair_docids,
+            });
+        }
+        Ok(docids.unwrap_or_else(RoaringBitmap::new))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quoted_parts() {
+        assert_eq!(quoted_parts(r#""new york" pizza"#), vec!["new york"]);
+        assert_eq!(quoted_parts(r#"a "b c" d "e""#), vec!["b c", "e"]);
+        assert!(quoted_parts("new york pizza").is_empty());
+        // An unclosed quote is ignored.
+        assert_eq!(quoted_parts(r#""new york" "pizza"#), vec!["new york"]);
+    }
+
+    #[test]
+    fn test_negative_terms() {
+        assert_eq!(negative_terms("pizza -pineapple -"), vec!["pineapple"]);
+        assert_eq!(negative_terms(r#""new -york" -ham"#), vec!["ham"]);
+        assert_eq!(negative_terms("new-york"), Vec::<&str>::new());
+
+        assert_eq!(without_negative_terms("pizza -pineapple"), "pizza");
+        assert_eq!(without_negative_terms("-ham pizza "), " pizza ");
+        assert_eq!(
+            without_negative_terms(r#""new -york" -ham pizza"#),
+            r#""new -york" pizza"#
+        );
+    }
+}
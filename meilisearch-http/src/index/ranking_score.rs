@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+
+use heed::RoTxn;
+use meilisearch_tokenizer::{Analyzer, AnalyzerConfig};
+use serde_json::{json, Map, Value};
+
+use super::exactness::{allowed_typos, for_each_string, match_typos, words};
+use super::Index;
+
+/// The ranking rules a score is computed for. milli doesn't expose the scores of its ranking,
+/// so they are computed from the words of the query found in the hits, the other rules are not
+/// taken into account.
+#[derive(Clone, Copy)]
+enum Rule {
+    Words,
+    Typo,
+    Exactness,
+}
+
+impl Rule {
+    fn from_criterion(criterion: &str) -> Option<Self> {
+        match criterion {
+            "words" => Some(Rule::Words),
+            "typo" => Some(Rule::Typo),
+            "exactness" => Some(Rule::Exactness),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Rule::Words => "words",
+            Rule::Typo => "typo",
+            Rule::Exactness => "exactness",
+        }
+    }
+}
+
+/// How a word of the query is found in a hit.
+struct WordMatch {
+    /// The fewest typos the word is found with.
+    typos: usize,
+    /// Whether the word is found as it is, neither with typos nor as a prefix.
+    exact: bool,
+}
+
+/// Computes the ranking scores of the hits of a query.
+pub struct RankingScorer {
+    words: Vec<String>,
+    /// Whether the last word of the query can match as a prefix.
+    prefix: bool,
+    /// The rules, with their position in the ranking rules of the index.
+    rules: Vec<(usize, Rule)>,
+    searchable_attributes: Option<HashSet<String>>,
+}
+
+/// The score of a rule, from `0.0` to `1.0`, given `value` over `max`.
+fn ratio(value: usize, max: usize) -> f64 {
+    if max == 0 {
+        1.0
+    } else {
+        value as f64 / max as f64
+    }
+}
+
+impl Index {
+    /// Returns the scorer of the hits of the query `q`, searched on the
+    /// `attributes_to_search_on`.
+    pub(super) fn ranking_scorer(
+        &self,
+        txn: &RoTxn,
+        q: &str,
+        attributes_to_search_on: Option<&HashSet<String>>,
+    ) -> anyhow::Result<RankingScorer> {
+        let stop_words = fst::Set::default();
+        let analyzer = Analyzer::new(AnalyzerConfig::default_with_stopwords(&stop_words));
+
+        let rules = self
+            .criteria(txn)?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, c)| Some((i, Rule::from_criterion(&c.to_string())?)))
+            .collect();
+
+        let searchable_attributes = match attributes_to_search_on {
+            Some(attributes) => Some(attributes.clone()),
+            None => self
+                .searchable_fields(txn)?
+                .map(|fields| fields.into_iter().map(String::from).collect()),
+        };
+
+        Ok(RankingScorer {
+            words: words(&analyzer, q),
+            prefix: q.chars().last().map_or(false, char::is_alphanumeric),
+            rules,
+            searchable_attributes,
+        })
+    }
+}
+
+impl RankingScorer {
+    /// Returns the ranking score of a hit, from `0.0` to `1.0`, and the details of the score of
+    /// each rule, given the JSON values of its fields.
+    pub fn score<'a>(
+        &self,
+        fields: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+    ) -> anyhow::Result<(f64, Map<String, Value>)> {
+        let matches = self.word_matches(fields)?;
+        Ok(self.score_matches(&matches))
+    }
+
+    /// Whether the ranking score of a hit, given the JSON values of its fields, reaches the
+    /// `threshold`.
+    pub(super) fn is_relevant(
+        &self,
+        fields: &[(&str, &[u8])],
+        threshold: f64,
+    ) -> anyhow::Result<bool> {
+        let matches = self.word_matches(fields.iter().copied())?;
+        Ok(self.score_matches(&matches).0 >= threshold)
+    }
+
+    /// Returns how each word of the query is found in a hit, given the JSON values of its
+    /// fields.
+    fn word_matches<'a>(
+        &self,
+        fields: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+    ) -> anyhow::Result<Vec<Option<WordMatch>>> {
+        let stop_words = fst::Set::default();
+        let analyzer = Analyzer::new(AnalyzerConfig::default_with_stopwords(&stop_words));
+
+        let last = self.words.len().saturating_sub(1);
+        let mut matches: Vec<Option<WordMatch>> = self.words.iter().map(|_| None).collect();
+        for (name, value) in fields {
+            if let Some(ref searchable) = self.searchable_attributes {
+                if !searchable.contains(name) {
+                    continue;
+                }
+            }
+
+            let value = serde_json::from_slice(value)?;
+            for_each_string(&value, &mut |text| {
+                for token in words(&analyzer, text) {
+                    for (i, (word, best)) in self.words.iter().zip(&mut matches).enumerate() {
+                        let typos = match match_typos(word, self.prefix && i == last, &token) {
+                            Some(typos) => typos,
+                            None => continue,
+                        };
+                        let best = best.get_or_insert(WordMatch {
+                            typos,
+                            exact: false,
+                        });
+                        best.typos = best.typos.min(typos);
+                        best.exact |= token == *word;
+                    }
+                }
+            });
+        }
+
+        Ok(matches)
+    }
+
+    /// Returns the ranking score of a hit and its details, given how each word of the query is
+    /// found in it. The score of a rule weighs twice as much as the score of the following one.
+    fn score_matches(&self, matches: &[Option<WordMatch>]) -> (f64, Map<String, Value>) {
+        let found: Vec<_> = self
+            .words
+            .iter()
+            .zip(matches)
+            .filter_map(|(word, m)| Some((word, m.as_ref()?)))
+            .collect();
+
+        let mut details = Map::new();
+        let mut weighted_scores = 0.0;
+        let mut total_weight = 0.0;
+        for (order, rule) in self.rules.iter().copied() {
+            let (score, detail) = match rule {
+                Rule::Words => {
+                    let score = ratio(found.len(), self.words.len());
+                    let detail = json!({
+                        "order": order,
+                        "matchingWords": found.len(),
+                        "maxMatchingWords": self.words.len(),
+                        "score": score,
+                    });
+                    (score, detail)
+                }
+                Rule::Typo => {
+                    let typo_count: usize = found.iter().map(|(_, m)| m.typos).sum();
+                    let max_typo_count: usize =
+                        found.iter().map(|(word, _)| allowed_typos(word)).sum();
+                    let score = 1.0 - ratio(typo_count, max_typo_count);
+                    // Without any allowed typo, there is no typo.
+                    let score = if max_typo_count == 0 { 1.0 } else { score };
+                    let detail = json!({
+                        "order": order,
+                        "typoCount": typo_count,
+                        "maxTypoCount": max_typo_count,
+                        "score": score,
+                    });
+                    (score, detail)
+                }
+                Rule::Exactness => {
+                    let exact_words = found.iter().filter(|(_, m)| m.exact).count();
+                    let score = ratio(exact_words, self.words.len());
+                    let detail = json!({
+                        "order": order,
+                        "exactWords": exact_words,
+                        "maxExactWords": self.words.len(),
+                        "score": score,
+                    });
+                    (score, detail)
+                }
+            };
+            let weight = 0.5f64.powi(total_weight_exponent(&details));
+            weighted_scores += weight * score;
+            total_weight += weight;
+            details.insert(rule.name().to_string(), detail);
+        }
+
+        let score = if total_weight == 0.0 {
+            1.0
+        } else {
+            weighted_scores / total_weight
+        };
+        (score, details)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        let scorer = RankingScorer {
+            words: vec!["red".to_string(), "planet".to_string()],
+            prefix: false,
+            rules: vec![(0, Rule::Words), (1, Rule::Typo), (5, Rule::Exactness)],
+            searchable_attributes: None,
+        };
+
+        let (score, details) = scorer
+            .score(vec![("title", br#""the red planet""# as &[u8])])
+            .unwrap();
+        assert_eq!(score, 1.0);
+        assert_eq!(details["words"]["matchingWords"], 2);
+        assert_eq!(details["exactness"]["order"], 5);
+
+        let (score, details) = scorer
+            .score(vec![("title", br#""a red planel""# as &[u8])])
+            .unwrap();
+        assert!(score < 1.0);
+        assert_eq!(details["typo"]["typoCount"], 1);
+        assert_eq!(details["exactness"]["exactWords"], 1);
+
+        let (score, details) = scorer.score(vec![("title", br#""red""# as &[u8])]).unwrap();
+        assert!(score < 1.0);
+        assert_eq!(details["words"]["matchingWords"], 1);
+    }
+}
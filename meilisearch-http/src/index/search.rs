@@ -1,42 +1,229 @@
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::mem;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use anyhow::bail;
+use anyhow::{bail, ensure};
 use either::Either;
 use heed::RoTxn;
 use meilisearch_tokenizer::{Analyzer, AnalyzerConfig};
 use milli::{facet::FacetValue, FacetCondition, MatchingWords};
-use serde::{Deserialize, Serialize};
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::{Map, Value};
 
-use super::Index;
+use super::flatten::unflatten_document;
+use super::phrase::{without_negative_terms, Phrases};
+use super::{Index, PrefixSearch};
 
 pub const DEFAULT_SEARCH_LIMIT: usize = 20;
 
+/// The weight of the vector in the ranking of a search with both keywords and a vector.
+const DEFAULT_SEMANTIC_RATIO: f32 = 0.5;
+
 const fn default_search_limit() -> usize {
     DEFAULT_SEARCH_LIMIT
 }
 
-#[derive(Deserialize)]
+/// Whether all the words of a query must match, or the last ones can be dropped.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MatchingStrategy {
+    /// The hits must match all the words of the query.
+    All,
+    /// The last words of the query are dropped until there are enough hits.
+    Last,
+}
+
+impl Default for MatchingStrategy {
+    fn default() -> Self {
+        MatchingStrategy::Last
+    }
+}
+
+/// Serializes the set in order, so that equal sets are serialized the same way.
+fn serialize_sorted<S: Serializer>(
+    set: &Option<HashSet<String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let set: Option<BTreeSet<_>> = set.as_ref().map(|set| set.iter().collect());
+    set.serialize(serializer)
+}
+
+/// The query is serialized to be used as the key of the search cache.
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 #[allow(dead_code)]
 pub struct SearchQuery {
     pub q: Option<String>,
+    /// Restricts the words of `q` to match in these searchable attributes. milli searches all
+    /// the searchable attributes, the hits matching only outside of these are removed before
+    /// they are paginated.
+    #[serde(serialize_with = "serialize_sorted")]
+    pub attributes_to_search_on: Option<HashSet<String>>,
     pub offset: Option<usize>,
     #[serde(default = "default_search_limit")]
     pub limit: usize,
     pub attributes_to_retrieve: Option<Vec<String>>,
     pub attributes_to_crop: Option<Vec<String>>,
     pub crop_length: Option<usize>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub attributes_to_highlight: Option<HashSet<String>>,
+    #[serde(alias = "filter")]
     pub filters: Option<String>,
     pub matches: Option<bool>,
     pub facet_filters: Option<Value>,
     pub facet_distributions: Option<Vec<String>>,
+    /// Counts the hits whose value of a numeric facet is in each of the given ranges.
+    pub facet_ranges: Option<BTreeMap<String, Vec<FacetRange>>>,
+    pub page: Option<usize>,
+    pub hits_per_page: Option<usize>,
+    /// Ranks the documents by the distance of their `_vectors` to this vector.
+    pub vector: Option<Vec<f32>>,
+    /// The weight of the vector in the ranking, from `0.0` to `1.0`, when both `q` and `vector`
+    /// are given. The keyword ranking has a weight of `1.0 - semanticRatio`.
+    pub semantic_ratio: Option<f32>,
+    /// The time budget of the search, in milliseconds, after which the best results found so
+    /// far are returned and flagged as `degraded`.
+    pub timeout_ms: Option<u64>,
+    /// Adds the ranking score of the hits to them, as `_rankingScore`.
+    #[serde(default)]
+    pub show_ranking_score: bool,
+    /// Adds the details of the ranking score of the hits to them, as `_rankingScoreDetails`.
+    #[serde(default)]
+    pub show_ranking_score_details: bool,
+    /// The ranking score, from `0.0` to `1.0`, below which the hits are removed. All the
+    /// candidates are scored, the hits below it aren't counted in `nbHits`.
+    pub ranking_score_threshold: Option<f64>,
+    #[serde(default)]
+    pub matching_strategy: MatchingStrategy,
+}
+
+impl SearchQuery {
+    /// Returns `true` if the query asks for page-based pagination, i.e. if either `page` or
+    /// `hitsPerPage` was provided.
+    pub fn is_paginated(&self) -> bool {
+        self.page.is_some() || self.hits_per_page.is_some()
+    }
+
+    /// Returns the `(offset, limit)` couple to give to the search, computed from the page
+    /// parameters when the query is paginated. Pages start at 1, page 0 returns no hits.
+    fn offset_and_limit(&self) -> (usize, usize) {
+        if self.is_paginated() {
+            let page = self.page.unwrap_or(1);
+            let hits_per_page = self.hits_per_page.unwrap_or(DEFAULT_SEARCH_LIMIT);
+            match page.checked_sub(1) {
+                Some(page) => (page.saturating_mul(hits_per_page), hits_per_page),
+                None => (0, 0),
+            }
+        } else {
+            (self.offset.unwrap_or_default(), self.limit)
+        }
+    }
+}
+
+/// The instant after which a search must return the results it found so far.
+///
+/// The search of milli can't be interrupted, the deadline is checked between the windows of its
+/// ranking and by the steps following it: the exactness rules, the vector ranking, the
+/// highlighting and the facet distributions.
+#[derive(Clone, Copy)]
+pub(super) struct Deadline(Option<Instant>);
+
+impl Deadline {
+    fn new(start: Instant, timeout_ms: Option<u64>) -> Self {
+        Self(timeout_ms.map(|ms| start + Duration::from_millis(ms)))
+    }
+
+    pub(super) fn is_reached(&self) -> bool {
+        self.0.map_or(false, |deadline| Instant::now() >= deadline)
+    }
+}
+
+/// The bounds of the values of a numeric facet among the hits.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FacetStats {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Returns the stats of the facets of `distributions` having numeric values, computed on their
+/// numeric values.
+fn facet_stats(
+    distributions: &BTreeMap<String, BTreeMap<FacetValue, u64>>,
+) -> anyhow::Result<BTreeMap<String, FacetStats>> {
+    let mut stats = BTreeMap::new();
+    for (name, values) in distributions {
+        for value in values.keys() {
+            let number = match serde_json::to_value(value)?.as_f64() {
+                Some(number) => number,
+                None => continue,
+            };
+            let stats = stats.entry(name.clone()).or_insert(FacetStats {
+                min: number,
+                max: number,
+            });
+            stats.min = stats.min.min(number);
+            stats.max = stats.max.max(number);
+        }
+    }
+    Ok(stats)
+}
+
+/// A range of the values of a numeric facet, `from` is inclusive and `to` exclusive. A missing
+/// bound leaves the range unbounded on its side.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FacetRange {
+    pub from: Option<f64>,
+    pub to: Option<f64>,
+}
+
+impl FacetRange {
+    fn contains(&self, value: f64) -> bool {
+        self.from.map_or(true, |from| from <= value) && self.to.map_or(true, |to| value < to)
+    }
 }
 
-#[derive(Serialize)]
+/// The number of hits whose value of a facet is in the range.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FacetRangeCount {
+    #[serde(flatten)]
+    pub range: FacetRange,
+    pub count: u64,
+}
+
+/// Counts the values of the facets of `distributions` in each of their `ranges`. The values
+/// that aren't numbers are ignored.
+fn facet_range_counts(
+    ranges: &BTreeMap<String, Vec<FacetRange>>,
+    distributions: &BTreeMap<String, BTreeMap<FacetValue, u64>>,
+) -> anyhow::Result<BTreeMap<String, Vec<FacetRangeCount>>> {
+    let mut counts = BTreeMap::new();
+    for (name, ranges) in ranges {
+        let mut range_counts: Vec<_> = ranges
+            .iter()
+            .map(|range| FacetRangeCount {
+                range: range.clone(),
+                count: 0,
+            })
+            .collect();
+        for (value, count) in distributions.get(name).into_iter().flatten() {
+            let number = match serde_json::to_value(value)?.as_f64() {
+                Some(number) => number,
+                None => continue,
+            };
+            for range_count in &mut range_counts {
+                if range_count.range.contains(number) {
+                    range_count.count += count;
+                }
+            }
+        }
+        counts.insert(name.clone(), range_counts);
+    }
+    Ok(counts)
+}
+
+#[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchResult {
     pub hits: Vec<Map<String, Value>>,
@@ -48,21 +235,170 @@ pub struct SearchResult {
     pub processing_time_ms: u128,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub facet_distributions: Option<BTreeMap<String, BTreeMap<FacetValue, u64>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facet_stats: Option<BTreeMap<String, FacetStats>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facet_ranges: Option<BTreeMap<String, Vec<FacetRangeCount>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hits_per_page: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_hits: Option<u64>,
+    /// Whether the time budget of the search was exhausted, the results are then partial.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
 }
 
 impl Index {
     pub fn perform_search(&self, query: SearchQuery) -> anyhow::Result<SearchResult> {
         let before_search = Instant::now();
+        let deadline = Deadline::new(before_search, query.timeout_ms);
+        let mut degraded = false;
         let rtxn = self.read_txn()?;
 
         let mut search = self.search(&rtxn);
 
-        if let Some(ref query) = query.q {
-            search.query(query);
+        // The negative terms are not searched, the documents containing them are removed from the
+        // candidates.
+        let keywords = query.q.as_deref().map(without_negative_terms);
+        // Without prefix search, the last word of the query only matches whole words, as when
+        // it is followed by a separator.
+        let keywords = match keywords {
+            Some(keywords)
+                if keywords.ends_with(char::is_alphanumeric)
+                    && self.prefix_search(&rtxn)? == PrefixSearch::Disabled =>
+            {
+                Some(keywords + " ")
+            }
+            keywords => keywords,
+        };
+        if let Some(ref keywords) = keywords {
+            search.query(keywords);
+        }
+
+        let (offset, limit) = query.offset_and_limit();
+
+        if let Some(max_total_hits) = self.max_total_hits(&rtxn)? {
+            ensure!(
+                offset.saturating_add(limit) as u64 <= max_total_hits,
+                "Pagination beyond the {} first hits is not allowed (offset: {}, limit: {}), \
+                see the `pagination.maxTotalHits` setting.",
+                max_total_hits,
+                offset,
+                limit,
+            );
+        }
+
+        if let Some(ref vector) = query.vector {
+            ensure!(!vector.is_empty(), "The search vector can't be empty.");
+        }
+        let semantic_ratio = match (query.semantic_ratio, &query.vector) {
+            (Some(ratio), Some(_)) => {
+                ensure!(
+                    (0.0..=1.0).contains(&ratio),
+                    "The semantic ratio must be between 0.0 and 1.0, found {}.",
+                    ratio
+                );
+                ratio
+            }
+            (Some(_), None) => bail!("The semantic ratio can't be used without a vector."),
+            // Without keywords, the ranking of the keyword search is meaningless.
+            (None, _) if query.q.is_none() => 1.0,
+            (None, _) => DEFAULT_SEMANTIC_RATIO,
+        };
+
+        let exactness = match keywords {
+            Some(ref keywords) => {
+                self.exactness(&rtxn, keywords, query.attributes_to_search_on.as_ref())?
+            }
+            None => None,
+        };
+
+        let phrases = query.q.as_deref().and_then(Phrases::parse);
+
+        if let Some(ref ranges) = query.facet_ranges {
+            let faceted_fields = self.faceted_fields(&rtxn)?;
+            for (name, ranges) in ranges {
+                ensure!(
+                    faceted_fields.contains_key(name),
+                    "Attribute `{}` is not faceted, it can't be used in a range facet.",
+                    name
+                );
+                for range in ranges {
+                    if let (Some(from), Some(to)) = (range.from, range.to) {
+                        ensure!(
+                            from <= to,
+                            "The range of the `{}` facet starts after its end ({} > {}).",
+                            name,
+                            from,
+                            to
+                        );
+                    }
+                }
+            }
+        }
+
+        let filter_candidates = match query.filters {
+            Some(ref filter) => {
+                let filter = self.filter(&rtxn, filter)?;
+                Some(self.filter_candidates(&rtxn, &filter)?)
+            }
+            None => None,
+        };
+
+        if let Some(threshold) = query.ranking_score_threshold {
+            ensure!(
+                (0.0..=1.0).contains(&threshold),
+                "The ranking score threshold must be between 0.0 and 1.0, found {}.",
+                threshold
+            );
         }
 
-        search.limit(query.limit);
-        search.offset(query.offset.unwrap_or_default());
+        // milli drops the last words of the query when there are not enough hits, the documents
+        // not matching all of them are removed from the candidates.
+        let all_words = query.matching_strategy == MatchingStrategy::All && keywords.is_some();
+
+        let scorer = if query.show_ranking_score
+            || query.show_ranking_score_details
+            || query.ranking_score_threshold.is_some()
+        {
+            let q = keywords.as_deref().unwrap_or_default();
+            let attributes_to_search_on = query.attributes_to_search_on.as_ref();
+            Some(self.ranking_scorer(&rtxn, q, attributes_to_search_on)?)
+        } else {
+            None
+        };
+
+        // The candidates not matching the filter, not containing the phrases, containing the
+        // negative terms, or not matching all the words, are known before the search.
+        let mut restriction = filter_candidates;
+        if let Some(ref phrases) = phrases {
+            if let Some(docids) = self.phrases_docids(&rtxn, phrases)? {
+                restriction = Some(intersection(restriction, docids));
+            }
+            let negative_docids = self.negative_terms_docids(&rtxn, phrases)?;
+            if !negative_docids.is_empty() {
+                let docids = match restriction {
+                    Some(docids) => docids,
+                    None => self.documents_ids(&rtxn)?,
+                };
+                restriction = Some(docids - negative_docids);
+            }
+        }
+        if all_words {
+            let keywords = keywords.as_deref().unwrap_or_default();
+            if let Some(docids) = self.all_words_docids(&rtxn, keywords)? {
+                restriction = Some(intersection(restriction, docids));
+            }
+        }
+
+        // Whether the candidates of milli are checked against their documents.
+        let checked = exactness.is_some() || query.ranking_score_threshold.is_some();
+        // Whether some of the hits of milli are removed.
+        let filtered = checked || restriction.is_some();
 
         if let Some(ref facets) = query.facet_filters {
             if let Some(facets) = parse_facets(facets, self, &rtxn)? {
@@ -70,55 +406,306 @@ impl Index {
             }
         }
 
-        let milli::SearchResult {
-            documents_ids,
-            matching_words,
-            candidates,
-            ..
-        } = search.execute()?;
+        let (documents_ids, matching_words, candidates) = if query.vector.is_none() && !filtered {
+            search.limit(limit);
+            search.offset(offset);
+            let milli::SearchResult {
+                documents_ids,
+                matching_words,
+                candidates,
+                ..
+            } = search.execute()?;
+            (documents_ids, matching_words, candidates)
+        } else {
+            let number_of_documents = self.number_of_documents(&rtxn)? as usize;
+            // The whole keyword ranking is needed to mix it with the distance of the documents
+            // to the vector.
+            let needed = if query.vector.is_some() {
+                number_of_documents
+            } else {
+                offset.saturating_add(limit)
+            };
+            search.limit(needed);
+            search.offset(0);
+            let milli::SearchResult {
+                documents_ids,
+                matching_words,
+                mut candidates,
+                ..
+            } = search.execute()?;
+
+            if let Some(ref docids) = restriction {
+                candidates &= docids;
+            }
+            // The documents of the candidates are read once, to check them against the
+            // exactness rules, the attributes to search on and the score threshold, before the
+            // hits are ranked.
+            if checked {
+                let (retained, complete) =
+                    self.retain_candidates(&rtxn, &candidates, deadline, |fields| {
+                        if let Some(ref exactness) = exactness {
+                            if !exactness.allows(fields)? {
+                                return Ok(false);
+                            }
+                        }
+                        match (&scorer, query.ranking_score_threshold) {
+                            (Some(scorer), Some(threshold)) => {
+                                scorer.is_relevant(fields, threshold)
+                            }
+                            _ => Ok(true),
+                        }
+                    })?;
+                degraded |= !complete;
+                candidates = retained;
+            }
+
+            // Only the ranked hits among the candidates are kept.
+            let (documents_ids, complete) = self.ranked_hits(
+                &mut search,
+                documents_ids,
+                &candidates,
+                needed,
+                number_of_documents,
+                deadline,
+            )?;
+            degraded |= !complete;
+            (documents_ids, matching_words, candidates)
+        };
+
+        // The facets are computed on all the hits, not only those of the page.
+        let (documents_ids, nb_hits, hits_docids) = match query.vector {
+            Some(ref vector) => {
+                // Without keywords, all the documents are candidates, whatever their keyword
+                // ranking. Otherwise, only the keyword hits that aren't removed are.
+                let keyword_ranking = if semantic_ratio < 1.0 {
+                    documents_ids.as_slice()
+                } else {
+                    &[]
+                };
+                let (ranking, complete) = self.vector_ranking(
+                    &rtxn,
+                    candidates.iter(),
+                    vector,
+                    keyword_ranking,
+                    semantic_ratio,
+                    deadline,
+                )?;
+                degraded |= !complete;
+                let nb_hits = ranking.len() as u64;
+                let hits_docids = ranking.iter().copied().collect();
+                let documents_ids = ranking.into_iter().skip(offset).take(limit).collect();
+                (documents_ids, nb_hits, hits_docids)
+            }
+            None if filtered => {
+                let documents_ids = documents_ids.into_iter().skip(offset).take(limit).collect();
+                (documents_ids, candidates.len(), candidates)
+            }
+            None => (documents_ids, candidates.len(), candidates),
+        };
+
         let mut documents = Vec::new();
         let fields_ids_map = self.fields_ids_map(&rtxn).unwrap();
 
         let fields_to_display =
             self.fields_to_display(&rtxn, query.attributes_to_retrieve, &fields_ids_map)?;
+        let nested_fields = self.nested_fields(&rtxn)?;
 
         let stop_words = fst::Set::default();
         let highlighter = Highlighter::new(&stop_words);
 
+        let show_score = query.show_ranking_score || query.show_ranking_score_details;
         for (_id, obkv) in self.documents(&rtxn, documents_ids)? {
+            let score = match scorer {
+                Some(ref scorer) if show_score => {
+                    let fields = obkv
+                        .iter()
+                        .filter_map(|(fid, value)| Some((fields_ids_map.name(fid)?, value)));
+                    Some(scorer.score(fields)?)
+                }
+                _ => None,
+            };
+
             let mut object =
                 milli::obkv_to_json(&fields_to_display, &fields_ids_map, obkv).unwrap();
+            if let Some((score, details)) = score {
+                if query.show_ranking_score {
+                    object.insert("_rankingScore".to_string(), score.into());
+                }
+                if query.show_ranking_score_details {
+                    object.insert("_rankingScoreDetails".to_string(), details.into());
+                }
+            }
             if let Some(ref attributes_to_highlight) = query.attributes_to_highlight {
-                highlighter.highlight_record(&mut object, &matching_words, attributes_to_highlight);
+                // Once the time budget is exhausted, the hits are returned without highlighting.
+                if deadline.is_reached() {
+                    degraded = true;
+                } else {
+                    highlighter.highlight_record(
+                        &mut object,
+                        &matching_words,
+                        attributes_to_highlight,
+                    );
+                }
             }
-            documents.push(object);
+            documents.push(unflatten_document(object, &nested_fields));
         }
 
-        let nb_hits = candidates.len();
+        let facet_ranges = match query.facet_ranges {
+            Some(_) if deadline.is_reached() => {
+                degraded = true;
+                None
+            }
+            Some(ref ranges) => {
+                let mut facet_distribution = self.facets_distribution(&rtxn);
+                facet_distribution.facets(ranges.keys());
+                let distributions = facet_distribution
+                    .candidates(hits_docids.clone())
+                    .execute()?;
+                Some(facet_range_counts(ranges, &distributions)?)
+            }
+            None => None,
+        };
 
         let facet_distributions = match query.facet_distributions {
+            Some(_) if deadline.is_reached() => {
+                degraded = true;
+                None
+            }
             Some(ref fields) => {
                 let mut facet_distribution = self.facets_distribution(&rtxn);
                 if fields.iter().all(|f| f != "*") {
                     facet_distribution.facets(fields);
                 }
-                Some(facet_distribution.candidates(candidates).execute()?)
+                Some(facet_distribution.candidates(hits_docids).execute()?)
             }
             None => None,
         };
 
+        let facet_stats = match facet_distributions {
+            Some(ref distributions) => Some(facet_stats(distributions)?),
+            None => None,
+        };
+
+        let (page, hits_per_page, total_pages, total_hits) = if query.is_paginated() {
+            let total_pages = if limit == 0 {
+                0
+            } else {
+                (nb_hits as usize + limit - 1) / limit
+            };
+            (
+                Some(query.page.unwrap_or(1)),
+                Some(limit),
+                Some(total_pages),
+                Some(nb_hits),
+            )
+        } else {
+            (None, None, None, None)
+        };
+
         let result = SearchResult {
             exhaustive_nb_hits: false, // not implemented yet
             hits: documents,
             nb_hits,
             query: query.q.clone().unwrap_or_default(),
-            limit: query.limit,
-            offset: query.offset.unwrap_or_default(),
+            limit,
+            offset,
             processing_time_ms: before_search.elapsed().as_millis(),
             facet_distributions,
+            facet_stats,
+            facet_ranges,
+            page,
+            hits_per_page,
+            total_pages,
+            total_hits,
+            degraded,
         };
         Ok(result)
     }
+
+    /// Keeps the candidates of `docids` for which `retain` returns `true`, given the names and
+    /// the JSON values of the fields of their documents.
+    ///
+    /// The candidates are checked in the order of their ids until the `deadline` is reached, the
+    /// returned boolean tells whether all of them were.
+    fn retain_candidates<F>(
+        &self,
+        txn: &RoTxn,
+        docids: &RoaringBitmap,
+        deadline: Deadline,
+        mut retain: F,
+    ) -> anyhow::Result<(RoaringBitmap, bool)>
+    where
+        F: FnMut(&[(&str, &[u8])]) -> anyhow::Result<bool>,
+    {
+        let fields_ids_map = self.fields_ids_map(txn)?;
+
+        let mut retained = RoaringBitmap::new();
+        for (id, obkv) in self.documents(txn, docids.iter())? {
+            if deadline.is_reached() {
+                return Ok((retained, false));
+            }
+
+            let fields: Vec<_> = obkv
+                .iter()
+                .filter_map(|(fid, value)| Some((fields_ids_map.name(fid)?, value)))
+                .collect();
+            if retain(&fields)? {
+                retained.insert(id);
+            }
+        }
+
+        Ok((retained, true))
+    }
+
+    /// Returns the documents ranked by `search` that are in `hits`, in the order of the ranking,
+    /// until at least `needed` of them are found. `ranked` are the first `needed` documents of
+    /// the ranking.
+    ///
+    /// milli can't be given the candidates to search among, the next documents of the ranking
+    /// are asked in windows growing twice as big, until enough of them are hits, all of the
+    /// `number_of_documents` are ranked, or the `deadline` is reached. The returned boolean tells
+    /// whether the documents were ranked until the end.
+    fn ranked_hits(
+        &self,
+        search: &mut milli::Search,
+        ranked: Vec<u32>,
+        hits: &RoaringBitmap,
+        needed: usize,
+        number_of_documents: usize,
+        deadline: Deadline,
+    ) -> anyhow::Result<(Vec<u32>, bool)> {
+        let mut exhausted = ranked.len() < needed || needed >= number_of_documents;
+        let mut documents_ids: Vec<_> =
+            ranked.into_iter().filter(|id| hits.contains(*id)).collect();
+
+        let mut window = needed;
+        while documents_ids.len() < needed
+            && (documents_ids.len() as u64) < hits.len()
+            && !exhausted
+        {
+            if deadline.is_reached() {
+                return Ok((documents_ids, false));
+            }
+
+            let start = window;
+            window = window.saturating_mul(2).min(number_of_documents);
+            search.offset(start);
+            search.limit(window - start);
+            let ranked = search.execute()?.documents_ids;
+            exhausted = ranked.len() < window - start || window >= number_of_documents;
+            documents_ids.extend(ranked.into_iter().filter(|id| hits.contains(*id)));
+        }
+
+        Ok((documents_ids, true))
+    }
+}
+
+/// Restricts the optional `restriction` to the documents `docids`.
+fn intersection(restriction: Option<RoaringBitmap>, docids: RoaringBitmap) -> RoaringBitmap {
+    match restriction {
+        Some(restriction) => restriction & docids,
+        None => docids,
+    }
 }
 
 fn parse_facets_array(
@@ -216,7 +803,7 @@ impl<'a, A: AsRef<[u8]>> Highlighter<'a, A> {
     }
 }
 
-fn parse_facets(
+pub(super) fn parse_facets(
     facets: &Value,
     index: &Index,
     txn: &RoTxn,
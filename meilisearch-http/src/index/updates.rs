@@ -1,21 +1,81 @@
-use std::collections::HashMap;
-use std::io;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom};
 use std::num::NonZeroUsize;
 
+use anyhow::{bail, ensure};
 use flate2::read::GzDecoder;
 use log::info;
-use milli::update::{DocumentAdditionResult, IndexDocumentsMethod, UpdateBuilder, UpdateFormat};
+use milli::update::{IndexDocumentsMethod, UpdateBuilder, UpdateFormat, UpdateIndexingStep};
+use roaring::RoaringBitmap;
 use serde::{de::Deserializer, Deserialize, Serialize};
+use serde_json::Value;
 
-use super::Index;
+use super::flatten::flatten_documents;
+use super::validation::{document_errors, filter_documents, first_document, infer_primary_key};
+use super::validation::{DocumentError, InvalidDocuments};
+use super::vector::VECTORS_FIELD;
+use super::{Index, DICTIONARY_KEY, EXACT_ATTRIBUTES_KEY, EXACT_WORDS_KEY, LANGUAGES_KEY};
+use super::{
+    NON_SEPARATOR_TOKENS_KEY, PREFIX_SEARCH_KEY, PROXIMITY_PRECISION_KEY, SEPARATOR_TOKENS_KEY,
+};
+
+/// The maximum number of invalid documents reported when a document addition fails or skips
+/// them.
+const MAX_DOCUMENT_ERRORS: usize = 1000;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UpdateResult {
-    DocumentsAddition(DocumentAdditionResult),
-    DocumentDeletion { deleted: u64 },
+    DocumentsAddition(DocumentsAdditionResult),
+    DocumentDeletion {
+        deleted: u64,
+        /// The number of document ids sent, the ids that match no document are ignored.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        requested: Option<u64>,
+    },
+    ClearDocuments {
+        deleted: u64,
+    },
+    /// The settings changed by a settings update, by name.
+    Settings {
+        changes: BTreeMap<String, SettingChange>,
+        /// Whether the documents were indexed again, only the changes of the searchable and the
+        /// faceted attributes require it.
+        #[serde(default)]
+        reindexed: bool,
+    },
     Other,
 }
 
+/// The outcome of a documents addition. The counts missing from the additions processed before
+/// they were recorded are not returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentsAdditionResult {
+    /// The number of documents received in the payload of the update.
+    pub nb_documents: usize,
+    /// The number of documents the index gained, the other documents received replaced existing
+    /// documents. Unknown for the additions processed in a batch, as the documents of the batch
+    /// are indexed together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_documents: Option<u64>,
+    /// The number of documents in the index once the update is processed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_documents: Option<u64>,
+    /// The number of invalid documents skipped, only for the additions skipping them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped_documents: Option<usize>,
+    /// The errors of the skipped documents, at most `MAX_DOCUMENT_ERRORS` of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub document_errors: Vec<DocumentError>,
+}
+
+/// The value of a setting before and after a settings update.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub old: Value,
+    pub new: Value,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "camelCase")]
@@ -43,6 +103,91 @@ pub struct Settings {
         skip_serializing_if = "Option::is_none"
     )]
     pub ranking_rules: Option<Option<Vec<String>>>,
+
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pagination: Option<Option<PaginationSettings>>,
+
+    /// The languages of the documents, as ISO 639-3 codes (e.g. `jpn`), an empty list meaning
+    /// that the language is detected from the script of the text.
+    ///
+    /// The tokenizer doesn't accept a language hint yet, only the empty list is accepted for
+    /// now.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub languages: Option<Option<Vec<String>>>,
+
+    /// The strings that split the words, in addition to the default separators.
+    ///
+    /// As for the languages, only the empty list is accepted for now, the tokenizer doesn't
+    /// accept custom separators yet.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub separator_tokens: Option<Option<BTreeSet<String>>>,
+
+    /// The strings that don't split the words, even though they contain a default separator,
+    /// e.g. `#` to keep `C#` as a single word. Only the empty list is accepted for now, as for
+    /// the separator tokens.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub non_separator_tokens: Option<Option<BTreeSet<String>>>,
+
+    /// The words, or groups of words, that are kept as a single token, e.g. brand names. Only
+    /// the empty list is accepted for now, as for the separator tokens.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub dictionary: Option<Option<BTreeSet<String>>>,
+
+    /// The words that only match exactly, without typo and not as a prefix, e.g. codes.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub exact_words: Option<Option<BTreeSet<String>>>,
+
+    /// The attributes whose words only match exactly, without typo and not as a prefix.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub exact_attributes: Option<Option<BTreeSet<String>>>,
+
+    /// How precisely the distance between the words of the documents is measured.
+    ///
+    /// milli always computes the proximity of the word pairs by word and doesn't accept a
+    /// coarser precision yet, only `byWord` is accepted for now.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub proximity_precision: Option<Option<ProximityPrecision>>,
+
+    /// Whether the last word of the queries matches the words it is a prefix of. milli still
+    /// computes the prefixes of the words while indexing when it is disabled.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub prefix_search: Option<Option<PrefixSearch>>,
 }
 
 impl Settings {
@@ -52,10 +197,60 @@ impl Settings {
             searchable_attributes: Some(None),
             attributes_for_faceting: Some(None),
             ranking_rules: Some(None),
+            pagination: Some(None),
+            languages: Some(None),
+            separator_tokens: Some(None),
+            non_separator_tokens: Some(None),
+            dictionary: Some(None),
+            exact_words: Some(None),
+            exact_attributes: Some(None),
+            proximity_precision: Some(None),
+            prefix_search: Some(None),
         }
     }
 }
 
+/// The precision of the proximity between the words of the documents.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProximityPrecision {
+    /// The distance between the words is counted in words.
+    ByWord,
+    /// The words are only considered close when they are in the same attribute, which is much
+    /// cheaper to index.
+    ByAttribute,
+}
+
+impl Default for ProximityPrecision {
+    fn default() -> Self {
+        ProximityPrecision::ByWord
+    }
+}
+
+/// When the prefixes of the words are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PrefixSearch {
+    /// The prefixes are computed while indexing.
+    IndexingTime,
+    /// The words of the queries only match whole words.
+    Disabled,
+}
+
+impl Default for PrefixSearch {
+    fn default() -> Self {
+        PrefixSearch::IndexingTime
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginationSettings {
+    /// The number of hits beyond which the clients can't paginate, unbounded when `None`.
+    pub max_total_hits: Option<u64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "camelCase")]
@@ -64,6 +259,29 @@ pub struct Facets {
     pub min_level_size: Option<NonZeroUsize>,
 }
 
+/// Returns the settings whose value differ between `old` and `new`, by name.
+fn diff_settings(old: &Settings, new: &Settings) -> anyhow::Result<BTreeMap<String, SettingChange>> {
+    let mut changes = BTreeMap::new();
+
+    if let (Value::Object(old), Value::Object(mut new)) =
+        (serde_json::to_value(old)?, serde_json::to_value(new)?)
+    {
+        for (name, old) in old {
+            let new = new.remove(&name).unwrap_or(Value::Null);
+            if old != new {
+                changes.insert(name, SettingChange { old, new });
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Whether `code` has the form of an ISO 639-3 language code: three lowercase ASCII letters.
+fn is_language_code(code: &str) -> bool {
+    code.len() == 3 && code.bytes().all(|b| b.is_ascii_lowercase())
+}
+
 fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
 where
     T: Deserialize<'de>,
@@ -73,45 +291,208 @@ where
 }
 
 impl Index {
-    pub fn update_documents(
+    /// Adds the documents of the payload to the index. The addition fails, without changing the
+    /// index, if the index would contain more than `max_fields` distinct fields.
+    #[allow(clippy::too_many_arguments)]
+    /// Adds the documents of `content`. `update_builder` returns the builders of the milli
+    /// updates, as the searchable fields may be updated before the documents are added.
+    pub fn update_documents<'a>(
         &self,
         format: UpdateFormat,
         method: IndexDocumentsMethod,
-        content: impl io::Read,
-        update_builder: UpdateBuilder,
+        mut content: File,
+        update_builder: impl Fn() -> UpdateBuilder<'a>,
         primary_key: Option<&str>,
+        max_fields: usize,
+        progress: impl Fn(UpdateIndexingStep) + Sync,
     ) -> anyhow::Result<UpdateResult> {
         info!("performing document addition");
         // We must use the write transaction of the update here.
         let mut wtxn = self.write_txn()?;
 
-        // Set the primary key if not set already, ignore if already set.
-        if let (None, Some(ref primary_key)) = (self.primary_key(&wtxn)?, primary_key) {
-            self.put_primary_key(&mut wtxn, primary_key)?;
+        // Set the primary key if not set already, ignore if already set. It is inferred here
+        // rather than by milli, to explain why it can't be.
+        if self.primary_key(&wtxn)?.is_none() {
+            let primary_key = match primary_key {
+                Some(primary_key) => Some(primary_key.to_string()),
+                None if matches!(format, UpdateFormat::Json) => {
+                    let document = first_document(&mut content)?;
+                    content.seek(SeekFrom::Start(0))?;
+                    infer_primary_key(document.as_ref())?
+                }
+                None => None,
+            };
+            if let Some(ref primary_key) = primary_key {
+                self.put_primary_key(&mut wtxn, primary_key)?;
+            }
         }
+        let primary_key = self.primary_key(&wtxn)?.map(String::from);
+
+        // The nested objects are flattened, as milli only indexes the top-level fields. The
+        // flattened fields are recorded to restore the nesting of the retrieved documents.
+        let mut fields = BTreeSet::new();
+        // The ids of the documents of the payload, `None` when they are unknown.
+        let mut ids = None;
+        let mut content = match format {
+            UpdateFormat::Json | UpdateFormat::JsonStream if content.metadata()?.len() > 0 => {
+                let mut nested = self.nested_fields(&wtxn)?;
+                let mut flattened = tempfile::tempfile()?;
+                let mut payload_ids = Vec::new();
+                flatten_documents(
+                    &mut content,
+                    &mut flattened,
+                    format,
+                    primary_key.as_deref(),
+                    &mut nested,
+                    &mut fields,
+                    &mut payload_ids,
+                )?;
+                flattened.seek(SeekFrom::Start(0))?;
+                self.put_nested_fields(&mut wtxn, &nested)?;
+                ids = Some(payload_ids);
+                flattened
+            }
+            _ => content,
+        };
+
+        // With the wildcard, milli would make the new fields searchable, `_vectors` included.
+        // The searchable fields are set to all the fields but `_vectors` before adding the
+        // documents instead.
+        if self.is_searchable_wildcard(&wtxn)? {
+            if let Some(names) = self.searchable_without_vectors(&wtxn, &fields)? {
+                let current = self
+                    .searchable_fields(&wtxn)?
+                    .map(|fields| fields.into_iter().map(String::from).collect::<Vec<_>>());
+                if current.as_ref() != Some(&names) {
+                    let mut builder = update_builder().settings(&mut wtxn, self);
+                    builder.set_searchable_fields(names);
+                    builder.execute(|_, _| ())?;
+                    self.put_searchable_wildcard(&mut wtxn, true)?;
+                }
+            }
+        }
+
+        let documents_before = self.number_of_documents(&wtxn)?;
 
-        let mut builder = update_builder.index_documents(&mut wtxn, self);
+        // The documents replaced by the addition are indexed again in the filter databases,
+        // along with the new ones, their previous entries are read before they are replaced.
+        let docids_before = self.documents_ids(&wtxn)?;
+        let replaced = match ids {
+            Some(ids) => {
+                let docids: RoaringBitmap = {
+                    let external_documents_ids = self.external_documents_ids(&wtxn)?;
+                    ids.iter()
+                        .filter_map(|id| external_documents_ids.get(id.as_bytes()))
+                        .collect()
+                };
+                let entries = self.filter_entries(&wtxn, &docids)?;
+                Some((docids, entries))
+            }
+            None => None,
+        };
+
+        let mut builder = update_builder().index_documents(&mut wtxn, self);
         builder.update_format(format);
         builder.index_documents_method(method);
 
         let gzipped = false;
         let reader = if gzipped {
-            Box::new(GzDecoder::new(content))
+            Box::new(GzDecoder::new(&mut content))
         } else {
-            Box::new(content) as Box<dyn io::Read>
+            Box::new(&mut content) as Box<dyn io::Read + '_>
         };
 
         let result = builder.execute(reader, |indexing_step, update_id| {
-            info!("update {}: {:?}", update_id, indexing_step)
+            info!("update {}: {:?}", update_id, indexing_step);
+            progress(indexing_step);
         });
 
         info!("document addition done: {:?}", result);
 
-        result.and_then(|addition_result| {
-            wtxn.commit()
-                .and(Ok(UpdateResult::DocumentsAddition(addition_result)))
-                .map_err(Into::into)
-        })
+        let addition_result = match result {
+            Ok(result) => result,
+            // The documents are checked only once the addition failed, to find which of them
+            // made it fail.
+            Err(e) if matches!(format, UpdateFormat::Json) => {
+                content.seek(SeekFrom::Start(0))?;
+                let primary_key = primary_key.as_deref();
+                let errors = document_errors(&mut content, primary_key, MAX_DOCUMENT_ERRORS)
+                    .unwrap_or_default();
+                if errors.is_empty() {
+                    return Err(e);
+                }
+                return Err(InvalidDocuments {
+                    message: e.to_string(),
+                    errors,
+                }
+                .into());
+            }
+            Err(e) => return Err(e),
+        };
+
+        // The transaction is aborted if the limit is exceeded, no document is added.
+        let number_of_fields = self.fields_ids_map(&wtxn)?.len();
+        ensure!(
+            number_of_fields <= max_fields,
+            "The documents can't be added, the index would contain {} distinct fields but it \
+            can't contain more than {}.",
+            number_of_fields,
+            max_fields
+        );
+
+        // Without the ids of the payload, all the documents are indexed again.
+        match replaced {
+            Some((replaced_docids, replaced_entries)) => {
+                self.remove_filter_entries(&mut wtxn, replaced_entries)?;
+                let docids = (self.documents_ids(&wtxn)? - docids_before) | replaced_docids;
+                self.index_filter_docids(&mut wtxn, &docids)?;
+            }
+            None => self.reindex_filter_docids(&mut wtxn)?,
+        }
+
+        let total_documents = self.number_of_documents(&wtxn)?;
+        wtxn.commit()?;
+
+        Ok(UpdateResult::DocumentsAddition(DocumentsAdditionResult {
+            nb_documents: addition_result.nb_documents,
+            new_documents: Some(total_documents.saturating_sub(documents_before)),
+            total_documents: Some(total_documents),
+            skipped_documents: None,
+            document_errors: Vec::new(),
+        }))
+    }
+
+    /// Copies the valid documents of a JSON payload to a new file, so that the invalid documents
+    /// are skipped instead of failing the whole addition. Returns the new payload, the number of
+    /// skipped documents and their errors.
+    pub fn skip_invalid_documents(
+        &self,
+        mut content: File,
+        primary_key: Option<&str>,
+    ) -> anyhow::Result<(File, usize, Vec<DocumentError>)> {
+        if content.metadata()?.len() == 0 {
+            return Ok((content, 0, Vec::new()));
+        }
+
+        let rtxn = self.read_txn()?;
+        let primary_key = match (self.primary_key(&rtxn)?, primary_key) {
+            (Some(primary_key), _) | (None, Some(primary_key)) => Some(primary_key.to_string()),
+            (None, None) => {
+                let document = first_document(&mut content)?;
+                content.seek(SeekFrom::Start(0))?;
+                infer_primary_key(document.as_ref())?
+            }
+        };
+
+        let mut filtered = tempfile::tempfile()?;
+        let (skipped, errors) = filter_documents(
+            content,
+            &mut filtered,
+            primary_key.as_deref(),
+            MAX_DOCUMENT_ERRORS,
+        )?;
+        filtered.seek(SeekFrom::Start(0))?;
+        Ok((filtered, skipped, errors))
     }
 
     pub fn clear_documents(&self, update_builder: UpdateBuilder) -> anyhow::Result<UpdateResult> {
@@ -120,10 +501,11 @@ impl Index {
         let builder = update_builder.clear_documents(&mut wtxn, self);
 
         match builder.execute() {
-            Ok(_count) => wtxn
-                .commit()
-                .and(Ok(UpdateResult::Other))
-                .map_err(Into::into),
+            Ok(deleted) => {
+                self.clear_filter_docids(&mut wtxn)?;
+                wtxn.commit()?;
+                Ok(UpdateResult::ClearDocuments { deleted })
+            }
             Err(e) => Err(e),
         }
     }
@@ -132,13 +514,87 @@ impl Index {
         &self,
         settings: &Settings,
         update_builder: UpdateBuilder,
+        progress: impl Fn(UpdateIndexingStep) + Sync,
     ) -> anyhow::Result<UpdateResult> {
+        if let Some(Some(ref languages)) = settings.languages {
+            if let Some(language) = languages.iter().find(|l| !is_language_code(l)) {
+                bail!(
+                    "Invalid language `{}`, the languages must be ISO 639-3 codes, e.g. `jpn`.",
+                    language
+                );
+            }
+            ensure!(
+                languages.is_empty(),
+                "The languages can't be set yet, the tokenizer detects the language from the \
+                script of the text."
+            );
+        }
+
+        // The tokenizer splits the words on its own separators, the custom tokens and words are
+        // refused instead of being ignored.
+        if let Some(Some(ref tokens)) = settings.separator_tokens {
+            ensure!(
+                tokens.is_empty(),
+                "The separator tokens can't be set yet, the tokenizer only splits the words on \
+                its own separators."
+            );
+        }
+        if let Some(Some(ref tokens)) = settings.non_separator_tokens {
+            ensure!(
+                tokens.is_empty(),
+                "The non-separator tokens can't be set yet, the tokenizer only splits the words \
+                on its own separators."
+            );
+        }
+        if let Some(Some(ref words)) = settings.dictionary {
+            ensure!(
+                words.is_empty(),
+                "The dictionary can't be set yet, the tokenizer can't be given custom words."
+            );
+        }
+
+        ensure!(
+            settings.proximity_precision != Some(Some(ProximityPrecision::ByAttribute)),
+            "The proximity precision can't be set to `byAttribute` yet, milli always measures \
+            the proximity of the words by word."
+        );
+
+        // The previous settings are kept to report the changes made by the update.
+        let old_settings = self.settings()?;
+
+        // milli reindexes all the documents as soon as the searchable or the faceted attributes
+        // are set, so they are only given to milli when they actually change.
+        let searchable_attributes = settings.searchable_attributes.as_ref().filter(|names| {
+            let names = (*names).clone().unwrap_or_else(|| vec!["*".to_string()]);
+            old_settings.searchable_attributes != Some(Some(names))
+        });
+        // The `_vectors` field is never searchable, with the wildcard milli is given all the
+        // other fields.
+        let searchable_attributes = match searchable_attributes {
+            Some(Some(names)) if names.iter().all(|name| name != "*") => {
+                let names = names.iter().filter(|name| *name != VECTORS_FIELD);
+                Some((Some(names.cloned().collect::<Vec<_>>()), false))
+            }
+            Some(_) => {
+                let txn = self.read_txn()?;
+                let names = self.searchable_without_vectors(&txn, &BTreeSet::new())?;
+                Some((names, true))
+            }
+            None => None,
+        };
+        let facet_types = settings.attributes_for_faceting.as_ref().filter(|types| {
+            let types = (*types).clone().unwrap_or_default();
+            old_settings.attributes_for_faceting != Some(Some(types))
+        });
+        let reindexed_facets = facet_types.is_some();
+        let reindexed = searchable_attributes.is_some() || reindexed_facets;
+
         // We must use the write transaction of the update here.
         let mut wtxn = self.write_txn()?;
         let mut builder = update_builder.settings(&mut wtxn, self);
 
         // We transpose the settings JSON struct into a real setting update.
-        if let Some(ref names) = settings.searchable_attributes {
+        if let Some((ref names, _)) = searchable_attributes {
             match names {
                 Some(names) => builder.set_searchable_fields(names.clone()),
                 None => builder.reset_searchable_fields(),
@@ -154,7 +610,7 @@ impl Index {
         }
 
         // We transpose the settings JSON struct into a real setting update.
-        if let Some(ref facet_types) = settings.attributes_for_faceting {
+        if let Some(facet_types) = facet_types {
             let facet_types = facet_types.clone().unwrap_or_else(HashMap::new);
             builder.set_faceted_fields(facet_types);
         }
@@ -167,14 +623,64 @@ impl Index {
             }
         }
 
-        let result = builder
-            .execute(|indexing_step, update_id| info!("update {}: {:?}", update_id, indexing_step));
+        let result = builder.execute(|indexing_step, update_id| {
+            info!("update {}: {:?}", update_id, indexing_step);
+            progress(indexing_step);
+        });
 
         match result {
-            Ok(()) => wtxn
-                .commit()
-                .and(Ok(UpdateResult::Other))
-                .map_err(Into::into),
+            Ok(()) => {
+                if let Some((ref names, wildcard)) = searchable_attributes {
+                    self.put_searchable_wildcard(&mut wtxn, wildcard && names.is_some())?;
+                }
+
+                // The filterable fields changed with the faceted ones.
+                if reindexed_facets {
+                    self.reindex_filter_docids(&mut wtxn)?;
+                }
+
+                // The pagination settings are not handled by milli, we store them ourselves.
+                if let Some(ref pagination) = settings.pagination {
+                    match pagination.as_ref().and_then(|p| p.max_total_hits) {
+                        Some(max_total_hits) => self.put_max_total_hits(&mut wtxn, max_total_hits)?,
+                        None => {
+                            self.delete_max_total_hits(&mut wtxn)?;
+                        }
+                    }
+                }
+
+                // Neither are the tokenizer settings.
+                if let Some(ref languages) = settings.languages {
+                    let languages = languages.as_deref().unwrap_or_default();
+                    self.put_string_list(&mut wtxn, LANGUAGES_KEY, languages)?;
+                }
+                let token_settings = [
+                    (SEPARATOR_TOKENS_KEY, &settings.separator_tokens),
+                    (NON_SEPARATOR_TOKENS_KEY, &settings.non_separator_tokens),
+                    (DICTIONARY_KEY, &settings.dictionary),
+                    (EXACT_WORDS_KEY, &settings.exact_words),
+                    (EXACT_ATTRIBUTES_KEY, &settings.exact_attributes),
+                ];
+                for (key, tokens) in token_settings.iter() {
+                    if let Some(tokens) = tokens {
+                        let tokens: Vec<_> = tokens.iter().flatten().cloned().collect();
+                        self.put_string_list(&mut wtxn, key, &tokens)?;
+                    }
+                }
+
+                // Nor the indexing precision settings.
+                if let Some(precision) = settings.proximity_precision {
+                    self.put_json_setting(&mut wtxn, PROXIMITY_PRECISION_KEY, precision)?;
+                }
+                if let Some(prefix_search) = settings.prefix_search {
+                    self.put_json_setting(&mut wtxn, PREFIX_SEARCH_KEY, prefix_search)?;
+                }
+
+                wtxn.commit()?;
+
+                let changes = diff_settings(&old_settings, &self.settings()?)?;
+                Ok(UpdateResult::Settings { changes, reindexed })
+            }
             Err(e) => Err(e),
         }
     }
@@ -209,6 +715,17 @@ impl Index {
     ) -> anyhow::Result<UpdateResult> {
         let ids: Vec<String> = serde_json::from_reader(document_ids)?;
         let mut txn = self.write_txn()?;
+
+        // The documents are removed from the filter databases once they are deleted, their
+        // entries are read before.
+        let docids: RoaringBitmap = {
+            let external_documents_ids = self.external_documents_ids(&txn)?;
+            ids.iter()
+                .filter_map(|id| external_documents_ids.get(id.as_bytes()))
+                .collect()
+        };
+        let entries = self.filter_entries(&txn, &docids)?;
+
         let mut builder = update_builder.delete_documents(&mut txn, self)?;
 
         // We ignore unexisting document ids
@@ -217,10 +734,14 @@ impl Index {
         });
 
         match builder.execute() {
-            Ok(deleted) => txn
-                .commit()
-                .and(Ok(UpdateResult::DocumentDeletion { deleted }))
-                .map_err(Into::into),
+            Ok(deleted) => {
+                self.remove_filter_entries(&mut txn, entries)?;
+                txn.commit()?;
+                Ok(UpdateResult::DocumentDeletion {
+                    deleted,
+                    requested: Some(ids.len() as u64),
+                })
+            }
             Err(e) => Err(e),
         }
     }
@@ -0,0 +1,420 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+
+use serde::de::{self, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// The maximum number of distinct fields an index can contain, milli identifies them by a
+/// `FieldId`.
+const MAX_FIELDS: usize = milli::FieldId::MAX as usize + 1;
+
+#[derive(Debug, Error)]
+pub enum PrimaryKeyError {
+    #[error("The primary key can't be inferred, the first document is not an object. It must be given with the `primaryKey` parameter.")]
+    NotAnObject,
+    #[error("The primary key can't be inferred, none of the fields of the first document contain `id`: {}. It must be given with the `primaryKey` parameter.", list(.fields))]
+    NoCandidate { fields: Vec<String> },
+    #[error("The primary key can't be inferred, several fields of the first document could be the primary key: {}. It must be given with the `primaryKey` parameter.", list(.candidates))]
+    Ambiguous { candidates: Vec<String> },
+}
+
+fn list(fields: &[String]) -> String {
+    if fields.is_empty() {
+        return String::from("the document is empty");
+    }
+    let fields: Vec<_> = fields.iter().map(|f| format!("`{}`", f)).collect();
+    fields.join(", ")
+}
+
+/// Infers the primary key from the first document of a payload: the field named `id`, or else
+/// the only field containing `id`, case insensitively. Returns `None` if there is no document.
+pub fn infer_primary_key(first: Option<&Value>) -> Result<Option<String>, PrimaryKeyError> {
+    let document = match first {
+        Some(Value::Object(document)) => document,
+        Some(_) => return Err(PrimaryKeyError::NotAnObject),
+        None => return Ok(None),
+    };
+
+    if let Some(id) = document.keys().find(|key| key.eq_ignore_ascii_case("id")) {
+        return Ok(Some(id.clone()));
+    }
+
+    let mut candidates: Vec<_> = document
+        .keys()
+        .filter(|key| key.to_lowercase().contains("id"))
+        .cloned()
+        .collect();
+    match candidates.len() {
+        0 => Err(PrimaryKeyError::NoCandidate {
+            fields: document.keys().cloned().collect(),
+        }),
+        1 => Ok(candidates.pop()),
+        _ => Err(PrimaryKeyError::Ambiguous { candidates }),
+    }
+}
+
+/// Reads the first document of a JSON array of documents, without keeping the other documents
+/// in memory. An empty payload has no document.
+pub fn first_document(reader: impl io::Read) -> serde_json::Result<Option<Value>> {
+    struct FirstDocument;
+
+    impl<'de> Visitor<'de> for FirstDocument {
+        type Value = Option<Value>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array of documents")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let first = seq.next_element()?;
+            while seq.next_element::<IgnoredAny>()?.is_some() {}
+            Ok(first)
+        }
+    }
+
+    let mut reader = BufReader::new(reader);
+    if reader.fill_buf().map_err(serde_json::Error::io)?.is_empty() {
+        return Ok(None);
+    }
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer.deserialize_seq(FirstDocument)
+}
+
+/// The result of the validation of a document payload, the documents are not indexed.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub valid: bool,
+    /// The primary key the documents would be indexed with, given or inferred from the first
+    /// document.
+    pub primary_key: Option<String>,
+    pub number_of_documents: usize,
+    /// The number of distinct fields of the documents.
+    pub number_of_fields: usize,
+    pub errors: Vec<DocumentError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentError {
+    /// The position of the invalid document in the payload, missing when the error concerns the
+    /// whole payload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<usize>,
+    /// The id of the invalid document, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub message: String,
+}
+
+/// The error of a document addition that failed because of some of its documents.
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct InvalidDocuments {
+    pub message: String,
+    pub errors: Vec<DocumentError>,
+}
+
+impl ValidationReport {
+    fn error(&mut self, document: Option<usize>, message: String) {
+        self.errors.push(DocumentError {
+            document,
+            id: None,
+            message,
+        });
+    }
+}
+
+/// Validates a JSON array of documents the way milli would index it: the payload must be valid
+/// JSON, and each document must be an object containing a valid primary key.
+pub fn validate_documents(content: &[u8], primary_key: Option<&str>) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let documents: Vec<Value> = match serde_json::from_slice(content) {
+        Ok(documents) => documents,
+        Err(e) => {
+            report.error(None, format!("Invalid JSON payload: {}.", e));
+            return report;
+        }
+    };
+    report.number_of_documents = documents.len();
+
+    report.primary_key = match primary_key {
+        Some(primary_key) => Some(primary_key.to_string()),
+        None => match infer_primary_key(documents.first()) {
+            Ok(primary_key) => primary_key,
+            Err(e) => {
+                report.error(None, e.to_string());
+                None
+            }
+        },
+    };
+
+    let mut fields = HashSet::new();
+    for (position, document) in documents.iter().enumerate() {
+        if let Some(document) = document.as_object() {
+            fields.extend(document.keys());
+        }
+        let primary_key = report.primary_key.as_deref();
+        report
+            .errors
+            .extend(document_error(position, document, primary_key));
+    }
+
+    report.number_of_fields = fields.len();
+    if fields.len() > MAX_FIELDS {
+        report.error(
+            None,
+            format!(
+                "The documents contain {} distinct fields, an index can't contain more than {}.",
+                fields.len(),
+                MAX_FIELDS
+            ),
+        );
+    }
+
+    report.valid = report.errors.is_empty();
+    report
+}
+
+/// Returns at most `limit` errors of the documents of a JSON array of documents, without keeping
+/// the documents in memory.
+pub fn document_errors(
+    reader: impl io::Read,
+    primary_key: Option<&str>,
+    limit: usize,
+) -> serde_json::Result<Vec<DocumentError>> {
+    struct DocumentErrors<'a> {
+        primary_key: Option<&'a str>,
+        limit: usize,
+    }
+
+    impl<'de> Visitor<'de> for DocumentErrors<'_> {
+        type Value = Vec<DocumentError>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array of documents")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut errors = Vec::new();
+            let mut position = 0;
+            while errors.len() < self.limit {
+                match seq.next_element::<Value>()? {
+                    Some(document) => {
+                        errors.extend(document_error(position, &document, self.primary_key));
+                        position += 1;
+                    }
+                    None => return Ok(errors),
+                }
+            }
+            while seq.next_element::<IgnoredAny>()?.is_some() {}
+            Ok(errors)
+        }
+    }
+
+    let visitor = DocumentErrors { primary_key, limit };
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    deserializer.deserialize_seq(visitor)
+}
+
+/// Writes the valid documents of a JSON array of documents to `writer`, as a JSON array, and
+/// skips the others. Returns the number of skipped documents and at most `limit` of their errors.
+pub fn filter_documents(
+    reader: impl io::Read,
+    writer: impl io::Write,
+    primary_key: Option<&str>,
+    limit: usize,
+) -> serde_json::Result<(usize, Vec<DocumentError>)> {
+    struct FilterDocuments<'a, W> {
+        writer: W,
+        primary_key: Option<&'a str>,
+        limit: usize,
+    }
+
+    impl<'de, W: io::Write> Visitor<'de> for FilterDocuments<'_, W> {
+        type Value = (usize, Vec<DocumentError>);
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array of documents")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut skipped = 0;
+            let mut errors = Vec::new();
+            let mut written = 0;
+            let mut position = 0;
+
+            self.writer.write_all(b"[").map_err(de::Error::custom)?;
+            while let Some(document) = seq.next_element::<Value>()? {
+                match document_error(position, &document, self.primary_key) {
+                    Some(error) => {
+                        skipped += 1;
+                        if errors.len() < self.limit {
+                            errors.push(error);
+                        }
+                    }
+                    None => {
+                        if written > 0 {
+                            self.writer.write_all(b",").map_err(de::Error::custom)?;
+                        }
+                        serde_json::to_writer(&mut self.writer, &document)
+                            .map_err(de::Error::custom)?;
+                        written += 1;
+                    }
+                }
+                position += 1;
+            }
+            self.writer.write_all(b"]").map_err(de::Error::custom)?;
+            self.writer.flush().map_err(de::Error::custom)?;
+
+            Ok((skipped, errors))
+        }
+    }
+
+    let visitor = FilterDocuments {
+        writer: io::BufWriter::new(writer),
+        primary_key,
+        limit,
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    deserializer.deserialize_seq(visitor)
+}
+
+/// Checks that the document is an object containing a valid primary key, if it is known.
+fn document_error(
+    position: usize,
+    document: &Value,
+    primary_key: Option<&str>,
+) -> Option<DocumentError> {
+    let document = match document.as_object() {
+        Some(document) => document,
+        None => {
+            return Some(DocumentError {
+                document: Some(position),
+                id: None,
+                message: "The document is not an object.".to_string(),
+            })
+        }
+    };
+
+    let primary_key = primary_key?;
+    let message = match document.get(primary_key) {
+        Some(id) if is_valid_document_id(id) => return None,
+        Some(id) => format!(
+            "Invalid document id {}, it must be an integer or a string of alphanumeric \
+            characters, hyphens and underscores.",
+            id
+        ),
+        None => format!("The primary key `{}` is missing.", primary_key),
+    };
+    let id = document.get(primary_key).map(|id| {
+        id.as_str()
+            .map(String::from)
+            .unwrap_or_else(|| id.to_string())
+    });
+    Some(DocumentError {
+        document: Some(position),
+        id,
+        message,
+    })
+}
+
+fn is_valid_document_id(id: &Value) -> bool {
+    match id {
+        Value::Number(n) => n.is_u64() || n.is_i64(),
+        Value::String(s) => {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn infers_the_primary_key() {
+        let report = validate_documents(br#"[{ "title": "a", "movie_id": 1 }]"#, None);
+        assert!(report.valid, "{:?}", report);
+        assert_eq!(report.primary_key.as_deref(), Some("movie_id"));
+        assert_eq!(report.number_of_fields, 2);
+    }
+
+    #[test]
+    fn primary_key_inference_errors() {
+        let document = json!({ "title": "a", "movie_id": 1, "author_id": 2 });
+        match infer_primary_key(Some(&document)) {
+            Err(PrimaryKeyError::Ambiguous { candidates }) => {
+                assert_eq!(candidates, ["movie_id", "author_id"])
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let document = json!({ "title": "a", "ID": 1, "movie_id": 1 });
+        let primary_key = infer_primary_key(Some(&document)).unwrap();
+        assert_eq!(primary_key.as_deref(), Some("ID"));
+
+        let document = json!({ "title": "a" });
+        let error = infer_primary_key(Some(&document)).unwrap_err();
+        assert!(error.to_string().contains("`title`"), "{}", error);
+    }
+
+    #[test]
+    fn reads_the_first_document() {
+        let payload = br#"[{ "id": 1 }, { "id": 2 }]"#;
+        let first = first_document(&payload[..]).unwrap();
+        assert_eq!(first, Some(json!({ "id": 1 })));
+        assert_eq!(first_document(&b"[]"[..]).unwrap(), None);
+    }
+
+    #[test]
+    fn reports_invalid_documents() {
+        let payload = br#"[{ "id": 1 }, { "title": "a" }, { "id": "a b" }, 12]"#;
+        let report = validate_documents(payload, Some("id"));
+        assert!(!report.valid);
+        assert_eq!(report.number_of_documents, 4);
+        let documents: Vec<_> = report.errors.iter().map(|e| e.document).collect();
+        assert_eq!(documents, [Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn streams_the_document_errors() {
+        let payload = br#"[{ "id": 1 }, { "id": "a b" }, 12, { "title": "a" }]"#;
+        let errors = document_errors(&payload[..], Some("id"), 2).unwrap();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].document, Some(1));
+        assert_eq!(errors[0].id.as_deref(), Some("a b"));
+        assert_eq!(errors[1].document, Some(2));
+        assert_eq!(errors[1].id, None);
+    }
+
+    #[test]
+    fn filters_the_invalid_documents() {
+        let payload = br#"[{ "id": 1 }, { "id": "a b" }, 12, { "id": 2 }]"#;
+        let mut filtered = Vec::new();
+        let (skipped, errors) =
+            filter_documents(&payload[..], &mut filtered, Some("id"), 1).unwrap();
+        assert_eq!(skipped, 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].document, Some(1));
+        let filtered: Value = serde_json::from_slice(&filtered).unwrap();
+        assert_eq!(filtered, json!([{ "id": 1 }, { "id": 2 }]));
+    }
+
+    #[test]
+    fn reports_invalid_json() {
+        let report = validate_documents(br#"[{ "id": 1 "#, None);
+        assert!(!report.valid);
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].document.is_none());
+    }
+}
@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+use heed::RoTxn;
+use serde_json::Value;
+
+use super::search::Deadline;
+use super::Index;
+
+/// The field of the documents holding their embeddings, either a single vector
+/// (`[0.1, 0.2]`) or a list of vectors (`[[0.1, 0.2], [0.3, 0.4]]`).
+pub const VECTORS_FIELD: &str = "_vectors";
+
+/// Set in the main database when the searchable attributes are the wildcard while milli's
+/// searchable fields are set, to all the fields but the `_vectors` field.
+const SEARCHABLE_WILDCARD_KEY: &str = "searchable-wildcard";
+
+/// Returns the embeddings of a `_vectors` field. The values that are not a vector of numbers
+/// are ignored.
+fn parse_embeddings(value: &Value) -> Vec<Vec<f32>> {
+    fn parse_vector(values: &[Value]) -> Option<Vec<f32>> {
+        values
+            .iter()
+            .map(|v| v.as_f64().map(|v| v as f32))
+            .collect()
+    }
+
+    match value {
+        Value::Array(values) if values.iter().all(Value::is_array) => values
+            .iter()
+            .filter_map(|v| v.as_array().and_then(|v| parse_vector(v)))
+            .collect(),
+        Value::Array(values) => parse_vector(values).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The cosine similarity of two vectors, `None` if their dimensions differ or one of them is
+/// null.
+fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() {
+        return None;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm_a = a.iter().map(|a| a * a).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|b| b * b).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        None
+    } else {
+        Some(dot / (norm_a * norm_b))
+    }
+}
+
+/// The semantic score of a document, between 0 and 1, computed from the closest of its
+/// embeddings to the query vector. `None` if the document has no embedding of the dimension of
+/// the query vector.
+fn semantic_score(vector: &[f32], embeddings: &[Vec<f32>]) -> Option<f32> {
+    embeddings
+        .iter()
+        .filter_map(|embedding| cosine_similarity(vector, embedding))
+        .map(|similarity| (similarity + 1.0) / 2.0)
+        .fold(None, |max, score| match max {
+            Some(max) if max >= score => Some(max),
+            _ => Some(score),
+        })
+}
+
+impl Index {
+    /// Whether the searchable attributes are the wildcard, all the fields but the `_vectors`
+    /// field being searchable.
+    pub(super) fn is_searchable_wildcard(&self, txn: &RoTxn) -> anyhow::Result<bool> {
+        let wildcard = self
+            .json_setting::<bool>(txn, SEARCHABLE_WILDCARD_KEY)?
+            .unwrap_or(false);
+        Ok(wildcard || self.searchable_fields(txn)?.is_none())
+    }
+
+    pub(super) fn put_searchable_wildcard(
+        &self,
+        txn: &mut heed::RwTxn,
+        wildcard: bool,
+    ) -> anyhow::Result<()> {
+        let value = if wildcard { Some(true) } else { None };
+        self.put_json_setting(txn, SEARCHABLE_WILDCARD_KEY, value)
+    }
+
+    /// Returns the fields searchable with the wildcard, all the fields of the index and the
+    /// `new_fields` but the `_vectors` field, whose values are only used by the vector search.
+    /// `None` when there is no `_vectors` field, all the fields being searchable.
+    pub(super) fn searchable_without_vectors(
+        &self,
+        txn: &RoTxn,
+        new_fields: &BTreeSet<String>,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        let fields_ids_map = self.fields_ids_map(txn)?;
+        if fields_ids_map.id(VECTORS_FIELD).is_none() && !new_fields.contains(VECTORS_FIELD) {
+            return Ok(None);
+        }
+
+        let mut fields: Vec<_> = fields_ids_map
+            .iter()
+            .map(|(_, name)| name.to_string())
+            .collect();
+        for field in new_fields {
+            if fields_ids_map.id(field).is_none() {
+                fields.push(field.clone());
+            }
+        }
+        fields.retain(|field| field != VECTORS_FIELD);
+        Ok(Some(fields))
+    }
+
+    /// Ranks the `candidates` by their distance to `vector`, mixed with their rank in the
+    /// keyword search, `keyword_ranking`, according to `semantic_ratio`: `1.0` only takes the
+    /// vectors into account, `0.0` only the keywords.
+    ///
+    /// Every candidate is compared with the query vector, the documents with no embedding of the
+    /// right dimension are only returned when they match the keywords. Returns the ids of the
+    /// ranked documents, from the closest to the farthest.
+    ///
+    /// Only the candidates compared before the `deadline` is reached are ranked, the returned
+    /// boolean tells whether all of them were.
+    ///
+    /// This is a brute-force search: there is no vector index, so the embeddings of all the
+    /// candidates are read and compared on each search, which only fits indexes of a few hundred
+    /// thousand documents. A filter reduces the candidates, and so the cost of the search.
+    pub(super) fn vector_ranking(
+        &self,
+        txn: &RoTxn,
+        candidates: impl IntoIterator<Item = u32>,
+        vector: &[f32],
+        keyword_ranking: &[u32],
+        semantic_ratio: f32,
+        deadline: Deadline,
+    ) -> anyhow::Result<(Vec<u32>, bool)> {
+        let fields_ids_map = self.fields_ids_map(txn)?;
+        let vectors_fid = fields_ids_map.id(VECTORS_FIELD);
+
+        // The best ranked keyword hit has a keyword score of 1, the last one is close to 0.
+        let keyword_scores: HashMap<u32, f32> = keyword_ranking
+            .iter()
+            .enumerate()
+            .map(|(rank, id)| {
+                let score = 1.0 - rank as f32 / keyword_ranking.len() as f32;
+                (*id, score)
+            })
+            .collect();
+
+        let mut scores = Vec::new();
+        let mut complete = true;
+        for (id, obkv) in self.documents(txn, candidates)? {
+            if deadline.is_reached() {
+                complete = false;
+                break;
+            }
+
+            let semantic_score = vectors_fid
+                .and_then(|fid| obkv.get(fid))
+                .and_then(|value| serde_json::from_slice(value).ok())
+                .and_then(|value| semantic_score(vector, &parse_embeddings(&value)));
+            let keyword_score = keyword_scores.get(&id).copied();
+
+            if semantic_score.is_none() && keyword_score.is_none() {
+                continue;
+            }
+
+            let score = semantic_ratio * semantic_score.unwrap_or_default()
+                + (1.0 - semantic_ratio) * keyword_score.unwrap_or_default();
+            scores.push((id, score));
+        }
+
+        scores.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        Ok((scores.into_iter().map(|(id, _)| id).collect(), complete))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_embeddings() {
+        assert_eq!(parse_embeddings(&json!([1, 0.5])), vec![vec![1.0, 0.5]]);
+        assert_eq!(
+            parse_embeddings(&json!([[1, 0], [0, 1]])),
+            vec![vec![1.0, 0.0], vec![0.0, 1.0]]
+        );
+        assert!(parse_embeddings(&json!([1, "foo"])).is_empty());
+        assert!(parse_embeddings(&json!("foo")).is_empty());
+    }
+
+    #[test]
+    fn test_semantic_score() {
+        assert_eq!(semantic_score(&[1.0, 0.0], &[vec![1.0, 0.0]]), Some(1.0));
+        assert_eq!(semantic_score(&[1.0, 0.0], &[vec![-1.0, 0.0]]), Some(0.0));
+        assert_eq!(
+            semantic_score(&[1.0, 0.0], &[vec![0.0, 1.0], vec![2.0, 0.0]]),
+            Some(1.0)
+        );
+        assert_eq!(semantic_score(&[1.0, 0.0], &[vec![1.0, 0.0, 0.0]]), None);
+        assert_eq!(semantic_score(&[1.0, 0.0], &[vec![0.0, 0.0]]), None);
+    }
+}
@@ -0,0 +1,159 @@
+//! Detects the indexes whose data is missing from the disk, and the data directories that no
+//! index references, left behind by a crash or by a manual intervention on the database.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{IndexController, UpdateStatus};
+use crate::option::OrphanRepair;
+
+/// The prefix of the uid under which an orphan index is relinked.
+const RELINKED_PREFIX: &str = "orphan-";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingIndex {
+    pub uid: String,
+    pub uuid: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsistencyReport {
+    /// The indexes without a database on the disk, nor pending updates creating it.
+    pub missing_indexes: Vec<MissingIndex>,
+    /// The uuids of the index databases and update stores that no index references.
+    pub orphans: BTreeSet<Uuid>,
+    /// The repair applied to the inconsistencies.
+    pub repair: OrphanRepair,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing_indexes.is_empty() && self.orphans.is_empty()
+    }
+}
+
+impl IndexController {
+    /// Compares the indexes of the uuid resolver with the data directories on the disk, and
+    /// repairs the inconsistencies according to `repair`.
+    pub async fn check_consistency(
+        &self,
+        repair: OrphanRepair,
+    ) -> anyhow::Result<ConsistencyReport> {
+        // No index can be created or deleted during the check, and no update is processed, so
+        // that the shadow indexes of the indexes being rebuilt aren't taken for orphans.
+        let _guard = self.replication_guard().await;
+        let _paused = self.update_handle.pause().await;
+        let _snapshot_guard = self.snapshot_lock.write().await;
+
+        let indexes: HashMap<Uuid, String> = self
+            .uuid_resolver
+            .list()
+            .await?
+            .into_iter()
+            .map(|(uid, uuid)| (uuid, uid))
+            .collect();
+
+        let mut missing_indexes = Vec::new();
+        for (uuid, uid) in &indexes {
+            // The database of an index created by its first update is only created once the
+            // update is processed.
+            let creating = match self.update_handle.get_all_updates_status(*uuid).await {
+                Ok(updates) => updates
+                    .iter()
+                    .any(|u| matches!(u, UpdateStatus::Pending(_) | UpdateStatus::Processing(_))),
+                Err(_) => false,
+            };
+            if !creating && !index_path(&self.path, uuid).exists() {
+                missing_indexes.push(MissingIndex {
+                    uid: uid.clone(),
+                    uuid: *uuid,
+                });
+            }
+        }
+
+        let mut orphans = list_uuids(&self.path.join("indexes"), "index-")?;
+        orphans.extend(list_uuids(&self.path.join("updates"), "updates-")?);
+        orphans.retain(|uuid| {
+            !indexes.contains_key(uuid) && !self.garbage_collector.is_collecting(uuid)
+        });
+
+        for MissingIndex { uid, uuid } in &missing_indexes {
+            warn!("The data of the index {} ({}) is missing.", uid, uuid);
+        }
+        for uuid in &orphans {
+            warn!("The data of {} isn't referenced by any index.", uuid);
+        }
+
+        match repair {
+            OrphanRepair::Report => (),
+            OrphanRepair::Purge | OrphanRepair::Relink => {
+                for MissingIndex { uid, uuid } in &missing_indexes {
+                    info!("Removing the index {} whose data is missing.", uid);
+                    self.uuid_resolver.delete(uid.clone()).await?;
+                    self.delete_index_data(*uuid).await?;
+                }
+            }
+        }
+
+        match repair {
+            OrphanRepair::Report => (),
+            OrphanRepair::Purge => {
+                for uuid in &orphans {
+                    self.delete_index_data(*uuid).await?;
+                }
+            }
+            OrphanRepair::Relink => {
+                for uuid in &orphans {
+                    if index_path(&self.path, uuid).exists() {
+                        let uid = format!("{}{}", RELINKED_PREFIX, uuid);
+                        info!("Relinking the data of {} as the index {}.", uuid, uid);
+                        self.uuid_resolver.insert(uid, *uuid).await?;
+                    } else {
+                        self.delete_index_data(*uuid).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(ConsistencyReport {
+            missing_indexes,
+            orphans,
+            repair,
+        })
+    }
+}
+
+fn index_path(db_path: &Path, uuid: &Uuid) -> PathBuf {
+    db_path.join("indexes").join(format!("index-{}", uuid))
+}
+
+/// Lists the uuids of the directories of `dir` named `{prefix}{uuid}`.
+fn list_uuids(dir: &Path, prefix: &str) -> anyhow::Result<BTreeSet<Uuid>> {
+    let mut uuids = BTreeSet::new();
+    if !dir.exists() {
+        return Ok(uuids);
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let uuid = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|uuid| Uuid::parse_str(uuid).ok());
+        if let Some(uuid) = uuid {
+            uuids.insert(uuid);
+        }
+    }
+
+    Ok(uuids)
+}
@@ -0,0 +1,221 @@
+//! Readers of the dumps of the legacy MeiliSearch engine, converting them to the format of this
+//! crate.
+//!
+//! `V1` dumps, created up to MeiliSearch v0.20, have a directory per index uid at the root of the
+//! archive. `V2` dumps, created by MeiliSearch v0.21, have a directory per index uuid, and a
+//! table mapping the index uids to their uuid. The pending updates of a `V2` dump are not
+//! imported.
+
+use std::collections::HashMap;
+use std::fs::{self, create_dir_all, File};
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::super::uuid_resolver::validate_index_uid;
+use super::{write_metadata, DumpIndex, DumpVersion, Metadata, METADATA_FILE};
+use crate::index::Settings;
+
+/// The facet type given to the attributes used for faceting or filtering in a legacy dump, whose
+/// values were always handled as strings.
+const LEGACY_FACET_TYPE: &str = "string";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetadataV1 {
+    // The first dumps called the indexes `indices`.
+    #[serde(alias = "indices")]
+    indexes: Vec<IndexV1>,
+    db_version: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexV1 {
+    uid: String,
+    primary_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetadataV2 {
+    db_version: String,
+    dump_date: DateTime<Utc>,
+}
+
+/// An entry of the `index_uuids/data.jsonl` file of a `V2` dump.
+#[derive(Deserialize)]
+struct IndexUuidV2 {
+    uid: String,
+    uuid: Uuid,
+}
+
+/// The `meta.json` file of an index of a `V2` dump.
+#[derive(Deserialize)]
+struct IndexMetaV2 {
+    settings: LegacySettings,
+    primary_key: Option<String>,
+}
+
+/// The settings of both legacy formats. The unknown fields are ignored, and a `null` setting is
+/// the same as a missing one, as the indexes are imported with the default settings.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacySettings {
+    ranking_rules: Option<Vec<String>>,
+    distinct_attribute: Option<String>,
+    searchable_attributes: Option<Vec<String>>,
+    displayed_attributes: Option<Vec<String>>,
+    stop_words: Option<Vec<String>>,
+    synonyms: Option<HashMap<String, Vec<String>>>,
+    // `V1` dumps have facets, `V2` dumps have filters, both are imported as facets.
+    #[serde(alias = "filterableAttributes")]
+    attributes_for_faceting: Option<Vec<String>>,
+}
+
+impl LegacySettings {
+    fn into_settings(self, uid: &str) -> Settings {
+        if self.distinct_attribute.is_some() {
+            warn!("The distinct attribute of index {} can't be imported.", uid);
+        }
+        if self.stop_words.map_or(false, |words| !words.is_empty()) {
+            warn!("The stop words of index {} can't be imported.", uid);
+        }
+        if self.synonyms.map_or(false, |synonyms| !synonyms.is_empty()) {
+            warn!("The synonyms of index {} can't be imported.", uid);
+        }
+
+        // A wildcard means that all the attributes are displayed or searchable, which is the
+        // default.
+        let without_wildcard =
+            |attributes: Vec<String>| Some(attributes).filter(|a| a.iter().all(|a| a != "*"));
+
+        Settings {
+            displayed_attributes: self
+                .displayed_attributes
+                .and_then(without_wildcard)
+                .map(Some),
+            searchable_attributes: self
+                .searchable_attributes
+                .and_then(without_wildcard)
+                .map(Some),
+            attributes_for_faceting: self.attributes_for_faceting.map(|attributes| {
+                let facets = attributes
+                    .into_iter()
+                    .map(|attribute| (attribute, LEGACY_FACET_TYPE.to_string()))
+                    .collect();
+                Some(facets)
+            }),
+            ranking_rules: self.ranking_rules.map(Some),
+            pagination: None,
+            languages: None,
+            separator_tokens: None,
+            non_separator_tokens: None,
+            dictionary: None,
+            exact_words: None,
+            exact_attributes: None,
+            proximity_precision: None,
+            prefix_search: None,
+        }
+    }
+}
+
+/// Writes the settings and the documents of an index in the `V3` format, in `dest`. The
+/// documents are moved rather than copied, as they may be large.
+fn write_index(
+    dest: &Path,
+    uid: &str,
+    settings: LegacySettings,
+    documents: &Path,
+) -> anyhow::Result<()> {
+    let index_dir = dest.join("indexes").join(uid);
+    create_dir_all(&index_dir)?;
+
+    let file = File::create(index_dir.join("settings.json"))?;
+    serde_json::to_writer(BufWriter::new(file), &settings.into_settings(uid))?;
+
+    let documents_path = index_dir.join("documents.jsonl");
+    if documents.exists() {
+        fs::rename(documents, documents_path)?;
+    } else {
+        File::create(documents_path)?;
+    }
+    Ok(())
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+pub fn v1_to_v3(src: &Path, dest: &Path) -> anyhow::Result<DumpVersion> {
+    let metadata: MetadataV1 = read_json(&src.join(METADATA_FILE))?;
+
+    let mut indexes = Vec::new();
+    for IndexV1 { uid, primary_key } in metadata.indexes {
+        // The uid is part of the paths read and written, it must not escape the dump.
+        validate_index_uid(&uid)?;
+        let index_dir = src.join(&uid);
+        let settings = if index_dir.exists() {
+            read_json(&index_dir.join("settings.json"))?
+        } else {
+            warn!(
+                "The dump has no data for index {}, it is imported empty.",
+                uid
+            );
+            LegacySettings::default()
+        };
+        write_index(dest, &uid, settings, &index_dir.join("documents.jsonl"))?;
+        indexes.push(DumpIndex { uid, primary_key });
+    }
+
+    let metadata = Metadata {
+        dump_version: DumpVersion::V3,
+        db_version: metadata.db_version,
+        dump_date: None,
+        indexes,
+    };
+    write_metadata(dest, &metadata)?;
+    Ok(DumpVersion::V3)
+}
+
+pub fn v2_to_v3(src: &Path, dest: &Path) -> anyhow::Result<DumpVersion> {
+    let metadata: MetadataV2 = read_json(&src.join(METADATA_FILE))?;
+
+    let mut indexes = Vec::new();
+    let file = File::open(src.join("index_uuids").join("data.jsonl"))?;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let IndexUuidV2 { uid, uuid } = serde_json::from_str(&line)?;
+        validate_index_uid(&uid)?;
+
+        let index_dir = src.join("indexes").join(format!("index-{}", uuid));
+        let meta: IndexMetaV2 = read_json(&index_dir.join("meta.json"))?;
+        write_index(
+            dest,
+            &uid,
+            meta.settings,
+            &index_dir.join("documents.jsonl"),
+        )?;
+        indexes.push(DumpIndex {
+            uid,
+            primary_key: meta.primary_key,
+        });
+    }
+
+    let metadata = Metadata {
+        dump_version: DumpVersion::V3,
+        db_version: metadata.db_version,
+        dump_date: Some(metadata.dump_date),
+        indexes,
+    };
+    write_metadata(dest, &metadata)?;
+    Ok(DumpVersion::V3)
+}
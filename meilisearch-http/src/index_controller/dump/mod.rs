@@ -0,0 +1,572 @@
+mod legacy;
+
+use std::fs::{create_dir_all, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use actix_web::web::Bytes;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use meilisearch_error::{Code, ErrorCode};
+use milli::update::{IndexDocumentsMethod, UpdateFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use super::uuid_resolver::validate_index_uid;
+use super::{
+    IndexController, IndexMetadata, Priority, TaskType, UpdateMeta, UpdateStatus, UuidError,
+    WaitFor,
+};
+use crate::helpers::compression;
+use crate::index::{Document, Settings, VECTORS_FIELD};
+
+/// The name of the file describing the content of a dump, at the root of the archive.
+const METADATA_FILE: &str = "metadata.json";
+
+/// The version of the layout of the dump archives.
+///
+/// `V1` and `V2` are the formats of the dumps of the legacy MeiliSearch engine, up to v0.21. `V3`
+/// is the first format of this crate: a `metadata.json` file, and for each index an
+/// `indexes/{uid}` directory with a `settings.json` file and a `documents.jsonl` file, holding
+/// one document per line.
+///
+/// To change the format, add a version, make it `CURRENT`, and add the step migrating an
+/// extracted dump of the previous version to it in `migrate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DumpVersion {
+    // The first legacy dumps were versioned with a bare number.
+    #[serde(alias = "1")]
+    V1,
+    #[serde(alias = "2")]
+    V2,
+    V3,
+}
+
+impl DumpVersion {
+    pub const CURRENT: Self = Self::V3;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Metadata {
+    dump_version: DumpVersion,
+    /// The version of the crate that created the dump.
+    db_version: String,
+    /// Unknown for the dumps migrated from the `V1` format.
+    dump_date: Option<DateTime<Utc>>,
+    indexes: Vec<DumpIndex>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DumpIndex {
+    uid: String,
+    primary_key: Option<String>,
+}
+
+/// Only the version of the dump is read before the migration, as the rest of the metadata may
+/// not follow the current format.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionOnly {
+    dump_version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DumpStatus {
+    Done,
+    InProgress,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpInfo {
+    pub uid: String,
+    pub status: DumpStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DumpInfo {
+    fn new(uid: String, status: DumpStatus) -> Self {
+        Self {
+            uid,
+            status,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DumpError {
+    #[error("Another dump is already in progress")]
+    DumpAlreadyInProgress,
+    #[error("Dump `{0}` not found")]
+    DumpNotFound(String),
+    #[error("Dump version `{0}` is not supported, the dump may come from a newer version")]
+    UnsupportedVersion(String),
+    #[error("Index `{0}` is not part of the dump")]
+    IndexNotInDump(String),
+    #[error("Index `{0}` couldn't be imported: {1}")]
+    ImportFailed(String, String),
+    #[error("The indexes of a replication primary can't be imported from a dump, as the imports are not replicated.")]
+    ImportOnPrimary,
+}
+
+impl ErrorCode for DumpError {
+    fn error_code(&self) -> Code {
+        match self {
+            DumpError::DumpAlreadyInProgress => Code::DumpAlreadyInProgress,
+            DumpError::DumpNotFound(_) => Code::NotFound,
+            DumpError::UnsupportedVersion(_) => Code::DumpProcessFailed,
+            DumpError::IndexNotInDump(_) => Code::IndexNotFound,
+            DumpError::ImportFailed(_, _) => Code::DumpProcessFailed,
+            DumpError::ImportOnPrimary => Code::BadRequest,
+        }
+    }
+}
+
+/// Returns the path of the archive of the dump `uid`.
+pub fn dump_path(dumps_dir: &Path, uid: &str) -> PathBuf {
+    dumps_dir.join(format!("{}.dump", uid))
+}
+
+fn generate_uid() -> String {
+    Utc::now().format("%Y%m%d-%H%M%S%3f").to_string()
+}
+
+/// Reads the version of an extracted dump, refusing the versions this crate doesn't know of.
+fn read_version(dir: &Path) -> anyhow::Result<DumpVersion> {
+    let file = File::open(dir.join(METADATA_FILE))?;
+    let VersionOnly { dump_version } = serde_json::from_reader(BufReader::new(file))?;
+    serde_json::from_value(Value::String(dump_version.clone()))
+        .map_err(|_| DumpError::UnsupportedVersion(dump_version).into())
+}
+
+/// Converts an extracted dump of the `version` format into the current format. Each step writes
+/// the dump it reads from `dir` into a new directory, in a newer format, until the current one is
+/// reached.
+fn migrate(mut dir: TempDir, mut version: DumpVersion) -> anyhow::Result<TempDir> {
+    while version < DumpVersion::CURRENT {
+        let dest = TempDir::new()?;
+        version = match version {
+            DumpVersion::V1 => legacy::v1_to_v3(dir.path(), dest.path())?,
+            DumpVersion::V2 => legacy::v2_to_v3(dir.path(), dest.path())?,
+            DumpVersion::V3 => unreachable!("V3 is the current version"),
+        };
+        dir = dest;
+    }
+    Ok(dir)
+}
+
+fn write_metadata(dir: &Path, metadata: &Metadata) -> anyhow::Result<()> {
+    let file = File::create(dir.join(METADATA_FILE))?;
+    serde_json::to_writer(BufWriter::new(file), metadata)?;
+    Ok(())
+}
+
+/// The settings of an index in a form that can be sent back as a settings update: the
+/// attributes set to `*` are reset rather than set to a field literally named `*`.
+pub(super) fn importable_settings(mut settings: Settings) -> Settings {
+    fn is_wildcard(attributes: &Option<Option<Vec<String>>>) -> bool {
+        match attributes {
+            Some(Some(attributes)) => attributes.iter().any(|a| a == "*"),
+            _ => false,
+        }
+    }
+
+    if is_wildcard(&settings.displayed_attributes) {
+        settings.displayed_attributes = Some(None);
+    }
+    if is_wildcard(&settings.searchable_attributes) {
+        settings.searchable_attributes = Some(None);
+    }
+    settings
+}
+
+/// Extracts the dump at `path` in a temporary directory, and migrates it to the current format.
+async fn extract_dump(path: &Path) -> anyhow::Result<(TempDir, Metadata)> {
+    let tmp_dir = TempDir::new()?;
+    let src = path.to_owned();
+    let dest = tmp_dir.path().to_owned();
+    tokio::task::spawn_blocking(move || compression::from_tar(&src, &dest)).await??;
+
+    let version = read_version(tmp_dir.path())?;
+    if version < DumpVersion::CURRENT {
+        info!("Migrating the dump from the {:?} format.", version);
+    }
+    let tmp_dir = migrate(tmp_dir, version)?;
+
+    let file = File::open(tmp_dir.path().join(METADATA_FILE))?;
+    let metadata: Metadata = serde_json::from_reader(BufReader::new(file))?;
+    match metadata.dump_date {
+        Some(date) => info!(
+            "Reading a dump created on {} by version {}.",
+            date, metadata.db_version
+        ),
+        None => info!("Reading a dump created by version {}.", metadata.db_version),
+    }
+
+    Ok((tmp_dir, metadata))
+}
+
+impl IndexController {
+    /// Starts dumping all the indexes in the background, and returns the info of the dump, which
+    /// is then available with `dump_info`. Only one dump can be in progress at a time.
+    ///
+    /// The updates processed while the dump is being created may or may not be part of it.
+    pub fn create_dump(&self, dumps_dir: PathBuf, batch_size: usize) -> anyhow::Result<DumpInfo> {
+        let info = {
+            let mut dumps = self.dumps.lock();
+            if dumps
+                .values()
+                .any(|info| info.status == DumpStatus::InProgress)
+            {
+                return Err(DumpError::DumpAlreadyInProgress.into());
+            }
+            let info = DumpInfo::new(generate_uid(), DumpStatus::InProgress);
+            dumps.insert(info.uid.clone(), info.clone());
+            info
+        };
+
+        let details = json!({ "dumpUid": info.uid });
+        let task = match self
+            .task_store
+            .start(None, TaskType::DumpCreation, details.clone())
+        {
+            Ok(task) => task,
+            Err(e) => {
+                self.dumps.lock().remove(&info.uid);
+                return Err(e.into());
+            }
+        };
+
+        let controller = self.clone();
+        let uid = info.uid.clone();
+        tokio::task::spawn_local(async move {
+            let result = controller.perform_dump(&dumps_dir, &uid, batch_size).await;
+            let mut info = DumpInfo::new(uid, DumpStatus::Done);
+            let task_result = match result {
+                Ok(()) => {
+                    info!("Dump {} created.", info.uid);
+                    Ok(details)
+                }
+                Err(e) => {
+                    error!("Dump {} failed: {}", info.uid, e);
+                    info.status = DumpStatus::Failed;
+                    info.error = Some(e.to_string());
+                    Err(e.to_string())
+                }
+            };
+            if let Err(e) = controller.finish_task(task, task_result).await {
+                error!("Can't record the end of the dump task: {}", e);
+            }
+            controller.dumps.lock().insert(info.uid.clone(), info);
+        });
+
+        Ok(info)
+    }
+
+    /// Returns the info of the dump `uid`: a dump created by this instance since its start, with
+    /// its error if it failed, or any dump found in `dumps_dir`.
+    pub fn dump_info(&self, dumps_dir: &Path, uid: String) -> anyhow::Result<DumpInfo> {
+        if let Some(info) = self.dumps.lock().get(&uid) {
+            return Ok(info.clone());
+        }
+
+        if dump_path(dumps_dir, &uid).exists() {
+            Ok(DumpInfo::new(uid, DumpStatus::Done))
+        } else {
+            Err(DumpError::DumpNotFound(uid).into())
+        }
+    }
+
+    async fn perform_dump(
+        &self,
+        dumps_dir: &Path,
+        uid: &str,
+        batch_size: usize,
+    ) -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut indexes = Vec::new();
+
+        for index in self.list_indexes(None, 0, None).await? {
+            let index_dir = tmp_dir.path().join("indexes").join(&index.uid);
+            create_dir_all(&index_dir)?;
+
+            let settings = importable_settings(self.settings(index.uid.clone()).await?);
+            let file = File::create(index_dir.join("settings.json"))?;
+            serde_json::to_writer(BufWriter::new(file), &settings)?;
+
+            let file = File::create(index_dir.join("documents.jsonl"))?;
+            let mut documents = BufWriter::new(file);
+            // The embeddings are only retrieved when they are explicitly asked for.
+            let attributes = vec!["*".to_string(), VECTORS_FIELD.to_string()];
+            let mut offset = 0;
+            loop {
+                let batch = self
+                    .documents(
+                        index.uid.clone(),
+                        offset,
+                        batch_size,
+                        Some(attributes.clone()),
+                    )
+                    .await?;
+                if batch.is_empty() {
+                    break;
+                }
+                offset += batch.len();
+                for document in batch {
+                    serde_json::to_writer(&mut documents, &document)?;
+                    documents.write_all(b"\n")?;
+                }
+            }
+            documents.flush()?;
+
+            indexes.push(DumpIndex {
+                uid: index.uid,
+                primary_key: index.meta.primary_key,
+            });
+        }
+
+        let metadata = Metadata {
+            dump_version: DumpVersion::CURRENT,
+            db_version: env!("CARGO_PKG_VERSION").to_string(),
+            dump_date: Some(Utc::now()),
+            indexes,
+        };
+        write_metadata(tmp_dir.path(), &metadata)?;
+
+        // The archive is written under a temporary name, so that a dump is never seen as done
+        // before it is complete.
+        create_dir_all(dumps_dir)?;
+        let dump_path = dump_path(dumps_dir, uid);
+        let tmp_dump_path = dump_path.with_extension("dump.tmp");
+        let src = tmp_dir.path().to_owned();
+        let dest = tmp_dump_path.clone();
+        let archive_compression = self.archive_compression;
+        tokio::task::spawn_blocking(move || compression::to_tar(&src, &dest, archive_compression))
+            .await??;
+        std::fs::rename(tmp_dump_path, dump_path)?;
+
+        Ok(())
+    }
+
+    /// Imports the indexes of the dump at `path`, migrating it first if it comes from an older
+    /// version. The existing indexes with the same uid as an index of the dump are replaced. The
+    /// documents are registered as updates of `batch_size` documents, and each index is only
+    /// replaced once all its updates have been processed.
+    pub async fn import_dump(&self, path: &Path, batch_size: usize) -> anyhow::Result<()> {
+        let (tmp_dir, metadata) = extract_dump(path).await?;
+
+        for index in metadata.indexes {
+            let index_dir = tmp_dir.path().join("indexes").join(&index.uid);
+            let uid = index.uid.clone();
+            self.import_index(&index_dir, index, uid, batch_size)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Imports only the index `uid` of the dump at `path`, under the uid `target_uid`. An existing
+    /// index named `target_uid` is replaced.
+    pub async fn restore_index(
+        &self,
+        path: &Path,
+        uid: String,
+        target_uid: String,
+        batch_size: usize,
+    ) -> anyhow::Result<IndexMetadata> {
+        let (tmp_dir, metadata) = extract_dump(path).await?;
+
+        let index = metadata
+            .indexes
+            .into_iter()
+            .find(|index| index.uid == uid)
+            .ok_or(DumpError::IndexNotInDump(uid))?;
+        let index_dir = tmp_dir.path().join("indexes").join(&index.uid);
+        self.import_index(&index_dir, index, target_uid.clone(), batch_size)
+            .await?;
+
+        self.get_index(target_uid).await
+    }
+
+    /// Creates a new index under `uid` from the settings and documents of `index_dir`. The index
+    /// is built under a new uuid, and only registered under `uid` once all its updates have been
+    /// processed, replacing the index previously registered under `uid`, if any. The new index is
+    /// removed if any of its updates fails, and the previous index is kept. The imports are
+    /// refused on a replication primary, as its replicas couldn't apply them.
+    async fn import_index(
+        &self,
+        index_dir: &Path,
+        index: DumpIndex,
+        uid: String,
+        batch_size: usize,
+    ) -> anyhow::Result<()> {
+        if self.replication_log.is_some() {
+            return Err(DumpError::ImportOnPrimary.into());
+        }
+
+        info!("Importing index {} as {}.", index.uid, uid);
+
+        // The uid is checked before building the index, which would be left unused otherwise.
+        validate_index_uid(&uid)?;
+
+        let uuid = Uuid::new_v4();
+        self.index_handle
+            .create_index(uuid, index.primary_key.clone())
+            .await?;
+        if let Err(e) = self.update_handle.create(uuid).await {
+            self.remove_imported_index(uuid).await;
+            return Err(e.into());
+        }
+        if let Err(e) = self
+            .build_index(index_dir, &uid, uuid, index, batch_size)
+            .await
+        {
+            self.remove_imported_index(uuid).await;
+            return Err(e);
+        }
+
+        let _snapshot_guard = self.snapshot_lock.read().await;
+        let previous = match self.uuid_resolver.get(uid.clone()).await {
+            Ok(uuid) => Some(uuid),
+            Err(UuidError::UnexistingIndex(_)) => None,
+            Err(e) => {
+                self.remove_imported_index(uuid).await;
+                return Err(e.into());
+            }
+        };
+
+        // The previous mapping is removed first, as the uuid resolver refuses to map an uid to
+        // a new uuid when the uids are case-insensitive.
+        if previous.is_some() {
+            if let Err(e) = self.uuid_resolver.delete(uid.clone()).await {
+                self.remove_imported_index(uuid).await;
+                return Err(e);
+            }
+        }
+        if let Err(e) = self.uuid_resolver.insert(uid.clone(), uuid).await {
+            if let Some(previous) = previous {
+                self.uuid_resolver.insert(uid, previous).await?;
+            }
+            self.remove_imported_index(uuid).await;
+            return Err(e);
+        }
+        if let Some(previous) = previous {
+            self.delete_index_data(previous).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Registers the updates importing the settings and documents of `index_dir` in the index
+    /// `uuid`, and waits for them to be processed. Fails if any of them failed.
+    async fn build_index(
+        &self,
+        index_dir: &Path,
+        uid: &str,
+        uuid: Uuid,
+        index: DumpIndex,
+        batch_size: usize,
+    ) -> anyhow::Result<()> {
+        let DumpIndex { primary_key, .. } = index;
+
+        let file = File::open(index_dir.join("settings.json"))?;
+        let settings: Settings = serde_json::from_reader(BufReader::new(file))?;
+        // Nothing to send, drop the sender right away, as not to block the update actor.
+        let (_, receiver) = mpsc::channel(1);
+        let mut last_update = self
+            .update_handle
+            .update(
+                UpdateMeta::Settings(settings),
+                Priority::Normal,
+                receiver,
+                uuid,
+            )
+            .await?
+            .id();
+
+        let file = File::open(index_dir.join("documents.jsonl"))?;
+        let mut batch = Vec::with_capacity(batch_size);
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            batch.push(serde_json::from_str::<Document>(&line)?);
+            if batch.len() >= batch_size {
+                let documents = std::mem::take(&mut batch);
+                last_update = self
+                    .import_documents(uuid, documents, primary_key.clone())
+                    .await?;
+            }
+        }
+        if !batch.is_empty() {
+            last_update = self.import_documents(uuid, batch, primary_key).await?;
+        }
+
+        // The updates of an index are processed in order, they are all finished once the last
+        // one is.
+        let events = self.update_handle.subscribe();
+        self.wait_for_status(uuid, last_update, WaitFor::Processed, events)
+            .await?;
+        for update in self.update_handle.get_all_updates_status(uuid).await? {
+            match update {
+                UpdateStatus::Failed(failed) => {
+                    let error = DumpError::ImportFailed(uid.to_string(), failed.error().clone());
+                    return Err(error.into());
+                }
+                UpdateStatus::Aborted(_) => {
+                    let error = DumpError::ImportFailed(uid.to_string(), "aborted".to_string());
+                    return Err(error.into());
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the index `uuid` built by a failed import, which is not referenced by the uuid
+    /// resolver.
+    async fn remove_imported_index(&self, uuid: Uuid) {
+        if let Err(e) = self.delete_index_data(uuid).await {
+            error!("Error while removing the index of a failed import: {}", e);
+        }
+    }
+
+    async fn import_documents(
+        &self,
+        uuid: Uuid,
+        documents: Vec<Document>,
+        primary_key: Option<String>,
+    ) -> anyhow::Result<u64> {
+        let meta = UpdateMeta::DocumentsAddition {
+            method: IndexDocumentsMethod::ReplaceDocuments,
+            format: UpdateFormat::Json,
+            primary_key,
+            skip_invalid_documents: false,
+        };
+
+        // The whole payload fits in the channel, so it is sent before registering the update,
+        // and the sender is dropped to signal its end.
+        let (sender, receiver) = mpsc::channel(1);
+        let bytes = Bytes::from(serde_json::to_vec(&documents)?);
+        let _ = sender.send(Ok(bytes)).await;
+        drop(sender);
+
+        let status = self
+            .update_handle
+            .update(meta, Priority::Normal, receiver, uuid)
+            .await?;
+        Ok(status.id())
+    }
+}
@@ -0,0 +1,150 @@
+//! Removes the data of the deleted indexes from the disk, once they are no longer referenced.
+
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use parking_lot::Mutex;
+use tokio::fs::{remove_dir_all, remove_file};
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use uuid::Uuid;
+
+/// Delay before retrying to remove the data that couldn't be removed.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The data of a deleted index: its database, its update store and the content files of its
+/// pending updates.
+#[derive(Debug)]
+pub struct Garbage {
+    pub uuid: Uuid,
+    pub update_files: Vec<PathBuf>,
+}
+
+impl Garbage {
+    fn paths(&self, db_path: &Path) -> Vec<PathBuf> {
+        let mut paths = vec![
+            db_path.join("indexes").join(format!("index-{}", self.uuid)),
+            db_path
+                .join("updates")
+                .join(format!("updates-{}", self.uuid)),
+        ];
+        paths.extend(self.update_files.iter().cloned());
+        paths
+    }
+}
+
+/// Sends the data of the deleted indexes to the task removing it from the disk.
+#[derive(Clone)]
+pub struct GarbageCollector {
+    sender: mpsc::UnboundedSender<Garbage>,
+    /// The uuids of the indexes whose data is not entirely removed yet.
+    collecting: Arc<Mutex<HashSet<Uuid>>>,
+}
+
+impl GarbageCollector {
+    /// Spawns the task removing the data of the deleted indexes of the database at `db_path`.
+    pub fn spawn(db_path: impl AsRef<Path>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let collecting = Arc::new(Mutex::new(HashSet::new()));
+        tokio::task::spawn(run(
+            db_path.as_ref().to_owned(),
+            receiver,
+            collecting.clone(),
+        ));
+        Self { sender, collecting }
+    }
+
+    /// Schedules the removal of the data of a deleted index. It must not be referenced by the
+    /// uuid resolver anymore.
+    pub fn collect(&self, garbage: Garbage) {
+        self.collecting.lock().insert(garbage.uuid);
+        if let Err(mpsc::error::SendError(garbage)) = self.sender.send(garbage) {
+            error!(
+                "The data of the deleted index {} can't be removed.",
+                garbage.uuid
+            );
+        }
+    }
+
+    /// Whether the data of the index `uuid` is being removed.
+    pub fn is_collecting(&self, uuid: &Uuid) -> bool {
+        self.collecting.lock().contains(uuid)
+    }
+}
+
+async fn run(
+    db_path: PathBuf,
+    mut receiver: mpsc::UnboundedReceiver<Garbage>,
+    collecting: Arc<Mutex<HashSet<Uuid>>>,
+) {
+    // The paths that couldn't be removed are retried until they are, the index they belong to
+    // can't be reopened anyway.
+    let mut remaining: Vec<(Uuid, PathBuf)> = Vec::new();
+    let mut closed = false;
+    loop {
+        let garbage = match (closed, remaining.is_empty()) {
+            (true, true) => break,
+            (true, false) => {
+                sleep(RETRY_DELAY).await;
+                None
+            }
+            (false, true) => {
+                let garbage = receiver.recv().await;
+                closed = garbage.is_none();
+                garbage
+            }
+            (false, false) => tokio::select! {
+                garbage = receiver.recv() => {
+                    closed = garbage.is_none();
+                    garbage
+                }
+                _ = sleep(RETRY_DELAY) => None,
+            },
+        };
+
+        if let Some(garbage) = garbage {
+            info!("Removing the data of the deleted index {}.", garbage.uuid);
+            let uuid = garbage.uuid;
+            remaining.extend(garbage.paths(&db_path).into_iter().map(|p| (uuid, p)));
+        }
+
+        let mut removed = HashSet::new();
+        let mut failed = Vec::new();
+        for (uuid, path) in remaining.drain(..) {
+            match remove(&path).await {
+                Ok(()) => {
+                    removed.insert(uuid);
+                }
+                Err(e) => {
+                    error!("Could not remove {}, retrying later: {}", path.display(), e);
+                    failed.push((uuid, path));
+                }
+            }
+        }
+        let mut collecting = collecting.lock();
+        for uuid in removed {
+            if !failed.iter().any(|(u, _)| *u == uuid) {
+                collecting.remove(&uuid);
+            }
+        }
+        drop(collecting);
+        remaining = failed;
+    }
+}
+
+/// Removes the file or the directory at `path`, succeeds if it doesn't exist.
+async fn remove(path: &Path) -> std::io::Result<()> {
+    let result = if path.is_dir() {
+        remove_dir_all(path).await
+    } else {
+        remove_file(path).await
+    };
+    match result {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
@@ -1,28 +1,38 @@
-use std::collections::HashMap;
-use std::fs::{create_dir_all, File};
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, remove_dir_all, File};
 use std::future::Future;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 
 use async_stream::stream;
 use chrono::{DateTime, Utc};
 use futures::pin_mut;
 use futures::stream::StreamExt;
-use heed::EnvOpenOptions;
+use heed::{CompactionOption, EnvOpenOptions};
 use log::debug;
+use meilisearch_error::{Code, ErrorCode};
+use milli::update::{IndexDocumentsMethod, UpdateFormat};
+use milli::FieldsDistribution;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tokio::fs::remove_dir_all;
 use tokio::sync::{mpsc, oneshot, RwLock};
 use tokio::task::spawn_blocking;
 use uuid::Uuid;
 
-use super::update_handler::UpdateHandler;
+use super::dump::importable_settings;
+use super::map_size::{grown_map_size, is_map_full_anyhow};
+use super::search_cache::SearchCache;
+use super::supervisor::{inbox, recv, supervise, Inbox};
+use super::update_handler::{merge_document_additions, UpdateHandler};
 use super::{get_arc_ownership_blocking, IndexSettings};
-use crate::index::UpdateResult as UResult;
-use crate::index::{Document, Index, SearchQuery, SearchResult, Settings};
+use crate::index::{
+    Document, FacetSearchQuery, FacetSearchResult, Index, SearchQuery, SearchResult, Settings,
+};
+use crate::index::{DocumentsAdditionResult, UpdateResult as UResult};
 use crate::index_controller::{
-    updates::{Failed, Processed, Processing},
+    updates::{Failed, Processed, Processing, UpdateProgress},
     UpdateMeta,
 };
 use crate::option::IndexerOpts;
@@ -34,9 +44,9 @@ type UpdateResult = std::result::Result<Processed<UpdateMeta, UResult>, Failed<U
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexMeta {
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
-    primary_key: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub primary_key: Option<String>,
 }
 
 impl IndexMeta {
@@ -57,6 +67,22 @@ impl IndexMeta {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStats {
+    /// The size, in bytes, of the database of the index.
+    pub size: u64,
+    pub number_of_documents: u64,
+    pub is_indexing: bool,
+    pub fields_distribution: FieldsDistribution,
+}
+
+/// A chunk of the documents of an index, and the cursor to retrieve the next chunk, if any.
+pub struct DocumentsChunk {
+    pub documents: Vec<Document>,
+    pub next: Option<u32>,
+}
+
 enum IndexMsg {
     CreateIndex {
         uuid: Uuid,
@@ -68,11 +94,20 @@ enum IndexMsg {
         data: std::fs::File,
         ret: oneshot::Sender<Result<UpdateResult>>,
     },
+    UpdateBatch {
+        batch: Vec<(Processing<UpdateMeta>, std::fs::File)>,
+        ret: oneshot::Sender<Result<Vec<UpdateResult>>>,
+    },
     Search {
         uuid: Uuid,
         query: SearchQuery,
         ret: oneshot::Sender<anyhow::Result<SearchResult>>,
     },
+    FacetSearch {
+        uuid: Uuid,
+        query: FacetSearchQuery,
+        ret: oneshot::Sender<anyhow::Result<FacetSearchResult>>,
+    },
     Settings {
         uuid: Uuid,
         ret: oneshot::Sender<Result<Settings>>,
@@ -84,12 +119,25 @@ enum IndexMsg {
         limit: usize,
         ret: oneshot::Sender<Result<Vec<Document>>>,
     },
+    DocumentsChunk {
+        uuid: Uuid,
+        attributes_to_retrieve: Option<Vec<String>>,
+        after: Option<u32>,
+        limit: usize,
+        ret: oneshot::Sender<Result<DocumentsChunk>>,
+    },
     Document {
         uuid: Uuid,
         attributes_to_retrieve: Option<Vec<String>>,
         doc_id: String,
         ret: oneshot::Sender<Result<Document>>,
     },
+    DocumentsByIds {
+        uuid: Uuid,
+        attributes_to_retrieve: Option<Vec<String>>,
+        doc_ids: Vec<String>,
+        ret: oneshot::Sender<Result<Vec<Document>>>,
+    },
     Delete {
         uuid: Uuid,
         ret: oneshot::Sender<Result<()>>,
@@ -103,12 +151,45 @@ enum IndexMsg {
         index_settings: IndexSettings,
         ret: oneshot::Sender<Result<IndexMeta>>,
     },
+    GetStats {
+        uuid: Uuid,
+        ret: oneshot::Sender<Result<IndexStats>>,
+    },
+    Progress {
+        uuid: Uuid,
+        ret: oneshot::Sender<Option<UpdateProgress>>,
+    },
+    NumberOfDocuments {
+        uuid: Uuid,
+        ret: oneshot::Sender<Result<u64>>,
+    },
+    Snapshot {
+        uuids: HashSet<Uuid>,
+        path: PathBuf,
+        compact: bool,
+        ret: oneshot::Sender<Result<()>>,
+    },
+    Close {
+        ret: oneshot::Sender<Result<()>>,
+    },
+    Health {
+        ret: oneshot::Sender<()>,
+    },
 }
 
 struct IndexActor<S> {
-    read_receiver: Option<mpsc::Receiver<IndexMsg>>,
-    write_receiver: Option<mpsc::Receiver<IndexMsg>>,
+    read_receiver: Inbox<IndexMsg>,
+    write_receiver: Inbox<IndexMsg>,
+    update_receiver: Inbox<IndexMsg>,
     update_handler: Arc<UpdateHandler>,
+    /// The uuids of the indexes currently being updated.
+    processing: RwLock<HashSet<Uuid>>,
+    /// The progress of the indexing of the updates being processed, by index uuid.
+    progress: Arc<parking_lot::RwLock<HashMap<Uuid, UpdateProgress>>>,
+    /// The maximum number of updates processed at the same time, on different indexes.
+    max_concurrent_updates: usize,
+    /// The cache of the search results, when enabled.
+    search_cache: Option<SearchCache>,
     store: S,
 }
 
@@ -122,62 +203,109 @@ pub enum IndexError {
     UnexistingIndex,
     #[error("Heed error: {0}")]
     HeedError(#[from] heed::Error),
-    #[error("Existing primary key")]
+    #[error("Can't change the primary key of an index that contains documents")]
     ExistingPrimaryKey,
+    #[error("The index actor is unavailable, please retry.")]
+    Unavailable,
+}
+
+impl ErrorCode for IndexError {
+    fn error_code(&self) -> Code {
+        match self {
+            IndexError::Error(_) => Code::BadRequest,
+            IndexError::IndexAlreadyExists => Code::IndexAlreadyExists,
+            IndexError::UnexistingIndex => Code::IndexNotFound,
+            IndexError::HeedError(_) => Code::Internal,
+            IndexError::ExistingPrimaryKey => Code::PrimaryKeyAlreadyPresent,
+            IndexError::Unavailable => Code::Unavailable,
+        }
+    }
 }
 
 #[async_trait::async_trait]
 trait IndexStore {
     async fn create(&self, uuid: Uuid, primary_key: Option<String>) -> Result<Index>;
     async fn get(&self, uuid: Uuid) -> Result<Option<Index>>;
+    /// Removes the index, and returns it if it was opened. Its data is left on the disk.
     async fn delete(&self, uuid: Uuid) -> Result<Option<Index>>;
+    async fn clone_index(&self, source: Uuid, dest: Uuid) -> Result<Index>;
+    /// Reopens the index with a bigger map size. Returns `false` if the index can't grow anymore.
+    async fn grow(&self, uuid: Uuid) -> Result<bool>;
+    /// Removes all the opened indexes.
+    async fn drain(&self) -> Vec<(Uuid, Index)>;
+    /// Returns the size, in bytes, of the database of an index.
+    async fn size(&self, uuid: Uuid) -> Result<u64>;
+    /// Replaces the database of the index `uuid` by the one of the index `shadow`, which is
+    /// removed. The database is renamed over the previous one, so that the index is either
+    /// entirely replaced or left untouched.
+    async fn swap(&self, uuid: Uuid, shadow: Uuid) -> Result<Index>;
+    /// Closes the index and removes its data from the disk.
+    async fn discard(&self, uuid: Uuid) -> Result<()>;
 }
 
 impl<S: IndexStore + Sync + Send> IndexActor<S> {
     fn new(
-        read_receiver: mpsc::Receiver<IndexMsg>,
-        write_receiver: mpsc::Receiver<IndexMsg>,
+        read_receiver: Inbox<IndexMsg>,
+        write_receiver: Inbox<IndexMsg>,
+        update_receiver: Inbox<IndexMsg>,
+        max_concurrent_updates: usize,
+        indexer_options: &IndexerOpts,
+        search_cache_size: usize,
         store: S,
     ) -> Result<Self> {
-        let options = IndexerOpts::default();
-        let update_handler = UpdateHandler::new(&options).map_err(IndexError::Error)?;
+        let update_handler = UpdateHandler::new(indexer_options).map_err(IndexError::Error)?;
         let update_handler = Arc::new(update_handler);
-        let read_receiver = Some(read_receiver);
-        let write_receiver = Some(write_receiver);
+        let processing = RwLock::new(HashSet::new());
         Ok(Self {
             read_receiver,
             write_receiver,
+            update_receiver,
             store,
             update_handler,
+            processing,
+            progress: Arc::default(),
+            max_concurrent_updates,
+            search_cache: match search_cache_size {
+                0 => None,
+                size => Some(SearchCache::new(size)),
+            },
         })
     }
 
-    /// `run` poll the write_receiver and read_receiver concurrently, but while messages send
-    /// through the read channel are processed concurrently, the messages sent through the write
-    /// channel are processed one at a time.
-    async fn run(mut self) {
-        let mut read_receiver = self
-            .read_receiver
-            .take()
-            .expect("Index Actor must have a inbox at this point.");
+    /// `run` poll the write_receiver, update_receiver and read_receiver concurrently, but while
+    /// messages send through the read channel are processed concurrently, the messages sent
+    /// through the write channel are processed one at a time. The updates are processed
+    /// concurrently, up to `max_concurrent_updates` at a time: the update stores only send one
+    /// update at a time for each index, so the updates of a given index are never processed
+    /// concurrently.
+    async fn run(self) {
+        let read_receiver = self.read_receiver.clone();
 
         let read_stream = stream! {
             loop {
-                match read_receiver.recv().await {
+                match recv(&read_receiver).await {
                     Some(msg) => yield msg,
                     None => break,
                 }
             }
         };
 
-        let mut write_receiver = self
-            .write_receiver
-            .take()
-            .expect("Index Actor must have a inbox at this point.");
+        let write_receiver = self.write_receiver.clone();
 
         let write_stream = stream! {
             loop {
-                match write_receiver.recv().await {
+                match recv(&write_receiver).await {
+                    Some(msg) => yield msg,
+                    None => break,
+                }
+            }
+        };
+
+        let update_receiver = self.update_receiver.clone();
+
+        let update_stream = stream! {
+            loop {
+                match recv(&update_receiver).await {
                     Some(msg) => yield msg,
                     None => break,
                 }
@@ -186,14 +314,19 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
 
         pin_mut!(write_stream);
         pin_mut!(read_stream);
+        pin_mut!(update_stream);
 
         let fut1 = read_stream.for_each_concurrent(Some(10), |msg| self.handle_message(msg));
         let fut2 = write_stream.for_each_concurrent(Some(1), |msg| self.handle_message(msg));
+        let fut3 = update_stream.for_each_concurrent(Some(self.max_concurrent_updates), |msg| {
+            self.handle_message(msg)
+        });
 
         let fut1: Box<dyn Future<Output = ()> + Unpin + Send> = Box::new(fut1);
         let fut2: Box<dyn Future<Output = ()> + Unpin + Send> = Box::new(fut2);
+        let fut3: Box<dyn Future<Output = ()> + Unpin + Send> = Box::new(fut3);
 
-        tokio::join!(fut1, fut2);
+        tokio::join!(fut1, fut2, fut3);
     }
 
     async fn handle_message(&self, msg: IndexMsg) {
@@ -209,9 +342,15 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
             Update { ret, meta, data } => {
                 let _ = ret.send(self.handle_update(meta, data).await);
             }
+            UpdateBatch { ret, batch } => {
+                let _ = ret.send(self.handle_update_batch(batch).await);
+            }
             Search { ret, query, uuid } => {
                 let _ = ret.send(self.handle_search(uuid, query).await);
             }
+            FacetSearch { ret, query, uuid } => {
+                let _ = ret.send(self.handle_facet_search(uuid, query).await);
+            }
             Settings { ret, uuid } => {
                 let _ = ret.send(self.handle_settings(uuid).await);
             }
@@ -227,6 +366,18 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
                         .await,
                 );
             }
+            DocumentsChunk {
+                ret,
+                uuid,
+                attributes_to_retrieve,
+                after,
+                limit,
+            } => {
+                let _ = ret.send(
+                    self.handle_fetch_documents_chunk(uuid, after, limit, attributes_to_retrieve)
+                        .await,
+                );
+            }
             Document {
                 uuid,
                 attributes_to_retrieve,
@@ -238,6 +389,17 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
                         .await,
                 );
             }
+            DocumentsByIds {
+                uuid,
+                attributes_to_retrieve,
+                doc_ids,
+                ret,
+            } => {
+                let _ = ret.send(
+                    self.handle_fetch_documents_by_ids(uuid, doc_ids, attributes_to_retrieve)
+                        .await,
+                );
+            }
             Delete { uuid, ret } => {
                 let _ = ret.send(self.handle_delete(uuid).await);
             }
@@ -251,6 +413,29 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
             } => {
                 let _ = ret.send(self.handle_update_index(uuid, index_settings).await);
             }
+            GetStats { uuid, ret } => {
+                let _ = ret.send(self.handle_get_stats(uuid).await);
+            }
+            Progress { uuid, ret } => {
+                let _ = ret.send(self.progress.read().get(&uuid).cloned());
+            }
+            NumberOfDocuments { uuid, ret } => {
+                let _ = ret.send(self.handle_number_of_documents(uuid).await);
+            }
+            Snapshot {
+                uuids,
+                path,
+                compact,
+                ret,
+            } => {
+                let _ = ret.send(self.handle_snapshot(uuids, path, compact).await);
+            }
+            Close { ret } => {
+                let _ = ret.send(self.handle_close().await);
+            }
+            Health { ret } => {
+                let _ = ret.send(());
+            }
         }
     }
 
@@ -260,7 +445,39 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
             .get(uuid)
             .await?
             .ok_or(IndexError::UnexistingIndex)?;
-        spawn_blocking(move || index.perform_search(query)).await?
+
+        let cache = match self.search_cache {
+            Some(ref cache) => cache,
+            None => return spawn_blocking(move || index.perform_search(query)).await?,
+        };
+
+        let before_search = Instant::now();
+        let key = SearchCache::key(&query)?;
+        if let Some(mut result) = cache.get(uuid, key.clone()) {
+            result.processing_time_ms = before_search.elapsed().as_millis();
+            return Ok(result);
+        }
+
+        let generation = cache.generation(uuid);
+        let result = spawn_blocking(move || index.perform_search(query)).await??;
+        // The results of a degraded search depend on the load of the server, they are not reused.
+        if !result.degraded {
+            cache.insert(uuid, key, generation, result.clone());
+        }
+        Ok(result)
+    }
+
+    async fn handle_facet_search(
+        &self,
+        uuid: Uuid,
+        query: FacetSearchQuery,
+    ) -> anyhow::Result<FacetSearchResult> {
+        let index = self
+            .store
+            .get(uuid)
+            .await?
+            .ok_or(IndexError::UnexistingIndex)?;
+        spawn_blocking(move || index.perform_facet_search(query)).await?
     }
 
     async fn handle_create_index(
@@ -280,16 +497,242 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
         meta: Processing<UpdateMeta>,
         data: File,
     ) -> Result<UpdateResult> {
-        log::info!("Processing update {}", meta.id());
+        match meta.request_id() {
+            Some(request_id) => {
+                log::info!("Processing update {} (request {})", meta.id(), request_id)
+            }
+            None => log::info!("Processing update {}", meta.id()),
+        }
         let uuid = meta.index_uuid();
-        let update_handler = self.update_handler.clone();
-        let index = match self.store.get(*uuid).await? {
+        let index = match meta.meta() {
+            // A clone that can't be made fails the update, so that the cloned index is removed.
+            UpdateMeta::Clone { source } => match self.store.clone_index(*source, *uuid).await {
+                Ok(index) => index,
+                Err(e) => return Ok(Err(meta.fail_with(e.into()))),
+            },
+            _ => match self.store.get(*uuid).await? {
+                Some(index) => index,
+                None => self.store.create(*uuid, None).await?,
+            },
+        };
+        self.processing.write().await.insert(*uuid);
+        let result = match meta.meta() {
+            UpdateMeta::Reindex { settings } => {
+                self.reindex(*uuid, meta.id(), settings.clone(), index)
+                    .await
+            }
+            _ => {
+                self.apply_update(*uuid, meta.id(), meta.meta(), data, index)
+                    .await
+            }
+        };
+        self.processing.write().await.remove(uuid);
+        self.progress.write().remove(uuid);
+        match result {
+            Ok(result) => Ok(Ok(meta.process(result))),
+            Err(e) => Ok(Err(meta.fail_with(e))),
+        }
+    }
+
+    /// Processes a batch of document additions in a single indexing pass. If the batch fails,
+    /// its updates are processed one after the other, so that an invalid update doesn't fail the
+    /// other updates of the batch.
+    async fn handle_update_batch(
+        &self,
+        batch: Vec<(Processing<UpdateMeta>, File)>,
+    ) -> Result<Vec<UpdateResult>> {
+        let (uuid, update_id, meta) = match batch.first() {
+            Some((meta, _)) => (*meta.index_uuid(), meta.id(), meta.meta().clone()),
+            None => return Ok(Vec::new()),
+        };
+        log::info!("Processing a batch of {} updates", batch.len());
+
+        let (method, primary_key) = match meta {
+            UpdateMeta::DocumentsAddition {
+                method,
+                primary_key,
+                ..
+            } => (method, primary_key),
+            _ => return self.handle_updates(batch).await,
+        };
+
+        let index = match self.store.get(uuid).await? {
             Some(index) => index,
-            None => self.store.create(*uuid, None).await?,
+            None => self.store.create(uuid, None).await?,
         };
-        spawn_blocking(move || update_handler.handle_update(meta, data, index))
-            .await
-            .map_err(|e| IndexError::Error(e.into()))
+
+        self.processing.write().await.insert(uuid);
+        let result = async {
+            let contents = batch
+                .iter()
+                .map(|(_, content)| content.try_clone())
+                .collect::<std::io::Result<Vec<_>>>()?;
+            let (merged, counts) =
+                spawn_blocking(move || merge_document_additions(contents)).await??;
+            let meta = UpdateMeta::DocumentsAddition {
+                method,
+                format: UpdateFormat::JsonStream,
+                primary_key,
+                skip_invalid_documents: false,
+            };
+            let total_documents = match self
+                .apply_update(uuid, update_id, &meta, merged, index)
+                .await?
+            {
+                UResult::DocumentsAddition(result) => result.total_documents,
+                _ => None,
+            };
+            Ok::<_, anyhow::Error>((counts, total_documents))
+        }
+        .await;
+        self.processing.write().await.remove(&uuid);
+        self.progress.write().remove(&uuid);
+
+        match result {
+            Ok((counts, total_documents)) => Ok(batch
+                .into_iter()
+                .zip(counts)
+                .map(|((meta, _), nb_documents)| {
+                    let result = DocumentsAdditionResult {
+                        nb_documents,
+                        new_documents: None,
+                        total_documents,
+                        skipped_documents: None,
+                        document_errors: Vec::new(),
+                    };
+                    Ok(meta.process(UResult::DocumentsAddition(result)))
+                })
+                .collect()),
+            Err(e) => {
+                log::warn!("Batch failed, processing its updates one by one: {}", e);
+                self.handle_updates(batch).await
+            }
+        }
+    }
+
+    /// Processes the updates of a batch one after the other, they are not reported as batched.
+    async fn handle_updates(
+        &self,
+        batch: Vec<(Processing<UpdateMeta>, File)>,
+    ) -> Result<Vec<UpdateResult>> {
+        let mut results = Vec::with_capacity(batch.len());
+        for (mut meta, data) in batch {
+            meta.batch = None;
+            results.push(self.handle_update(meta, data).await?);
+        }
+        Ok(results)
+    }
+
+    /// Rebuilds the `index` in a shadow index, from its documents and its settings updated by
+    /// `settings`, and replaces the index by the shadow index once it is rebuilt. The index can
+    /// still be searched meanwhile, and its next updates wait for the rebuild.
+    async fn reindex(
+        &self,
+        uuid: Uuid,
+        update_id: u64,
+        settings: Settings,
+        index: Index,
+    ) -> anyhow::Result<UResult> {
+        // The index is moved in the closure, so that it is dropped once the documents are
+        // exported: it can't be replaced as long as it is referenced here.
+        let (current_settings, primary_key, documents) = spawn_blocking(move || {
+            let settings = importable_settings(index.settings()?);
+            let primary_key = index.primary_key(&index.read_txn()?)?.map(String::from);
+            let documents = tempfile::tempfile()?;
+            let mut writer = BufWriter::new(documents.try_clone()?);
+            index.write_all_documents(&mut writer)?;
+            writer.flush()?;
+            Ok::<_, anyhow::Error>((settings, primary_key, documents))
+        })
+        .await??;
+
+        let shadow = Uuid::new_v4();
+        self.store.create(shadow, None).await?;
+        let result = async {
+            // The settings are applied first, so that the documents are only indexed once.
+            for settings in vec![current_settings, settings] {
+                let index = self
+                    .store
+                    .get(shadow)
+                    .await?
+                    .ok_or(IndexError::UnexistingIndex)?;
+                let meta = UpdateMeta::Settings(settings);
+                self.apply_update(shadow, update_id, &meta, tempfile::tempfile()?, index)
+                    .await?;
+            }
+
+            let index = self
+                .store
+                .get(shadow)
+                .await?
+                .ok_or(IndexError::UnexistingIndex)?;
+            let meta = UpdateMeta::DocumentsAddition {
+                method: IndexDocumentsMethod::ReplaceDocuments,
+                format: UpdateFormat::JsonStream,
+                primary_key,
+                skip_invalid_documents: false,
+            };
+            self.apply_update(shadow, update_id, &meta, documents, index)
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(result) => {
+                self.store.swap(uuid, shadow).await?;
+                self.invalidate_search_cache(uuid);
+                Ok(result)
+            }
+            Err(e) => {
+                self.store.discard(shadow).await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Applies the update to the index, growing the index and retrying the update each time it
+    /// fails because the index is full.
+    async fn apply_update(
+        &self,
+        uuid: Uuid,
+        update_id: u64,
+        meta: &UpdateMeta,
+        data: File,
+        mut index: Index,
+    ) -> anyhow::Result<UResult> {
+        loop {
+            let update_handler = self.update_handler.clone();
+            let update_meta = meta.clone();
+            let mut content = data.try_clone()?;
+            content.seek(SeekFrom::Start(0))?;
+
+            let progress = self.progress.clone();
+            let result = spawn_blocking(move || {
+                update_handler.apply_update(update_id, &update_meta, content, &index, |step| {
+                    progress.write().insert(uuid, step.into());
+                })
+            })
+            .await;
+            // The cached results may be outdated once the update is applied, even if it failed.
+            self.invalidate_search_cache(uuid);
+
+            match result? {
+                Err(e) if is_map_full_anyhow(&e) && self.store.grow(uuid).await? => {
+                    index = self
+                        .store
+                        .get(uuid)
+                        .await?
+                        .ok_or(IndexError::UnexistingIndex)?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn invalidate_search_cache(&self, uuid: Uuid) {
+        if let Some(ref cache) = self.search_cache {
+            cache.invalidate(uuid);
+        }
     }
 
     async fn handle_settings(&self, uuid: Uuid) -> Result<Settings> {
@@ -324,6 +767,30 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
         .map_err(|e| IndexError::Error(e.into()))?
     }
 
+    async fn handle_fetch_documents_chunk(
+        &self,
+        uuid: Uuid,
+        after: Option<u32>,
+        limit: usize,
+        attributes_to_retrieve: Option<Vec<String>>,
+    ) -> Result<DocumentsChunk> {
+        let index = self
+            .store
+            .get(uuid)
+            .await?
+            .ok_or(IndexError::UnexistingIndex)?;
+        spawn_blocking(move || {
+            let (documents, last_id) = index
+                .retrieve_documents_after(after, limit, attributes_to_retrieve)
+                .map_err(IndexError::Error)?;
+            // A chunk that is not full is the last one.
+            let next = last_id.filter(|_| documents.len() == limit);
+            Ok(DocumentsChunk { documents, next })
+        })
+        .await
+        .map_err(|e| IndexError::Error(e.into()))?
+    }
+
     async fn handle_fetch_document(
         &self,
         uuid: Uuid,
@@ -344,8 +811,29 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
         .map_err(|e| IndexError::Error(e.into()))?
     }
 
+    async fn handle_fetch_documents_by_ids(
+        &self,
+        uuid: Uuid,
+        doc_ids: Vec<String>,
+        attributes_to_retrieve: Option<Vec<String>>,
+    ) -> Result<Vec<Document>> {
+        let index = self
+            .store
+            .get(uuid)
+            .await?
+            .ok_or(IndexError::UnexistingIndex)?;
+        spawn_blocking(move || {
+            index
+                .retrieve_documents_by_ids(doc_ids, attributes_to_retrieve)
+                .map_err(IndexError::Error)
+        })
+        .await
+        .map_err(|e| IndexError::Error(e.into()))?
+    }
+
     async fn handle_delete(&self, uuid: Uuid) -> Result<()> {
         let index = self.store.delete(uuid).await?;
+        self.invalidate_search_cache(uuid);
 
         if let Some(index) = index {
             tokio::task::spawn(async move {
@@ -387,7 +875,9 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
         spawn_blocking(move || match index_settings.primary_key {
             Some(ref primary_key) => {
                 let mut txn = index.write_txn()?;
-                if index.primary_key(&txn)?.is_some() {
+                // The primary key can only be changed as long as no document has been indexed
+                // with the previous one.
+                if index.primary_key(&txn)?.is_some() && index.number_of_documents(&txn)? > 0 {
                     return Err(IndexError::ExistingPrimaryKey);
                 }
                 index.put_primary_key(&mut txn, primary_key)?;
@@ -403,25 +893,132 @@ impl<S: IndexStore + Sync + Send> IndexActor<S> {
         .await
         .map_err(|e| IndexError::Error(e.into()))?
     }
+
+    async fn handle_get_stats(&self, uuid: Uuid) -> Result<IndexStats> {
+        let index = self
+            .store
+            .get(uuid)
+            .await?
+            .ok_or(IndexError::UnexistingIndex)?;
+
+        let is_indexing = self.processing.read().await.contains(&uuid);
+        let size = self.store.size(uuid).await?;
+
+        spawn_blocking(move || {
+            let rtxn = index.read_txn()?;
+
+            Ok(IndexStats {
+                size,
+                number_of_documents: index.number_of_documents(&rtxn)?,
+                is_indexing,
+                fields_distribution: index.fields_distribution(&rtxn)?,
+            })
+        })
+        .await
+        .map_err(|e| IndexError::Error(e.into()))?
+    }
+
+    /// Reads the number of documents from the index metadata, without iterating the documents.
+    async fn handle_number_of_documents(&self, uuid: Uuid) -> Result<u64> {
+        let index = self
+            .store
+            .get(uuid)
+            .await?
+            .ok_or(IndexError::UnexistingIndex)?;
+
+        spawn_blocking(move || {
+            let rtxn = index.read_txn()?;
+            Ok(index.number_of_documents(&rtxn)?)
+        })
+        .await
+        .map_err(|e| IndexError::Error(e.into()))?
+    }
+
+    async fn handle_snapshot(
+        &self,
+        uuids: HashSet<Uuid>,
+        path: PathBuf,
+        compact: bool,
+    ) -> Result<()> {
+        let path = path.join("indexes/");
+        for uuid in uuids {
+            // The index of an index whose first update hasn't been processed yet doesn't exist,
+            // it is created when the update is processed after the restore.
+            let index = match self.store.get(uuid).await? {
+                Some(index) => index,
+                None => continue,
+            };
+            let index_path = path.join(format!("index-{}", uuid));
+            spawn_blocking(move || -> Result<()> {
+                create_dir_all(&index_path).map_err(|e| IndexError::Error(e.into()))?;
+                let compaction = if compact {
+                    CompactionOption::Enabled
+                } else {
+                    CompactionOption::Disabled
+                };
+                index
+                    .env
+                    .copy_to_path(index_path.join("data.mdb"), compaction)?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| IndexError::Error(e.into()))??;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_close(&self) -> Result<()> {
+        for (uuid, index) in self.store.drain().await {
+            close_index(index).await?;
+            debug!("Index {} closed", uuid);
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
 pub struct IndexActorHandle {
     read_sender: mpsc::Sender<IndexMsg>,
     write_sender: mpsc::Sender<IndexMsg>,
+    update_sender: mpsc::Sender<IndexMsg>,
 }
 
 impl IndexActorHandle {
-    pub fn new(path: impl AsRef<Path>, index_size: usize) -> anyhow::Result<Self> {
+    pub fn new(
+        path: impl AsRef<Path>,
+        index_size: usize,
+        max_map_size: usize,
+        max_concurrent_updates: usize,
+        indexer_options: IndexerOpts,
+        search_cache_size: usize,
+    ) -> anyhow::Result<Self> {
         let (read_sender, read_receiver) = mpsc::channel(100);
         let (write_sender, write_receiver) = mpsc::channel(100);
+        let (update_sender, update_receiver) = mpsc::channel(100);
 
-        let store = HeedIndexStore::new(path, index_size);
-        let actor = IndexActor::new(read_receiver, write_receiver, store)?;
-        tokio::task::spawn(actor.run());
+        let read_receiver = inbox(read_receiver);
+        let write_receiver = inbox(write_receiver);
+        let update_receiver = inbox(update_receiver);
+        let path = path.as_ref().to_owned();
+        supervise("index actor", move || {
+            let store = HeedIndexStore::new(&path, index_size, max_map_size);
+            let actor = IndexActor::new(
+                read_receiver.clone(),
+                write_receiver.clone(),
+                update_receiver.clone(),
+                max_concurrent_updates,
+                &indexer_options,
+                search_cache_size,
+                store,
+            )?;
+            Ok(actor.run())
+        })?;
         Ok(Self {
             read_sender,
             write_sender,
+            update_sender,
         })
     }
 
@@ -433,7 +1030,7 @@ impl IndexActorHandle {
             primary_key,
         };
         let _ = self.read_sender.send(msg).await;
-        receiver.await.expect("IndexActor has been killed")
+        receiver.await.map_err(|_| IndexError::Unavailable)?
     }
 
     pub async fn update(
@@ -443,22 +1040,43 @@ impl IndexActorHandle {
     ) -> anyhow::Result<UpdateResult> {
         let (ret, receiver) = oneshot::channel();
         let msg = IndexMsg::Update { ret, meta, data };
-        let _ = self.write_sender.send(msg).await;
-        Ok(receiver.await.expect("IndexActor has been killed")?)
+        let _ = self.update_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    pub async fn update_batch(
+        &self,
+        batch: Vec<(Processing<UpdateMeta>, std::fs::File)>,
+    ) -> anyhow::Result<Vec<UpdateResult>> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::UpdateBatch { ret, batch };
+        let _ = self.update_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
     }
 
     pub async fn search(&self, uuid: Uuid, query: SearchQuery) -> Result<SearchResult> {
         let (ret, receiver) = oneshot::channel();
         let msg = IndexMsg::Search { uuid, query, ret };
         let _ = self.read_sender.send(msg).await;
-        Ok(receiver.await.expect("IndexActor has been killed")?)
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    pub async fn facet_search(
+        &self,
+        uuid: Uuid,
+        query: FacetSearchQuery,
+    ) -> Result<FacetSearchResult> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::FacetSearch { uuid, query, ret };
+        let _ = self.read_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
     }
 
     pub async fn settings(&self, uuid: Uuid) -> Result<Settings> {
         let (ret, receiver) = oneshot::channel();
         let msg = IndexMsg::Settings { uuid, ret };
         let _ = self.read_sender.send(msg).await;
-        Ok(receiver.await.expect("IndexActor has been killed")?)
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
     }
 
     pub async fn documents(
@@ -477,7 +1095,28 @@ impl IndexActorHandle {
             limit,
         };
         let _ = self.read_sender.send(msg).await;
-        Ok(receiver.await.expect("IndexActor has been killed")?)
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    /// Returns a chunk of at most `limit` documents, starting after the cursor `after`, which is
+    /// the `next` cursor of the previous chunk, or `None` for the first one.
+    pub async fn documents_chunk(
+        &self,
+        uuid: Uuid,
+        after: Option<u32>,
+        limit: usize,
+        attributes_to_retrieve: Option<Vec<String>>,
+    ) -> Result<DocumentsChunk> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::DocumentsChunk {
+            uuid,
+            ret,
+            after,
+            attributes_to_retrieve,
+            limit,
+        };
+        let _ = self.read_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
     }
 
     pub async fn document(
@@ -494,21 +1133,38 @@ impl IndexActorHandle {
             attributes_to_retrieve,
         };
         let _ = self.read_sender.send(msg).await;
-        Ok(receiver.await.expect("IndexActor has been killed")?)
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    pub async fn documents_by_ids(
+        &self,
+        uuid: Uuid,
+        doc_ids: Vec<String>,
+        attributes_to_retrieve: Option<Vec<String>>,
+    ) -> Result<Vec<Document>> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::DocumentsByIds {
+            uuid,
+            ret,
+            doc_ids,
+            attributes_to_retrieve,
+        };
+        let _ = self.read_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
     }
 
     pub async fn delete(&self, uuid: Uuid) -> Result<()> {
         let (ret, receiver) = oneshot::channel();
         let msg = IndexMsg::Delete { uuid, ret };
         let _ = self.write_sender.send(msg).await;
-        Ok(receiver.await.expect("IndexActor has been killed")?)
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
     }
 
     pub async fn get_index_meta(&self, uuid: Uuid) -> Result<IndexMeta> {
         let (ret, receiver) = oneshot::channel();
         let msg = IndexMsg::GetMeta { uuid, ret };
         let _ = self.read_sender.send(msg).await;
-        Ok(receiver.await.expect("IndexActor has been killed")?)
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
     }
 
     pub async fn update_index(
@@ -523,7 +1179,66 @@ impl IndexActorHandle {
             ret,
         };
         let _ = self.read_sender.send(msg).await;
-        Ok(receiver.await.expect("IndexActor has been killed")?)
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    pub async fn get_index_stats(&self, uuid: Uuid) -> Result<IndexStats> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::GetStats { uuid, ret };
+        let _ = self.read_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    pub async fn number_of_documents(&self, uuid: Uuid) -> Result<u64> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::NumberOfDocuments { uuid, ret };
+        let _ = self.read_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    /// Returns the progress of the indexing of the update being processed on the index `uuid`,
+    /// if any.
+    pub async fn progress(&self, uuid: Uuid) -> Result<Option<UpdateProgress>> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::Progress { uuid, ret };
+        let _ = self.read_sender.send(msg).await;
+        receiver.await.map_err(|_| IndexError::Unavailable)
+    }
+
+    /// Copies the indexes with the given uuids in the `indexes` directory of `path`, compacted
+    /// if `compact` is set. The updates must be paused, so that the copies are consistent with
+    /// the update stores.
+    pub async fn snapshot(&self, uuids: HashSet<Uuid>, path: PathBuf, compact: bool) -> Result<()> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::Snapshot {
+            uuids,
+            path,
+            compact,
+            ret,
+        };
+        let _ = self.write_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    /// Closes all the opened indexes, once the updates being processed are done.
+    pub async fn close(&self) -> Result<()> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::Close { ret };
+        let _ = self.write_sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| IndexError::Unavailable)??)
+    }
+
+    /// Checks that the index actor is still alive and processing messages.
+    pub async fn health(&self) -> anyhow::Result<()> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = IndexMsg::Health { ret };
+        self.read_sender
+            .send(msg)
+            .await
+            .map_err(|_| anyhow::anyhow!("IndexActor has been killed"))?;
+        receiver
+            .await
+            .map_err(|_| anyhow::anyhow!("IndexActor has been killed"))
     }
 }
 
@@ -531,16 +1246,18 @@ struct HeedIndexStore {
     index_store: AsyncMap<Uuid, Index>,
     path: PathBuf,
     index_size: usize,
+    max_map_size: usize,
 }
 
 impl HeedIndexStore {
-    fn new(path: impl AsRef<Path>, index_size: usize) -> Self {
+    fn new(path: impl AsRef<Path>, index_size: usize, max_map_size: usize) -> Self {
         let path = path.as_ref().join("indexes/");
         let index_store = Arc::new(RwLock::new(HashMap::new()));
         Self {
             index_store,
             path,
             index_size,
+            max_map_size,
         }
     }
 }
@@ -594,13 +1311,109 @@ impl IndexStore for HeedIndexStore {
     }
 
     async fn delete(&self, uuid: Uuid) -> Result<Option<Index>> {
-        let db_path = self.path.join(format!("index-{}", uuid));
-        remove_dir_all(db_path)
+        let index = self.index_store.write().await.remove(&uuid);
+        Ok(index)
+    }
+
+    async fn clone_index(&self, source: Uuid, dest: Uuid) -> Result<Index> {
+        let source = self.get(source).await?.ok_or(IndexError::UnexistingIndex)?;
+
+        let path = self.path.join(format!("index-{}", dest));
+        if path.exists() {
+            return Err(IndexError::IndexAlreadyExists);
+        }
+
+        let index_size = self.index_size;
+        let index = spawn_blocking(move || -> Result<Index> {
+            create_dir_all(&path).map_err(|e| IndexError::Error(e.into()))?;
+            // The copy is made from a read transaction, so it is a consistent view of the
+            // source index, even if it is being updated at the same time.
+            source
+                .env
+                .copy_to_path(path.join("data.mdb"), CompactionOption::Enabled)?;
+            open_index(&path, index_size)
+        })
+        .await
+        .map_err(|e| IndexError::Error(e.into()))??;
+
+        self.index_store.write().await.insert(dest, index.clone());
+
+        Ok(index)
+    }
+
+    async fn grow(&self, uuid: Uuid) -> Result<bool> {
+        let path = self.path.join(format!("index-{}", uuid));
+        let map_size = match grown_map_size(&path, self.index_size, self.max_map_size) {
+            Some(map_size) => map_size,
+            None => return Ok(false),
+        };
+
+        // The write lock is held for the whole operation, so that nobody can reopen the index
+        // while it is being closed.
+        let mut guard = self.index_store.write().await;
+        if let Some(index) = guard.remove(&uuid) {
+            close_index(index).await?;
+        }
+
+        log::info!("Resizing index {} to {} bytes.", uuid, map_size);
+        let index = spawn_blocking(move || open_index(path, map_size))
+            .await
+            .map_err(|e| IndexError::Error(e.into()))??;
+        guard.insert(uuid, index);
+
+        Ok(true)
+    }
+    async fn drain(&self) -> Vec<(Uuid, Index)> {
+        self.index_store.write().await.drain().collect()
+    }
+
+    async fn size(&self, uuid: Uuid) -> Result<u64> {
+        let path = self.path.join(format!("index-{}", uuid)).join("data.mdb");
+        let metadata = tokio::fs::metadata(path)
             .await
             .map_err(|e| IndexError::Error(e.into()))?;
-        let index = self.index_store.write().await.remove(&uuid);
+        Ok(metadata.len())
+    }
+
+    async fn swap(&self, uuid: Uuid, shadow: Uuid) -> Result<Index> {
+        let path = self.path.join(format!("index-{}", uuid));
+        let shadow_path = self.path.join(format!("index-{}", shadow));
+
+        // The write lock is held for the whole operation, so that nobody can reopen the indexes
+        // while they are being closed.
+        let mut guard = self.index_store.write().await;
+        for uuid in [uuid, shadow].iter() {
+            if let Some(index) = guard.remove(uuid) {
+                close_index(index).await?;
+            }
+        }
+
+        let index_size = self.index_size;
+        let index = spawn_blocking(move || -> Result<Index> {
+            std::fs::rename(shadow_path.join("data.mdb"), path.join("data.mdb"))
+                .map_err(|e| IndexError::Error(e.into()))?;
+            remove_dir_all(&shadow_path).map_err(|e| IndexError::Error(e.into()))?;
+            open_index(&path, index_size)
+        })
+        .await
+        .map_err(|e| IndexError::Error(e.into()))??;
+        guard.insert(uuid, index.clone());
+
         Ok(index)
     }
+
+    async fn discard(&self, uuid: Uuid) -> Result<()> {
+        let path = self.path.join(format!("index-{}", uuid));
+
+        let mut guard = self.index_store.write().await;
+        if let Some(index) = guard.remove(&uuid) {
+            close_index(index).await?;
+        }
+        spawn_blocking(move || remove_dir_all(path))
+            .await
+            .map_err(|e| IndexError::Error(e.into()))?
+            .map_err(|e| IndexError::Error(e.into()))
+    }
 }
 
 fn open_index(path: impl AsRef<Path>, size: usize) -> Result<Index> {
@@ -610,3 +1423,11 @@ fn open_index(path: impl AsRef<Path>, size: usize) -> Result<Index> {
     let index = milli::Index::new(options, &path).map_err(IndexError::Error)?;
     Ok(Index(Arc::new(index)))
 }
+
+/// Closes the index, once all its other references are dropped.
+async fn close_index(index: Index) -> Result<()> {
+    let index = get_arc_ownership_blocking(index.0).await;
+    spawn_blocking(move || index.prepare_for_closing().wait())
+        .await
+        .map_err(|e| IndexError::Error(e.into()))
+}
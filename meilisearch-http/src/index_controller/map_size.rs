@@ -0,0 +1,96 @@
+//! Helpers used to grow the lmdb environments when they reach their maximum map size.
+
+use std::fs;
+use std::path::Path;
+
+/// Returns `true` if the error is an lmdb `MDB_MAP_FULL` error.
+pub fn is_map_full(error: &heed::Error) -> bool {
+    matches!(error, heed::Error::Mdb(heed::MdbError::MapFull))
+}
+
+/// Same as [`is_map_full`] but looks for the lmdb error in the whole chain of an
+/// `anyhow::Error`.
+pub fn is_map_full_anyhow(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|e| e.downcast_ref::<heed::Error>())
+        .any(is_map_full)
+}
+
+/// Returns the map size to use to reopen the full environment located at `path`: twice the size
+/// of its data file, bounded by `ceiling`. Returns `None` if the environment already reached the
+/// `ceiling` and can't grow anymore.
+pub fn grown_map_size(path: impl AsRef<Path>, current: usize, ceiling: usize) -> Option<usize> {
+    let file_size = fs::metadata(path.as_ref().join("data.mdb"))
+        .map(|metadata| metadata.len() as usize)
+        .unwrap_or_default();
+    let current = current.max(file_size);
+
+    if current >= ceiling {
+        None
+    } else {
+        Some(current.saturating_mul(2).min(ceiling))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use heed::{types::Str, Database, EnvOpenOptions};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const PAGE: usize = 4096;
+
+    #[test]
+    fn grows_twice_the_current_size() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            grown_map_size(dir.path(), 10 * PAGE, 100 * PAGE),
+            Some(20 * PAGE)
+        );
+    }
+
+    #[test]
+    fn growth_is_bounded_by_the_ceiling() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            grown_map_size(dir.path(), 60 * PAGE, 100 * PAGE),
+            Some(100 * PAGE)
+        );
+        assert_eq!(grown_map_size(dir.path(), 100 * PAGE, 100 * PAGE), None);
+        assert_eq!(grown_map_size(dir.path(), 120 * PAGE, 100 * PAGE), None);
+    }
+
+    #[test]
+    fn grows_from_the_data_file_size() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("data.mdb"), vec![0; 30 * PAGE]).unwrap();
+        assert_eq!(
+            grown_map_size(dir.path(), 10 * PAGE, 100 * PAGE),
+            Some(60 * PAGE)
+        );
+    }
+
+    #[test]
+    fn full_environment_is_detected() {
+        let dir = TempDir::new().unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(10 * PAGE);
+        let env = options.open(dir.path()).unwrap();
+        let db: Database<Str, Str> = env.create_database(None).unwrap();
+
+        let value = "a".repeat(PAGE);
+        let mut wtxn = env.write_txn().unwrap();
+        let error = (0..100)
+            .map(|i| db.put(&mut wtxn, &i.to_string(), &value))
+            .find_map(Result::err)
+            .unwrap();
+        assert!(is_map_full(&error));
+        assert!(is_map_full_anyhow(
+            &anyhow::Error::new(error).context("while writing")
+        ));
+
+        assert!(!is_map_full(&heed::Error::InvalidDatabaseTyping));
+    }
+}
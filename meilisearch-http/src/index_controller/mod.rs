@@ -1,27 +1,62 @@
+mod consistency;
+mod dump;
+mod garbage_collector;
 mod index_actor;
+mod map_size;
+mod replication;
+mod search_cache;
+mod search_limiter;
+mod snapshot;
+mod supervisor;
+mod tasks;
 mod update_actor;
 mod update_handler;
 mod update_store;
 mod updates;
 mod uuid_resolver;
+mod verify;
 
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
 use actix_web::web::{Bytes, Payload};
 use anyhow::bail;
-use futures::stream::StreamExt;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream, StreamExt};
 use milli::update::{IndexDocumentsMethod, UpdateFormat};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
-use tokio::time::sleep;
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, RwLock};
+use tokio::time::{sleep, timeout};
 use uuid::Uuid;
 
-use crate::index::{Document, SearchQuery, SearchResult};
+use crate::error::Error;
+use crate::helpers::compression::ArchiveCompression;
+use crate::index::{validate_documents, ValidationReport};
+use crate::index::{Document, FacetSearchQuery, FacetSearchResult, SearchQuery, SearchResult};
 use crate::index::{Facets, Settings, UpdateResult};
-pub use updates::{Failed, Processed, Processing};
-use uuid_resolver::UuidError;
+use crate::option::Opt;
+pub use consistency::ConsistencyReport;
+pub use dump::{dump_path, DumpError, DumpInfo, DumpStatus};
+use garbage_collector::{Garbage, GarbageCollector};
+pub use index_actor::{IndexError, IndexStats};
+use replication::ReplicatedOp;
+pub use replication::{spawn_replica, LogEntry, ReplicationError};
+pub use search_limiter::SearchLimitError;
+use search_limiter::SearchLimiter;
+pub use snapshot::{load_snapshot, snapshot_path, spawn_snapshots};
+use tasks::TaskStore;
+pub use tasks::{Task, TaskError, TaskFilter, TaskType};
+pub use update_actor::UpdateError;
+pub use update_store::RetentionPolicy;
+pub use updates::{Failed, Priority, Processed, Processing};
+pub use uuid_resolver::UuidError;
+pub use verify::{verify_database, VerifyReport};
 
 pub type UpdateStatus = updates::UpdateStatus<UpdateMeta, UpdateResult, String>;
 
@@ -41,11 +76,77 @@ pub enum UpdateMeta {
         method: IndexDocumentsMethod,
         format: UpdateFormat,
         primary_key: Option<String>,
+        /// Skips the invalid documents instead of failing the whole addition.
+        #[serde(default)]
+        skip_invalid_documents: bool,
     },
     ClearDocuments,
     DeleteDocuments,
     Settings(Settings),
     Facets(Facets),
+    /// Copies the data and settings of the `source` index into the index the update is
+    /// registered on.
+    Clone { source: Uuid },
+    /// Rebuilds the index in a shadow index, with its settings updated by `settings`, and
+    /// replaces the index by the shadow index once it is entirely rebuilt.
+    Reindex {
+        #[serde(default)]
+        settings: Settings,
+    },
+}
+
+impl update_store::Batchable for UpdateMeta {
+    /// Only the JSON document additions with identical options can be batched together. The
+    /// additions skipping their invalid documents are never batched, as their documents are
+    /// filtered one update at a time.
+    fn batches_with(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                UpdateMeta::DocumentsAddition {
+                    method,
+                    format: UpdateFormat::Json,
+                    primary_key,
+                    skip_invalid_documents: false,
+                },
+                UpdateMeta::DocumentsAddition {
+                    method: other_method,
+                    format: UpdateFormat::Json,
+                    primary_key: other_primary_key,
+                    skip_invalid_documents: false,
+                },
+            ) => method == other_method && primary_key == other_primary_key,
+            _ => false,
+        }
+    }
+}
+
+/// The status an update can be waited for.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WaitFor {
+    /// The update is being processed, or is finished.
+    Processing,
+    /// The update is finished: processed, failed or aborted.
+    Processed,
+}
+
+impl WaitFor {
+    fn is_reached(self, update: &UpdateStatus) -> bool {
+        match (self, update) {
+            (_, UpdateStatus::Pending(_)) => false,
+            (WaitFor::Processed, UpdateStatus::Processing(_)) => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub database_size: u64,
+    /// The disk space, in bytes, available to the database.
+    pub free_disk_space: u64,
+    pub last_update: Option<DateTime<Utc>>,
+    pub indexes: BTreeMap<String, IndexStats>,
 }
 
 #[derive(Clone, Debug)]
@@ -54,26 +155,123 @@ pub struct IndexSettings {
     pub primary_key: Option<String>,
 }
 
+#[derive(Clone)]
 pub struct IndexController {
+    path: PathBuf,
     uuid_resolver: uuid_resolver::UuidResolverHandle,
     index_handle: index_actor::IndexActorHandle,
     update_handle: update_actor::UpdateActorHandle<Bytes>,
+    /// The info of the dumps created since the start, by uid, shared by the clones of the
+    /// controller.
+    dumps: Arc<Mutex<BTreeMap<String, DumpInfo>>>,
+    /// The log of the operations replicated to the read replicas, when this instance is a
+    /// primary.
+    replication_log: Option<Arc<replication::ReplicationLog>>,
+    /// Held for reading while an index is created or deleted, and for writing while a snapshot
+    /// is made or the consistency of the database is checked.
+    snapshot_lock: Arc<RwLock<()>>,
+    /// Shared by the clones of the controller, so that the limit applies to all the searches.
+    search_limiter: Arc<SearchLimiter>,
+    /// Removes the data of the deleted indexes from the disk.
+    garbage_collector: GarbageCollector,
+    /// The compression of the snapshots and the dumps.
+    archive_compression: ArchiveCompression,
+    /// The number of incremental snapshots between two full snapshots.
+    snapshot_increments: u32,
+    /// The last snapshot created since the start, which the next incremental snapshot follows.
+    snapshot_chain: Arc<Mutex<Option<snapshot::Chain>>>,
+    /// Reserves the uids of the tasks, and stores the tasks that aren't updates.
+    task_store: Arc<TaskStore>,
 }
 
 impl IndexController {
-    pub fn new(
-        path: impl AsRef<Path>,
-        index_size: usize,
-        update_store_size: usize,
-    ) -> anyhow::Result<Self> {
-        let uuid_resolver = uuid_resolver::UuidResolverHandle::new(&path)?;
-        let index_actor = index_actor::IndexActorHandle::new(&path, index_size)?;
-        let update_handle =
-            update_actor::UpdateActorHandle::new(index_actor.clone(), &path, update_store_size)?;
+    pub fn new(path: impl AsRef<Path>, options: &Opt) -> anyhow::Result<Self> {
+        let index_size = options.max_index_size.get_bytes() as usize;
+        let update_store_size = options.max_update_store_size.get_bytes() as usize;
+        let uuid_store_size = options.max_uuid_store_size.get_bytes() as usize;
+        let max_map_size = options.max_map_size.get_bytes() as usize;
+        let archive_compression = ArchiveCompression::new(
+            options.archive_compression,
+            options.archive_compression_level,
+        )?;
+        let retention_policy = RetentionPolicy {
+            max_history: options.max_update_history,
+            max_age: options
+                .update_retention_days
+                .map(|days| chrono::Duration::days(days as i64)),
+        };
+
+        let uuid_resolver = uuid_resolver::UuidResolverHandle::new(
+            &path,
+            uuid_store_size,
+            max_map_size,
+            options.case_insensitive_index_uids,
+        )?;
+        let index_actor = index_actor::IndexActorHandle::new(
+            &path,
+            index_size,
+            max_map_size,
+            options.max_concurrent_updates.get(),
+            options.indexer_options.tuned_for_memory(),
+            options.search_cache_size,
+        )?;
+        let queue_limits = update_actor::QueueLimits {
+            per_index: options.max_pending_updates_per_index,
+            total: options.max_pending_updates,
+            min_free_disk_space: match options.min_free_disk_space.get_bytes() {
+                0 => None,
+                bytes => Some(bytes),
+            },
+        };
+
+        let task_store = Arc::new(TaskStore::open(
+            path.as_ref().join("tasks"),
+            update_store_size,
+        )?);
+
+        let update_handle = update_actor::UpdateActorHandle::new(
+            index_actor.clone(),
+            &path,
+            update_store_size,
+            max_map_size,
+            retention_policy,
+            queue_limits,
+            match options.autobatch_debounce_ms {
+                0 => None,
+                ms => Some(Duration::from_millis(ms)),
+            },
+            task_store.clone(),
+        )?;
+
+        let replication_log = if options.replication_primary {
+            let log_path = path.as_ref().join("replication");
+            let log = replication::ReplicationLog::open(
+                log_path,
+                update_store_size,
+                options.replication_log_retention.get(),
+            )?;
+            Some(Arc::new(log))
+        } else {
+            None
+        };
+
         Ok(Self {
+            path: path.as_ref().to_owned(),
             uuid_resolver,
             index_handle: index_actor,
             update_handle,
+            dumps: Arc::new(Mutex::new(BTreeMap::new())),
+            replication_log,
+            snapshot_lock: Arc::new(RwLock::new(())),
+            search_limiter: Arc::new(SearchLimiter::new(
+                options.max_concurrent_searches,
+                options.search_queue_size,
+            )),
+            garbage_collector: GarbageCollector::spawn(&path),
+            archive_compression,
+            snapshot_increments: options.snapshot_increments,
+            snapshot_chain: Arc::new(Mutex::new(None)),
+            task_store,
         })
     }
 
@@ -84,13 +282,32 @@ impl IndexController {
         format: milli::update::UpdateFormat,
         payload: Payload,
         primary_key: Option<String>,
+        skip_invalid_documents: bool,
+        priority: Priority,
     ) -> anyhow::Result<UpdateStatus> {
+        let meta = UpdateMeta::DocumentsAddition {
+            method,
+            format,
+            primary_key,
+            skip_invalid_documents,
+        };
+
+        // The payload of a primary is written to a file of the replication log, the update store
+        // reads it from this file, and the log keeps it.
+        if let Some(ref log) = self.replication_log {
+            let file = log.payload_file()?;
+            let mut writer = tokio::fs::File::from_std(file.reopen()?);
+            let mut payload = payload;
+            while let Some(bytes) = payload.next().await {
+                writer.write_all(&bytes?).await?;
+            }
+            writer.flush().await?;
+            return self
+                .replicated_file_update(uid, meta, priority, file, true)
+                .await;
+        }
+
         let perform_update = |uuid| async move {
-            let meta = UpdateMeta::DocumentsAddition {
-                method,
-                format,
-                primary_key,
-            };
             let (sender, receiver) = mpsc::channel(10);
 
             // It is necessary to spawn a local task to send the payload to the update handle to
@@ -110,12 +327,13 @@ impl IndexController {
             });
 
             // This must be done *AFTER* spawning the task.
-            self.update_handle.update(meta, receiver, uuid).await
+            self.update_handle.update(meta, priority, receiver, uuid).await
         };
 
         match self.uuid_resolver.get(uid).await {
             Ok(uuid) => Ok(perform_update(uuid).await?),
             Err(UuidError::UnexistingIndex(name)) => {
+                let _snapshot_guard = self.snapshot_lock.read().await;
                 let uuid = Uuid::new_v4();
                 let status = perform_update(uuid).await?;
                 self.uuid_resolver.insert(name, uuid).await?;
@@ -125,31 +343,26 @@ impl IndexController {
         }
     }
 
-    pub async fn clear_documents(&self, uid: String) -> anyhow::Result<UpdateStatus> {
-        let uuid = self.uuid_resolver.get(uid).await?;
+    pub async fn clear_documents(
+        &self,
+        uid: String,
+        priority: Priority,
+    ) -> anyhow::Result<UpdateStatus> {
         let meta = UpdateMeta::ClearDocuments;
-        let (_, receiver) = mpsc::channel(1);
-        let status = self.update_handle.update(meta, receiver, uuid).await?;
-        Ok(status)
+        self.replicated_update(uid, meta, priority, Bytes::new(), false)
+            .await
     }
 
     pub async fn delete_documents(
         &self,
         uid: String,
         document_ids: Vec<String>,
+        priority: Priority,
     ) -> anyhow::Result<UpdateStatus> {
-        let uuid = self.uuid_resolver.get(uid).await?;
         let meta = UpdateMeta::DeleteDocuments;
-        let (sender, receiver) = mpsc::channel(10);
-
-        tokio::task::spawn(async move {
-            let json = serde_json::to_vec(&document_ids).unwrap();
-            let bytes = Bytes::from(json);
-            let _ = sender.send(Ok(bytes)).await;
-        });
-
-        let status = self.update_handle.update(meta, receiver, uuid).await?;
-        Ok(status)
+        let payload = Bytes::from(serde_json::to_vec(&document_ids)?);
+        self.replicated_update(uid, meta, priority, payload, false)
+            .await
     }
 
     pub async fn update_settings(
@@ -157,24 +370,11 @@ impl IndexController {
         uid: String,
         settings: Settings,
         create: bool,
+        priority: Priority,
     ) -> anyhow::Result<UpdateStatus> {
-        let perform_udpate = |uuid| async move {
-            let meta = UpdateMeta::Settings(settings);
-            // Nothing so send, drop the sender right away, as not to block the update actor.
-            let (_, receiver) = mpsc::channel(1);
-            self.update_handle.update(meta, receiver, uuid).await
-        };
-
-        match self.uuid_resolver.get(uid).await {
-            Ok(uuid) => Ok(perform_udpate(uuid).await?),
-            Err(UuidError::UnexistingIndex(name)) if create => {
-                let uuid = Uuid::new_v4();
-                let status = perform_udpate(uuid).await?;
-                self.uuid_resolver.insert(name, uuid).await?;
-                Ok(status)
-            }
-            Err(e) => Err(e.into()),
-        }
+        let meta = UpdateMeta::Settings(settings);
+        self.replicated_update(uid, meta, priority, Bytes::new(), create)
+            .await
     }
 
     pub async fn create_index(
@@ -183,9 +383,22 @@ impl IndexController {
     ) -> anyhow::Result<IndexMetadata> {
         let IndexSettings { uid, primary_key } = index_settings;
         let uid = uid.ok_or_else(|| anyhow::anyhow!("Can't create an index without a uid."))?;
+        let _guard = self.replication_guard().await;
+        let _snapshot_guard = self.snapshot_lock.read().await;
         let uuid = self.uuid_resolver.create(uid.clone()).await?;
-        let meta = self.index_handle.create_index(uuid, primary_key).await?;
+        let meta = self
+            .index_handle
+            .create_index(uuid, primary_key.clone())
+            .await?;
         let _ = self.update_handle.create(uuid).await?;
+        let details = json!({ "primaryKey": primary_key });
+        let op = ReplicatedOp::CreateIndex {
+            uid: uid.clone(),
+            primary_key,
+        };
+        self.record(op, Bytes::new()).await?;
+        self.record_task(Some(uid.clone()), TaskType::IndexCreation, details)
+            .await?;
         let meta = IndexMetadata {
             name: uid.clone(),
             uid,
@@ -195,27 +408,208 @@ impl IndexController {
         Ok(meta)
     }
 
+    /// Registers an update cloning the index `uid` into a new index named `new_uid`.
+    pub async fn clone_index(&self, uid: String, new_uid: String) -> anyhow::Result<UpdateStatus> {
+        let _guard = self.replication_guard().await;
+        let source = self.uuid_resolver.get(uid.clone()).await?;
+
+        let _snapshot_guard = self.snapshot_lock.read().await;
+        // Creating the mapping fails if `new_uid` is already taken, so that two concurrent clones
+        // can't both take it.
+        let uuid = self.uuid_resolver.create(new_uid.clone()).await?;
+        let meta = UpdateMeta::Clone { source };
+        // Nothing to send, drop the sender right away, as not to block the update actor.
+        let (_, receiver) = mpsc::channel(1);
+        let events = self.update_handle.subscribe();
+        let status = match self
+            .update_handle
+            .update(meta, Priority::Normal, receiver, uuid)
+            .await
+        {
+            Ok(status) => status,
+            Err(e) => {
+                self.uuid_resolver.delete(new_uid).await?;
+                return Err(e.into());
+            }
+        };
+
+        let controller = self.clone();
+        let (clone_uid, update_id) = (new_uid.clone(), status.id());
+        tokio::spawn(async move {
+            if let Err(e) = controller
+                .remove_failed_clone(clone_uid, uuid, update_id, events)
+                .await
+            {
+                log::error!("Error while removing a failed clone: {}", e);
+            }
+        });
+
+        self.record(ReplicatedOp::CloneIndex { uid, new_uid }, Bytes::new())
+            .await?;
+        Ok(status)
+    }
+
+    /// Waits for the update `update_id` cloning an index into the index `uid` to be finished, and
+    /// removes the index if the clone failed or was aborted.
+    async fn remove_failed_clone(
+        &self,
+        uid: String,
+        uuid: Uuid,
+        update_id: u64,
+        events: broadcast::Receiver<UpdateStatus>,
+    ) -> anyhow::Result<()> {
+        self.wait_for_status(uuid, update_id, WaitFor::Processed, events)
+            .await?;
+        match self.update_handle.update_status(uuid, update_id).await? {
+            UpdateStatus::Failed(_) | UpdateStatus::Aborted(_) => (),
+            _ => return Ok(()),
+        }
+
+        let _snapshot_guard = self.snapshot_lock.read().await;
+        // The index may have been deleted, and its uid reused, in the meantime.
+        match self.uuid_resolver.get(uid.clone()).await {
+            Ok(current) if current == uuid => (),
+            _ => return Ok(()),
+        }
+        self.uuid_resolver.delete(uid).await?;
+        self.delete_index_data(uuid).await
+    }
+
+    /// Registers an update rebuilding the index `uid` in a shadow index, with its settings
+    /// updated by `settings`. The index is replaced by the shadow index once it is rebuilt.
+    pub async fn reindex(&self, uid: String, settings: Settings) -> anyhow::Result<UpdateStatus> {
+        let meta = UpdateMeta::Reindex { settings };
+        self.replicated_update(uid, meta, Priority::Normal, Bytes::new(), false)
+            .await
+    }
+
     pub async fn delete_index(&self, uid: String) -> anyhow::Result<()> {
-        let uuid = self.uuid_resolver.delete(uid).await?;
-        self.update_handle.delete(uuid).await?;
+        let _guard = self.replication_guard().await;
+        let _snapshot_guard = self.snapshot_lock.read().await;
+        let uuid = self.uuid_resolver.delete(uid.clone()).await?;
+        self.delete_index_data(uuid).await?;
+        self.record(ReplicatedOp::DeleteIndex { uid: uid.clone() }, Bytes::new())
+            .await?;
+        self.record_task(Some(uid), TaskType::IndexDeletion, json!({}))
+            .await?;
+        Ok(())
+    }
+
+    /// Closes the index and its update store, whose uuid is no longer referenced by the uuid
+    /// resolver, and schedules the removal of their data from the disk.
+    async fn delete_index_data(&self, uuid: Uuid) -> anyhow::Result<()> {
+        let update_files = self.update_handle.delete(uuid).await?;
         self.index_handle.delete(uuid).await?;
+        self.garbage_collector
+            .collect(Garbage { uuid, update_files });
         Ok(())
     }
 
     pub async fn update_status(&self, uid: String, id: u64) -> anyhow::Result<UpdateStatus> {
         let uuid = self.uuid_resolver.get(uid).await?;
-        let result = self.update_handle.update_status(uuid, id).await?;
+        let mut result = self.update_handle.update_status(uuid, id).await?;
+        if let UpdateStatus::Processing(ref mut processing) = result {
+            processing.progress = self.index_handle.progress(uuid).await?;
+        }
         Ok(result)
     }
 
+    /// Returns the status of the update `id` once it reached `wait_for`, or its current status if
+    /// it didn't within `duration`.
+    pub async fn wait_update_status(
+        &self,
+        uid: String,
+        id: u64,
+        wait_for: WaitFor,
+        duration: Duration,
+    ) -> anyhow::Result<UpdateStatus> {
+        let uuid = self.uuid_resolver.get(uid.clone()).await?;
+        let events = self.update_handle.subscribe();
+        let reached = self.wait_for_status(uuid, id, wait_for, events);
+        if let Ok(result) = timeout(duration, reached).await {
+            result?;
+        }
+        self.update_status(uid, id).await
+    }
+
+    /// Waits for the update `id` of the index `uuid` to reach `wait_for`. The `events` must be
+    /// subscribed to before the status can change, so that no change is missed.
+    async fn wait_for_status(
+        &self,
+        uuid: Uuid,
+        id: u64,
+        wait_for: WaitFor,
+        mut events: broadcast::Receiver<UpdateStatus>,
+    ) -> anyhow::Result<()> {
+        let status = self.update_handle.update_status(uuid, id).await?;
+        if wait_for.is_reached(&status) {
+            return Ok(());
+        }
+        loop {
+            match events.recv().await {
+                Ok(update) if *update.index_uuid() == uuid && update.id() == id => {
+                    if wait_for.is_reached(&update) {
+                        return Ok(());
+                    }
+                }
+                Ok(_) => (),
+                // The missed changes are caught up by fetching the status again.
+                Err(RecvError::Lagged(_)) => {
+                    let status = self.update_handle.update_status(uuid, id).await?;
+                    if wait_for.is_reached(&status) {
+                        return Ok(());
+                    }
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
     pub async fn all_update_status(&self, uid: String) -> anyhow::Result<Vec<UpdateStatus>> {
         let uuid = self.uuid_resolver.get(uid).await?;
-        let result = self.update_handle.get_all_updates_status(uuid).await?;
+        let mut result = self.update_handle.get_all_updates_status(uuid).await?;
+        // All the updates being processed are processed in the same batch.
+        if result
+            .iter()
+            .any(|u| matches!(u, UpdateStatus::Processing(_)))
+        {
+            let progress = self.index_handle.progress(uuid).await?;
+            for update in result.iter_mut() {
+                if let UpdateStatus::Processing(ref mut processing) = update {
+                    processing.progress = progress.clone();
+                }
+            }
+        }
         Ok(result)
     }
 
-    pub async fn list_indexes(&self) -> anyhow::Result<Vec<IndexMetadata>> {
-        let uuids = self.uuid_resolver.list().await?;
+    /// Streams the new status of the updates of the index `uid`, each time an update changes
+    /// state, starting once the stream is created. The stream ends when it lags too far behind
+    /// the updates, the current status of the updates must then be fetched again.
+    pub async fn update_events(
+        &self,
+        uid: String,
+    ) -> anyhow::Result<impl Stream<Item = UpdateStatus>> {
+        let uuid = self.uuid_resolver.get(uid).await?;
+        let receiver = self.update_handle.subscribe();
+        Ok(stream::unfold(receiver, move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(update) if *update.index_uuid() == uuid => return Some((update, receiver)),
+                    Ok(_) => (),
+                    Err(RecvError::Lagged(_)) | Err(RecvError::Closed) => return None,
+                }
+            }
+        }))
+    }
+
+    pub async fn list_indexes(
+        &self,
+        prefix: Option<String>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<IndexMetadata>> {
+        let uuids = self.uuid_resolver.list_range(prefix, offset, limit).await?;
 
         let mut ret = Vec::new();
 
@@ -253,6 +647,47 @@ impl IndexController {
         Ok(documents)
     }
 
+    /// Streams all the documents of the index `uid` as newline-delimited JSON, reading them from
+    /// the index in chunks of `chunk_size` documents. A chunk is only read once the previous one
+    /// has been consumed.
+    ///
+    /// Each chunk is read in its own transaction: the documents updated during the export may be
+    /// exported in either version, but no document is exported twice.
+    pub async fn export_documents(
+        &self,
+        uid: String,
+        chunk_size: usize,
+        attributes_to_retrieve: Option<Vec<String>>,
+    ) -> anyhow::Result<impl Stream<Item = anyhow::Result<Bytes>>> {
+        let uuid = self.uuid_resolver.get(uid).await?;
+        let index_handle = self.index_handle.clone();
+
+        // The state is the cursor of the next chunk, `None` once all the chunks have been read.
+        let stream = stream::try_unfold(Some(None), move |cursor| {
+            let index_handle = index_handle.clone();
+            let attributes_to_retrieve = attributes_to_retrieve.clone();
+            async move {
+                let after = match cursor {
+                    Some(after) => after,
+                    None => return Ok(None),
+                };
+                let chunk = index_handle
+                    .documents_chunk(uuid, after, chunk_size, attributes_to_retrieve)
+                    .await?;
+
+                let mut bytes = Vec::new();
+                for document in chunk.documents {
+                    serde_json::to_writer(&mut bytes, &document)?;
+                    bytes.push(b'\n');
+                }
+                let next = chunk.next.map(Some);
+                Ok::<_, anyhow::Error>(Some((Bytes::from(bytes), next)))
+            }
+        });
+
+        Ok(stream)
+    }
+
     pub async fn document(
         &self,
         uid: String,
@@ -267,6 +702,20 @@ impl IndexController {
         Ok(document)
     }
 
+    pub async fn documents_by_ids(
+        &self,
+        uid: String,
+        doc_ids: Vec<String>,
+        attributes_to_retrieve: Option<Vec<String>>,
+    ) -> anyhow::Result<Vec<Document>> {
+        let uuid = self.uuid_resolver.get(uid).await?;
+        let documents = self
+            .index_handle
+            .documents_by_ids(uuid, doc_ids, attributes_to_retrieve)
+            .await?;
+        Ok(documents)
+    }
+
     pub async fn update_index(
         &self,
         uid: String,
@@ -276,8 +725,15 @@ impl IndexController {
             bail!("Can't change the index uid.")
         }
 
+        let _guard = self.replication_guard().await;
         let uuid = self.uuid_resolver.get(uid.clone()).await?;
+        let primary_key = index_settings.primary_key.clone();
         let meta = self.index_handle.update_index(uuid, index_settings).await?;
+        let op = ReplicatedOp::UpdateIndex {
+            uid: uid.clone(),
+            primary_key,
+        };
+        self.record(op, Bytes::new()).await?;
         let meta = IndexMetadata {
             name: uid.clone(),
             uid,
@@ -288,10 +744,141 @@ impl IndexController {
 
     pub async fn search(&self, uid: String, query: SearchQuery) -> anyhow::Result<SearchResult> {
         let uuid = self.uuid_resolver.get(uid).await?;
+        let _permit = self.search_limiter.acquire().await?;
         let result = self.index_handle.search(uuid, query).await?;
         Ok(result)
     }
 
+    pub async fn facet_search(
+        &self,
+        uid: String,
+        query: FacetSearchQuery,
+    ) -> anyhow::Result<FacetSearchResult> {
+        let uuid = self.uuid_resolver.get(uid).await?;
+        let _permit = self.search_limiter.acquire().await?;
+        let result = self.index_handle.facet_search(uuid, query).await?;
+        Ok(result)
+    }
+
+    /// Checks that every actor answers a health message before the `deadline` expires.
+    pub async fn ready(&self, deadline: Duration) -> anyhow::Result<()> {
+        let checks = async {
+            tokio::try_join!(
+                self.uuid_resolver.health(),
+                self.update_handle.health(),
+                self.index_handle.health(),
+            )
+        };
+
+        match timeout(deadline, checks).await {
+            Ok(result) => result.map(|_| ()),
+            Err(_) => bail!("The actors did not respond within {:?}.", deadline),
+        }
+    }
+
+    /// Stops processing the updates, waits for the updates being processed to finish, and closes
+    /// the update stores and the indexes. The pending updates are processed on the next start.
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        // The update stores must be closed first, so that no update is sent to the indexes
+        // while they are being closed.
+        self.update_handle.close().await?;
+        self.index_handle.close().await?;
+        Ok(())
+    }
+
+    /// Waits for the pending updates of all the indexes to be processed.
+    pub async fn wait_pending_updates(&self) -> anyhow::Result<()> {
+        for (_, uuid) in self.uuid_resolver.list().await? {
+            loop {
+                let updates = self.update_handle.get_all_updates_status(uuid).await?;
+                let pending = updates
+                    .iter()
+                    .any(|u| matches!(u, UpdateStatus::Pending(_) | UpdateStatus::Processing(_)));
+                if !pending {
+                    break;
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn get_stats(&self, uid: String) -> anyhow::Result<IndexStats> {
+        let uuid = self.uuid_resolver.get(uid).await?;
+        let stats = self.index_handle.get_index_stats(uuid).await?;
+        Ok(stats)
+    }
+
+    pub async fn number_of_documents(&self, uid: String) -> anyhow::Result<u64> {
+        let uuid = self.uuid_resolver.get(uid).await?;
+        let count = self.index_handle.number_of_documents(uuid).await?;
+        Ok(count)
+    }
+
+    /// Returns the stats of the instance and of the `index_uids` indexes, or of all the indexes if
+    /// none are given. The indexes are resolved at once, and it is an error if one of them
+    /// doesn't exist.
+    pub async fn get_all_stats(&self, index_uids: Option<Vec<String>>) -> anyhow::Result<Stats> {
+        let uuids = match index_uids {
+            Some(uids) => {
+                let uuids = self.uuid_resolver.get_many(uids.clone()).await?;
+                uids.into_iter().zip(uuids).collect()
+            }
+            None => self.uuid_resolver.list().await?,
+        };
+
+        let mut last_update: Option<DateTime<Utc>> = None;
+        let mut indexes = BTreeMap::new();
+
+        for (uid, uuid) in uuids {
+            let meta = self.index_handle.get_index_meta(uuid).await?;
+            last_update = last_update.max(Some(meta.updated_at));
+
+            let stats = self.index_handle.get_index_stats(uuid).await?;
+            indexes.insert(uid, stats);
+        }
+
+        let path = self.path.clone();
+        let (database_size, free_disk_space) = tokio::task::spawn_blocking(move || {
+            std::io::Result::Ok((dir_size(&path)?, fs2::available_space(&path)?))
+        })
+        .await??;
+
+        Ok(Stats {
+            database_size,
+            free_disk_space,
+            last_update,
+            indexes,
+        })
+    }
+
+    /// Validates a document payload without registering any update. The primary key of the
+    /// index, if it exists and has one, takes precedence over the given primary key.
+    pub async fn validate_documents(
+        &self,
+        uid: String,
+        mut payload: Payload,
+        primary_key: Option<String>,
+    ) -> anyhow::Result<ValidationReport> {
+        let mut buffer = Vec::new();
+        while let Some(bytes) = payload.next().await {
+            buffer.extend_from_slice(&bytes.map_err(Error::bad_request)?);
+        }
+
+        let index_primary_key = match self.uuid_resolver.get(uid).await {
+            Ok(uuid) => self.index_handle.get_index_meta(uuid).await?.primary_key,
+            Err(UuidError::UnexistingIndex(_)) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let primary_key = index_primary_key.or(primary_key);
+
+        let report = tokio::task::spawn_blocking(move || {
+            validate_documents(&buffer, primary_key.as_deref())
+        })
+        .await?;
+        Ok(report)
+    }
+
     pub async fn get_index(&self, uid: String) -> anyhow::Result<IndexMetadata> {
         let uuid = self.uuid_resolver.get(uid.clone()).await?;
         let meta = self.index_handle.get_index_meta(uuid).await?;
@@ -304,6 +891,21 @@ impl IndexController {
     }
 }
 
+/// Returns the size, in bytes, of all the files contained in `path` and its sub-directories.
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
 pub async fn get_arc_ownership_blocking<T>(mut item: Arc<T>) -> T {
     loop {
         match Arc::try_unwrap(item) {
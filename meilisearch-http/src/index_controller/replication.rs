@@ -0,0 +1,560 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use heed::types::{DecodeIgnore, OwnedType, SerdeJson};
+use heed::{Database, Env, EnvOpenOptions};
+use log::{error, info, warn};
+use meilisearch_error::{Code, ErrorCode};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{mpsc, Mutex, MutexGuard};
+use uuid::Uuid;
+
+use super::{
+    IndexController, IndexSettings, Priority, TaskFilter, UpdateMeta, UpdateStatus, UuidError,
+};
+
+type BEU64 = heed::zerocopy::U64<heed::byteorder::BE>;
+type PayloadData = Result<Bytes, Box<dyn std::error::Error + Sync + Send + 'static>>;
+
+/// The file, in the database directory of a replica, holding the sequence number of the last
+/// entry of the log of the primary it applied.
+const LAST_APPLIED_FILE: &str = "replication-last-applied";
+/// The maximum number of entries returned by the primary at once.
+pub const MAX_LOG_ENTRIES: usize = 100;
+/// The maximum delay between two attempts of a replica to fetch or apply the log of its primary,
+/// the delay doubles after each failure until it reaches this one.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// The size of the chunks a payload file is sent to the update store in.
+const PAYLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// An operation recorded in the replication log of a primary, and applied by its replicas.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ReplicatedOp {
+    #[serde(rename_all = "camelCase")]
+    CreateIndex {
+        uid: String,
+        primary_key: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    UpdateIndex {
+        uid: String,
+        primary_key: Option<String>,
+    },
+    DeleteIndex {
+        uid: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    CloneIndex {
+        uid: String,
+        new_uid: String,
+    },
+    /// An update registered on the index `uid`, its payload is stored apart from the entry.
+    Update {
+        uid: String,
+        meta: UpdateMeta,
+        priority: Priority,
+    },
+    /// A bulk deletion of the finished tasks matching `filter`.
+    DeleteTasks {
+        filter: TaskFilter,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub seq: u64,
+    pub op: ReplicatedOp,
+    pub has_payload: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum ReplicationError {
+    #[error("The replication log is disabled, the instance must be started with `--replication-primary`")]
+    Disabled,
+    #[error("There is no payload for the log entry {0}")]
+    PayloadNotFound(u64),
+    #[error("The log entries after {0} have been removed from the replication log, the replica must be restarted from a snapshot of the primary")]
+    Truncated(u64),
+}
+
+impl ErrorCode for ReplicationError {
+    fn error_code(&self) -> Code {
+        match self {
+            ReplicationError::Disabled => Code::ReplicationDisabled,
+            ReplicationError::PayloadNotFound(_) => Code::NotFound,
+            ReplicationError::Truncated(_) => Code::ReplicationLogTruncated,
+        }
+    }
+}
+
+/// The log of the operations of a primary, in the order they were registered. Entries are
+/// numbered from 1, so that a replica that applied nothing yet asks for the entries after 0. Only
+/// the last `retention` entries are kept.
+pub struct ReplicationLog {
+    env: Env,
+    entries: Database<OwnedType<BEU64>, SerdeJson<ReplicatedOp>>,
+    /// The payloads of the entries, each in a file named after the sequence number of its entry,
+    /// so that the payloads of the document additions are never held in memory.
+    payloads_path: PathBuf,
+    retention: u64,
+    /// Held while an operation is registered and recorded, so that the operations are recorded
+    /// in the order they are registered.
+    write_lock: Mutex<()>,
+}
+
+impl ReplicationLog {
+    pub fn open(path: impl AsRef<Path>, map_size: usize, retention: u64) -> heed::Result<Self> {
+        let path = path.as_ref();
+        let payloads_path = path.join("payloads");
+        fs::create_dir_all(&payloads_path)?;
+        let mut options = EnvOpenOptions::new();
+        options.map_size(map_size);
+        options.max_dbs(1);
+        let env = options.open(path)?;
+        let entries = env.create_database(Some("entries"))?;
+        Ok(Self {
+            env,
+            entries,
+            payloads_path,
+            retention,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    fn payload_path(&self, seq: u64) -> PathBuf {
+        self.payloads_path.join(seq.to_string())
+    }
+
+    /// Creates a file to write the payload of an entry to, before the entry is appended.
+    pub fn payload_file(&self) -> io::Result<NamedTempFile> {
+        NamedTempFile::new_in(&self.payloads_path)
+    }
+
+    fn append(&self, op: &ReplicatedOp, payload: Option<NamedTempFile>) -> anyhow::Result<u64> {
+        let mut txn = self.env.write_txn()?;
+        let seq = match self.entries.last(&txn)? {
+            Some((seq, _)) => seq.get() + 1,
+            None => 1,
+        };
+        self.entries.put(&mut txn, &BEU64::new(seq), op)?;
+        // A payload left by an entry that wasn't committed is replaced.
+        if let Some(payload) = payload {
+            payload.persist(self.payload_path(seq))?;
+        }
+        let mut removed_payloads = Vec::new();
+        if seq > self.retention {
+            let removed = ..=BEU64::new(seq - self.retention);
+            let removed_entries = self.entries.remap_data_type::<DecodeIgnore>();
+            for entry in removed_entries.range(&txn, &removed)? {
+                let (seq, _) = entry?;
+                removed_payloads.push(self.payload_path(seq.get()));
+            }
+            self.entries.delete_range(&mut txn, &removed)?;
+        }
+        txn.commit()?;
+
+        for path in removed_payloads {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    warn!(
+                        "Can't remove the replication payload {}: {}",
+                        path.display(),
+                        e
+                    )
+                }
+                _ => (),
+            }
+        }
+        Ok(seq)
+    }
+
+    /// Returns the sequence number of the oldest entry kept in the log, if any.
+    fn first_seq(&self) -> heed::Result<Option<u64>> {
+        let txn = self.env.read_txn()?;
+        Ok(self.entries.first(&txn)?.map(|(seq, _)| seq.get()))
+    }
+
+    /// Returns at most `limit` entries, starting after the entry `after`.
+    pub fn entries(&self, after: u64, limit: usize) -> heed::Result<Vec<LogEntry>> {
+        let txn = self.env.read_txn()?;
+        let range = (
+            std::ops::Bound::Excluded(BEU64::new(after)),
+            std::ops::Bound::Unbounded,
+        );
+        let mut entries = Vec::new();
+        for result in self.entries.range(&txn, &range)?.take(limit) {
+            let (seq, op) = result?;
+            entries.push(LogEntry {
+                seq: seq.get(),
+                op,
+                has_payload: self.payload_path(seq.get()).exists(),
+            });
+        }
+        Ok(entries)
+    }
+
+    pub fn payload(&self, seq: u64) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.payload_path(seq)) {
+            Ok(payload) => Ok(Some(payload)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl IndexController {
+    /// Serializes the operations on a primary, the returned guard must be held until the
+    /// operation has been recorded with `record`. Does nothing on the other instances.
+    pub(super) async fn replication_guard(&self) -> Option<MutexGuard<'_, ()>> {
+        match self.replication_log {
+            Some(ref log) => Some(log.write_lock.lock().await),
+            None => None,
+        }
+    }
+
+    /// Records an operation in the replication log, if this instance is a primary.
+    pub(super) async fn record(&self, op: ReplicatedOp, payload: Bytes) -> anyhow::Result<()> {
+        if let Some(ref log) = self.replication_log {
+            let log = log.clone();
+            tokio::task::spawn_blocking(move || {
+                let file = if payload.is_empty() {
+                    None
+                } else {
+                    let mut file = log.payload_file()?;
+                    file.write_all(&payload)?;
+                    Some(file)
+                };
+                log.append(&op, file)
+            })
+            .await??;
+        }
+        Ok(())
+    }
+
+    /// Registers an update whose payload is entirely known, and records it in the replication
+    /// log. The index is created if it doesn't exist and `create` is set.
+    pub(super) async fn replicated_update(
+        &self,
+        uid: String,
+        meta: UpdateMeta,
+        priority: Priority,
+        payload: Bytes,
+        create: bool,
+    ) -> anyhow::Result<UpdateStatus> {
+        let _guard = self.replication_guard().await;
+        let receiver = bytes_receiver(payload.clone());
+        let status = self
+            .register_update(uid.clone(), meta.clone(), priority, receiver, create)
+            .await?;
+        let op = ReplicatedOp::Update {
+            uid,
+            meta,
+            priority,
+        };
+        self.record(op, payload).await?;
+        Ok(status)
+    }
+
+    /// Registers an update whose payload was written to `payload`, a file created by
+    /// `ReplicationLog::payload_file`, and records it in the replication log with this file. The
+    /// payload is streamed from the file to the update store.
+    pub(super) async fn replicated_file_update(
+        &self,
+        uid: String,
+        meta: UpdateMeta,
+        priority: Priority,
+        payload: NamedTempFile,
+        create: bool,
+    ) -> anyhow::Result<UpdateStatus> {
+        let log = match self.replication_log {
+            Some(ref log) => log.clone(),
+            None => return Err(ReplicationError::Disabled.into()),
+        };
+        let _guard = self.replication_guard().await;
+        let file = tokio::fs::File::open(payload.path()).await?;
+        let status = self
+            .register_update(
+                uid.clone(),
+                meta.clone(),
+                priority,
+                file_receiver(file),
+                create,
+            )
+            .await?;
+        let op = ReplicatedOp::Update {
+            uid,
+            meta,
+            priority,
+        };
+        tokio::task::spawn_blocking(move || log.append(&op, Some(payload))).await??;
+        Ok(status)
+    }
+
+    async fn register_update(
+        &self,
+        uid: String,
+        meta: UpdateMeta,
+        priority: Priority,
+        payload: mpsc::Receiver<PayloadData>,
+        create: bool,
+    ) -> anyhow::Result<UpdateStatus> {
+        let perform_update = |uuid| async move {
+            self.update_handle
+                .update(meta, priority, payload, uuid)
+                .await
+        };
+
+        match self.uuid_resolver.get(uid).await {
+            Ok(uuid) => Ok(perform_update(uuid).await?),
+            Err(UuidError::UnexistingIndex(name)) if create => {
+                let _snapshot_guard = self.snapshot_lock.read().await;
+                let uuid = Uuid::new_v4();
+                let status = perform_update(uuid).await?;
+                self.uuid_resolver.insert(name, uuid).await?;
+                Ok(status)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn replication_log_entries(
+        &self,
+        after: u64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<LogEntry>> {
+        let log = self
+            .replication_log
+            .as_ref()
+            .ok_or(ReplicationError::Disabled)?;
+        // The entries following `after` must all still be in the log for the replica to apply
+        // them.
+        if let Some(first) = log.first_seq()? {
+            if first > after + 1 {
+                return Err(ReplicationError::Truncated(after).into());
+            }
+        }
+        Ok(log.entries(after, limit.min(MAX_LOG_ENTRIES))?)
+    }
+
+    pub fn replication_log_payload(&self, seq: u64) -> anyhow::Result<Vec<u8>> {
+        let log = self
+            .replication_log
+            .as_ref()
+            .ok_or(ReplicationError::Disabled)?;
+        Ok(log
+            .payload(seq)?
+            .ok_or(ReplicationError::PayloadNotFound(seq))?)
+    }
+
+    /// Applies an operation of the log of the primary. An operation is applied twice when the
+    /// replica stops before recording it as applied, so creating an index that already exists,
+    /// or deleting one that doesn't, is not an error.
+    async fn apply(&self, op: ReplicatedOp, payload: Bytes) -> anyhow::Result<()> {
+        let result = match op {
+            ReplicatedOp::CreateIndex { uid, primary_key } => {
+                let settings = IndexSettings {
+                    uid: Some(uid),
+                    primary_key,
+                };
+                self.create_index(settings).await.map(drop)
+            }
+            ReplicatedOp::UpdateIndex { uid, primary_key } => {
+                let settings = IndexSettings {
+                    uid: None,
+                    primary_key,
+                };
+                self.update_index(uid, settings).await.map(drop)
+            }
+            ReplicatedOp::DeleteIndex { uid } => self.delete_index(uid).await,
+            ReplicatedOp::CloneIndex { uid, new_uid } => {
+                self.clone_index(uid, new_uid).await.map(drop)
+            }
+            ReplicatedOp::Update {
+                uid,
+                meta,
+                priority,
+            } => self
+                .register_update(uid, meta, priority, bytes_receiver(payload), true)
+                .await
+                .map(drop),
+            ReplicatedOp::DeleteTasks { filter } => self.delete_tasks(&filter).await.map(drop),
+        };
+
+        match result {
+            Err(e) => match e.downcast_ref::<UuidError>() {
+                Some(UuidError::NameAlreadyExist) | Some(UuidError::UnexistingIndex(_)) => Ok(()),
+                _ => Err(e),
+            },
+            Ok(()) => Ok(()),
+        }
+    }
+}
+
+/// Sends a payload known in full to the update store. The whole payload fits in the channel, so
+/// it is sent before the update is registered, and the sender is dropped to signal its end.
+fn bytes_receiver(payload: Bytes) -> mpsc::Receiver<PayloadData> {
+    let (sender, receiver) = mpsc::channel(1);
+    if !payload.is_empty() {
+        let _ = sender.try_send(Ok(payload));
+    }
+    receiver
+}
+
+/// Streams a payload file to the update store. The chunks are sent from a local task, as the
+/// update is only registered once its whole payload has been received.
+fn file_receiver(mut file: tokio::fs::File) -> mpsc::Receiver<PayloadData> {
+    let (sender, receiver) = mpsc::channel(10);
+    tokio::task::spawn_local(async move {
+        let mut buffer = vec![0; PAYLOAD_CHUNK_SIZE];
+        loop {
+            let chunk = match file.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(len) => Ok(Bytes::copy_from_slice(&buffer[..len])),
+                Err(e) => Err(Box::new(e) as Box<dyn std::error::Error + Sync + Send + 'static>),
+            };
+            let failed = chunk.is_err();
+            if sender.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Follows the replication log of a primary, and applies its operations in order.
+struct Replica {
+    controller: IndexController,
+    primary: String,
+    api_key: Option<String>,
+    last_applied_path: PathBuf,
+    poll_interval: Duration,
+}
+
+impl Replica {
+    async fn run(self) {
+        let mut last_applied = self.read_last_applied();
+        info!(
+            "Replicating {}, starting after the log entry {}.",
+            self.primary, last_applied
+        );
+
+        let mut retry_delay = self.poll_interval;
+        loop {
+            let entries = match self.fetch_entries(last_applied).await {
+                Ok(entries) => entries,
+                Err(e) => {
+                    warn!("Can't fetch the replication log of {}: {}", self.primary, e);
+                    retry_delay = self.retry(retry_delay).await;
+                    continue;
+                }
+            };
+
+            // The replica catches up with the primary as fast as possible, and only waits for
+            // new entries once it has applied all of them.
+            let up_to_date = entries.len() < MAX_LOG_ENTRIES;
+            let mut failed = false;
+            for entry in entries {
+                let seq = entry.seq;
+                if let Err(e) = self.apply_entry(entry).await {
+                    error!("Can't apply the replication log entry {}: {}", seq, e);
+                    failed = true;
+                    break;
+                }
+                last_applied = seq;
+                if let Err(e) = fs::write(&self.last_applied_path, seq.to_string()) {
+                    error!("Can't record the last applied log entry: {}", e);
+                }
+            }
+
+            if failed {
+                retry_delay = self.retry(retry_delay).await;
+            } else {
+                retry_delay = self.poll_interval;
+                if up_to_date {
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+            }
+        }
+    }
+
+    /// Waits `delay` before retrying after a failure, and returns the delay before the next
+    /// attempt if it fails too.
+    async fn retry(&self, delay: Duration) -> Duration {
+        tokio::time::sleep(delay).await;
+        (delay * 2).min(MAX_RETRY_DELAY)
+    }
+
+    fn read_last_applied(&self) -> u64 {
+        fs::read_to_string(&self.last_applied_path)
+            .ok()
+            .and_then(|seq| seq.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    async fn apply_entry(&self, entry: LogEntry) -> anyhow::Result<()> {
+        let payload = if entry.has_payload {
+            let url = format!("{}/replication/log/{}/payload", self.primary, entry.seq);
+            let response = self.get(url).await?;
+            let payload = tokio::task::spawn_blocking(move || {
+                let mut payload = Vec::new();
+                response.into_reader().read_to_end(&mut payload)?;
+                Ok::<_, std::io::Error>(payload)
+            })
+            .await??;
+            Bytes::from(payload)
+        } else {
+            Bytes::new()
+        };
+        self.controller.apply(entry.op, payload).await
+    }
+
+    async fn fetch_entries(&self, after: u64) -> anyhow::Result<Vec<LogEntry>> {
+        let url = format!(
+            "{}/replication/log?after={}&limit={}",
+            self.primary, after, MAX_LOG_ENTRIES
+        );
+        let response = self.get(url).await?;
+        let entries = tokio::task::spawn_blocking(move || response.into_json()).await??;
+        Ok(entries)
+    }
+
+    async fn get(&self, url: String) -> anyhow::Result<ureq::Response> {
+        let api_key = self.api_key.clone();
+        let response = tokio::task::spawn_blocking(move || {
+            let mut request = ureq::get(&url);
+            if let Some(key) = api_key {
+                request = request.set("X-Meili-API-Key", &key);
+            }
+            request.call()
+        })
+        .await??;
+        Ok(response)
+    }
+}
+
+/// Starts replicating the primary at `primary` into the indexes of `controller`.
+pub fn spawn_replica(
+    controller: IndexController,
+    db_path: &Path,
+    primary: &str,
+    api_key: Option<String>,
+    poll_interval: Duration,
+) {
+    let replica = Replica {
+        controller,
+        primary: primary.trim_end_matches('/').to_string(),
+        api_key,
+        last_applied_path: db_path.join(LAST_APPLIED_FILE),
+        poll_interval,
+    };
+    tokio::task::spawn_local(replica.run());
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+use crate::index::{SearchQuery, SearchResult};
+
+/// A LRU cache of the search results, keyed by the index and the normalized parameters of the
+/// search. The results of an index are invalidated each time an update is applied to it.
+pub struct SearchCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// The results, from the least to the most recently used.
+    results: IndexMap<(Uuid, String), SearchResult>,
+    /// Incremented each time the results of an index are invalidated, so that a search that
+    /// started before an update is applied doesn't cache its now outdated results.
+    generations: HashMap<Uuid, u64>,
+}
+
+impl SearchCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::default(),
+        }
+    }
+
+    /// Returns the key of the query in the cache. The default values of the omitted parameters
+    /// are part of the key, so that two queries returning the same results share their key.
+    pub fn key(query: &SearchQuery) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(query)?)
+    }
+
+    /// Returns the generation of the results of the index, to give to `insert`.
+    pub fn generation(&self, uuid: Uuid) -> u64 {
+        let inner = self.inner.lock();
+        inner.generations.get(&uuid).copied().unwrap_or_default()
+    }
+
+    pub fn get(&self, uuid: Uuid, key: String) -> Option<SearchResult> {
+        let mut inner = self.inner.lock();
+        let key = (uuid, key);
+        // The result is moved to the end, as the most recently used.
+        let result = inner.results.shift_remove(&key)?;
+        inner.results.insert(key, result.clone());
+        Some(result)
+    }
+
+    /// Caches the result of a search, unless the results of the index were invalidated since
+    /// the `generation` was read, before the search.
+    pub fn insert(&self, uuid: Uuid, key: String, generation: u64, result: SearchResult) {
+        let mut inner = self.inner.lock();
+        if inner.generations.get(&uuid).copied().unwrap_or_default() != generation {
+            return;
+        }
+        let key = (uuid, key);
+        inner.results.shift_remove(&key);
+        inner.results.insert(key, result);
+        while inner.results.len() > self.capacity {
+            inner.results.shift_remove_index(0);
+        }
+    }
+
+    pub fn invalidate(&self, uuid: Uuid) {
+        let mut inner = self.inner.lock();
+        *inner.generations.entry(uuid).or_default() += 1;
+        inner.results.retain(|(index, _), _| *index != uuid);
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use meilisearch_error::{Code, ErrorCode};
+use thiserror::Error;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+#[derive(Error, Debug)]
+pub enum SearchLimitError {
+    #[error("Too many search requests: {max_concurrent} searches are running and {max_waiting} are waiting, please retry.")]
+    TooManySearchRequests {
+        max_concurrent: usize,
+        max_waiting: usize,
+    },
+}
+
+impl ErrorCode for SearchLimitError {
+    fn error_code(&self) -> Code {
+        match self {
+            SearchLimitError::TooManySearchRequests { .. } => Code::TooManySearchRequests,
+        }
+    }
+}
+
+/// Limits the number of searches executed at the same time, so that the searches don't take all
+/// the blocking threads shared with the indexation. The searches over the limit wait for their
+/// turn, and are refused right away once `max_waiting` searches are already waiting.
+pub struct SearchLimiter {
+    permits: Semaphore,
+    max_concurrent: usize,
+    waiting: AtomicUsize,
+    max_waiting: usize,
+}
+
+/// Decrements the number of waiting searches when dropped, even if the waiting search is
+/// cancelled.
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl SearchLimiter {
+    pub fn new(max_concurrent: usize, max_waiting: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            permits: Semaphore::new(max_concurrent),
+            max_concurrent,
+            waiting: AtomicUsize::new(0),
+            max_waiting,
+        }
+    }
+
+    /// Waits until a search can be executed. The search must be executed while the returned
+    /// permit is held.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, SearchLimitError> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Ok(permit);
+        }
+
+        let guard = WaitingGuard(&self.waiting);
+        if self.waiting.fetch_add(1, Ordering::SeqCst) >= self.max_waiting {
+            return Err(SearchLimitError::TooManySearchRequests {
+                max_concurrent: self.max_concurrent,
+                max_waiting: self.max_waiting,
+            });
+        }
+
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("the search semaphore is never closed");
+        drop(guard);
+        Ok(permit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn refuses_searches_when_the_queue_is_full() {
+        let limiter = SearchLimiter::new(1, 0);
+        let permit = limiter.acquire().await.unwrap();
+        assert!(matches!(
+            limiter.acquire().await,
+            Err(SearchLimitError::TooManySearchRequests { .. })
+        ));
+        drop(permit);
+        assert!(limiter.acquire().await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn queued_searches_wait_for_a_permit() {
+        let limiter = SearchLimiter::new(1, 1);
+        let permit = limiter.acquire().await.unwrap();
+
+        let waiting = limiter.acquire();
+        tokio::pin!(waiting);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), &mut waiting)
+                .await
+                .is_err()
+        );
+        // The timed out search is still waiting, the queue is full.
+        assert!(limiter.acquire().await.is_err());
+
+        drop(permit);
+        assert!(waiting.await.is_ok());
+        assert_eq!(limiter.waiting.load(Ordering::SeqCst), 0);
+    }
+
+    #[actix_rt::test]
+    async fn cancelled_searches_leave_the_queue() {
+        let limiter = SearchLimiter::new(1, 1);
+        let _permit = limiter.acquire().await.unwrap();
+
+        let waiting = tokio::time::timeout(Duration::from_millis(10), limiter.acquire()).await;
+        assert!(waiting.is_err());
+        assert_eq!(limiter.waiting.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,372 @@
+//! The incremental snapshots. The files of a snapshot are split in blocks, whose hashes are
+//! recorded in the manifest of the snapshot. A full snapshot contains all the files, and each
+//! increment following it only contains the blocks that changed since the previous snapshot of
+//! the chain, along with its manifest.
+
+use std::collections::BTreeMap;
+use std::fs::{self, create_dir_all, read_dir, remove_file, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+use super::write_archive;
+use crate::helpers::compression::{self, ArchiveCompression};
+
+/// The manifest of a full or incremental snapshot, at the root of its archive.
+const MANIFEST_FILE: &str = "snapshot-manifest.json";
+/// The directory of an increment containing its blocks, named after their hash.
+const BLOCKS_DIR: &str = "blocks";
+/// The size of the blocks the files are split in, a multiple of the size of the LMDB pages.
+const BLOCK_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    /// The id of the full snapshot of the chain.
+    base: Uuid,
+    id: Uuid,
+    /// The id of the previous snapshot of the chain, `None` for the full snapshot.
+    parent: Option<Uuid>,
+    block_size: u64,
+    /// The files of the snapshot, by path relative to the root of the database.
+    files: BTreeMap<PathBuf, FileManifest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileManifest {
+    len: u64,
+    /// The hex encoded SHA-256 digests of the blocks of the file.
+    blocks: Vec<String>,
+}
+
+impl Manifest {
+    /// Hashes the files of `dir`, as a snapshot following `parent`, or as the full snapshot of a
+    /// new chain.
+    fn new(dir: &Path, parent: Option<&Manifest>) -> anyhow::Result<Self> {
+        let mut files = BTreeMap::new();
+        for path in list_files(dir, Path::new(""))? {
+            // The manifest of the full snapshot is written in the directory of the database.
+            if path == Path::new(MANIFEST_FILE) {
+                continue;
+            }
+            let file = hash_blocks(&dir.join(&path))?;
+            files.insert(path, file);
+        }
+
+        let id = Uuid::new_v4();
+        Ok(Self {
+            base: parent.map_or(id, |parent| parent.base),
+            id,
+            parent: parent.map(|parent| parent.id),
+            block_size: BLOCK_SIZE,
+            files,
+        })
+    }
+
+    fn read(dir: &Path) -> anyhow::Result<Self> {
+        let file = File::open(dir.join(MANIFEST_FILE))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    fn write(&self, dir: &Path) -> anyhow::Result<()> {
+        let file = File::create(dir.join(MANIFEST_FILE))?;
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+/// The last snapshot written, which the next increment is chained to.
+pub struct Chain {
+    manifest: Manifest,
+    /// The number of increments following the full snapshot.
+    increments: u32,
+}
+
+/// Archives the database copied in `dir` as the next increment of `chain`, or as a full snapshot
+/// at `snapshot_path` when there is no chain or when it already has `max_increments` increments.
+/// Returns the chain the next snapshot follows, and the path of the written archive.
+pub fn write_snapshot(
+    dir: &Path,
+    snapshot_path: &Path,
+    chain: Option<Chain>,
+    max_increments: u32,
+    compression: ArchiveCompression,
+) -> anyhow::Result<(Chain, PathBuf)> {
+    let chain = match chain.filter(|chain| chain.increments < max_increments) {
+        Some(chain) => chain,
+        None => {
+            let manifest = Manifest::new(dir, None)?;
+            manifest.write(dir)?;
+            write_archive(dir, snapshot_path, compression)?;
+            // The increments of the previous chain are now useless.
+            for increment in increment_paths(snapshot_path) {
+                remove_file(increment)?;
+            }
+            let chain = Chain {
+                manifest,
+                increments: 0,
+            };
+            return Ok((chain, snapshot_path.to_owned()));
+        }
+    };
+
+    let manifest = Manifest::new(dir, Some(&chain.manifest))?;
+    let increment_dir = TempDir::new()?;
+    let blocks_dir = increment_dir.path().join(BLOCKS_DIR);
+    create_dir_all(&blocks_dir)?;
+    for (path, file) in &manifest.files {
+        let changed = changed_blocks(file, chain.manifest.files.get(path));
+        if changed.is_empty() {
+            continue;
+        }
+        let mut src = File::open(dir.join(path))?;
+        for (index, hash) in changed {
+            let block_path = blocks_dir.join(hash);
+            if !block_path.exists() {
+                src.seek(SeekFrom::Start(index * BLOCK_SIZE))?;
+                io::copy(
+                    &mut (&mut src).take(BLOCK_SIZE),
+                    &mut File::create(block_path)?,
+                )?;
+            }
+        }
+    }
+    manifest.write(increment_dir.path())?;
+
+    let increments = chain.increments + 1;
+    let increment_path = increment_path(snapshot_path, increments);
+    write_archive(increment_dir.path(), &increment_path, compression)?;
+    Ok((
+        Chain {
+            manifest,
+            increments,
+        },
+        increment_path,
+    ))
+}
+
+/// Applies the increments following the full snapshot at `snapshot_path`, already extracted at
+/// `db_path`. The increments are applied in order, up to the first missing one or the first one
+/// that doesn't follow the previous snapshot, e.g. an increment left by a previous chain.
+pub fn apply_increments(db_path: &Path, snapshot_path: &Path) -> anyhow::Result<()> {
+    // Only the snapshots created with increments enabled have a manifest.
+    if !db_path.join(MANIFEST_FILE).exists() {
+        return Ok(());
+    }
+    let mut manifest = Manifest::read(db_path)?;
+    remove_file(db_path.join(MANIFEST_FILE))?;
+
+    for path in increment_paths(snapshot_path) {
+        let increment_dir = TempDir::new()?;
+        compression::from_tar(&path, increment_dir.path())?;
+        let increment = Manifest::read(increment_dir.path())?;
+        if increment.base != manifest.base || increment.parent != Some(manifest.id) {
+            warn!(
+                "The snapshot increment {} doesn't follow the previous snapshot, it is ignored with the next ones.",
+                path.display()
+            );
+            break;
+        }
+
+        apply_increment(db_path, increment_dir.path(), &manifest, &increment)
+            .with_context(|| format!("Can't apply the snapshot increment {}", path.display()))?;
+        info!("Snapshot increment {} applied.", path.display());
+        manifest = increment;
+    }
+
+    Ok(())
+}
+
+fn apply_increment(
+    db_path: &Path,
+    increment_dir: &Path,
+    parent: &Manifest,
+    increment: &Manifest,
+) -> anyhow::Result<()> {
+    if increment.block_size != parent.block_size {
+        bail!(
+            "The blocks of the increment are of {} bytes instead of {}",
+            increment.block_size,
+            parent.block_size
+        );
+    }
+
+    for path in parent.files.keys() {
+        if !increment.files.contains_key(path) {
+            remove_file(db_path.join(path))?;
+            // The directory of a deleted index is removed once empty.
+            if let Some(dir) = path.parent() {
+                let _ = fs::remove_dir(db_path.join(dir));
+            }
+        }
+    }
+
+    let blocks_dir = increment_dir.join(BLOCKS_DIR);
+    for (path, file) in &increment.files {
+        let dest_path = db_path.join(path);
+        if let Some(dir) = dest_path.parent() {
+            create_dir_all(dir)?;
+        }
+        let mut dest = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&dest_path)?;
+        dest.set_len(file.len)?;
+        for (index, hash) in changed_blocks(file, parent.files.get(path)) {
+            dest.seek(SeekFrom::Start(index * increment.block_size))?;
+            io::copy(&mut File::open(blocks_dir.join(hash))?, &mut dest)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The indexes and hashes of the blocks of `file` that differ from the ones of `previous`.
+fn changed_blocks<'a>(
+    file: &'a FileManifest,
+    previous: Option<&FileManifest>,
+) -> Vec<(u64, &'a str)> {
+    let previous = previous.map_or(&[][..], |previous| previous.blocks.as_slice());
+    file.blocks
+        .iter()
+        .enumerate()
+        .filter(|(index, hash)| previous.get(*index) != Some(hash))
+        .map(|(index, hash)| (index as u64, hash.as_str()))
+        .collect()
+}
+
+fn hash_blocks(path: &Path) -> io::Result<FileManifest> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut blocks = Vec::new();
+    let mut buffer = Vec::with_capacity(BLOCK_SIZE as usize);
+    loop {
+        buffer.clear();
+        (&mut file).take(BLOCK_SIZE).read_to_end(&mut buffer)?;
+        if buffer.is_empty() {
+            break;
+        }
+        blocks.push(format!("{:x}", Sha256::digest(&buffer)));
+    }
+
+    Ok(FileManifest { len, blocks })
+}
+
+/// The paths of the files in `dir`, relative to `dir`, prefixed by `prefix`.
+fn list_files(dir: &Path, prefix: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = prefix.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            files.extend(list_files(&entry.path(), &path)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// The path of the increment number `n` of the snapshot at `snapshot_path`.
+fn increment_path(snapshot_path: &Path, n: u32) -> PathBuf {
+    let mut path = snapshot_path.as_os_str().to_owned();
+    path.push(format!(".{}", n));
+    PathBuf::from(path)
+}
+
+/// The paths of the increments of the snapshot at `snapshot_path`, up to the first missing one.
+fn increment_paths(snapshot_path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    (1..)
+        .map(move |n| increment_path(snapshot_path, n))
+        .take_while(|path| path.exists())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_files(dir: &Path, files: &[(&str, &[u8])]) {
+        for (path, content) in files {
+            let path = dir.join(path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    #[test]
+    fn restore_increments() {
+        let dir = TempDir::new().unwrap();
+        let snapshot_path = dir.path().join("snapshots/data.ms.snapshot");
+        let block = vec![1; BLOCK_SIZE as usize];
+        let compression = ArchiveCompression::default();
+
+        let db = TempDir::new().unwrap();
+        let big_file = [&block[..], &block[..], b"end"].concat();
+        write_files(
+            db.path(),
+            &[("a/big", &big_file), ("b/deleted", b"deleted")],
+        );
+        let (chain, path) =
+            write_snapshot(db.path(), &snapshot_path, None, 2, compression).unwrap();
+        assert_eq!(path, snapshot_path);
+
+        // Only the last block of the big file is changed.
+        let db = TempDir::new().unwrap();
+        let big_file = [&block[..], &block[..], b"new end"].concat();
+        write_files(db.path(), &[("a/big", &big_file), ("c/new", b"new")]);
+        let (chain, path) =
+            write_snapshot(db.path(), &snapshot_path, Some(chain), 2, compression).unwrap();
+        assert_eq!(path, increment_path(&snapshot_path, 1));
+
+        let restored = dir.path().join("restored");
+        compression::from_tar(&snapshot_path, &restored).unwrap();
+        apply_increments(&restored, &snapshot_path).unwrap();
+        assert_eq!(fs::read(restored.join("a/big")).unwrap(), big_file);
+        assert_eq!(fs::read(restored.join("c/new")).unwrap(), b"new");
+        assert!(!restored.join("b").exists());
+        assert!(!restored.join(MANIFEST_FILE).exists());
+
+        let increment = TempDir::new().unwrap();
+        compression::from_tar(&path, increment.path()).unwrap();
+        let blocks = read_dir(increment.path().join(BLOCKS_DIR)).unwrap().count();
+        assert_eq!(blocks, 2);
+
+        // A new full snapshot is written after `max_increments` increments, and the previous
+        // increments are removed.
+        let (chain, _) =
+            write_snapshot(db.path(), &snapshot_path, Some(chain), 1, compression).unwrap();
+        assert_eq!(chain.increments, 0);
+        assert!(!increment_path(&snapshot_path, 1).exists());
+    }
+
+    #[test]
+    fn ignore_increments_of_another_chain() {
+        let dir = TempDir::new().unwrap();
+        let snapshot_path = dir.path().join("data.ms.snapshot");
+        let other_path = dir.path().join("other.snapshot");
+        let compression = ArchiveCompression::default();
+
+        let db = TempDir::new().unwrap();
+        write_files(db.path(), &[("file", b"first")]);
+        write_snapshot(db.path(), &snapshot_path, None, 1, compression).unwrap();
+        let (chain, _) = write_snapshot(db.path(), &other_path, None, 1, compression).unwrap();
+
+        let db = TempDir::new().unwrap();
+        write_files(db.path(), &[("file", b"second")]);
+        let (_, path) =
+            write_snapshot(db.path(), &other_path, Some(chain), 1, compression).unwrap();
+        fs::rename(path, increment_path(&snapshot_path, 1)).unwrap();
+
+        let restored = dir.path().join("restored");
+        compression::from_tar(&snapshot_path, &restored).unwrap();
+        apply_increments(&restored, &snapshot_path).unwrap();
+        assert_eq!(fs::read(restored.join("file")).unwrap(), b"first");
+    }
+}
@@ -0,0 +1,218 @@
+mod incremental;
+
+use std::collections::HashSet;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::bail;
+use log::{error, info};
+use serde_json::{json, Value};
+use tempfile::{NamedTempFile, TempDir};
+
+use super::{IndexController, TaskType};
+use crate::helpers::compression::{self, ArchiveCompression};
+pub use incremental::Chain;
+
+impl IndexController {
+    /// Creates a snapshot of the database: an archive of the uuid store, the update stores, the
+    /// task store and the indexes, compressed as set by the options and written at `snapshot_path`. When the
+    /// snapshots are incremental, the snapshot is written as the next increment of the last
+    /// snapshot, at `{snapshot_path}.{n}`, unless a full snapshot is due. Returns the path of the
+    /// written archive.
+    ///
+    /// The stores are copied one after the other, so the updates are paused and no index is
+    /// created or deleted until all of them are copied: the uuid store only references copied
+    /// indexes, and the indexes are in the state described by their update stores. The updates
+    /// can still be registered during the snapshot, they are processed once it is done.
+    ///
+    /// The snapshot is recorded as a task once it is done, so that the snapshot doesn't contain
+    /// its own unfinished task.
+    pub async fn create_snapshot(&self, snapshot_path: &Path) -> anyhow::Result<PathBuf> {
+        let task_store = self.task_store.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            task_store.reserve(None, TaskType::SnapshotCreation, Value::Null)
+        })
+        .await??;
+
+        let result = self.perform_snapshot(snapshot_path).await;
+        let task_result = match result {
+            Ok(ref path) => Ok(json!({ "path": path })),
+            Err(ref e) => Err(e.to_string()),
+        };
+        if let Err(e) = self.finish_task(task, task_result).await {
+            error!("Can't record the snapshot task: {}", e);
+        }
+        result
+    }
+
+    async fn perform_snapshot(&self, snapshot_path: &Path) -> anyhow::Result<PathBuf> {
+        let temp_dir = TempDir::new()?;
+
+        {
+            // The updates are paused first, as waiting for the update being processed to finish
+            // must not prevent the indexes from being created or deleted.
+            let _paused = self.update_handle.pause().await;
+            let _lifecycle = self.snapshot_lock.write().await;
+
+            let path = temp_dir.path().to_owned();
+            let uuids: HashSet<_> = self
+                .uuid_resolver
+                .snapshot(path.clone())
+                .await?
+                .into_iter()
+                .collect();
+            self.update_handle
+                .snapshot(uuids.clone(), path.clone())
+                .await?;
+            // The task store is copied after the update stores, so that the task uids of all the
+            // copied updates are reserved in the copy.
+            let task_store = self.task_store.clone();
+            let tasks_path = path.clone();
+            tokio::task::spawn_blocking(move || task_store.snapshot(&tasks_path)).await??;
+            // The indexes are copied without compaction when the snapshots are incremental, so
+            // that their unchanged pages keep their place in the copies.
+            let compact = self.snapshot_increments == 0;
+            self.index_handle.snapshot(uuids, path, compact).await?;
+        }
+
+        let snapshot_path = snapshot_path.to_owned();
+        let archive_compression = self.archive_compression;
+        let max_increments = self.snapshot_increments;
+        // A snapshot failing in the middle of the chain is followed by a full snapshot.
+        let chain = self.snapshot_chain.lock().take();
+        let (chain, path) = tokio::task::spawn_blocking(move || {
+            if max_increments == 0 {
+                write_archive(temp_dir.path(), &snapshot_path, archive_compression)?;
+                Ok((None, snapshot_path))
+            } else {
+                incremental::write_snapshot(
+                    temp_dir.path(),
+                    &snapshot_path,
+                    chain,
+                    max_increments,
+                    archive_compression,
+                )
+                .map(|(chain, path)| (Some(chain), path))
+            }
+        })
+        .await??;
+        *self.snapshot_chain.lock() = chain;
+
+        Ok(path)
+    }
+}
+
+/// Archives the content of `src` at `dest`. The archive is written next to its destination and
+/// then moved, so that the previous archive is replaced only once the new one is complete.
+fn write_archive(src: &Path, dest: &Path, compression: ArchiveCompression) -> anyhow::Result<()> {
+    let dir = dest.parent().unwrap_or_else(|| Path::new("."));
+    create_dir_all(dir)?;
+    let temp_archive = NamedTempFile::new_in(dir)?;
+    compression::to_tar(src, temp_archive.path(), compression)?;
+    temp_archive.persist(dest)?;
+    Ok(())
+}
+
+/// Extracts the snapshot at `snapshot_path` as the database at `db_path`, which must not exist,
+/// and applies the increments following it.
+pub fn load_snapshot(
+    db_path: &Path,
+    snapshot_path: &Path,
+    ignore_snapshot_if_db_exists: bool,
+    ignore_missing_snapshot: bool,
+) -> anyhow::Result<()> {
+    if !db_path.exists() && snapshot_path.exists() {
+        compression::from_tar(snapshot_path, db_path)?;
+        incremental::apply_increments(db_path, snapshot_path)?;
+        info!("Snapshot {} imported.", snapshot_path.display());
+        Ok(())
+    } else if db_path.exists() && !ignore_snapshot_if_db_exists {
+        bail!(
+            "database already exists at {:?}, try to delete it or rename it",
+            db_path.canonicalize().unwrap_or_else(|_| db_path.into())
+        )
+    } else if !snapshot_path.exists() && !ignore_missing_snapshot {
+        bail!(
+            "snapshot doesn't exist at {:?}",
+            snapshot_path
+                .canonicalize()
+                .unwrap_or_else(|_| snapshot_path.into())
+        )
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the path of the snapshots of the database at `db_path`, in `snapshot_dir`.
+pub fn snapshot_path(snapshot_dir: &Path, db_path: &Path) -> PathBuf {
+    let db_name = db_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("data.ms");
+    snapshot_dir.join(format!("{}.snapshot", db_name))
+}
+
+/// Creates a snapshot at `snapshot_path` every `interval`, replacing the previous one.
+pub fn spawn_snapshots(controller: IndexController, snapshot_path: PathBuf, interval: Duration) {
+    tokio::task::spawn_local(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match controller.create_snapshot(&snapshot_path).await {
+                Ok(path) => info!("Snapshot {} created.", path.display()),
+                Err(e) => error!("Unsuccessful snapshot creation: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let tempdir = TempDir::new().unwrap();
+
+        let test_dir = tempdir.path();
+        let src_dir = test_dir.join("src");
+        let dest_dir = test_dir.join("complex/destination/path/");
+        let archive_path = test_dir.join("archive.snapshot");
+
+        create_dir_all(src_dir.join("subdir")).unwrap();
+        fs::File::create(src_dir.join("file1.txt"))
+            .unwrap()
+            .write_all(b"Hello_file_1")
+            .unwrap();
+        fs::File::create(src_dir.join("subdir/file2.txt"))
+            .unwrap()
+            .write_all(b"Hello_file_2")
+            .unwrap();
+
+        compression::to_tar(&src_dir, &archive_path, Default::default()).unwrap();
+        load_snapshot(&dest_dir, &archive_path, false, false).unwrap();
+
+        let contents = fs::read_to_string(dest_dir.join("file1.txt")).unwrap();
+        assert_eq!(contents, "Hello_file_1");
+        let contents = fs::read_to_string(dest_dir.join("subdir/file2.txt")).unwrap();
+        assert_eq!(contents, "Hello_file_2");
+
+        // The database now exists.
+        assert!(load_snapshot(&dest_dir, &archive_path, false, false).is_err());
+        assert!(load_snapshot(&dest_dir, &archive_path, true, false).is_ok());
+        let missing = test_dir.join("missing.snapshot");
+        assert!(load_snapshot(&test_dir.join("other"), &missing, false, false).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_path() {
+        assert_eq!(
+            snapshot_path(Path::new("snapshots"), Path::new("./data.ms")),
+            PathBuf::from("snapshots/data.ms.snapshot")
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Restarts the actors when they panic, so that a single failing message doesn't take down the
+//! whole actor, and every request after it.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::sleep;
+
+/// Delay before an actor that panicked is restarted.
+const RESTART_DELAY: Duration = Duration::from_millis(100);
+
+/// The inbox of an actor. It is shared between the successive instances of the actor, so that
+/// the messages sent to a crashed actor are processed by the restarted one.
+pub type Inbox<T> = Arc<Mutex<mpsc::Receiver<T>>>;
+
+pub fn inbox<T>(receiver: mpsc::Receiver<T>) -> Inbox<T> {
+    Arc::new(Mutex::new(receiver))
+}
+
+/// Receives the next message of the `inbox`, returns `None` once all the senders are dropped.
+pub async fn recv<T>(inbox: &Inbox<T>) -> Option<T> {
+    inbox.lock().await.recv().await
+}
+
+/// Runs the actor returned by `make_actor`, and restarts a new one each time it panics. The
+/// supervisor stops when the actor returns, or when a new actor can't be built. Only the errors
+/// building the first actor are returned.
+pub fn supervise<F, A>(name: &'static str, mut make_actor: F) -> anyhow::Result<()>
+where
+    F: FnMut() -> anyhow::Result<A> + Send + 'static,
+    A: Future<Output = ()> + Send + 'static,
+{
+    let mut actor = make_actor()?;
+
+    tokio::task::spawn(async move {
+        loop {
+            match tokio::task::spawn(actor).await {
+                Err(e) if e.is_panic() => {
+                    error!("The {} panicked, restarting it.", name);
+                    sleep(RESTART_DELAY).await;
+                }
+                _ => {
+                    info!("The {} exited.", name);
+                    break;
+                }
+            }
+
+            actor = match make_actor() {
+                Ok(actor) => actor,
+                Err(e) => {
+                    error!("Could not restart the {}: {}", name, e);
+                    break;
+                }
+            };
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn restart_panicking_actor() {
+        let (sender, receiver) = mpsc::channel::<(bool, oneshot::Sender<()>)>(10);
+        let inbox = inbox(receiver);
+        let starts = Arc::new(AtomicUsize::new(0));
+        let actor_starts = starts.clone();
+        supervise("test actor", move || {
+            actor_starts.fetch_add(1, Ordering::SeqCst);
+            let inbox = inbox.clone();
+            Ok(async move {
+                while let Some((panics, ret)) = recv(&inbox).await {
+                    if panics {
+                        panic!("the message asked the actor to panic");
+                    }
+                    let _ = ret.send(());
+                }
+            })
+        })
+        .unwrap();
+
+        let (ret, answer) = oneshot::channel();
+        sender.send((true, ret)).await.unwrap();
+        // The message is lost with the actor that panicked.
+        assert!(answer.await.is_err());
+
+        // The messages sent after the panic are processed by the restarted actor.
+        let (ret, answer) = oneshot::channel();
+        sender.send((false, ret)).await.unwrap();
+        answer.await.unwrap();
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,599 @@
+//! The tasks: a single view of the updates of the indexes and of the operations that aren't
+//! processed as updates, i.e. the creation and deletion of the indexes, the dumps and the
+//! snapshots.
+//!
+//! The updates are the source of truth of their tasks, a task uid is reserved for each update
+//! when it is registered, and stored in the update. The other tasks are stored in the task
+//! store.
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use actix_web::web::Bytes;
+use chrono::{DateTime, Utc};
+use heed::types::{OwnedType, SerdeJson, Str};
+use heed::{CompactionOption, Database, Env, EnvOpenOptions};
+use meilisearch_error::{Code, ErrorCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::replication::ReplicatedOp;
+use super::{IndexController, UpdateError, UpdateMeta, UpdateStatus};
+
+type BEU64 = heed::zerocopy::U64<heed::byteorder::BE>;
+
+const NEXT_UID_KEY: &str = "next-uid";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskType {
+    IndexCreation,
+    IndexDeletion,
+    DocumentsAddition,
+    DocumentsDeletion,
+    ClearDocuments,
+    SettingsUpdate,
+    FacetsUpdate,
+    IndexClone,
+    Reindex,
+    DumpCreation,
+    SnapshotCreation,
+}
+
+impl TaskType {
+    /// Whether the tasks of this type are updates of an index.
+    fn is_update(&self) -> bool {
+        !matches!(
+            self,
+            TaskType::IndexCreation
+                | TaskType::IndexDeletion
+                | TaskType::DumpCreation
+                | TaskType::SnapshotCreation
+        )
+    }
+}
+
+impl From<&UpdateMeta> for TaskType {
+    fn from(meta: &UpdateMeta) -> Self {
+        match meta {
+            UpdateMeta::DocumentsAddition { .. } => TaskType::DocumentsAddition,
+            UpdateMeta::ClearDocuments => TaskType::ClearDocuments,
+            UpdateMeta::DeleteDocuments => TaskType::DocumentsDeletion,
+            UpdateMeta::Settings(_) => TaskType::SettingsUpdate,
+            UpdateMeta::Facets(_) => TaskType::FacetsUpdate,
+            UpdateMeta::Clone { .. } => TaskType::IndexClone,
+            UpdateMeta::Reindex { .. } => TaskType::Reindex,
+        }
+    }
+}
+
+impl FromStr for TaskType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(Value::String(s.to_string()))
+            .map_err(|_| format!("Invalid task type `{}`.", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskStatus {
+    Enqueued,
+    Processing,
+    Succeeded,
+    Failed,
+    Canceled,
+}
+
+impl TaskStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Succeeded | TaskStatus::Failed | TaskStatus::Canceled
+        )
+    }
+}
+
+impl FromStr for TaskStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(Value::String(s.to_string()))
+            .map_err(|_| format!("Invalid task status `{}`.", s))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Task {
+    pub uid: u64,
+    pub index_uid: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: TaskType,
+    pub status: TaskStatus,
+    /// Depends on the type of the task, e.g. the id of the update of the index or the uid of the
+    /// dump.
+    pub details: Value,
+    pub error: Option<String>,
+    pub enqueued_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl Task {
+    /// The task of an update of the index `index_uid`, `None` if the update was registered before
+    /// the tasks were introduced.
+    pub fn from_update(index_uid: &str, update: &UpdateStatus) -> Option<Self> {
+        let (pending, started_at) = match update {
+            UpdateStatus::Pending(pending) => (pending, None),
+            UpdateStatus::Processing(processing) => {
+                (&processing.from, Some(processing.started_processing_at))
+            }
+            UpdateStatus::Processed(processed) => (
+                &processed.from.from,
+                Some(processed.from.started_processing_at),
+            ),
+            UpdateStatus::Failed(failed) => (
+                &failed.processing().from,
+                Some(failed.processing().started_processing_at),
+            ),
+            UpdateStatus::Aborted(aborted) => (aborted.pending(), None),
+        };
+
+        let mut details = json!({ "updateId": pending.update_id });
+        let (status, finished_at, error) = match update {
+            UpdateStatus::Pending(_) => (TaskStatus::Enqueued, None, None),
+            UpdateStatus::Processing(_) => (TaskStatus::Processing, None, None),
+            UpdateStatus::Processed(processed) => {
+                details["result"] = serde_json::to_value(&processed.success).ok()?;
+                (TaskStatus::Succeeded, Some(processed.processed_at), None)
+            }
+            UpdateStatus::Failed(failed) => (
+                TaskStatus::Failed,
+                Some(failed.failed_at()),
+                Some(failed.error().clone()),
+            ),
+            UpdateStatus::Aborted(aborted) => {
+                (TaskStatus::Canceled, Some(aborted.aborted_at()), None)
+            }
+        };
+
+        Some(Self {
+            uid: pending.task_uid?,
+            index_uid: Some(index_uid.to_string()),
+            kind: TaskType::from(&pending.meta),
+            status,
+            details,
+            error,
+            enqueued_at: pending.enqueued_at,
+            started_at,
+            finished_at,
+        })
+    }
+
+    fn finish(&mut self, result: Result<Value, String>) {
+        let now = Utc::now();
+        self.started_at.get_or_insert(now);
+        self.finished_at = Some(now);
+        match result {
+            Ok(details) => {
+                self.status = TaskStatus::Succeeded;
+                self.details = details;
+            }
+            Err(error) => {
+                self.status = TaskStatus::Failed;
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+/// The tasks returned by a listing, or deleted or canceled by a bulk operation, all the tasks
+/// match when a field is `None`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskFilter {
+    pub index_uids: Option<Vec<String>>,
+    pub types: Option<Vec<TaskType>>,
+    pub statuses: Option<Vec<TaskStatus>>,
+    /// Only the tasks enqueued strictly before this date match.
+    pub before_date: Option<DateTime<Utc>>,
+}
+
+impl TaskFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        let index_matches = match (&self.index_uids, &task.index_uid) {
+            (None, _) => true,
+            (Some(uids), Some(uid)) => uids.contains(uid),
+            (Some(_), None) => false,
+        };
+        index_matches
+            && self
+                .types
+                .as_ref()
+                .map_or(true, |types| types.contains(&task.kind))
+            && self
+                .statuses
+                .as_ref()
+                .map_or(true, |statuses| statuses.contains(&task.status))
+            && self
+                .before_date
+                .map_or(true, |date| task.enqueued_at < date)
+    }
+
+    fn matches_updates_of(&self, index_uid: &str) -> bool {
+        self.index_uids
+            .as_ref()
+            .map_or(true, |uids| uids.iter().any(|uid| uid == index_uid))
+            && self
+                .types
+                .as_ref()
+                .map_or(true, |types| types.iter().any(TaskType::is_update))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TaskError {
+    #[error("Task {0} not found")]
+    TaskNotFound(u64),
+    #[error("The tasks of a replication primary can't be canceled, as the cancellations are not replicated.")]
+    CancelOnPrimary,
+}
+
+impl ErrorCode for TaskError {
+    fn error_code(&self) -> Code {
+        match self {
+            TaskError::TaskNotFound(_) => Code::TaskNotFound,
+            TaskError::CancelOnPrimary => Code::BadRequest,
+        }
+    }
+}
+
+/// Reserves the uids of all the tasks, and stores the tasks that aren't updates.
+pub struct TaskStore {
+    env: Env,
+    tasks: Database<OwnedType<BEU64>, SerdeJson<Task>>,
+    metadata: Database<Str, OwnedType<BEU64>>,
+}
+
+impl TaskStore {
+    /// Opens the task store at `path`. The tasks left unfinished by the previous run are failed,
+    /// as they were interrupted.
+    pub fn open(path: impl AsRef<Path>, map_size: usize) -> heed::Result<Self> {
+        let path = path.as_ref();
+        fs::create_dir_all(path)?;
+        let mut options = EnvOpenOptions::new();
+        options.map_size(map_size);
+        options.max_dbs(2);
+        let env = options.open(path)?;
+        let tasks = env.create_database(Some("tasks"))?;
+        let metadata = env.create_database(Some("metadata"))?;
+        let store = Self {
+            env,
+            tasks,
+            metadata,
+        };
+
+        let mut txn = store.env.write_txn()?;
+        let mut interrupted = Vec::new();
+        for result in store.tasks.iter(&txn)? {
+            let (_, task) = result?;
+            if matches!(task.status, TaskStatus::Enqueued | TaskStatus::Processing) {
+                interrupted.push(task);
+            }
+        }
+        for mut task in interrupted {
+            task.finish(Err("The task was interrupted by a restart.".to_string()));
+            store.tasks.put(&mut txn, &BEU64::new(task.uid), &task)?;
+        }
+        txn.commit()?;
+
+        Ok(store)
+    }
+
+    /// Reserves the uid of a new task.
+    pub fn next_uid(&self) -> heed::Result<u64> {
+        let mut txn = self.env.write_txn()?;
+        let uid = self
+            .metadata
+            .get(&txn, NEXT_UID_KEY)?
+            .map_or(0, |uid| uid.get());
+        self.metadata
+            .put(&mut txn, NEXT_UID_KEY, &BEU64::new(uid + 1))?;
+        txn.commit()?;
+        Ok(uid)
+    }
+
+    /// Registers a new task of type `kind`, being processed.
+    pub fn start(
+        &self,
+        index_uid: Option<String>,
+        kind: TaskType,
+        details: Value,
+    ) -> heed::Result<Task> {
+        let task = self.reserve(index_uid, kind, details)?;
+        self.put(&task)?;
+        Ok(task)
+    }
+
+    /// Returns a new task of type `kind`, being processed, which is only stored once it is
+    /// finished.
+    pub fn reserve(
+        &self,
+        index_uid: Option<String>,
+        kind: TaskType,
+        details: Value,
+    ) -> heed::Result<Task> {
+        let now = Utc::now();
+        Ok(Task {
+            uid: self.next_uid()?,
+            index_uid,
+            kind,
+            status: TaskStatus::Processing,
+            details,
+            error: None,
+            enqueued_at: now,
+            started_at: Some(now),
+            finished_at: None,
+        })
+    }
+
+    /// Records the end of `task`, replacing its details on success.
+    pub fn finish(&self, mut task: Task, result: Result<Value, String>) -> heed::Result<Task> {
+        task.finish(result);
+        self.put(&task)?;
+        Ok(task)
+    }
+
+    /// Records a task of type `kind`, done as soon as it was registered.
+    pub fn record(
+        &self,
+        index_uid: Option<String>,
+        kind: TaskType,
+        details: Value,
+    ) -> heed::Result<Task> {
+        let task = self.reserve(index_uid, kind, Value::Null)?;
+        self.finish(task, Ok(details))
+    }
+
+    fn put(&self, task: &Task) -> heed::Result<()> {
+        let mut txn = self.env.write_txn()?;
+        self.tasks.put(&mut txn, &BEU64::new(task.uid), task)?;
+        txn.commit()
+    }
+
+    pub fn get(&self, uid: u64) -> heed::Result<Option<Task>> {
+        let txn = self.env.read_txn()?;
+        self.tasks.get(&txn, &BEU64::new(uid))
+    }
+
+    pub fn list(&self) -> heed::Result<Vec<Task>> {
+        let txn = self.env.read_txn()?;
+        self.tasks
+            .iter(&txn)?
+            .map(|result| result.map(|(_, task)| task))
+            .collect()
+    }
+
+    /// Removes the finished tasks matching `filter`, and returns their uids.
+    pub fn delete(&self, filter: &TaskFilter) -> heed::Result<Vec<u64>> {
+        let mut txn = self.env.write_txn()?;
+        let mut deleted = Vec::new();
+        for result in self.tasks.iter(&txn)? {
+            let (_, task) = result?;
+            if task.status.is_finished() && filter.matches(&task) {
+                deleted.push(task.uid);
+            }
+        }
+        for uid in &deleted {
+            self.tasks.delete(&mut txn, &BEU64::new(*uid))?;
+        }
+        txn.commit()?;
+        Ok(deleted)
+    }
+
+    /// Copies the task store in the `tasks` directory of `path`.
+    pub fn snapshot(&self, path: &Path) -> anyhow::Result<()> {
+        let path = path.join("tasks");
+        fs::create_dir_all(&path)?;
+        self.env
+            .copy_to_path(path.join("data.mdb"), CompactionOption::Enabled)?;
+        Ok(())
+    }
+}
+
+impl IndexController {
+    /// Lists the `limit` most recent tasks matching `filter`, whose uid is at most `from`. The
+    /// tasks of the updates of the deleted indexes, and of the updates removed by the retention
+    /// policy, are not listed.
+    pub async fn list_tasks(
+        &self,
+        filter: &TaskFilter,
+        from: Option<u64>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Task>> {
+        let task_store = self.task_store.clone();
+        let mut tasks = tokio::task::spawn_blocking(move || task_store.list()).await??;
+        for (uid, uuid) in self.indexes_with_tasks(filter).await? {
+            let updates = match self.update_handle.get_all_updates_status(uuid).await {
+                Ok(updates) => updates,
+                // The index was deleted in the meantime.
+                Err(UpdateError::UnexistingIndex(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            tasks.extend(
+                updates
+                    .iter()
+                    .filter_map(|update| Task::from_update(&uid, update)),
+            );
+        }
+
+        tasks.retain(|task| filter.matches(task) && from.map_or(true, |from| task.uid <= from));
+        tasks.sort_unstable_by(|a, b| b.uid.cmp(&a.uid));
+        tasks.truncate(limit);
+        Ok(tasks)
+    }
+
+    pub async fn task(&self, uid: u64) -> anyhow::Result<Task> {
+        let task_store = self.task_store.clone();
+        if let Some(task) = tokio::task::spawn_blocking(move || task_store.get(uid)).await?? {
+            return Ok(task);
+        }
+
+        let tasks = self
+            .list_tasks(&TaskFilter::default(), Some(uid), 1)
+            .await?;
+        match tasks.into_iter().next() {
+            Some(task) if task.uid == uid => Ok(task),
+            _ => Err(TaskError::TaskNotFound(uid).into()),
+        }
+    }
+
+    /// Removes the finished tasks matching `filter`, and returns their uids, the most recent
+    /// first. The most recent update of an index is never removed, as the id of the next update
+    /// of the index is generated from it. The deletion is replicated with its filter, the
+    /// replicas remove their own finished tasks matching it.
+    pub async fn delete_tasks(&self, filter: &TaskFilter) -> anyhow::Result<Vec<u64>> {
+        let _guard = self.replication_guard().await;
+        let task_store = self.task_store.clone();
+        let store_filter = filter.clone();
+        let mut deleted =
+            tokio::task::spawn_blocking(move || task_store.delete(&store_filter)).await??;
+        for (uid, uuid) in self.indexes_with_tasks(filter).await? {
+            match self
+                .update_handle
+                .delete_tasks(uuid, uid, filter.clone())
+                .await
+            {
+                Ok(uids) => deleted.extend(uids),
+                // The index was deleted in the meantime.
+                Err(UpdateError::UnexistingIndex(_)) => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let op = ReplicatedOp::DeleteTasks {
+            filter: filter.clone(),
+        };
+        self.record(op, Bytes::new()).await?;
+
+        deleted.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(deleted)
+    }
+
+    /// Cancels the enqueued tasks matching `filter`, and returns their uids, the most recent
+    /// first. Only the updates can be canceled, and only until they start being processed.
+    pub async fn cancel_tasks(&self, filter: &TaskFilter) -> anyhow::Result<Vec<u64>> {
+        if self.replication_log.is_some() {
+            return Err(TaskError::CancelOnPrimary.into());
+        }
+
+        let mut canceled = Vec::new();
+        for (uid, uuid) in self.indexes_with_tasks(filter).await? {
+            match self
+                .update_handle
+                .cancel_tasks(uuid, uid, filter.clone())
+                .await
+            {
+                Ok(uids) => canceled.extend(uids),
+                // The index was deleted in the meantime.
+                Err(UpdateError::UnexistingIndex(_)) => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        canceled.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(canceled)
+    }
+
+    /// The indexes whose updates may have a task matching `filter`.
+    async fn indexes_with_tasks(&self, filter: &TaskFilter) -> anyhow::Result<Vec<(String, Uuid)>> {
+        let mut indexes = self.uuid_resolver.list().await?;
+        indexes.retain(|(uid, _)| filter.matches_updates_of(uid));
+        Ok(indexes)
+    }
+
+    /// Records a task done as soon as it was registered, e.g. the creation of an index.
+    pub(super) async fn record_task(
+        &self,
+        index_uid: Option<String>,
+        kind: TaskType,
+        details: Value,
+    ) -> anyhow::Result<Task> {
+        let task_store = self.task_store.clone();
+        let task = tokio::task::spawn_blocking(move || task_store.record(index_uid, kind, details))
+            .await??;
+        Ok(task)
+    }
+
+    /// Records the end of a task started with `TaskStore::start` or `TaskStore::reserve`.
+    pub(super) async fn finish_task(
+        &self,
+        task: Task,
+        result: Result<Value, String>,
+    ) -> anyhow::Result<Task> {
+        let task_store = self.task_store.clone();
+        let task = tokio::task::spawn_blocking(move || task_store.finish(task, result)).await??;
+        Ok(task)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reserve_and_record_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TaskStore::open(dir.path(), 4096 * 100).unwrap();
+        assert_eq!(store.next_uid().unwrap(), 0);
+
+        let task = store
+            .record(Some("test".to_string()), TaskType::IndexCreation, json!({}))
+            .unwrap();
+        assert_eq!(task.uid, 1);
+        assert_eq!(task.status, TaskStatus::Succeeded);
+        let dump = store
+            .start(None, TaskType::DumpCreation, json!({ "dumpUid": "foo" }))
+            .unwrap();
+        assert_eq!(dump.status, TaskStatus::Processing);
+        drop(store);
+
+        // The unfinished tasks are failed once the store is reopened.
+        let store = TaskStore::open(dir.path(), 4096 * 100).unwrap();
+        let dump = store.get(dump.uid).unwrap().unwrap();
+        assert_eq!(dump.status, TaskStatus::Failed);
+        assert!(dump.finished_at.is_some());
+        assert_eq!(store.list().unwrap().len(), 2);
+        assert_eq!(store.next_uid().unwrap(), 3);
+    }
+
+    #[test]
+    fn filter_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TaskStore::open(dir.path(), 4096 * 100).unwrap();
+        let task = store
+            .record(Some("test".to_string()), TaskType::IndexCreation, json!({}))
+            .unwrap();
+
+        let filter = TaskFilter {
+            index_uids: Some(vec!["test".to_string()]),
+            statuses: Some(vec![TaskStatus::Succeeded]),
+            ..Default::default()
+        };
+        assert!(filter.matches(&task));
+        assert!(!filter.matches_updates_of("other"));
+        let filter = TaskFilter {
+            types: Some(vec![TaskType::DumpCreation]),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&task));
+        assert!(!filter.matches_updates_of("test"));
+        assert_eq!("settingsUpdate".parse(), Ok(TaskType::SettingsUpdate));
+        assert!("foo".parse::<TaskStatus>().is_err());
+    }
+}
@@ -1,24 +1,39 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::io::SeekFrom;
-use std::fs::{create_dir_all, remove_dir_all};
+use std::fs::{create_dir_all, File};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
-use log::info;
+use log::{error, info};
+use meilisearch_error::{Code, ErrorCode};
+use milli::update::UpdateFormat;
 use oxidized_json_checker::JsonChecker;
-use super::index_actor::IndexActorHandle;
+use super::index_actor::{IndexActorHandle, IndexError};
 use thiserror::Error;
 use tokio::fs::OpenOptions;
 use tokio::io::{AsyncWriteExt, AsyncSeekExt};
-use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, OwnedRwLockWriteGuard, RwLock};
 use uuid::Uuid;
 
 use super::get_arc_ownership_blocking;
-use crate::index::UpdateResult;
+use super::supervisor::{inbox, recv, supervise, Inbox};
+use super::tasks::{Task, TaskFilter, TaskStore};
+use super::map_size::{grown_map_size, is_map_full};
+use super::update_store::{HandleUpdate, RetentionPolicy};
+use super::updates::{Failed, Priority, Processed, Processing};
+use crate::helpers::request_id::current_request_id;
+use crate::index::{first_document, infer_primary_key, PrimaryKeyError, UpdateResult};
 use crate::index_controller::{UpdateMeta, UpdateStatus};
 
 pub type Result<T> = std::result::Result<T, UpdateError>;
+
+/// The number of update events kept for the subscribers that are late to receive them, the
+/// subscribers missing more events than that skip the oldest ones.
+const UPDATE_EVENTS_CAPACITY: usize = 1000;
+
 type UpdateStore = super::update_store::UpdateStore<UpdateMeta, UpdateResult, String>;
+type HandleResult = std::result::Result<Processed<UpdateMeta, UpdateResult>, Failed<UpdateMeta, String>>;
 type PayloadData<D> = std::result::Result<D, Box<dyn std::error::Error + Sync + Send + 'static>>;
 
 #[derive(Debug, Error)]
@@ -29,12 +44,43 @@ pub enum UpdateError {
     UnexistingIndex(Uuid),
     #[error("Update {0} doesn't exist.")]
     UnexistingUpdate(u64),
+    #[error("The update stores are closed, MeiliSearch is shutting down.")]
+    Closed,
+    #[error("The update actor is unavailable, please retry.")]
+    Unavailable,
+    #[error("Too many pending updates: {pending} updates are waiting to be processed {scope}, the maximum is {max}.")]
+    TooManyPendingUpdates {
+        pending: usize,
+        max: usize,
+        scope: &'static str,
+    },
+    #[error("Not enough disk space: {available} bytes are available, the updates are refused under {min} bytes.")]
+    NotEnoughDiskSpace { available: u64, min: u64 },
+    #[error("{0}")]
+    PrimaryKey(#[from] PrimaryKeyError),
+}
+
+impl ErrorCode for UpdateError {
+    fn error_code(&self) -> Code {
+        match self {
+            UpdateError::Error(_) => Code::BadRequest,
+            UpdateError::UnexistingIndex(_) => Code::IndexNotFound,
+            UpdateError::UnexistingUpdate(_) => Code::UpdateNotFound,
+            UpdateError::Closed => Code::ShuttingDown,
+            UpdateError::Unavailable => Code::Unavailable,
+            UpdateError::TooManyPendingUpdates { .. } => Code::TooManyPendingUpdates,
+            UpdateError::NotEnoughDiskSpace { .. } => Code::NotEnoughDiskSpace,
+            UpdateError::PrimaryKey(_) => Code::MissingPrimaryKey,
+        }
+    }
 }
 
 enum UpdateMsg<D> {
     Update {
         uuid: Uuid,
         meta: UpdateMeta,
+        priority: Priority,
+        request_id: Option<String>,
         data: mpsc::Receiver<PayloadData<D>>,
         ret: oneshot::Sender<Result<UpdateStatus>>,
     },
@@ -49,25 +95,115 @@ enum UpdateMsg<D> {
     },
     Delete {
         uuid: Uuid,
-        ret: oneshot::Sender<Result<()>>,
+        ret: oneshot::Sender<Result<Vec<PathBuf>>>,
     },
     Create {
         uuid: Uuid,
         ret: oneshot::Sender<Result<()>>,
     },
+    Snapshot {
+        uuids: HashSet<Uuid>,
+        path: PathBuf,
+        ret: oneshot::Sender<Result<()>>,
+    },
+    DeleteTasks {
+        uuid: Uuid,
+        index_uid: String,
+        filter: TaskFilter,
+        ret: oneshot::Sender<Result<Vec<u64>>>,
+    },
+    CancelTasks {
+        uuid: Uuid,
+        index_uid: String,
+        filter: TaskFilter,
+        ret: oneshot::Sender<Result<Vec<u64>>>,
+    },
+    Close {
+        ret: oneshot::Sender<Result<()>>,
+    },
+    Health {
+        ret: oneshot::Sender<()>,
+    },
+}
+
+impl<D> UpdateMsg<D> {
+    /// Answers the message with an error, because the actor is closed.
+    fn refuse(self) {
+        use UpdateMsg::*;
+
+        match self {
+            Update { ret, .. } => {
+                let _ = ret.send(Err(UpdateError::Closed));
+            }
+            ListUpdates { ret, .. } => {
+                let _ = ret.send(Err(UpdateError::Closed));
+            }
+            GetUpdate { ret, .. } => {
+                let _ = ret.send(Err(UpdateError::Closed));
+            }
+            Delete { ret, .. } => {
+                let _ = ret.send(Err(UpdateError::Closed));
+            }
+            Create { ret, .. } => {
+                let _ = ret.send(Err(UpdateError::Closed));
+            }
+            Snapshot { ret, .. } => {
+                let _ = ret.send(Err(UpdateError::Closed));
+            }
+            DeleteTasks { ret, .. } => {
+                let _ = ret.send(Err(UpdateError::Closed));
+            }
+            CancelTasks { ret, .. } => {
+                let _ = ret.send(Err(UpdateError::Closed));
+            }
+            Close { ret } => {
+                let _ = ret.send(Ok(()));
+            }
+            Health { ret } => {
+                let _ = ret.send(());
+            }
+        }
+    }
+}
+
+/// The maximum number of updates that can wait to be processed, and the free disk space, before
+/// new updates are refused.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueueLimits {
+    /// The maximum number of pending updates of a single index.
+    pub per_index: Option<usize>,
+    /// The maximum number of pending updates of all the indexes.
+    pub total: Option<usize>,
+    /// The free disk space, in bytes, under which the updates are refused.
+    pub min_free_disk_space: Option<u64>,
 }
 
 struct UpdateActor<D, S> {
     path: PathBuf,
     store: S,
-    inbox: mpsc::Receiver<UpdateMsg<D>>,
+    index_handle: IndexActorHandle,
+    queue_limits: QueueLimits,
+    /// Reserves the uid of the task of each registered update.
+    task_store: Arc<TaskStore>,
+    inbox: Inbox<UpdateMsg<D>>,
+    /// Once closed, the actor refuses all the messages, so that no update store is reopened.
+    closed: bool,
 }
 
 #[async_trait::async_trait]
 trait UpdateStoreStore {
     async fn get_or_create(&self, uuid: Uuid) -> Result<Arc<UpdateStore>>;
-    async fn delete(&self, uuid: Uuid) -> Result<Option<Arc<UpdateStore>>>;
+    /// Removes the update store, and returns it if it was opened, along with the content files
+    /// of its pending updates. Its data is left on the disk.
+    async fn delete(&self, uuid: Uuid) -> Result<(Option<Arc<UpdateStore>>, Vec<PathBuf>)>;
     async fn get(&self, uuid: Uuid) -> Result<Option<Arc<UpdateStore>>>;
+    /// Removes all the opened update stores.
+    async fn drain(&self) -> Vec<(Uuid, Arc<UpdateStore>)>;
+    /// Returns the number of pending updates of all the opened update stores.
+    async fn pending_count(&self) -> Result<usize>;
+    /// Reopens the update store with a bigger map size. Returns `false` if the update store can't
+    /// grow anymore.
+    async fn grow(&self, uuid: Uuid) -> Result<bool>;
 }
 
 impl<D, S> UpdateActor<D, S>
@@ -77,13 +213,24 @@ where
 {
     fn new(
         store: S,
-        inbox: mpsc::Receiver<UpdateMsg<D>>,
+        index_handle: IndexActorHandle,
+        inbox: Inbox<UpdateMsg<D>>,
         path: impl AsRef<Path>,
+        queue_limits: QueueLimits,
+        task_store: Arc<TaskStore>,
     ) -> anyhow::Result<Self> {
         let path = path.as_ref().to_owned().join("update_files");
         create_dir_all(&path)?;
         assert!(path.exists());
-        Ok(Self { store, inbox, path })
+        Ok(Self {
+            store,
+            index_handle,
+            inbox,
+            path,
+            queue_limits,
+            task_store,
+            closed: false,
+        })
     }
 
     async fn run(mut self) {
@@ -92,14 +239,20 @@ where
         info!("Started update actor.");
 
         loop {
-            match self.inbox.recv().await {
+            match recv(&self.inbox).await {
+                Some(msg) if self.closed => msg.refuse(),
                 Some(Update {
                     uuid,
                     meta,
+                    priority,
+                    request_id,
                     data,
                     ret,
                 }) => {
-                    let _ = ret.send(self.handle_update(uuid, meta, data).await);
+                    let result = self
+                        .handle_update(uuid, meta, priority, request_id, data)
+                        .await;
+                    let _ = ret.send(result);
                 }
                 Some(ListUpdates { uuid, ret }) => {
                     let _ = ret.send(self.handle_list_updates(uuid).await);
@@ -113,6 +266,31 @@ where
                 Some(Create { uuid, ret }) => {
                     let _ = ret.send(self.handle_create(uuid).await);
                 }
+                Some(Snapshot { uuids, path, ret }) => {
+                    let _ = ret.send(self.handle_snapshot(uuids, path).await);
+                }
+                Some(DeleteTasks {
+                    uuid,
+                    index_uid,
+                    filter,
+                    ret,
+                }) => {
+                    let _ = ret.send(self.handle_delete_tasks(uuid, index_uid, filter).await);
+                }
+                Some(CancelTasks {
+                    uuid,
+                    index_uid,
+                    filter,
+                    ret,
+                }) => {
+                    let _ = ret.send(self.handle_cancel_tasks(uuid, index_uid, filter).await);
+                }
+                Some(Close { ret }) => {
+                    let _ = ret.send(self.handle_close().await);
+                }
+                Some(Health { ret }) => {
+                    let _ = ret.send(());
+                }
                 None => break,
             }
         }
@@ -122,9 +300,14 @@ where
         &self,
         uuid: Uuid,
         meta: UpdateMeta,
+        priority: Priority,
+        request_id: Option<String>,
         mut payload: mpsc::Receiver<PayloadData<D>>,
     ) -> Result<UpdateStatus> {
         let update_store = self.store.get_or_create(uuid).await?;
+        // The limits are checked before the payload is written, so a full queue doesn't consume
+        // any disk space.
+        self.check_queue_limits(&update_store).await?;
         let update_file_id = uuid::Uuid::new_v4();
         let path = self.path.join(format!("update_{}", update_file_id));
         let mut file = OpenOptions::new()
@@ -158,6 +341,21 @@ where
 
         let mut file = file.into_std().await;
 
+        // The primary key is inferred before the update is registered, so that a payload it
+        // can't be inferred from is refused right away.
+        let check_primary_key = match meta {
+            UpdateMeta::DocumentsAddition {
+                format: UpdateFormat::Json,
+                primary_key: None,
+                ..
+            } => match self.index_handle.get_index_meta(uuid).await {
+                Ok(meta) => meta.primary_key.is_none(),
+                Err(IndexError::UnexistingIndex) => true,
+                Err(e) => return Err(UpdateError::Error(Box::new(e))),
+            },
+            _ => false,
+        };
+
         tokio::task::spawn_blocking(move || {
             use std::io::{BufReader, sink, copy, Seek};
 
@@ -171,19 +369,108 @@ where
                     // The json file is invalid, we use Serde to get a nice error message:
                     file.seek(SeekFrom::Start(0))
                         .map_err(|e| UpdateError::Error(Box::new(e)))?;
-                    let _: serde_json::Value = serde_json::from_reader(file)
+                    let _: serde_json::Value = serde_json::from_reader(&mut file)
+                        .map_err(|e| UpdateError::Error(Box::new(e)))?;
+                }
+
+                if check_primary_key {
+                    file.seek(SeekFrom::Start(0))
                         .map_err(|e| UpdateError::Error(Box::new(e)))?;
+                    let document =
+                        first_document(&mut file).map_err(|e| UpdateError::Error(Box::new(e)))?;
+                    infer_primary_key(document.as_ref())?;
                 }
             }
 
-            // The payload is valid, we can register it to the update store.
-            update_store
-                .register_update(meta, path, uuid)
-                .map(UpdateStatus::Pending)
-                .map_err(|e| UpdateError::Error(Box::new(e)))
+            Ok(())
         })
         .await
-        .map_err(|e| UpdateError::Error(Box::new(e)))?
+        .map_err(|e| UpdateError::Error(Box::new(e)))??;
+
+        // The payload is valid, we can register it to the update store.
+        let task_store = self.task_store.clone();
+        let task_uid = tokio::task::spawn_blocking(move || task_store.next_uid())
+            .await
+            .map_err(|e| UpdateError::Error(Box::new(e)))?
+            .map_err(|e| UpdateError::Error(Box::new(e)))?;
+        let mut update_store = update_store;
+        loop {
+            let store = update_store.clone();
+            let update_meta = meta.clone();
+            let update_path = path.clone();
+            let update_request_id = request_id.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                store.register_update(
+                    update_meta,
+                    priority,
+                    update_request_id,
+                    Some(task_uid),
+                    update_path,
+                    uuid,
+                )
+            })
+            .await
+            .map_err(|e| UpdateError::Error(Box::new(e)))?;
+
+            match result {
+                Ok(pending) => {
+                    if let Some(ref request_id) = pending.request_id {
+                        info!(
+                            "Registered update {} of index {} (request {})",
+                            pending.id(),
+                            uuid,
+                            request_id
+                        );
+                    }
+                    return Ok(UpdateStatus::Pending(pending));
+                }
+                Err(e) if is_map_full(&e) => {
+                    // We must release our handle on the store so it can be reopened.
+                    drop(update_store);
+                    if !self.store.grow(uuid).await? {
+                        return Err(UpdateError::Error(Box::new(e)));
+                    }
+                    update_store = self.store.get_or_create(uuid).await?;
+                }
+                Err(e) => return Err(UpdateError::Error(Box::new(e))),
+            }
+        }
+    }
+
+    async fn check_queue_limits(&self, update_store: &UpdateStore) -> Result<()> {
+        if let Some(max) = self.queue_limits.per_index {
+            let pending = update_store
+                .pending_count()
+                .map_err(|e| UpdateError::Error(Box::new(e)))?;
+            if pending >= max {
+                return Err(UpdateError::TooManyPendingUpdates {
+                    pending,
+                    max,
+                    scope: "for this index",
+                });
+            }
+        }
+
+        if let Some(max) = self.queue_limits.total {
+            let pending = self.store.pending_count().await?;
+            if pending >= max {
+                return Err(UpdateError::TooManyPendingUpdates {
+                    pending,
+                    max,
+                    scope: "for all the indexes",
+                });
+            }
+        }
+
+        if let Some(min) = self.queue_limits.min_free_disk_space {
+            let available =
+                fs2::available_space(&self.path).map_err(|e| UpdateError::Error(Box::new(e)))?;
+            if available < min {
+                return Err(UpdateError::NotEnoughDiskSpace { available, min });
+            }
+        }
+
+        Ok(())
     }
 
     async fn handle_list_updates(&self, uuid: Uuid) -> Result<Vec<UpdateStatus>> {
@@ -212,8 +499,10 @@ where
         Ok(result)
     }
 
-    async fn handle_delete(&self, uuid: Uuid) -> Result<()> {
-        let store = self.store.delete(uuid).await?;
+    /// Closes the update store, and returns the content files of its pending updates, they
+    /// are removed along with the update store by the garbage collector of the index controller.
+    async fn handle_delete(&self, uuid: Uuid) -> Result<Vec<PathBuf>> {
+        let (store, update_files) = self.store.delete(uuid).await?;
 
         if let Some(store) = store {
             tokio::task::spawn(async move {
@@ -225,18 +514,112 @@ where
             });
         }
 
-        Ok(())
+        Ok(update_files)
     }
 
     async fn handle_create(&self, uuid: Uuid) -> Result<()> {
         let _ = self.store.get_or_create(uuid).await?;
         Ok(())
     }
+
+    /// The update files of the pending updates are copied with the update stores. The actor
+    /// registers the updates one at a time, so no update file is being written meanwhile.
+    async fn handle_snapshot(&self, uuids: HashSet<Uuid>, path: PathBuf) -> Result<()> {
+        let path = path.join("updates");
+        let update_files_path = path.join("update_files");
+        for uuid in uuids {
+            let store = match self.store.get(uuid).await? {
+                Some(store) => store,
+                None => continue,
+            };
+            let store_path = path.join(format!("updates-{}", uuid));
+            let update_files_path = update_files_path.clone();
+            tokio::task::spawn_blocking(move || store.snapshot(&store_path, &update_files_path))
+                .await
+                .map_err(|e| UpdateError::Error(Box::new(e)))?
+                .map_err(|e| UpdateError::Error(e.into()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the finished updates of the index `index_uid` whose task matches `filter`, and
+    /// returns the uids of their tasks.
+    async fn handle_delete_tasks(
+        &self,
+        uuid: Uuid,
+        index_uid: String,
+        filter: TaskFilter,
+    ) -> Result<Vec<u64>> {
+        let store = self
+            .store
+            .get(uuid)
+            .await?
+            .ok_or(UpdateError::UnexistingIndex(uuid))?;
+        let deleted = tokio::task::spawn_blocking(move || {
+            store.delete_finished(|update| {
+                Task::from_update(&index_uid, update).map_or(false, |task| filter.matches(&task))
+            })
+        })
+        .await
+        .map_err(|e| UpdateError::Error(Box::new(e)))?
+        .map_err(|e| UpdateError::Error(Box::new(e)))?;
+        Ok(deleted.iter().filter_map(UpdateStatus::task_uid).collect())
+    }
+
+    /// Aborts the pending updates of the index `index_uid` whose task matches `filter`, and
+    /// returns the uids of their tasks.
+    async fn handle_cancel_tasks(
+        &self,
+        uuid: Uuid,
+        index_uid: String,
+        filter: TaskFilter,
+    ) -> Result<Vec<u64>> {
+        let store = self
+            .store
+            .get(uuid)
+            .await?
+            .ok_or(UpdateError::UnexistingIndex(uuid))?;
+        let aborted = tokio::task::spawn_blocking(move || {
+            store.abort_pendings_where(|pending| {
+                let update = UpdateStatus::Pending(pending.clone());
+                Task::from_update(&index_uid, &update).map_or(false, |task| filter.matches(&task))
+            })
+        })
+        .await
+        .map_err(|e| UpdateError::Error(Box::new(e)))?
+        .map_err(|e| UpdateError::Error(Box::new(e)))?;
+        Ok(aborted
+            .iter()
+            .filter_map(|(_, aborted)| aborted.pending().task_uid)
+            .collect())
+    }
+
+    async fn handle_close(&mut self) -> Result<()> {
+        self.closed = true;
+
+        for (uuid, store) in self.store.drain().await {
+            // Let the update being processed finish, the pending ones are processed on restart.
+            store.stop();
+            let store = get_arc_ownership_blocking(store).await;
+            tokio::task::spawn_blocking(move || store.prepare_for_closing().wait())
+                .await
+                .map_err(|e| UpdateError::Error(Box::new(e)))?;
+            info!("Update store {} was closed.", uuid);
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
 pub struct UpdateActorHandle<D> {
     sender: mpsc::Sender<UpdateMsg<D>>,
+    /// Shared by all the update stores, which hold it for reading while they process an update.
+    pause: Arc<RwLock<()>>,
+    /// Shared by all the update stores, which send the new status of an update each time it
+    /// changes state.
+    events: broadcast::Sender<UpdateStatus>,
 }
 
 impl<D> UpdateActorHandle<D>
@@ -247,20 +630,52 @@ where
         index_handle: IndexActorHandle,
         path: impl AsRef<Path>,
         update_store_size: usize,
+        max_map_size: usize,
+        retention_policy: RetentionPolicy,
+        queue_limits: QueueLimits,
+        autobatch_debounce: Option<Duration>,
+        task_store: Arc<TaskStore>,
     ) -> anyhow::Result<Self> {
         let path = path.as_ref().to_owned().join("updates");
         let (sender, receiver) = mpsc::channel(100);
-        let store = MapUpdateStoreStore::new(index_handle, &path, update_store_size);
-        let actor = UpdateActor::new(store, receiver, path)?;
-
-        tokio::task::spawn(actor.run());
-
-        Ok(Self { sender })
+        let inbox = inbox(receiver);
+        let pause = Arc::new(RwLock::new(()));
+        let store_pause = pause.clone();
+        let (events, _) = broadcast::channel(UPDATE_EVENTS_CAPACITY);
+        let store_events = events.clone();
+        supervise("update actor", move || {
+            let store = MapUpdateStoreStore::new(
+                index_handle.clone(),
+                &path,
+                update_store_size,
+                max_map_size,
+                retention_policy,
+                store_pause.clone(),
+                autobatch_debounce,
+                store_events.clone(),
+            );
+            let actor = UpdateActor::new(
+                store,
+                index_handle.clone(),
+                inbox.clone(),
+                &path,
+                queue_limits,
+                task_store.clone(),
+            )?;
+            Ok(actor.run())
+        })?;
+
+        Ok(Self {
+            sender,
+            pause,
+            events,
+        })
     }
 
     pub async fn update(
         &self,
         meta: UpdateMeta,
+        priority: Priority,
         data: mpsc::Receiver<PayloadData<D>>,
         uuid: Uuid,
     ) -> Result<UpdateStatus> {
@@ -269,46 +684,139 @@ where
             uuid,
             data,
             meta,
+            priority,
+            // The update is registered on behalf of the request being handled, if any.
+            request_id: current_request_id(),
             ret,
         };
         let _ = self.sender.send(msg).await;
-        receiver.await.expect("update actor killed.")
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
     }
 
     pub async fn get_all_updates_status(&self, uuid: Uuid) -> Result<Vec<UpdateStatus>> {
         let (ret, receiver) = oneshot::channel();
         let msg = UpdateMsg::ListUpdates { uuid, ret };
         let _ = self.sender.send(msg).await;
-        receiver.await.expect("update actor killed.")
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
     }
 
     pub async fn update_status(&self, uuid: Uuid, id: u64) -> Result<UpdateStatus> {
         let (ret, receiver) = oneshot::channel();
         let msg = UpdateMsg::GetUpdate { uuid, id, ret };
         let _ = self.sender.send(msg).await;
-        receiver.await.expect("update actor killed.")
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
     }
 
-    pub async fn delete(&self, uuid: Uuid) -> Result<()> {
+    /// Deletes the update store, and returns the content files of its pending updates.
+    pub async fn delete(&self, uuid: Uuid) -> Result<Vec<PathBuf>> {
         let (ret, receiver) = oneshot::channel();
         let msg = UpdateMsg::Delete { uuid, ret };
         let _ = self.sender.send(msg).await;
-        receiver.await.expect("update actor killed.")
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
     }
 
     pub async fn create(&self, uuid: Uuid) -> Result<()> {
         let (ret, receiver) = oneshot::channel();
         let msg = UpdateMsg::Create { uuid, ret };
         let _ = self.sender.send(msg).await;
-        receiver.await.expect("update actor killed.")
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
+    }
+
+    /// Waits for the updates being processed to finish, and prevents the update stores from
+    /// processing the pending updates until the returned guard is dropped. The updates can still
+    /// be registered meanwhile.
+    pub async fn pause(&self) -> OwnedRwLockWriteGuard<()> {
+        self.pause.clone().write_owned().await
+    }
+
+    /// Receives the new status of the updates of all the indexes, each time an update changes
+    /// state: once registered, processing, and once processed, failed or aborted.
+    pub fn subscribe(&self) -> broadcast::Receiver<UpdateStatus> {
+        self.events.subscribe()
+    }
+
+    /// Copies the update stores of the indexes with the given uuids, and the content of their
+    /// pending updates, in the `updates` directory of `path`. The updates must be paused.
+    pub async fn snapshot(&self, uuids: HashSet<Uuid>, path: PathBuf) -> Result<()> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = UpdateMsg::Snapshot { uuids, path, ret };
+        let _ = self.sender.send(msg).await;
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
+    }
+
+    /// Removes the finished updates of the index whose task matches `filter`, and returns the
+    /// uids of their tasks.
+    pub async fn delete_tasks(
+        &self,
+        uuid: Uuid,
+        index_uid: String,
+        filter: TaskFilter,
+    ) -> Result<Vec<u64>> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = UpdateMsg::DeleteTasks {
+            uuid,
+            index_uid,
+            filter,
+            ret,
+        };
+        let _ = self.sender.send(msg).await;
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
+    }
+
+    /// Aborts the pending updates of the index whose task matches `filter`, and returns the
+    /// uids of their tasks.
+    pub async fn cancel_tasks(
+        &self,
+        uuid: Uuid,
+        index_uid: String,
+        filter: TaskFilter,
+    ) -> Result<Vec<u64>> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = UpdateMsg::CancelTasks {
+            uuid,
+            index_uid,
+            filter,
+            ret,
+        };
+        let _ = self.sender.send(msg).await;
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
+    }
+
+    /// Waits for the updates being processed to finish and closes all the update stores. The
+    /// actor refuses all the messages after that.
+    pub async fn close(&self) -> Result<()> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = UpdateMsg::Close { ret };
+        let _ = self.sender.send(msg).await;
+        receiver.await.map_err(|_| UpdateError::Unavailable)?
+    }
+
+    /// Checks that the update actor is still alive and processing messages.
+    pub async fn health(&self) -> anyhow::Result<()> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = UpdateMsg::Health { ret };
+        self.sender
+            .send(msg)
+            .await
+            .map_err(|_| anyhow::anyhow!("update actor killed."))?;
+        receiver
+            .await
+            .map_err(|_| anyhow::anyhow!("update actor killed."))
     }
 }
 
+#[derive(Clone)]
 struct MapUpdateStoreStore {
     db: Arc<RwLock<HashMap<Uuid, Arc<UpdateStore>>>>,
     index_handle: IndexActorHandle,
     path: PathBuf,
     update_store_size: usize,
+    max_map_size: usize,
+    retention_policy: RetentionPolicy,
+    pause: Arc<RwLock<()>>,
+    /// How long the update stores wait for more updates before processing a batch.
+    autobatch_debounce: Option<Duration>,
+    events: broadcast::Sender<UpdateStatus>,
 }
 
 impl MapUpdateStoreStore {
@@ -316,6 +824,11 @@ impl MapUpdateStoreStore {
         index_handle: IndexActorHandle,
         path: impl AsRef<Path>,
         update_store_size: usize,
+        max_map_size: usize,
+        retention_policy: RetentionPolicy,
+        pause: Arc<RwLock<()>>,
+        autobatch_debounce: Option<Duration>,
+        events: broadcast::Sender<UpdateStatus>,
     ) -> Self {
         let db = Arc::new(RwLock::new(HashMap::new()));
         let path = path.as_ref().to_owned();
@@ -324,8 +837,69 @@ impl MapUpdateStoreStore {
             index_handle,
             path,
             update_store_size,
+            max_map_size,
+            retention_policy,
+            pause,
+            autobatch_debounce,
+            events,
         }
     }
+
+    fn open_store(
+        &self,
+        uuid: Uuid,
+        path: impl AsRef<Path>,
+        map_size: usize,
+    ) -> Result<Arc<UpdateStore>> {
+        let index_handle = self.index_handle.clone();
+        let mut options = heed::EnvOpenOptions::new();
+        options.map_size(map_size);
+        // Called by the store when it gets full while processing the updates.
+        let stores = self.clone();
+        let on_map_full = move || {
+            tokio::spawn(async move {
+                match stores.grow(uuid).await {
+                    Ok(true) => (),
+                    Ok(false) => error!("The update store {} is full and can't grow.", uuid),
+                    Err(e) => error!("Can't grow the update store {}: {}", uuid, e),
+                }
+            });
+        };
+        UpdateStore::open(
+            options,
+            path,
+            IndexUpdateHandler { index_handle },
+            self.retention_policy,
+            self.pause.clone(),
+            self.autobatch_debounce,
+            self.events.clone(),
+            on_map_full,
+        )
+        .map_err(|e| UpdateError::Error(e.into()))
+    }
+}
+
+/// Sends the updates of an update store to the index actor to be processed.
+#[derive(Clone)]
+struct IndexUpdateHandler {
+    index_handle: IndexActorHandle,
+}
+
+impl HandleUpdate<UpdateMeta, UpdateResult, String> for IndexUpdateHandler {
+    fn handle_update(
+        &mut self,
+        meta: Processing<UpdateMeta>,
+        content: File,
+    ) -> anyhow::Result<HandleResult> {
+        futures::executor::block_on(self.index_handle.update(meta, content))
+    }
+
+    fn handle_batch(
+        &mut self,
+        batch: Vec<(Processing<UpdateMeta>, File)>,
+    ) -> anyhow::Result<Vec<HandleResult>> {
+        futures::executor::block_on(self.index_handle.update_batch(batch))
+    }
 }
 
 #[async_trait::async_trait]
@@ -333,16 +907,9 @@ impl UpdateStoreStore for MapUpdateStoreStore {
     async fn get_or_create(&self, uuid: Uuid) -> Result<Arc<UpdateStore>> {
         match self.db.write().await.entry(uuid) {
             Entry::Vacant(e) => {
-                let mut options = heed::EnvOpenOptions::new();
-                let update_store_size = self.update_store_size;
-                options.map_size(update_store_size);
                 let path = self.path.clone().join(format!("updates-{}", e.key()));
                 create_dir_all(&path).unwrap();
-                let index_handle = self.index_handle.clone();
-                let store = UpdateStore::open(options, &path, move |meta, file| {
-                    futures::executor::block_on(index_handle.update(meta, file))
-                })
-                .map_err(|e| UpdateError::Error(e.into()))?;
+                let store = self.open_store(*e.key(), &path, self.update_store_size)?;
                 let store = e.insert(store);
                 Ok(store.clone())
             }
@@ -365,14 +932,7 @@ impl UpdateStoreStore for MapUpdateStoreStore {
                     match guard.entry(uuid) {
                         Entry::Vacant(entry) => {
                             // We can safely load the index
-                            let index_handle = self.index_handle.clone();
-                            let mut options = heed::EnvOpenOptions::new();
-                            let update_store_size = self.update_store_size;
-                            options.map_size(update_store_size);
-                            let store = UpdateStore::open(options, &path, move |meta, file| {
-                                futures::executor::block_on(index_handle.update(meta, file))
-                            })
-                            .map_err(|e| UpdateError::Error(e.into()))?;
+                            let store = self.open_store(uuid, &path, self.update_store_size)?;
                             let store = entry.insert(store);
                             Ok(Some(store.clone()))
                         }
@@ -388,12 +948,59 @@ impl UpdateStoreStore for MapUpdateStoreStore {
         }
     }
 
-    async fn delete(&self, uuid: Uuid) -> Result<Option<Arc<UpdateStore>>> {
-        let store = self.db.write().await.remove(&uuid);
+    async fn delete(&self, uuid: Uuid) -> Result<(Option<Arc<UpdateStore>>, Vec<PathBuf>)> {
+        // The write lock is held until the pending updates are read, so that nobody can open
+        // the store meanwhile.
+        let mut guard = self.db.write().await;
         let path = self.path.clone().join(format!("updates-{}", uuid));
-        if store.is_some() || path.exists() {
-            remove_dir_all(path).unwrap();
+        match guard.remove(&uuid) {
+            Some(store) => {
+                let update_files = store
+                    .pending_update_files()
+                    .map_err(|e| UpdateError::Error(e.into()))?;
+                Ok((Some(store), update_files))
+            }
+            None if path.exists() => {
+                let update_files = UpdateStore::pending_update_files_at(&path)
+                    .map_err(|e| UpdateError::Error(e.into()))?;
+                Ok((None, update_files))
+            }
+            None => Ok((None, Vec::new())),
+        }
+    }
+    async fn grow(&self, uuid: Uuid) -> Result<bool> {
+        let path = self.path.clone().join(format!("updates-{}", uuid));
+        let map_size = match grown_map_size(&path, self.update_store_size, self.max_map_size) {
+            Some(map_size) => map_size,
+            None => return Ok(false),
+        };
+
+        // The write lock is held for the whole operation, so that nobody can reopen the store
+        // while it is being closed.
+        let mut guard = self.db.write().await;
+        if let Some(store) = guard.remove(&uuid) {
+            let store = get_arc_ownership_blocking(store).await;
+            tokio::task::spawn_blocking(move || store.prepare_for_closing().wait())
+                .await
+                .map_err(|e| UpdateError::Error(Box::new(e)))?;
+        }
+
+        info!("Resizing update store {} to {} bytes.", uuid, map_size);
+        let store = self.open_store(uuid, &path, map_size)?;
+        guard.insert(uuid, store);
+
+        Ok(true)
+    }
+    async fn drain(&self) -> Vec<(Uuid, Arc<UpdateStore>)> {
+        self.db.write().await.drain().collect()
+    }
+    async fn pending_count(&self) -> Result<usize> {
+        let mut pending = 0;
+        for store in self.db.read().await.values() {
+            pending += store
+                .pending_count()
+                .map_err(|e| UpdateError::Error(Box::new(e)))?;
         }
-        Ok(store)
+        Ok(pending)
     }
 }
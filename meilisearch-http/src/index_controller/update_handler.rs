@@ -1,10 +1,12 @@
 use std::fs::File;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
 
 use crate::index::Index;
 use anyhow::Result;
 use grenad::CompressionType;
-use milli::update::UpdateBuilder;
+use milli::update::{UpdateBuilder, UpdateFormat, UpdateIndexingStep};
 use rayon::ThreadPool;
+use serde_json::{Map, Value};
 
 use crate::index::UpdateResult;
 use crate::index_controller::updates::{Failed, Processed, Processing};
@@ -20,6 +22,7 @@ pub struct UpdateHandler {
     linked_hash_map_size: usize,
     chunk_compression_type: CompressionType,
     chunk_fusing_shrink_size: u64,
+    max_fields_per_index: usize,
 }
 
 impl UpdateHandler {
@@ -36,6 +39,7 @@ impl UpdateHandler {
             linked_hash_map_size: opt.linked_hash_map_size,
             chunk_compression_type: opt.chunk_compression_type,
             chunk_fusing_shrink_size: opt.chunk_fusing_shrink_size.get_bytes(),
+            max_fields_per_index: opt.max_fields_per_index,
         })
     }
 
@@ -63,33 +67,96 @@ impl UpdateHandler {
         content: File,
         index: Index,
     ) -> Result<Processed<UpdateMeta, UpdateResult>, Failed<UpdateMeta, String>> {
-        use UpdateMeta::*;
+        match self.apply_update(meta.id(), meta.meta(), content, &index, |_| ()) {
+            Ok(result) => Ok(meta.process(result)),
+            Err(e) => Err(meta.fail_with(e)),
+        }
+    }
 
-        let update_id = meta.id();
+    /// Applies the update to the index without consuming its metadata, so that the update can be
+    /// retried if it failed because the index ran out of space. `progress` is called on each
+    /// indexing step reported by milli.
+    pub fn apply_update(
+        &self,
+        update_id: u64,
+        meta: &UpdateMeta,
+        content: File,
+        index: &Index,
+        progress: impl Fn(UpdateIndexingStep) + Sync,
+    ) -> Result<UpdateResult> {
+        use UpdateMeta::*;
 
         let update_builder = self.update_buidler(update_id);
 
-        let result = match meta.meta() {
+        match meta {
+            DocumentsAddition {
+                method,
+                format: UpdateFormat::Json,
+                primary_key,
+                skip_invalid_documents: true,
+            } => {
+                let (content, skipped, errors) =
+                    index.skip_invalid_documents(content, primary_key.as_deref())?;
+                let mut result = index.update_documents(
+                    UpdateFormat::Json,
+                    *method,
+                    content,
+                    || self.update_buidler(update_id),
+                    primary_key.as_deref(),
+                    self.max_fields_per_index,
+                    progress,
+                )?;
+                if let UpdateResult::DocumentsAddition(ref mut result) = result {
+                    result.nb_documents += skipped;
+                    result.skipped_documents = Some(skipped);
+                    result.document_errors = errors;
+                }
+                Ok(result)
+            }
             DocumentsAddition {
                 method,
                 format,
                 primary_key,
+                ..
             } => index.update_documents(
                 *format,
                 *method,
                 content,
-                update_builder,
+                || self.update_buidler(update_id),
                 primary_key.as_deref(),
+                self.max_fields_per_index,
+                progress,
             ),
             ClearDocuments => index.clear_documents(update_builder),
             DeleteDocuments => index.delete_documents(content, update_builder),
-            Settings(settings) => index.update_settings(settings, update_builder),
+            Settings(settings) => index.update_settings(settings, update_builder, progress),
             Facets(levels) => index.update_facets(levels, update_builder),
-        };
+            // The index has already been copied by the index actor at this point.
+            Clone { .. } => Ok(UpdateResult::Other),
+            // The index is rebuilt by the index actor, in a shadow index replacing it once built.
+            Reindex { .. } => Ok(UpdateResult::Other),
+        }
+    }
+}
 
-        match result {
-            Ok(result) => Ok(meta.process(result)),
-            Err(e) => Err(meta.fail(e.to_string())),
+/// Merges the JSON documents of several document additions into a single JSON stream file, so
+/// that they can be indexed in a single pass. Returns the merged file along with the number of
+/// documents of each addition.
+pub fn merge_document_additions(contents: Vec<File>) -> Result<(File, Vec<usize>)> {
+    let mut writer = BufWriter::new(tempfile::tempfile()?);
+    let mut counts = Vec::with_capacity(contents.len());
+
+    for mut content in contents {
+        content.seek(SeekFrom::Start(0))?;
+        let documents: Vec<Map<String, Value>> = serde_json::from_reader(BufReader::new(content))?;
+        for document in &documents {
+            serde_json::to_writer(&mut writer, document)?;
+            writer.write_all(b"\n")?;
         }
+        counts.push(documents.len());
     }
+
+    let mut merged = writer.into_inner()?;
+    merged.seek(SeekFrom::Start(0))?;
+    Ok((merged, counts))
 }
@@ -1,29 +1,106 @@
 use std::fs::remove_file;
+use std::ops::Bound;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use heed::types::{DecodeIgnore, OwnedType, SerdeJson};
-use heed::{Database, Env, EnvOpenOptions};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use heed::types::{DecodeIgnore, OwnedType, SerdeJson, Str, Unit};
+use heed::{CompactionOption, Database, Env, EnvOpenOptions};
+use log::{error, info};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, RwLock as AsyncRwLock};
 use uuid::Uuid;
 
+use super::map_size::is_map_full_anyhow;
 use crate::index_controller::updates::*;
 
 type BEU64 = heed::zerocopy::U64<heed::byteorder::BE>;
 
+/// Interval between two prunings of the finished updates.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The maximum number of updates processed in a single batch.
+const MAX_BATCH_SIZE: usize = 100;
+
+/// The number of named databases of an update store.
+pub const UPDATE_STORE_MAX_DBS: u32 = 7;
+
+/// The key of the version of the store in the `metadata` database.
+pub const VERSION_KEY: &str = "version";
+
+/// The version of the format of the update stores, recorded in the stores.
+///
+/// `V1` is the format of the stores created before the version was recorded. From `V2` on, all
+/// the updates are written in the format of the version, the fields added to the updates since
+/// `V1` included.
+///
+/// To change the format, add a version, make it `CURRENT`, and add the step migrating a store of
+/// the previous version to it in `UpdateStore::migrate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum UpdateStoreVersion {
+    V1,
+    V2,
+}
+
+impl UpdateStoreVersion {
+    pub const CURRENT: Self = Self::V2;
+
+    /// Reads a recorded version, refusing the versions this crate doesn't know of.
+    pub fn parse(version: &str) -> anyhow::Result<Self> {
+        serde_json::from_value(serde_json::Value::String(version.to_string())).map_err(|_| {
+            anyhow::anyhow!(
+                "Update store version `{}` is not supported, the store may come from a newer version",
+                version
+            )
+        })
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::V1 => "V1",
+            Self::V2 => "V2",
+        }
+    }
+}
+
+/// Describes which finished (processed, failed or aborted) updates are kept in the update store.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionPolicy {
+    /// The maximum number of finished updates to keep, the oldest are removed first.
+    pub max_history: Option<usize>,
+    /// The finished updates older than this are removed.
+    pub max_age: Option<chrono::Duration>,
+}
+
+impl RetentionPolicy {
+    fn keeps_everything(&self) -> bool {
+        self.max_history.is_none() && self.max_age.is_none()
+    }
+}
+
 #[derive(Clone)]
 pub struct UpdateStore<M, N, E> {
     env: Env,
     pending_meta: Database<OwnedType<BEU64>, SerdeJson<Pending<M>>>,
     pending: Database<OwnedType<BEU64>, SerdeJson<PathBuf>>,
+    /// The ids of the pending updates with a high priority, they are processed first.
+    high_priority: Database<OwnedType<BEU64>, Unit>,
     processed_meta: Database<OwnedType<BEU64>, SerdeJson<Processed<M, N>>>,
     failed_meta: Database<OwnedType<BEU64>, SerdeJson<Failed<M, E>>>,
     aborted_meta: Database<OwnedType<BEU64>, SerdeJson<Aborted<M>>>,
-    processing: Arc<RwLock<Option<Processing<M>>>>,
+    /// The updates currently being processed, there are several when they are processed in a
+    /// batch.
+    processing: Arc<RwLock<Vec<Processing<M>>>>,
     notification_sender: mpsc::Sender<()>,
+    /// Set when the store is being closed, no new update is processed after that.
+    stopped: Arc<AtomicBool>,
+    /// Receives the new status of the updates, each time an update changes state.
+    events: broadcast::Sender<UpdateStatus<M, N, E>>,
 }
 
 pub trait HandleUpdate<M, N, E> {
@@ -32,6 +109,23 @@ pub trait HandleUpdate<M, N, E> {
         meta: Processing<M>,
         content: File,
     ) -> anyhow::Result<Result<Processed<M, N>, Failed<M, E>>>;
+
+    /// Processes a batch of updates, and returns their results in the order of the batch. By
+    /// default, the updates are processed one after the other.
+    fn handle_batch(
+        &mut self,
+        batch: Vec<(Processing<M>, File)>,
+    ) -> anyhow::Result<Vec<Result<Processed<M, N>, Failed<M, E>>>> {
+        batch
+            .into_iter()
+            .map(|(meta, content)| self.handle_update(meta, content))
+            .collect()
+    }
+}
+
+/// Tells which consecutive updates can be processed together, in a single batch.
+pub trait Batchable {
+    fn batches_with(&self, other: &Self) -> bool;
 }
 
 impl<M, N, E, F> HandleUpdate<M, N, E> for F
@@ -49,53 +143,84 @@ where
 
 impl<M, N, E> UpdateStore<M, N, E>
 where
-    M: for<'a> Deserialize<'a> + Serialize + 'static + Send + Sync + Clone,
+    M: for<'a> Deserialize<'a> + Serialize + 'static + Send + Sync + Clone + Batchable,
     N: for<'a> Deserialize<'a> + Serialize + 'static + Send + Sync,
     E: for<'a> Deserialize<'a> + Serialize + 'static + Send + Sync,
 {
-    pub fn open<P, U>(
+    pub fn open<P, U, G>(
         mut options: EnvOpenOptions,
         path: P,
         update_handler: U,
-    ) -> heed::Result<Arc<Self>>
+        retention_policy: RetentionPolicy,
+        pause: Arc<AsyncRwLock<()>>,
+        debounce: Option<Duration>,
+        events: broadcast::Sender<UpdateStatus<M, N, E>>,
+        on_map_full: G,
+    ) -> anyhow::Result<Arc<Self>>
     where
         P: AsRef<Path>,
         U: HandleUpdate<M, N, E> + Sync + Clone + Send + 'static,
+        G: FnOnce() + Send + 'static,
     {
-        options.max_dbs(5);
+        options.max_dbs(UPDATE_STORE_MAX_DBS);
 
+        let path = path.as_ref();
         let env = options.open(path)?;
+        let metadata = env.create_database(Some("metadata"))?;
         let pending_meta = env.create_database(Some("pending-meta"))?;
         let pending = env.create_database(Some("pending"))?;
+        let high_priority = env.create_database(Some("high-priority"))?;
         let processed_meta = env.create_database(Some("processed-meta"))?;
         let aborted_meta = env.create_database(Some("aborted-meta"))?;
         let failed_meta = env.create_database(Some("failed-meta"))?;
-        let processing = Arc::new(RwLock::new(None));
+        let processing = Arc::new(RwLock::new(Vec::new()));
 
         let (notification_sender, mut notification_receiver) = mpsc::channel(10);
-        // Send a first notification to trigger the process.
-        let _ = notification_sender.send(());
+        // Send a first notification to trigger the process, so that the updates left pending
+        // when the store was closed are processed. The channel is empty, the notification is
+        // queued right away.
+        let _ = notification_sender.try_send(());
 
         let update_store = Arc::new(UpdateStore {
             env,
             pending,
             pending_meta,
+            high_priority,
             processed_meta,
             aborted_meta,
             notification_sender,
             failed_meta,
             processing,
+            stopped: Arc::new(AtomicBool::new(false)),
+            events,
         });
 
+        // The store is migrated before any update is processed or registered.
+        update_store
+            .migrate(metadata, path)
+            .with_context(|| format!("Can't open the update store at {}", path.display()))?;
+
         // We need a weak reference so we can take ownership on the arc later when we
         // want to close the index.
         let update_store_weak = Arc::downgrade(&update_store);
         tokio::task::spawn(async move {
             // Block and wait for something to process.
             'outer: while notification_receiver.recv().await.is_some() {
+                // Waits for more updates to be registered, so that they can be processed in the
+                // same batch.
+                if let Some(debounce) = debounce {
+                    tokio::time::sleep(debounce).await;
+                }
                 loop {
+                    // No update is processed while the updates are paused, the ones being
+                    // processed hold the lock until they are done. The lock is taken before the
+                    // store, so that a paused loop doesn't prevent the store from being closed.
+                    let _paused = pause.read().await;
                     match update_store_weak.upgrade() {
                         Some(update_store) => {
+                            if update_store.is_stopped() {
+                                break 'outer;
+                            }
                             let handler = update_handler.clone();
                             let res = tokio::task::spawn_blocking(move || {
                                 update_store.process_pending_update(handler)
@@ -105,6 +230,16 @@ where
                             match res {
                                 Ok(Some(_)) => (),
                                 Ok(None) => break,
+                                // The store can't be reopened while it is referenced here, so the
+                                // processing stops and the store is grown and reopened by
+                                // `on_map_full`. The batch, that wasn't committed, is still
+                                // pending and is processed by the reopened store on its first
+                                // notification.
+                                Err(e) if is_map_full_anyhow(&e) => {
+                                    error!("the update store is full: {}", e);
+                                    on_map_full();
+                                    break 'outer;
+                                }
                                 Err(e) => eprintln!("error while processing update: {}", e),
                             }
                         }
@@ -115,9 +250,242 @@ where
             }
         });
 
+        if !retention_policy.keeps_everything() {
+            let update_store_weak = Arc::downgrade(&update_store);
+            tokio::task::spawn(async move {
+                let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    match update_store_weak.upgrade() {
+                        Some(update_store) => {
+                            let res = tokio::task::spawn_blocking(move || {
+                                update_store.prune(retention_policy, Utc::now())
+                            })
+                            .await;
+                            match res {
+                                Ok(Ok(0)) => (),
+                                Ok(Ok(pruned)) => info!("pruned {} finished updates", pruned),
+                                Ok(Err(e)) => error!("error while pruning updates: {}", e),
+                                Err(e) => error!("error while pruning updates: {}", e),
+                            }
+                        }
+                        // the ownership on the arc has been taken, we need to exit.
+                        None => break,
+                    }
+                }
+            });
+        }
+
         Ok(update_store)
     }
 
+    /// Brings the store at `path` to the current format, and records its version.
+    fn migrate(&self, metadata: Database<Str, Str>, path: &Path) -> anyhow::Result<()> {
+        let mut wtxn = self.env.write_txn()?;
+        let mut version = match metadata.get(&wtxn, VERSION_KEY)? {
+            Some(version) => UpdateStoreVersion::parse(version)?,
+            None if self.is_empty(&wtxn)? => UpdateStoreVersion::CURRENT,
+            None => UpdateStoreVersion::V1,
+        };
+
+        if version < UpdateStoreVersion::CURRENT {
+            info!(
+                "Migrating the update store at {} from {:?} to {:?}.",
+                path.display(),
+                version,
+                UpdateStoreVersion::CURRENT
+            );
+        }
+        while version < UpdateStoreVersion::CURRENT {
+            version = match version {
+                UpdateStoreVersion::V1 => {
+                    // The updates written in V1 are read with the defaults of the fields added
+                    // since, and written back with them.
+                    rewrite(&mut wtxn, self.pending_meta, "pending-meta")?;
+                    rewrite(&mut wtxn, self.processed_meta, "processed-meta")?;
+                    rewrite(&mut wtxn, self.failed_meta, "failed-meta")?;
+                    rewrite(&mut wtxn, self.aborted_meta, "aborted-meta")?;
+                    UpdateStoreVersion::V2
+                }
+                UpdateStoreVersion::V2 => unreachable!("V2 is the current version"),
+            };
+        }
+
+        metadata.put(&mut wtxn, VERSION_KEY, version.as_str())?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// Whether no update was ever registered in the store.
+    fn is_empty(&self, txn: &heed::RoTxn) -> heed::Result<bool> {
+        Ok(self.pending_meta.is_empty(txn)?
+            && self.processed_meta.is_empty(txn)?
+            && self.failed_meta.is_empty(txn)?
+            && self.aborted_meta.is_empty(txn)?)
+    }
+
+    /// Removes the finished updates that are not allowed by the `retention_policy` anymore, and
+    /// returns the number of removed updates.
+    pub fn prune(&self, retention_policy: RetentionPolicy, now: DateTime<Utc>) -> heed::Result<usize> {
+        enum Finished {
+            Processed,
+            Failed,
+            Aborted,
+        }
+
+        let mut wtxn = self.env.write_txn()?;
+        let mut finished = Vec::new();
+
+        for result in self.processed_meta.iter(&wtxn)? {
+            let (key, processed) = result?;
+            finished.push((key.get(), processed.processed_at, Finished::Processed));
+        }
+
+        for result in self.failed_meta.iter(&wtxn)? {
+            let (key, failed) = result?;
+            finished.push((key.get(), failed.failed_at(), Finished::Failed));
+        }
+
+        for result in self.aborted_meta.iter(&wtxn)? {
+            let (key, aborted) = result?;
+            finished.push((key.get(), aborted.aborted_at(), Finished::Aborted));
+        }
+
+        // The most recent updates come first, so we keep them if the history is limited.
+        finished.sort_unstable_by(|(a, _, _), (b, _, _)| b.cmp(a));
+
+        let mut pruned = 0;
+        // The most recent update is always kept, since it is used to generate the next update id.
+        for (i, (id, finished_at, kind)) in finished.into_iter().enumerate().skip(1) {
+            let too_many = retention_policy.max_history.map_or(false, |max| i >= max);
+            let too_old = retention_policy
+                .max_age
+                .map_or(false, |max_age| finished_at < now - max_age);
+
+            if too_many || too_old {
+                let key = BEU64::new(id);
+                match kind {
+                    Finished::Processed => self.processed_meta.delete(&mut wtxn, &key)?,
+                    Finished::Failed => self.failed_meta.delete(&mut wtxn, &key)?,
+                    Finished::Aborted => self.aborted_meta.delete(&mut wtxn, &key)?,
+                };
+                pruned += 1;
+            }
+        }
+
+        wtxn.commit()?;
+
+        Ok(pruned)
+    }
+
+    /// Removes the finished (processed, failed or aborted) updates selected by `select`, and
+    /// returns them. The most recent update is never removed, since it is used to generate the
+    /// next update id.
+    pub fn delete_finished(
+        &self,
+        select: impl Fn(&UpdateStatus<M, N, E>) -> bool,
+    ) -> heed::Result<Vec<UpdateStatus<M, N, E>>> {
+        let mut wtxn = self.env.write_txn()?;
+        let last_id = self.new_update_id(&wtxn)?.checked_sub(1);
+        let mut finished: Vec<UpdateStatus<M, N, E>> = Vec::new();
+
+        for result in self.processed_meta.iter(&wtxn)? {
+            let (_, processed) = result?;
+            finished.push(processed.into());
+        }
+
+        for result in self.failed_meta.iter(&wtxn)? {
+            let (_, failed) = result?;
+            finished.push(failed.into());
+        }
+
+        for result in self.aborted_meta.iter(&wtxn)? {
+            let (_, aborted) = result?;
+            finished.push(aborted.into());
+        }
+
+        finished.retain(|update| Some(update.id()) != last_id && select(update));
+        for update in &finished {
+            let key = BEU64::new(update.id());
+            match update {
+                UpdateStatus::Processed(_) => self.processed_meta.delete(&mut wtxn, &key)?,
+                UpdateStatus::Failed(_) => self.failed_meta.delete(&mut wtxn, &key)?,
+                UpdateStatus::Aborted(_) => self.aborted_meta.delete(&mut wtxn, &key)?,
+                UpdateStatus::Pending(_) | UpdateStatus::Processing(_) => {
+                    unreachable!("only the finished updates are selected")
+                }
+            };
+        }
+
+        wtxn.commit()?;
+
+        Ok(finished)
+    }
+
+    /// Stops processing the pending updates. The update being processed, if any, is processed
+    /// until its end, the others are kept pending.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Sends the new status of an update to the subscribers of the update events.
+    fn notify(&self, update: impl Into<UpdateStatus<M, N, E>>) {
+        // Sending only fails when there is no subscriber.
+        let _ = self.events.send(update.into());
+    }
+
+    /// Copies the update store in the `data.mdb` file of `path`, and the content of its pending
+    /// updates in `update_files_path`. The updates must be paused, so that no pending update is
+    /// processed during the copy.
+    pub fn snapshot(&self, path: &Path, update_files_path: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(path)?;
+        std::fs::create_dir_all(update_files_path)?;
+        self.env
+            .copy_to_path(path.join("data.mdb"), CompactionOption::Enabled)?;
+
+        let rtxn = self.env.read_txn()?;
+        for entry in self.pending.iter(&rtxn)? {
+            let (_, content_path) = entry?;
+            if let Some(name) = content_path.file_name() {
+                std::fs::copy(&content_path, update_files_path.join(name))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the content files of the pending updates.
+    pub fn pending_update_files(&self) -> heed::Result<Vec<PathBuf>> {
+        let rtxn = self.env.read_txn()?;
+        self.pending
+            .iter(&rtxn)?
+            .map(|entry| entry.map(|(_, content_path)| content_path))
+            .collect()
+    }
+
+    /// Returns the content files of the pending updates of the update store at `path`, which
+    /// must not be opened. The updates are not processed.
+    pub fn pending_update_files_at(path: &Path) -> heed::Result<Vec<PathBuf>> {
+        let mut options = EnvOpenOptions::new();
+        options.max_dbs(UPDATE_STORE_MAX_DBS);
+        let env = options.open(path)?;
+        let pending: Database<OwnedType<BEU64>, SerdeJson<PathBuf>> =
+            env.create_database(Some("pending"))?;
+
+        let rtxn = env.read_txn()?;
+        let update_files = pending
+            .iter(&rtxn)?
+            .map(|entry| entry.map(|(_, content_path)| content_path))
+            .collect();
+        drop(rtxn);
+        env.prepare_for_closing().wait();
+        update_files
+    }
+
     pub fn prepare_for_closing(self) -> heed::EnvClosingEvent {
         self.env.prepare_for_closing()
     }
@@ -142,7 +510,13 @@ where
             .last(txn)?
             .map(|(k, _)| k.get());
 
-        let last_update_id = [last_pending, last_processed, last_aborted]
+        let last_failed = self
+            .failed_meta
+            .remap_data_type::<DecodeIgnore>()
+            .last(txn)?
+            .map(|(k, _)| k.get());
+
+        let last_update_id = [last_pending, last_processed, last_aborted, last_failed]
             .iter()
             .copied()
             .flatten()
@@ -159,6 +533,9 @@ where
     pub fn register_update(
         &self,
         meta: M,
+        priority: Priority,
+        request_id: Option<String>,
+        task_uid: Option<u64>,
         content: impl AsRef<Path>,
         index_uuid: Uuid,
     ) -> heed::Result<Pending<M>> {
@@ -171,66 +548,159 @@ where
         let update_id = self.new_update_id(&wtxn)?;
         let update_key = BEU64::new(update_id);
 
-        let meta = Pending::new(meta, update_id, index_uuid);
+        let meta = Pending::new(meta, update_id, index_uuid, priority, request_id, task_uid);
         self.pending_meta.put(&mut wtxn, &update_key, &meta)?;
         self.pending
             .put(&mut wtxn, &update_key, &content.as_ref().to_owned())?;
+        if priority == Priority::High {
+            self.high_priority.put(&mut wtxn, &update_key, &())?;
+        }
 
         wtxn.commit()?;
 
         self.notification_sender
             .blocking_send(())
             .expect("Update store loop exited.");
+        self.notify(meta.clone());
         Ok(meta)
     }
-    /// Executes the user provided function on the next pending update (the one with the lowest id).
+
+    /// Returns the next updates to process. The next update is the high priority update with the
+    /// lowest id if there is one, the update with the lowest id otherwise. The following updates
+    /// of the same priority class are added to the batch as long as they can be batched with it.
+    fn next_batch(&self, txn: &heed::RoTxn) -> heed::Result<Vec<(BEU64, Pending<M>)>> {
+        let first = match self.high_priority.first(txn)? {
+            Some((id, _)) => self.pending_meta.get(txn, &id)?.map(|meta| (id, meta)),
+            None => self.pending_meta.first(txn)?,
+        };
+
+        let (first_id, first) = match first {
+            Some(first) => first,
+            None => return Ok(Vec::new()),
+        };
+
+        let range = (Bound::Excluded(first_id), Bound::Unbounded);
+        let mut batch = Vec::new();
+
+        if first.priority == Priority::High {
+            for result in self.high_priority.range(txn, &range)? {
+                if batch.len() + 1 >= MAX_BATCH_SIZE {
+                    break;
+                }
+                let (id, _) = result?;
+                match self.pending_meta.get(txn, &id)? {
+                    Some(pending) if first.meta().batches_with(pending.meta()) => {
+                        batch.push((id, pending))
+                    }
+                    _ => break,
+                }
+            }
+        } else {
+            // There is no high priority update pending, since they are processed first.
+            for result in self.pending_meta.range(txn, &range)? {
+                if batch.len() + 1 >= MAX_BATCH_SIZE {
+                    break;
+                }
+                let (id, pending) = result?;
+                if !first.meta().batches_with(pending.meta()) {
+                    break;
+                }
+                batch.push((id, pending));
+            }
+        }
+
+        batch.insert(0, (first_id, first));
+
+        Ok(batch)
+    }
+
+    /// Executes the user provided function on the next pending updates, see `next_batch`.
     /// This is asynchronous as it let the user process the update with a read-only txn and
     /// only writing the result meta to the processed-meta store *after* it has been processed.
     fn process_pending_update<U>(&self, mut handler: U) -> anyhow::Result<Option<()>>
     where
         U: HandleUpdate<M, N, E>,
     {
-        // Create a read transaction to be able to retrieve the pending update in order.
+        // Create a read transaction to be able to retrieve the pending updates in order.
         let rtxn = self.env.read_txn()?;
-        let first_meta = self.pending_meta.first(&rtxn)?;
-
-        // If there is a pending update we process and only keep
-        // a reader while processing it, not a writer.
-        match first_meta {
-            Some((first_id, pending)) => {
-                let content_path = self
-                    .pending
-                    .get(&rtxn, &first_id)?
-                    .expect("associated update content");
-
-                // we change the state of the update from pending to processing before we pass it
-                // to the update handler. Processing store is non persistent to be able recover
-                // from a failure
-                let processing = pending.processing();
-                self.processing.write().replace(processing.clone());
-                let file = File::open(&content_path)?;
-                // Process the pending update using the provided user function.
-                let result = handler.handle_update(processing, file)?;
-                drop(rtxn);
-
-                // Once the pending update have been successfully processed
-                // we must remove the content from the pending and processing stores and
-                // write the *new* meta to the processed-meta store and commit.
-                let mut wtxn = self.env.write_txn()?;
-                self.processing.write().take();
-                self.pending_meta.delete(&mut wtxn, &first_id)?;
-                remove_file(&content_path)?;
-                self.pending.delete(&mut wtxn, &first_id)?;
-                match result {
-                    Ok(processed) => self.processed_meta.put(&mut wtxn, &first_id, &processed)?,
-                    Err(failed) => self.failed_meta.put(&mut wtxn, &first_id, &failed)?,
-                }
-                wtxn.commit()?;
+        let batch = self.next_batch(&rtxn)?;
 
-                Ok(Some(()))
+        // If there are pending updates we process them and only keep
+        // a reader while processing them, not a writer.
+        if batch.is_empty() {
+            return Ok(None);
+        }
+
+        let ids: Vec<u64> = batch.iter().map(|(id, _)| id.get()).collect();
+        let mut updates = Vec::with_capacity(batch.len());
+        let mut content_paths = Vec::with_capacity(batch.len());
+
+        for (id, pending) in batch {
+            let content_path = self
+                .pending
+                .get(&rtxn, &id)?
+                .expect("associated update content");
+
+            // we change the state of the update from pending to processing before we pass it
+            // to the update handler. Processing store is non persistent to be able recover
+            // from a failure
+            let mut processing = pending.processing();
+            if ids.len() > 1 {
+                processing.batch = Some(ids.clone());
             }
-            None => Ok(None),
+            let file = File::open(&content_path)?;
+            updates.push((processing, file));
+            content_paths.push((id, content_path));
+        }
+
+        *self.processing.write() = updates.iter().map(|(p, _)| p.clone()).collect();
+        for (processing, _) in &updates {
+            self.notify(processing.clone());
         }
+
+        // Process the pending updates using the provided user function.
+        let results = if updates.len() == 1 {
+            let (processing, file) = updates.remove(0);
+            vec![handler.handle_update(processing, file)?]
+        } else {
+            handler.handle_batch(updates)?
+        };
+        drop(rtxn);
+
+        // Once the pending updates have been successfully processed
+        // we must remove the content from the pending and processing stores and
+        // write the *new* meta to the processed-meta store and commit.
+        let mut wtxn = self.env.write_txn()?;
+        self.processing.write().clear();
+        let mut finished = Vec::with_capacity(results.len());
+        for ((id, content_path), result) in content_paths.iter().zip(results) {
+            self.pending_meta.delete(&mut wtxn, id)?;
+            self.high_priority.delete(&mut wtxn, id)?;
+            self.pending.delete(&mut wtxn, id)?;
+            match result {
+                Ok(processed) => {
+                    self.processed_meta.put(&mut wtxn, id, &processed)?;
+                    finished.push(UpdateStatus::from(processed));
+                }
+                Err(failed) => {
+                    self.failed_meta.put(&mut wtxn, id, &failed)?;
+                    finished.push(UpdateStatus::from(failed));
+                }
+            }
+        }
+        wtxn.commit()?;
+
+        // The content files are only removed once the updates are committed, so that the batch
+        // can be processed again if the commit fails.
+        for (_, content_path) in content_paths {
+            remove_file(&content_path)?;
+        }
+
+        for update in finished {
+            self.notify(update);
+        }
+
+        Ok(Some(()))
     }
 
     pub fn list(&self) -> anyhow::Result<Vec<UpdateStatus<M, N, E>>> {
@@ -238,16 +708,13 @@ where
         let mut updates = Vec::new();
 
         let processing = self.processing.read();
-        if let Some(ref processing) = *processing {
-            let update = UpdateStatus::from(processing.clone());
-            updates.push(update);
-        }
+        updates.extend(processing.iter().cloned().map(UpdateStatus::from));
 
         let pending = self
             .pending_meta
             .iter(&rtxn)?
             .filter_map(Result::ok)
-            .filter_map(|(_, p)| (Some(p.id()) != processing.as_ref().map(|p| p.id())).then(|| p))
+            .filter_map(|(_, p)| (!processing.iter().any(|u| u.id() == p.id())).then(|| p))
             .map(UpdateStatus::from);
 
         updates.extend(pending);
@@ -289,10 +756,8 @@ where
         let rtxn = self.env.read_txn()?;
         let key = BEU64::new(update_id);
 
-        if let Some(ref meta) = *self.processing.read() {
-            if meta.id() == update_id {
-                return Ok(Some(UpdateStatus::Processing(meta.clone())));
-            }
+        if let Some(meta) = self.processing.read().iter().find(|p| p.id() == update_id) {
+            return Ok(Some(UpdateStatus::Processing(meta.clone())));
         }
 
         if let Some(meta) = self.pending_meta.get(&rtxn, &key)? {
@@ -314,6 +779,13 @@ where
         Ok(None)
     }
 
+    /// Returns the number of updates that are waiting to be processed, including the one
+    /// currently being processed.
+    pub fn pending_count(&self) -> heed::Result<usize> {
+        let rtxn = self.env.read_txn()?;
+        self.pending_meta.len(&rtxn)
+    }
+
     /// Aborts an update, an aborted update content is deleted and
     /// the meta of it is moved into the aborted updates database.
     ///
@@ -326,7 +798,7 @@ where
         let key = BEU64::new(update_id);
 
         // We cannot abort an update that is currently being processed.
-        if self.pending_meta.first(&wtxn)?.map(|(key, _)| key.get()) == Some(update_id) {
+        if self.processing.read().iter().any(|p| p.id() == update_id) {
             return Ok(None);
         }
 
@@ -337,42 +809,337 @@ where
 
         let aborted = pending.abort();
 
+        if let Some(content_path) = self.pending.get(&wtxn, &key)? {
+            let _ = remove_file(content_path);
+        }
+
         self.aborted_meta.put(&mut wtxn, &key, &aborted)?;
         self.pending_meta.delete(&mut wtxn, &key)?;
         self.pending.delete(&mut wtxn, &key)?;
+        self.high_priority.delete(&mut wtxn, &key)?;
 
         wtxn.commit()?;
 
+        self.notify(aborted.clone());
         Ok(Some(aborted))
     }
 
-    /// Aborts all the pending updates, and not the one being currently processed.
+    /// Aborts all the pending updates, and not the ones being currently processed.
     /// Returns the update metas and ids that were successfully aborted.
     #[allow(dead_code)]
     pub fn abort_pendings(&self) -> heed::Result<Vec<(u64, Aborted<M>)>> {
+        self.abort_pendings_where(|_| true)
+    }
+
+    /// Aborts the pending updates selected by `select`, like `abort_pendings`.
+    pub fn abort_pendings_where(
+        &self,
+        select: impl Fn(&Pending<M>) -> bool,
+    ) -> heed::Result<Vec<(u64, Aborted<M>)>> {
         let mut wtxn = self.env.write_txn()?;
         let mut aborted_updates = Vec::new();
 
-        // We skip the first pending update as it is currently being processed.
-        for result in self.pending_meta.iter(&wtxn)?.skip(1) {
+        // We skip the updates that are currently being processed.
+        let processing: Vec<_> = self.processing.read().iter().map(|p| p.id()).collect();
+        for result in self.pending_meta.iter(&wtxn)? {
             let (key, pending) = result?;
             let id = key.get();
-            aborted_updates.push((id, pending.abort()));
+            if !processing.contains(&id) && select(&pending) {
+                aborted_updates.push((id, pending.abort()));
+            }
         }
 
         for (id, aborted) in &aborted_updates {
             let key = BEU64::new(*id);
+            if let Some(content_path) = self.pending.get(&wtxn, &key)? {
+                let _ = remove_file(content_path);
+            }
             self.aborted_meta.put(&mut wtxn, &key, &aborted)?;
             self.pending_meta.delete(&mut wtxn, &key)?;
             self.pending.delete(&mut wtxn, &key)?;
+            self.high_priority.delete(&mut wtxn, &key)?;
         }
 
         wtxn.commit()?;
 
+        for (_, aborted) in &aborted_updates {
+            self.notify(aborted.clone());
+        }
         Ok(aborted_updates)
     }
 }
 
+/// Writes all the entries of the `name` database back, in the current format.
+fn rewrite<T>(
+    wtxn: &mut heed::RwTxn,
+    db: Database<OwnedType<BEU64>, SerdeJson<T>>,
+    name: &str,
+) -> anyhow::Result<()>
+where
+    T: for<'a> Deserialize<'a> + Serialize + 'static,
+{
+    let entries = db
+        .iter(wtxn)?
+        .map(|entry| entry.map(|(id, value)| (id.get(), value)))
+        .collect::<heed::Result<Vec<_>>>()
+        .with_context(|| format!("The {} database can't be migrated", name))?;
+    for (id, value) in entries {
+        db.put(wtxn, &BEU64::new(id), &value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use heed::types::ByteSlice;
+    use serde_json::json;
+
+    use super::*;
+    use crate::index::UpdateResult;
+    use crate::index_controller::{get_arc_ownership_blocking, UpdateMeta};
+
+    type Store = UpdateStore<UpdateMeta, UpdateResult, String>;
+    type Outcome = Result<Processed<UpdateMeta, UpdateResult>, Failed<UpdateMeta, String>>;
+
+    fn process(meta: Processing<UpdateMeta>, _content: File) -> anyhow::Result<Outcome> {
+        Ok(Ok(meta.process(UpdateResult::Other)))
+    }
+
+    fn open(path: &Path) -> anyhow::Result<Arc<Store>> {
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100);
+        Store::open(
+            options,
+            path,
+            process,
+            RetentionPolicy::default(),
+            Arc::new(AsyncRwLock::new(())),
+            None,
+            broadcast::channel(1).0,
+            || (),
+        )
+    }
+
+    /// Writes a raw store, with the given processed updates and version.
+    fn write_store(path: &Path, processed: &[serde_json::Value], version: Option<&str>) {
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100).max_dbs(UPDATE_STORE_MAX_DBS);
+        let env = options.open(path).unwrap();
+        let processed_meta: Database<OwnedType<BEU64>, ByteSlice> =
+            env.create_database(Some("processed-meta")).unwrap();
+        let metadata: Database<Str, Str> = env.create_database(Some("metadata")).unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        for (id, update) in processed.iter().enumerate() {
+            let update = serde_json::to_vec(update).unwrap();
+            processed_meta
+                .put(&mut wtxn, &BEU64::new(id as u64), &update)
+                .unwrap();
+        }
+        if let Some(version) = version {
+            metadata.put(&mut wtxn, VERSION_KEY, version).unwrap();
+        }
+        wtxn.commit().unwrap();
+        env.prepare_for_closing().wait();
+    }
+
+    fn read_version(store: &Store) -> Option<String> {
+        let metadata: Database<Str, Str> = store.env.open_database(Some("metadata")).unwrap()?;
+        let rtxn = store.env.read_txn().unwrap();
+        let version = metadata.get(&rtxn, VERSION_KEY).unwrap();
+        version.map(String::from)
+    }
+
+    #[actix_rt::test]
+    async fn new_store_has_the_current_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(dir.path()).unwrap();
+        assert_eq!(read_version(&store).as_deref(), Some("V2"));
+    }
+
+    #[actix_rt::test]
+    async fn migrate_v1_store() {
+        let dir = tempfile::tempdir().unwrap();
+        // An update processed before the version was recorded, without the fields added since.
+        let update = json!({
+            "updateId": 0,
+            "meta": {
+                "type": "DocumentsAddition",
+                "method": "ReplaceDocuments",
+                "format": "Json",
+                "primary_key": null,
+            },
+            "enqueuedAt": "2021-03-01T10:00:00Z",
+            "indexUuid": Uuid::new_v4(),
+            "startedProcessingAt": "2021-03-01T10:00:01Z",
+            "success": { "DocumentsAddition": { "nb_documents": 1 } },
+            "processedAt": "2021-03-01T10:00:02Z",
+        });
+        write_store(dir.path(), &[update], None);
+
+        let store = open(dir.path()).unwrap();
+        assert_eq!(read_version(&store).as_deref(), Some("V2"));
+        match store.meta(0).unwrap() {
+            Some(UpdateStatus::Processed(processed)) => {
+                assert_eq!(processed.from.from.priority, Priority::Normal);
+                assert_eq!(processed.duration, None);
+            }
+            status => panic!("unexpected update status: {:?}", status),
+        }
+    }
+
+    fn processed_update(id: u64, processed_at: &str) -> serde_json::Value {
+        json!({
+            "updateId": id,
+            "meta": { "type": "ClearDocuments" },
+            "enqueuedAt": processed_at,
+            "indexUuid": Uuid::nil(),
+            "startedProcessingAt": processed_at,
+            "success": "Other",
+            "processedAt": processed_at,
+        })
+    }
+
+    fn remaining_updates(store: &Store, count: u64) -> Vec<u64> {
+        (0..count)
+            .filter(|id| store.meta(*id).unwrap().is_some())
+            .collect()
+    }
+
+    #[actix_rt::test]
+    async fn prune_by_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let updates: Vec<_> = (0..4)
+            .map(|id| processed_update(id, &format!("2021-03-0{}T10:00:00Z", id + 1)))
+            .collect();
+        write_store(dir.path(), &updates, None);
+        let store = open(dir.path()).unwrap();
+
+        let policy = RetentionPolicy {
+            max_history: Some(2),
+            max_age: None,
+        };
+        assert_eq!(store.prune(policy, Utc::now()).unwrap(), 2);
+        assert_eq!(remaining_updates(&store, 4), [2, 3]);
+    }
+
+    #[actix_rt::test]
+    async fn prune_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let updates: Vec<_> = (0..4)
+            .map(|id| processed_update(id, &format!("2021-03-0{}T10:00:00Z", id + 1)))
+            .collect();
+        write_store(dir.path(), &updates, None);
+        let store = open(dir.path()).unwrap();
+
+        let policy = RetentionPolicy {
+            max_history: None,
+            max_age: Some(chrono::Duration::days(1)),
+        };
+        let now = "2021-03-03T12:00:00Z".parse().unwrap();
+        assert_eq!(store.prune(policy, now).unwrap(), 2);
+        assert_eq!(remaining_updates(&store, 4), [2, 3]);
+
+        // The most recent update is kept, however old it is.
+        let now = "2022-01-01T00:00:00Z".parse().unwrap();
+        assert_eq!(store.prune(policy, now).unwrap(), 1);
+        assert_eq!(remaining_updates(&store, 4), [3]);
+    }
+
+    /// Fails the updates with an error too big to be written in the stores opened by `open`.
+    fn fail_with_a_big_error(
+        meta: Processing<UpdateMeta>,
+        _content: File,
+    ) -> anyhow::Result<Outcome> {
+        Ok(Err(meta.fail("a".repeat(4096 * 200))))
+    }
+
+    #[actix_rt::test]
+    async fn process_pending_update_after_map_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("updates");
+        std::fs::create_dir_all(&path).unwrap();
+        let content = dir.path().join("content");
+        std::fs::write(&content, b"[]").unwrap();
+
+        let (full_sender, full_receiver) = tokio::sync::oneshot::channel();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 100);
+        let store = Store::open(
+            options,
+            &path,
+            fail_with_a_big_error,
+            RetentionPolicy::default(),
+            Arc::new(AsyncRwLock::new(())),
+            None,
+            broadcast::channel(1).0,
+            move || {
+                let _ = full_sender.send(());
+            },
+        )
+        .unwrap();
+        let register = store.clone();
+        tokio::task::spawn_blocking(move || {
+            register.register_update(
+                UpdateMeta::ClearDocuments,
+                Priority::Normal,
+                None,
+                None,
+                content,
+                Uuid::nil(),
+            )
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        full_receiver.await.unwrap();
+
+        // The update wasn't committed, it is processed once the store is reopened with a bigger
+        // map, without any new update being registered.
+        let store = get_arc_ownership_blocking(store).await;
+        tokio::task::spawn_blocking(move || store.prepare_for_closing().wait())
+            .await
+            .unwrap();
+        let (events, mut receiver) = broadcast::channel(10);
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 1000);
+        let store = Store::open(
+            options,
+            &path,
+            process,
+            RetentionPolicy::default(),
+            Arc::new(AsyncRwLock::new(())),
+            None,
+            events,
+            || (),
+        )
+        .unwrap();
+        let processed = async {
+            loop {
+                if let Ok(UpdateStatus::Processed(_)) = receiver.recv().await {
+                    break;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), processed)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.meta(0).unwrap(),
+            Some(UpdateStatus::Processed(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn refuse_unknown_version() {
+        let dir = tempfile::tempdir().unwrap();
+        write_store(dir.path(), &[], Some("V42"));
+
+        let error = open(dir.path()).err().unwrap();
+        assert!(format!("{:#}", error).contains("`V42` is not supported"));
+    }
+}
+
 //#[cfg(test)]
 //mod tests {
 //use super::*;
@@ -1,7 +1,35 @@
 use chrono::{DateTime, Utc};
+use milli::update::UpdateIndexingStep;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::index::{DocumentError, InvalidDocuments};
+
+/// The time, in seconds, elapsed between `from` and `to`.
+fn duration(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from)
+        .num_microseconds()
+        .map_or(0.0, |micros| micros as f64 / 1_000_000.0)
+}
+
+/// The priority class of an update.
+///
+/// The pending high priority updates of an index are all processed before its pending normal
+/// priority updates, but never interrupt the update currently being processed. Within a priority
+/// class, the updates are processed in the order they were enqueued.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum Priority {
+    Normal,
+    High,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Pending<M> {
@@ -9,15 +37,33 @@ pub struct Pending<M> {
     pub meta: M,
     pub enqueued_at: DateTime<Utc>,
     pub index_uuid: Uuid,
+    #[serde(default)]
+    pub priority: Priority,
+    /// The id of the HTTP request that registered the update.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// The uid of the task of the update, missing for the updates registered before the tasks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_uid: Option<u64>,
 }
 
 impl<M> Pending<M> {
-    pub fn new(meta: M, update_id: u64, index_uuid: Uuid) -> Self {
+    pub fn new(
+        meta: M,
+        update_id: u64,
+        index_uuid: Uuid,
+        priority: Priority,
+        request_id: Option<String>,
+        task_uid: Option<u64>,
+    ) -> Self {
         Self {
             enqueued_at: Utc::now(),
             meta,
             update_id,
             index_uuid,
+            priority,
+            request_id,
+            task_uid,
         }
     }
 
@@ -25,6 +71,8 @@ impl<M> Pending<M> {
         Processing {
             from: self,
             started_processing_at: Utc::now(),
+            batch: None,
+            progress: None,
         }
     }
 
@@ -49,6 +97,10 @@ impl<M> Pending<M> {
 pub struct Processed<M, N> {
     pub success: N,
     pub processed_at: DateTime<Utc>,
+    /// The time, in seconds, the update took to be processed. Missing for the updates processed
+    /// before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
     #[serde(flatten)]
     pub from: Processing<M>,
 }
@@ -65,6 +117,77 @@ pub struct Processing<M> {
     #[serde(flatten)]
     pub from: Pending<M>,
     pub started_processing_at: DateTime<Utc>,
+    /// The ids of all the updates processed in the same batch as this one, if it was batched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch: Option<Vec<u64>>,
+    /// The progress of the indexing, only known while the update is being processed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<UpdateProgress>,
+}
+
+/// The progress of the indexing of an update, as reported by milli. The indexing goes through
+/// several steps, and the progress of a step is known once milli knows how much it has to do.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProgress {
+    pub step: String,
+    /// The number of the current step, starting at 1.
+    pub step_index: usize,
+    pub number_of_steps: usize,
+    /// The percentage of the indexing done, taking the previous steps into account.
+    pub percentage: usize,
+}
+
+impl From<UpdateIndexingStep> for UpdateProgress {
+    fn from(step: UpdateIndexingStep) -> Self {
+        use UpdateIndexingStep::*;
+
+        const NUMBER_OF_STEPS: usize = 4;
+
+        let (step, step_index, done, total) = match step {
+            TransformFromUserIntoGenericFormat { documents_seen } => (
+                "transformFromUserIntoGenericFormat",
+                1,
+                documents_seen,
+                None,
+            ),
+            ComputeIdsAndMergeDocuments {
+                documents_seen,
+                total_documents,
+            } => (
+                "computeIdsAndMergeDocuments",
+                2,
+                documents_seen,
+                Some(total_documents),
+            ),
+            IndexDocuments {
+                documents_seen,
+                total_documents,
+            } => ("indexDocuments", 3, documents_seen, Some(total_documents)),
+            MergeDataIntoFinalDatabase {
+                databases_seen,
+                total_databases,
+            } => (
+                "mergeDataIntoFinalDatabase",
+                4,
+                databases_seen,
+                Some(total_databases),
+            ),
+        };
+
+        let step_done = match total {
+            Some(total) if total > 0 => done.min(total) * 100 / total,
+            _ => 0,
+        };
+        let percentage = ((step_index - 1) * 100 + step_done) / NUMBER_OF_STEPS;
+
+        Self {
+            step: step.to_string(),
+            step_index,
+            number_of_steps: NUMBER_OF_STEPS,
+            percentage,
+        }
+    }
 }
 
 impl<M> Processing<M> {
@@ -80,21 +203,42 @@ impl<M> Processing<M> {
         &self.from.index_uuid
     }
 
+    pub fn request_id(&self) -> Option<&str> {
+        self.from.request_id.as_deref()
+    }
+
     pub fn process<N>(self, meta: N) -> Processed<M, N> {
+        let processed_at = Utc::now();
         Processed {
             success: meta,
+            duration: Some(duration(self.started_processing_at, processed_at)),
             from: self,
-            processed_at: Utc::now(),
+            processed_at,
         }
     }
 
     pub fn fail<E>(self, error: E) -> Failed<M, E> {
+        let failed_at = Utc::now();
         Failed {
+            duration: Some(duration(self.started_processing_at, failed_at)),
             from: self,
             error,
-            failed_at: Utc::now(),
+            failed_at,
+            document_errors: Vec::new(),
         }
     }
+
+    /// Fails the update with the message of `error`, reporting the documents that caused it, if
+    /// any.
+    pub fn fail_with(self, error: anyhow::Error) -> Failed<M, String> {
+        let document_errors = match error.downcast_ref::<InvalidDocuments>() {
+            Some(invalid) => invalid.errors.clone(),
+            None => Vec::new(),
+        };
+        let mut failed = self.fail(error.to_string());
+        failed.document_errors = document_errors;
+        failed
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone)]
@@ -109,6 +253,14 @@ impl<M> Aborted<M> {
     pub fn id(&self) -> u64 {
         self.from.id()
     }
+
+    pub fn aborted_at(&self) -> DateTime<Utc> {
+        self.aborted_at
+    }
+
+    pub fn pending(&self) -> &Pending<M> {
+        &self.from
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone)]
@@ -118,15 +270,34 @@ pub struct Failed<M, E> {
     from: Processing<M>,
     error: E,
     failed_at: DateTime<Utc>,
+    /// The time, in seconds, the update was processed before failing. Missing for the updates
+    /// failed before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+    /// The documents that made the update fail, with their position in the payload.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    document_errors: Vec<DocumentError>,
 }
 
 impl<M, E> Failed<M, E> {
     pub fn id(&self) -> u64 {
         self.from.id()
     }
+
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    pub fn failed_at(&self) -> DateTime<Utc> {
+        self.failed_at
+    }
+
+    pub fn processing(&self) -> &Processing<M> {
+        &self.from
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Clone)]
 #[serde(tag = "status", rename_all = "camelCase")]
 pub enum UpdateStatus<M, N, E> {
     Processing(Processing<M>),
@@ -147,6 +318,27 @@ impl<M, N, E> UpdateStatus<M, N, E> {
         }
     }
 
+    pub fn index_uuid(&self) -> &Uuid {
+        match self {
+            UpdateStatus::Processing(u) => u.index_uuid(),
+            UpdateStatus::Pending(u) => &u.index_uuid,
+            UpdateStatus::Processed(u) => u.from.index_uuid(),
+            UpdateStatus::Aborted(u) => &u.from.index_uuid,
+            UpdateStatus::Failed(u) => u.from.index_uuid(),
+        }
+    }
+
+    /// The uid of the task of the update, `None` if it was registered before the tasks.
+    pub fn task_uid(&self) -> Option<u64> {
+        match self {
+            UpdateStatus::Processing(u) => u.from.task_uid,
+            UpdateStatus::Pending(u) => u.task_uid,
+            UpdateStatus::Processed(u) => u.from.from.task_uid,
+            UpdateStatus::Aborted(u) => u.from.task_uid,
+            UpdateStatus::Failed(u) => u.from.from.task_uid,
+        }
+    }
+
     pub fn processed(&self) -> Option<&Processed<M, N>> {
         match self {
             UpdateStatus::Processed(p) => Some(p),
@@ -184,3 +376,33 @@ impl<M, N, E> From<Failed<M, E>> for UpdateStatus<M, N, E> {
         Self::Failed(other)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_update_progress() {
+        let progress =
+            UpdateProgress::from(UpdateIndexingStep::TransformFromUserIntoGenericFormat {
+                documents_seen: 10,
+            });
+        assert_eq!(progress.step_index, 1);
+        assert_eq!(progress.percentage, 0);
+
+        let progress = UpdateProgress::from(UpdateIndexingStep::IndexDocuments {
+            documents_seen: 50,
+            total_documents: 100,
+        });
+        assert_eq!(progress.step, "indexDocuments");
+        assert_eq!(progress.step_index, 3);
+        assert_eq!(progress.number_of_steps, 4);
+        assert_eq!(progress.percentage, 62);
+
+        let progress = UpdateProgress::from(UpdateIndexingStep::MergeDataIntoFinalDatabase {
+            databases_seen: 0,
+            total_databases: 0,
+        });
+        assert_eq!(progress.percentage, 75);
+    }
+}
@@ -1,14 +1,22 @@
-use std::{fs::create_dir_all, path::Path};
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use heed::{
     types::{ByteSlice, Str},
-    Database, Env, EnvOpenOptions,
+    CompactionOption, Database, Env, EnvOpenOptions,
 };
 use log::{info, warn};
+use meilisearch_error::{Code, ErrorCode};
+use parking_lot::RwLock;
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
+use super::map_size::{grown_map_size, is_map_full};
+use super::supervisor::{inbox, recv, supervise, Inbox};
+
 pub type Result<T> = std::result::Result<T, UuidError>;
 
 #[derive(Debug)]
@@ -17,6 +25,10 @@ enum UuidResolveMsg {
         uid: String,
         ret: oneshot::Sender<Result<Uuid>>,
     },
+    GetMultiple {
+        uids: Vec<String>,
+        ret: oneshot::Sender<Result<Vec<Uuid>>>,
+    },
     Create {
         uid: String,
         ret: oneshot::Sender<Result<Uuid>>,
@@ -26,47 +38,85 @@ enum UuidResolveMsg {
         ret: oneshot::Sender<Result<Uuid>>,
     },
     List {
+        prefix: Option<String>,
+        offset: usize,
+        limit: Option<usize>,
         ret: oneshot::Sender<Result<Vec<(String, Uuid)>>>,
     },
     Insert {
         uuid: Uuid,
         name: String,
         ret: oneshot::Sender<Result<()>>,
-    }
+    },
+    SnapshotRequest {
+        path: PathBuf,
+        ret: oneshot::Sender<Result<Vec<Uuid>>>,
+    },
+    Health {
+        ret: oneshot::Sender<()>,
+    },
 }
 
 struct UuidResolverActor<S> {
-    inbox: mpsc::Receiver<UuidResolveMsg>,
+    inbox: Inbox<UuidResolveMsg>,
     store: S,
+    /// Whether the uids are lowercased before being resolved.
+    case_insensitive: bool,
 }
 
 impl<S: UuidStore> UuidResolverActor<S> {
-    fn new(inbox: mpsc::Receiver<UuidResolveMsg>, store: S) -> Self {
-        Self { inbox, store }
+    fn new(inbox: Inbox<UuidResolveMsg>, store: S, case_insensitive: bool) -> Self {
+        Self {
+            inbox,
+            store,
+            case_insensitive,
+        }
     }
 
-    async fn run(mut self) {
+    fn normalize(&self, uid: String) -> String {
+        if self.case_insensitive {
+            uid.to_lowercase()
+        } else {
+            uid
+        }
+    }
+
+    async fn run(self) {
         use UuidResolveMsg::*;
 
         info!("uuid resolver started");
 
         loop {
-            match self.inbox.recv().await {
+            match recv(&self.inbox).await {
                 Some(Create { uid: name, ret }) => {
                     let _ = ret.send(self.handle_create(name).await);
                 }
                 Some(Get { uid: name, ret }) => {
                     let _ = ret.send(self.handle_get(name).await);
                 }
+                Some(GetMultiple { uids, ret }) => {
+                    let _ = ret.send(self.handle_get_many(uids).await);
+                }
                 Some(Delete { uid: name, ret }) => {
                     let _ = ret.send(self.handle_delete(name).await);
                 }
-                Some(List { ret }) => {
-                    let _ = ret.send(self.handle_list().await);
+                Some(List {
+                    prefix,
+                    offset,
+                    limit,
+                    ret,
+                }) => {
+                    let _ = ret.send(self.handle_list(prefix, offset, limit).await);
                 }
                 Some(Insert { ret, uuid, name }) => {
                     let _ = ret.send(self.handle_insert(name, uuid).await);
                 }
+                Some(SnapshotRequest { path, ret }) => {
+                    let _ = ret.send(self.handle_snapshot(path).await);
+                }
+                Some(Health { ret }) => {
+                    let _ = ret.send(());
+                }
                 // all senders have been dropped, need to quit.
                 None => break,
             }
@@ -76,43 +126,84 @@ impl<S: UuidStore> UuidResolverActor<S> {
     }
 
     async fn handle_create(&self, uid: String) -> Result<Uuid> {
-        if !is_index_uid_valid(&uid) {
-            return Err(UuidError::BadlyFormatted(uid));
-        }
+        let uid = self.normalize(uid);
+        validate_index_uid(&uid)?;
         self.store.create_uuid(uid, true).await
     }
 
     async fn handle_get(&self, uid: String) -> Result<Uuid> {
+        let uid = self.normalize(uid);
         self.store
             .get_uuid(uid.clone())
             .await?
             .ok_or(UuidError::UnexistingIndex(uid))
     }
 
+    async fn handle_get_many(&self, uids: Vec<String>) -> Result<Vec<Uuid>> {
+        let uids: Vec<_> = uids.into_iter().map(|uid| self.normalize(uid)).collect();
+        let uuids = self.store.get_many(uids.clone()).await?;
+        uids.into_iter()
+            .zip(uuids)
+            .map(|(uid, uuid)| uuid.ok_or(UuidError::UnexistingIndex(uid)))
+            .collect()
+    }
+
     async fn handle_delete(&self, uid: String) -> Result<Uuid> {
+        let uid = self.normalize(uid);
         self.store
             .delete(uid.clone())
             .await?
             .ok_or(UuidError::UnexistingIndex(uid))
     }
 
-    async fn handle_list(&self) -> Result<Vec<(String, Uuid)>> {
-        let result = self.store.list().await?;
+    async fn handle_list(
+        &self,
+        prefix: Option<String>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, Uuid)>> {
+        let result = self.store.list(prefix, offset, limit).await?;
         Ok(result)
     }
 
     async fn handle_insert(&self, uid: String, uuid: Uuid) -> Result<()> {
-        if !is_index_uid_valid(&uid) {
-            return Err(UuidError::BadlyFormatted(uid));
+        validate_index_uid(&uid)?;
+        let normalized = self.normalize(uid.clone());
+        if self.case_insensitive {
+            // Two distinct indexes whose uids only differ by their case can't both be imported.
+            if let Some(existing) = self.store.get_uuid(normalized.clone()).await? {
+                if existing != uuid {
+                    return Err(UuidError::CaseConflict(uid, normalized));
+                }
+            }
         }
-        self.store.insert(uid, uuid).await?;
+        self.store.insert(normalized, uuid).await?;
         Ok(())
     }
+
+    async fn handle_snapshot(&self, path: PathBuf) -> Result<Vec<Uuid>> {
+        self.store.snapshot(path).await
+    }
 }
 
-fn is_index_uid_valid(uid: &str) -> bool {
-    uid.chars()
-        .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_')
+/// The maximum length of an index uid, in bytes. This is a bit less than the maximum size of an
+/// lmdb key, so the uid can always be stored in the uuid store.
+const MAX_INDEX_UID_LENGTH: usize = 400;
+
+pub(super) fn validate_index_uid(uid: &str) -> Result<()> {
+    if uid.is_empty()
+        || !uid
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_')
+    {
+        return Err(UuidError::BadlyFormatted(uid.to_string()));
+    }
+
+    if uid.len() > MAX_INDEX_UID_LENGTH {
+        return Err(UuidError::UidTooLong(uid.len()));
+    }
+
+    Ok(())
 }
 
 #[derive(Clone)]
@@ -121,11 +212,20 @@ pub struct UuidResolverHandle {
 }
 
 impl UuidResolverHandle {
-    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+    pub fn new(
+        path: impl AsRef<Path>,
+        map_size: usize,
+        max_map_size: usize,
+        case_insensitive: bool,
+    ) -> anyhow::Result<Self> {
         let (sender, reveiver) = mpsc::channel(100);
-        let store = HeedUuidStore::new(path)?;
-        let actor = UuidResolverActor::new(reveiver, store);
-        tokio::spawn(actor.run());
+        let inbox = inbox(reveiver);
+        let path = path.as_ref().to_owned();
+        supervise("uuid resolver", move || {
+            let store = HeedUuidStore::new(&path, map_size, max_map_size)?;
+            let actor = UuidResolverActor::new(inbox.clone(), store, case_insensitive);
+            Ok(actor.run())
+        })?;
         Ok(Self { sender })
     }
 
@@ -133,51 +233,87 @@ impl UuidResolverHandle {
         let (ret, receiver) = oneshot::channel();
         let msg = UuidResolveMsg::Get { uid: name, ret };
         let _ = self.sender.send(msg).await;
-        Ok(receiver
-            .await
-            .expect("Uuid resolver actor has been killed")?)
+        Ok(receiver.await.map_err(|_| UuidError::Unavailable)??)
+    }
+
+    /// Resolves all the `names` at once, the uuids are returned in the same order as the names.
+    /// Returns an error if any of the indexes doesn't exist.
+    pub async fn get_many(&self, names: Vec<String>) -> Result<Vec<Uuid>> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = UuidResolveMsg::GetMultiple { uids: names, ret };
+        let _ = self.sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| UuidError::Unavailable)??)
     }
 
     pub async fn create(&self, name: String) -> anyhow::Result<Uuid> {
         let (ret, receiver) = oneshot::channel();
         let msg = UuidResolveMsg::Create { uid: name, ret };
         let _ = self.sender.send(msg).await;
-        Ok(receiver
-            .await
-            .expect("Uuid resolver actor has been killed")?)
+        Ok(receiver.await.map_err(|_| UuidError::Unavailable)??)
     }
 
     pub async fn delete(&self, name: String) -> anyhow::Result<Uuid> {
         let (ret, receiver) = oneshot::channel();
         let msg = UuidResolveMsg::Delete { uid: name, ret };
         let _ = self.sender.send(msg).await;
-        Ok(receiver
-            .await
-            .expect("Uuid resolver actor has been killed")?)
+        Ok(receiver.await.map_err(|_| UuidError::Unavailable)??)
     }
 
     pub async fn list(&self) -> anyhow::Result<Vec<(String, Uuid)>> {
+        self.list_range(None, 0, None).await
+    }
+
+    /// Lists the `limit` first indexes, after skipping `offset` of them, whose uid starts with
+    /// `prefix`. The indexes are returned in the lexicographic order of their uids.
+    pub async fn list_range(
+        &self,
+        prefix: Option<String>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<(String, Uuid)>> {
         let (ret, receiver) = oneshot::channel();
-        let msg = UuidResolveMsg::List { ret };
+        let msg = UuidResolveMsg::List {
+            prefix,
+            offset,
+            limit,
+            ret,
+        };
         let _ = self.sender.send(msg).await;
-        Ok(receiver
-            .await
-            .expect("Uuid resolver actor has been killed")?)
+        Ok(receiver.await.map_err(|_| UuidError::Unavailable)??)
     }
 
     pub async fn insert(&self, name: String, uuid: Uuid) -> anyhow::Result<()> {
         let (ret, receiver) = oneshot::channel();
         let msg = UuidResolveMsg::Insert { ret, name, uuid };
         let _ = self.sender.send(msg).await;
-        Ok(receiver
+        Ok(receiver.await.map_err(|_| UuidError::Unavailable)??)
+    }
+
+    /// Copies the uuid store in `path` and returns the uuids of all the indexes it contains.
+    pub async fn snapshot(&self, path: PathBuf) -> Result<Vec<Uuid>> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = UuidResolveMsg::SnapshotRequest { path, ret };
+        let _ = self.sender.send(msg).await;
+        Ok(receiver.await.map_err(|_| UuidError::Unavailable)??)
+    }
+
+    /// Checks that the uuid resolver actor is still alive and processing messages.
+    pub async fn health(&self) -> anyhow::Result<()> {
+        let (ret, receiver) = oneshot::channel();
+        let msg = UuidResolveMsg::Health { ret };
+        self.sender
+            .send(msg)
             .await
-            .expect("Uuid resolver actor has been killed")?)
+            .map_err(|_| anyhow::anyhow!("Uuid resolver actor has been killed"))?;
+        receiver
+            .await
+            .map_err(|_| anyhow::anyhow!("Uuid resolver actor has been killed"))
     }
 }
 
 #[derive(Debug, Error)]
 pub enum UuidError {
-    #[error("Name already exist.")]
+    #[error("Index already exists.")]
     NameAlreadyExist,
     #[error("Index \"{0}\" doesn't exist.")]
     UnexistingIndex(String),
@@ -187,8 +323,32 @@ pub enum UuidError {
     Heed(#[from] heed::Error),
     #[error("Uuid error: {0}")]
     Uuid(#[from] uuid::Error),
-    #[error("Badly formatted index uid: {0}")]
+    #[error("Index uid \"{0}\" is invalid, it must match the pattern `^[a-zA-Z0-9_-]+$`.")]
     BadlyFormatted(String),
+    #[error("Index uid is too long ({0} bytes), it can't be longer than {max} bytes.", max = MAX_INDEX_UID_LENGTH)]
+    UidTooLong(usize),
+    #[error("Index \"{0}\" conflicts with the existing index \"{1}\", index uids are case-insensitive.")]
+    CaseConflict(String, String),
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The uuid resolver is unavailable, please retry.")]
+    Unavailable,
+}
+
+impl ErrorCode for UuidError {
+    fn error_code(&self) -> Code {
+        match self {
+            UuidError::NameAlreadyExist | UuidError::CaseConflict(..) => Code::IndexAlreadyExists,
+            UuidError::UnexistingIndex(_) => Code::IndexNotFound,
+            UuidError::BadlyFormatted(_) => Code::InvalidIndexUid,
+            UuidError::UidTooLong(_) => Code::IndexUidTooLong,
+            UuidError::TokioTask(_)
+            | UuidError::Heed(_)
+            | UuidError::Uuid(_)
+            | UuidError::Io(_) => Code::Internal,
+            UuidError::Unavailable => Code::Unavailable,
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -197,34 +357,110 @@ trait UuidStore {
     // the uuid otherwise.
     async fn create_uuid(&self, uid: String, err: bool) -> Result<Uuid>;
     async fn get_uuid(&self, uid: String) -> Result<Option<Uuid>>;
+    async fn get_many(&self, uids: Vec<String>) -> Result<Vec<Option<Uuid>>>;
     async fn delete(&self, uid: String) -> Result<Option<Uuid>>;
-    async fn list(&self) -> Result<Vec<(String, Uuid)>>;
+    async fn list(
+        &self,
+        prefix: Option<String>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, Uuid)>>;
     async fn insert(&self, name: String, uuid: Uuid) -> Result<()>;
+    async fn snapshot(&self, path: PathBuf) -> Result<Vec<Uuid>>;
 }
 
 struct HeedUuidStore {
-    env: Env,
-    db: Database<Str, ByteSlice>,
+    // The environment is only `None` while it is being reopened with a bigger map size.
+    env: RwLock<Option<(Env, Database<Str, ByteSlice>)>>,
+    path: PathBuf,
+    map_size: AtomicUsize,
+    max_map_size: usize,
 }
 
 impl HeedUuidStore {
-    fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+    fn new(path: impl AsRef<Path>, map_size: usize, max_map_size: usize) -> anyhow::Result<Self> {
         let path = path.as_ref().join("index_uuids");
         create_dir_all(&path)?;
-        let mut options = EnvOpenOptions::new();
-        options.map_size(1_073_741_824); // 1GB
-        let env = options.open(path)?;
-        let db = env.create_database(None)?;
-        Ok(Self { env, db })
+        let (env, db) = open_uuid_store(&path, map_size)?;
+        Ok(Self {
+            env: RwLock::new(Some((env, db))),
+            path,
+            map_size: AtomicUsize::new(map_size),
+            max_map_size,
+        })
+    }
+
+    fn env(&self) -> Result<(Env, Database<Str, ByteSlice>)> {
+        self.env.read().clone().ok_or(UuidError::Unavailable)
+    }
+
+    /// Runs the write operation `op`, if the environment is full, it is reopened with a bigger
+    /// map size and the operation is retried.
+    async fn write<T, F>(&self, op: F) -> Result<T>
+    where
+        F: Fn(&Env, Database<Str, ByteSlice>) -> Result<T> + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        let op = Arc::new(op);
+        loop {
+            let (env, db) = self.env()?;
+            let to_run = op.clone();
+            match tokio::task::spawn_blocking(move || to_run(&env, db)).await? {
+                Err(UuidError::Heed(e)) if is_map_full(&e) => {
+                    let current = self.map_size.load(Ordering::Relaxed);
+                    match grown_map_size(&self.path, current, self.max_map_size) {
+                        Some(map_size) => self.reopen(map_size).await?,
+                        None => return Err(UuidError::Heed(e)),
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Reopens the environment with `map_size`. If it can't be reopened with this size, it is
+    /// reopened with the current one so that the store stays available.
+    async fn reopen(&self, map_size: usize) -> Result<()> {
+        let env = match self.env.write().take() {
+            Some((env, _)) => env,
+            // Another task is already reopening the environment.
+            None => return Err(UuidError::Unavailable),
+        };
+        let path = self.path.clone();
+        let current = self.map_size.load(Ordering::Relaxed);
+        let (result, reopened) = tokio::task::spawn_blocking(move || {
+            env.prepare_for_closing().wait();
+            info!("Resizing the uuid store to {} bytes.", map_size);
+            match open_uuid_store(&path, map_size) {
+                Ok(reopened) => (Ok(map_size), Ok(reopened)),
+                Err(e) => {
+                    warn!("Can't resize the uuid store: {}", e);
+                    (Err(e), open_uuid_store(&path, current))
+                }
+            }
+        })
+        .await?;
+        *self.env.write() = Some(reopened?);
+        self.map_size.store(result?, Ordering::Relaxed);
+        Ok(())
     }
 }
 
+fn open_uuid_store(
+    path: impl AsRef<Path>,
+    map_size: usize,
+) -> heed::Result<(Env, Database<Str, ByteSlice>)> {
+    let mut options = EnvOpenOptions::new();
+    options.map_size(map_size);
+    let env = options.open(path)?;
+    let db = env.create_database(None)?;
+    Ok((env, db))
+}
+
 #[async_trait::async_trait]
 impl UuidStore for HeedUuidStore {
     async fn create_uuid(&self, name: String, err: bool) -> Result<Uuid> {
-        let env = self.env.clone();
-        let db = self.db;
-        tokio::task::spawn_blocking(move || {
+        self.write(move |env, db| {
             let mut txn = env.write_txn()?;
             match db.get(&txn, &name)? {
                 Some(uuid) => {
@@ -243,12 +479,11 @@ impl UuidStore for HeedUuidStore {
                 }
             }
         })
-        .await?
+        .await
     }
 
     async fn get_uuid(&self, name: String) -> Result<Option<Uuid>> {
-        let env = self.env.clone();
-        let db = self.db;
+        let (env, db) = self.env()?;
         tokio::task::spawn_blocking(move || {
             let txn = env.read_txn()?;
             match db.get(&txn, &name)? {
@@ -262,10 +497,25 @@ impl UuidStore for HeedUuidStore {
         .await?
     }
 
-    async fn delete(&self, uid: String) -> Result<Option<Uuid>> {
-        let env = self.env.clone();
-        let db = self.db;
+    async fn get_many(&self, uids: Vec<String>) -> Result<Vec<Option<Uuid>>> {
+        let (env, db) = self.env()?;
         tokio::task::spawn_blocking(move || {
+            let txn = env.read_txn()?;
+            let mut uuids = Vec::with_capacity(uids.len());
+            for uid in uids {
+                let uuid = match db.get(&txn, &uid)? {
+                    Some(uuid) => Some(Uuid::from_slice(uuid)?),
+                    None => None,
+                };
+                uuids.push(uuid);
+            }
+            Ok(uuids)
+        })
+        .await?
+    }
+
+    async fn delete(&self, uid: String) -> Result<Option<Uuid>> {
+        self.write(move |env, db| {
             let mut txn = env.write_txn()?;
             match db.get(&txn, &uid)? {
                 Some(uuid) => {
@@ -277,16 +527,24 @@ impl UuidStore for HeedUuidStore {
                 None => Ok(None),
             }
         })
-        .await?
+        .await
     }
 
-    async fn list(&self) -> Result<Vec<(String, Uuid)>> {
-        let env = self.env.clone();
-        let db = self.db;
+    async fn list(
+        &self,
+        prefix: Option<String>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, Uuid)>> {
+        let (env, db) = self.env()?;
         tokio::task::spawn_blocking(move || {
             let txn = env.read_txn()?;
+            let iter: Box<dyn Iterator<Item = heed::Result<(&str, &[u8])>>> = match prefix {
+                Some(ref prefix) => Box::new(db.prefix_iter(&txn, prefix)?),
+                None => Box::new(db.iter(&txn)?),
+            };
             let mut entries = Vec::new();
-            for entry in db.iter(&txn)? {
+            for entry in iter.skip(offset).take(limit.unwrap_or(usize::MAX)) {
                 let (name, uuid) = entry?;
                 let uuid = Uuid::from_slice(uuid)?;
                 entries.push((name.to_owned(), uuid))
@@ -297,14 +555,34 @@ impl UuidStore for HeedUuidStore {
     }
 
     async fn insert(&self, name: String, uuid: Uuid) -> Result<()> {
-        let env = self.env.clone();
-        let db = self.db;
-        tokio::task::spawn_blocking(move || {
+        self.write(move |env, db| {
             let mut txn = env.write_txn()?;
             db.put(&mut txn, &name, uuid.as_bytes())?;
             txn.commit()?;
             Ok(())
         })
+        .await
+    }
+    async fn snapshot(&self, path: PathBuf) -> Result<Vec<Uuid>> {
+        let (env, db) = self.env()?;
+        tokio::task::spawn_blocking(move || {
+            // The write txn prevents any index from being created or deleted while the store is
+            // copied, so the snapshot is consistent with the returned uuids.
+            let txn = env.write_txn()?;
+            let mut entries = Vec::new();
+            for entry in db.iter(&txn)? {
+                let (_, uuid) = entry?;
+                let uuid = Uuid::from_slice(uuid)?;
+                entries.push(uuid)
+            }
+
+            let path = path.join("index_uuids");
+            create_dir_all(&path)?;
+            env.copy_to_path(path.join("data.mdb"), CompactionOption::Enabled)?;
+            drop(txn);
+
+            Ok(entries)
+        })
         .await?
     }
 }
@@ -0,0 +1,375 @@
+//! Checks the integrity of a database without starting the server: every environment is opened
+//! read-only and entirely read, and the uuid store, the update stores and the indexes are
+//! checked against each other.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use heed::flags::Flags;
+use heed::types::{ByteSlice, OwnedType, SerdeJson, Str, Unit};
+use heed::{Database, Env, EnvOpenOptions};
+use uuid::Uuid;
+
+use super::update_store::{UpdateStoreVersion, UPDATE_STORE_MAX_DBS, VERSION_KEY};
+use super::updates::{Aborted, Failed, Pending, Processed};
+use super::UpdateMeta;
+use crate::index::UpdateResult;
+
+type BEU64 = heed::zerocopy::U64<heed::byteorder::BE>;
+
+/// The maximum number of named databases of an index environment.
+const INDEX_MAX_DBS: u32 = 32;
+
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// The corruptions and the broken references, the database can't be used safely.
+    pub errors: Vec<Issue>,
+    /// The data that nothing references, it can be removed with `--repair-orphans purge`.
+    pub warnings: Vec<Issue>,
+    /// The number of indexes referenced by the uuid store.
+    pub indexes: usize,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, path: impl AsRef<Path>, message: impl fmt::Display) {
+        self.errors.push(Issue {
+            path: path.as_ref().to_owned(),
+            message: message.to_string(),
+        });
+    }
+
+    fn warning(&mut self, path: impl AsRef<Path>, message: impl fmt::Display) {
+        self.warnings.push(Issue {
+            path: path.as_ref().to_owned(),
+            message: message.to_string(),
+        });
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        write!(
+            f,
+            "{} indexes checked, {} errors, {} warnings.",
+            self.indexes,
+            self.errors.len(),
+            self.warnings.len()
+        )
+    }
+}
+
+/// Verifies the database at `db_path`. An error is only returned if the database can't be
+/// read at all, the issues found are listed in the report.
+pub fn verify_database(db_path: impl AsRef<Path>) -> anyhow::Result<VerifyReport> {
+    let db_path = db_path.as_ref();
+    if !db_path.exists() {
+        anyhow::bail!("There is no database at {}.", db_path.display());
+    }
+
+    let mut report = VerifyReport::default();
+
+    let uuids_path = db_path.join("index_uuids");
+    let indexes = match verify_uuid_store(&uuids_path) {
+        Ok(indexes) => indexes,
+        Err(e) => {
+            report.error(&uuids_path, e);
+            BTreeMap::new()
+        }
+    };
+    report.indexes = indexes.len();
+
+    let mut uids = HashMap::new();
+    for (uid, uuid) in &indexes {
+        if let Some(other) = uids.insert(uuid, uid) {
+            let message = format!("the indexes {} and {} share the uuid {}", other, uid, uuid);
+            report.error(&uuids_path, message);
+        }
+    }
+
+    let indexes_path = db_path.join("indexes");
+    let index_uuids = list_uuids(&indexes_path, "index-", &mut report);
+    for uuid in &index_uuids {
+        let path = indexes_path.join(format!("index-{}", uuid));
+        if let Err(e) = verify_index(&path) {
+            report.error(&path, e);
+        }
+        if !uids.contains_key(uuid) {
+            report.warning(&path, "no index references this database");
+        }
+    }
+
+    let updates_path = db_path.join("updates");
+    let update_files_path = updates_path.join("update_files");
+    let mut update_files = BTreeSet::new();
+    let mut pending_indexes = BTreeSet::new();
+    for uuid in list_uuids(&updates_path, "updates-", &mut report) {
+        let path = updates_path.join(format!("updates-{}", uuid));
+        match verify_update_store(&path, uuid, &mut report) {
+            Ok(files) => {
+                if !files.is_empty() {
+                    pending_indexes.insert(uuid);
+                }
+                update_files.extend(files);
+            }
+            Err(e) => report.error(&path, e),
+        }
+        if !uids.contains_key(&uuid) {
+            report.warning(&path, "no index references this update store");
+        }
+    }
+
+    for (uuid, uid) in &uids {
+        // The database of an index created by its first update is only created once the update
+        // is processed.
+        if !index_uuids.contains(*uuid) && !pending_indexes.contains(*uuid) {
+            let message = format!("the database of the index {} ({}) is missing", uid, uuid);
+            report.error(&indexes_path, message);
+        }
+    }
+
+    if let Ok(entries) = std::fs::read_dir(&update_files_path) {
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    report.error(&update_files_path, e);
+                    continue;
+                }
+            };
+            let referenced = path
+                .file_name()
+                .map_or(false, |name| update_files.iter().any(|f| f.ends_with(name)));
+            if !referenced {
+                report.warning(&path, "no pending update references this file");
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn open_read_only(path: &Path, max_dbs: u32) -> heed::Result<Env> {
+    let mut options = EnvOpenOptions::new();
+    options.max_dbs(max_dbs);
+    // Safety: the environment is only read.
+    unsafe {
+        options.flag(Flags::MdbRdOnly);
+    }
+    options.open(path)
+}
+
+fn open_database<KC: 'static, DC: 'static>(
+    env: &Env,
+    name: &str,
+) -> anyhow::Result<Database<KC, DC>> {
+    env.open_database(Some(name))?
+        .ok_or_else(|| anyhow::anyhow!("the {} database is missing", name))
+}
+
+/// Reads the uid and the uuid of all the indexes.
+fn verify_uuid_store(path: &Path) -> anyhow::Result<BTreeMap<String, Uuid>> {
+    let env = open_read_only(path, 1)?;
+    let db: Database<Str, ByteSlice> = env
+        .open_database(None)?
+        .ok_or_else(|| anyhow::anyhow!("the database of the uuids is missing"))?;
+
+    let rtxn = env.read_txn()?;
+    let mut indexes = BTreeMap::new();
+    for entry in db.iter(&rtxn)? {
+        let (uid, uuid) = entry?;
+        let uuid = Uuid::from_slice(uuid)
+            .map_err(|e| anyhow::anyhow!("invalid uuid for the index {}: {}", uid, e))?;
+        indexes.insert(uid.to_string(), uuid);
+    }
+    Ok(indexes)
+}
+
+/// Reads all the entries of all the databases of the index.
+fn verify_index(path: &Path) -> anyhow::Result<()> {
+    let env = open_read_only(path, INDEX_MAX_DBS)?;
+    let main: Database<ByteSlice, ByteSlice> = env
+        .open_database(None)?
+        .ok_or_else(|| anyhow::anyhow!("the main database is missing"))?;
+
+    let rtxn = env.read_txn()?;
+    let mut names = Vec::new();
+    for entry in main.iter(&rtxn)? {
+        let (key, _) = entry?;
+        if let Ok(name) = std::str::from_utf8(key) {
+            names.push(name.to_string());
+        }
+    }
+    drop(rtxn);
+
+    let mut databases = Vec::new();
+    for name in names {
+        match env.open_database::<ByteSlice, ByteSlice>(Some(name.as_str())) {
+            Ok(Some(db)) => databases.push((name, db)),
+            Ok(None) => (),
+            // The database is opened with flags that the verification doesn't know of.
+            Err(heed::Error::Mdb(heed::MdbError::Incompatible)) => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let rtxn = env.read_txn()?;
+    for (name, db) in databases {
+        for entry in db.iter(&rtxn)? {
+            entry.map_err(|e| anyhow::anyhow!("the {} database is corrupted: {}", name, e))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads all the updates of the update store of the index `uuid`, and returns the content files
+/// of its pending updates.
+fn verify_update_store(
+    path: &Path,
+    uuid: Uuid,
+    report: &mut VerifyReport,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let env = open_read_only(path, UPDATE_STORE_MAX_DBS)?;
+    // The stores created before the version was recorded have no metadata, they are migrated
+    // once opened.
+    let metadata: Option<Database<Str, Str>> = env.open_database(Some("metadata"))?;
+    let pending_meta: Database<OwnedType<BEU64>, SerdeJson<Pending<UpdateMeta>>> =
+        open_database(&env, "pending-meta")?;
+    let pending: Database<OwnedType<BEU64>, SerdeJson<PathBuf>> = open_database(&env, "pending")?;
+    let processed_meta: Database<OwnedType<BEU64>, SerdeJson<Processed<UpdateMeta, UpdateResult>>> =
+        open_database(&env, "processed-meta")?;
+    let failed_meta: Database<OwnedType<BEU64>, SerdeJson<Failed<UpdateMeta, String>>> =
+        open_database(&env, "failed-meta")?;
+    let aborted_meta: Database<OwnedType<BEU64>, SerdeJson<Aborted<UpdateMeta>>> =
+        open_database(&env, "aborted-meta")?;
+    // Only the stores that registered an update with a high priority have this database.
+    let high_priority: Option<Database<OwnedType<BEU64>, Unit>> =
+        env.open_database(Some("high-priority"))?;
+
+    let rtxn = env.read_txn()?;
+
+    if let Some(metadata) = metadata {
+        if let Some(version) = metadata.get(&rtxn, VERSION_KEY)? {
+            UpdateStoreVersion::parse(version)?;
+        }
+    }
+
+    let mut pending_ids = BTreeSet::new();
+    for entry in pending_meta.iter(&rtxn)? {
+        let (id, meta) = entry?;
+        if meta.index_uuid != uuid {
+            let message = format!(
+                "the update {} belongs to the index {}",
+                id.get(),
+                meta.index_uuid
+            );
+            report.error(path, message);
+        }
+        pending_ids.insert(id.get());
+    }
+
+    let mut update_files = Vec::new();
+    for entry in pending.iter(&rtxn)? {
+        let (id, content) = entry?;
+        if !pending_ids.remove(&id.get()) {
+            let message = format!("the content of the update {} has no metadata", id.get());
+            report.error(path, message);
+        }
+        if !content.exists() {
+            let message = format!(
+                "the content of the update {} is missing: {}",
+                id.get(),
+                content.display()
+            );
+            report.error(path, message);
+        }
+        update_files.push(content);
+    }
+    for id in pending_ids {
+        report.error(path, format!("the content of the update {} is missing", id));
+    }
+
+    if let Some(high_priority) = high_priority {
+        for entry in high_priority.iter(&rtxn)? {
+            let (id, ()) = entry?;
+            if pending.get(&rtxn, &id)?.is_none() {
+                let message = format!("the prioritized update {} isn't pending", id.get());
+                report.warning(path, message);
+            }
+        }
+    }
+
+    for entry in processed_meta.iter(&rtxn)? {
+        entry?;
+    }
+    for entry in failed_meta.iter(&rtxn)? {
+        entry?;
+    }
+    for entry in aborted_meta.iter(&rtxn)? {
+        entry?;
+    }
+
+    Ok(update_files)
+}
+
+/// Lists the uuids of the directories of `dir` named `{prefix}{uuid}`.
+fn list_uuids(dir: &Path, prefix: &str, report: &mut VerifyReport) -> BTreeSet<Uuid> {
+    let mut uuids = BTreeSet::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return uuids,
+        Err(e) => {
+            report.error(dir, e);
+            return uuids;
+        }
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.error(dir, e);
+                continue;
+            }
+        };
+        let uuid = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|uuid| Uuid::parse_str(uuid).ok());
+        match uuid {
+            Some(uuid) => {
+                uuids.insert(uuid);
+            }
+            None if entry.path().is_dir() && entry.file_name() != "update_files" => {
+                report.warning(entry.path(), "unexpected directory");
+            }
+            None => (),
+        }
+    }
+
+    uuids
+}
@@ -1,3 +1,4 @@
+pub mod analytics;
 pub mod data;
 pub mod error;
 pub mod helpers;
@@ -7,16 +8,19 @@ pub mod option;
 pub mod routes;
 
 pub use self::data::Data;
+pub use index_controller::{verify_database, VerifyReport};
 pub use option::Opt;
 
 #[macro_export]
 macro_rules! create_app {
     ($data:expr, $enable_frontend:expr) => {{
         use actix_cors::Cors;
+        use actix_web::dev::Service;
         use actix_web::middleware::TrailingSlash;
         use actix_web::App;
         use actix_web::{middleware, web};
-        use meilisearch_http::error::payload_error_handler;
+        use meilisearch_http::error::{payload_error_handler, with_verbose_internal_errors};
+        use meilisearch_http::helpers::{ReadOnlyReplica, RequestId};
         use meilisearch_http::routes::*;
 
         let app = App::new()
@@ -39,20 +43,46 @@ macro_rules! create_app {
             .configure(synonym::services)
             .configure(health::services)
             .configure(stats::services)
-            .configure(key::services);
-        //.configure(routes::dump::services);
+            .configure(key::services)
+            .configure(dump::services)
+            .configure(replication::services)
+            .configure(consistency::services)
+            .configure(tasks::services);
         let app = if $enable_frontend {
             app.service(load_html).service(load_css)
         } else {
             app.service(running)
         };
+        let cors = match $data.allowed_origins() {
+            Some(origins) => origins
+                .iter()
+                .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin)),
+            None => Cors::default().allow_any_origin().send_wildcard(),
+        };
+        let app = app
+            .wrap(ReadOnlyReplica)
+            .wrap(RequestId)
+            .wrap_fn({
+                let verbose = $data.verbose_internal_errors();
+                move |req, srv| with_verbose_internal_errors(verbose, srv.call(req))
+            });
         app.wrap(
-            Cors::default()
-                .send_wildcard()
+            cors.allow_any_method()
                 .allowed_headers(vec!["content-type", "x-meili-api-key"])
                 .max_age(86_400), // 24h
         )
-        .wrap(middleware::Logger::default())
+        .wrap_fn({
+            let analytics = $data.analytics().clone();
+            move |req, srv| {
+                if let Some(route) = req.match_pattern() {
+                    analytics.route_used(req.method().as_str(), &route);
+                }
+                srv.call(req)
+            }
+        })
+        .wrap(middleware::Logger::new(
+            r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#,
+        ))
         .wrap(middleware::Compress::default())
         .wrap(middleware::NormalizePath::new(TrailingSlash::Trim))
     }};
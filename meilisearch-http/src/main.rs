@@ -1,11 +1,13 @@
 use std::env;
 
+use actix_http::{HttpService, KeepAlive};
+use actix_server::Server;
+use actix_service::map_config;
+use actix_web::dev::AppConfig;
 use actix_web::HttpServer;
 use main_error::MainError;
-use meilisearch_http::{create_app, Data, Opt};
-use structopt::StructOpt;
-
-//mod analytics;
+use meilisearch_http::option::{Command, OrphanRepair};
+use meilisearch_http::{create_app, verify_database, Data, Opt};
 
 #[cfg(target_os = "linux")]
 #[global_allocator]
@@ -13,7 +15,21 @@ static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
 #[actix_web::main]
 async fn main() -> Result<(), MainError> {
-    let opt = Opt::from_args();
+    let opt = Opt::build()?;
+
+    if opt.print_config {
+        print_config(&opt);
+        return Ok(());
+    }
+
+    if opt.verify {
+        let report = verify_database(&opt.db_path)?;
+        println!("{}", report);
+        if !report.is_ok() {
+            return Err("The database is corrupted.".into());
+        }
+        return Ok(());
+    }
 
     #[cfg(all(not(debug_assertions), feature = "sentry"))]
     let _sentry = sentry::init((
@@ -30,7 +46,7 @@ async fn main() -> Result<(), MainError> {
 
     match opt.env.as_ref() {
         "production" => {
-            if opt.master_key.is_none() {
+            if opt.master_key.is_none() && opt.command.is_none() {
                 return Err(
                     "In production mode, the environment variable MEILI_MASTER_KEY is mandatory"
                         .into(),
@@ -50,32 +66,56 @@ async fn main() -> Result<(), MainError> {
         _ => unreachable!(),
     }
 
-    //if let Some(path) = &opt.import_snapshot {
-    //snapshot::load_snapshot(&opt.db_path, path, opt.ignore_snapshot_if_db_exists, opt.ignore_missing_snapshot)?;
-    //}
+    for warning in &opt.deprecation_warnings {
+        log::warn!("{}", warning);
+    }
 
     let data = Data::new(opt.clone())?;
 
-    //if !opt.no_analytics {
-    //let analytics_data = data.clone();
-    //let analytics_opt = opt.clone();
-    //thread::spawn(move || analytics::analytics_sender(analytics_data, analytics_opt));
-    //}
+    if let Some(command) = &opt.command {
+        run_command(&data, &opt, command).await?;
+        data.shutdown().await?;
+        return Ok(());
+    }
 
-    //if let Some(path) = &opt.import_dump {
-    //dump::import_dump(&data, path, opt.dump_batch_size)?;
-    //}
+    if let Some(path) = &opt.import_dump {
+        data.import_dump(path).await?;
+    }
 
-    //if opt.schedule_snapshot {
-    //snapshot::schedule_snapshot(data.clone(), &opt.snapshot_dir, opt.snapshot_interval_sec.unwrap_or(86400))?;
-    //}
+    let report = data.check_consistency(opt.repair_orphans).await?;
+    if !report.is_consistent() && opt.repair_orphans == OrphanRepair::Report {
+        log::warn!("The inconsistencies of the database can be repaired with `--repair-orphans`.");
+    }
 
     print_launch_resume(&opt, &data);
 
     let enable_frontend = opt.env != "production";
 
-    run_http(data, opt, enable_frontend).await?;
+    run_http(data.clone(), opt, enable_frontend).await?;
 
+    log::info!("Waiting for the updates being processed to finish.");
+    data.shutdown().await?;
+
+    Ok(())
+}
+
+/// Runs a command on the database, without starting the HTTP server.
+async fn run_command(data: &Data, opt: &Opt, command: &Command) -> anyhow::Result<()> {
+    match command {
+        Command::Dump => {
+            let info = data.create_dump_and_wait().await?;
+            println!("Dump {} created in {}.", info.uid, opt.dumps_dir.display());
+        }
+        Command::Import { path } => {
+            data.import_dump(path).await?;
+            data.wait_pending_updates().await?;
+            println!("Dump {} imported.", path.display());
+        }
+        Command::Reindex { uid } => {
+            data.reindex_and_wait(uid.clone()).await?;
+            println!("Index {} rebuilt.", uid);
+        }
+    }
     Ok(())
 }
 
@@ -84,21 +124,99 @@ async fn run_http(
     opt: Opt,
     enable_frontend: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let http_server = HttpServer::new(move || create_app!(&data, enable_frontend))
-        // Disable signals allows the server to terminate immediately when a user enter CTRL-C
-        .disable_signals();
-
-    if let Some(config) = opt.get_ssl_config()? {
-        http_server
-            .bind_rustls(opt.http_addr, config)?
-            .run()
-            .await?;
+    let ssl_config = opt.get_ssl_config()?;
+    let server = if opt.http2 && ssl_config.is_none() {
+        h2c_server(data, &opt, enable_frontend)?
     } else {
-        http_server.bind(opt.http_addr)?.run().await?;
-    }
+        let mut http_server = HttpServer::new(move || create_app!(&data, enable_frontend))
+            // The signals are handled below, so that the updates can be drained before exiting.
+            .disable_signals()
+            .keep_alive(keep_alive(&opt))
+            .client_timeout(opt.http_client_timeout_ms)
+            .shutdown_timeout(opt.http_shutdown_timeout_sec);
+        if let Some(workers) = opt.http_workers {
+            http_server = http_server.workers(workers.get());
+        }
+
+        match ssl_config {
+            Some(config) => http_server.bind_rustls(opt.http_addr, config)?.run(),
+            None => http_server.bind(opt.http_addr)?.run(),
+        }
+    };
+
+    let handle = server.clone();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        log::info!("Shutting down, no new request will be accepted.");
+        // Let the in-flight requests finish.
+        handle.stop(true).await;
+    });
+
+    server.await?;
     Ok(())
 }
 
+/// Builds a server speaking HTTP/2 with prior knowledge on cleartext connections, which
+/// `HttpServer` only supports over TLS.
+fn h2c_server(data: Data, opt: &Opt, enable_frontend: bool) -> std::io::Result<Server> {
+    let mut builder = Server::build()
+        .disable_signals()
+        .shutdown_timeout(opt.http_shutdown_timeout_sec);
+    if let Some(workers) = opt.http_workers {
+        builder = builder.workers(workers.get());
+    }
+    let keep_alive = keep_alive(opt);
+    let client_timeout = opt.http_client_timeout_ms;
+    let server = builder
+        .bind("meilisearch-h2c", &opt.http_addr, move || {
+            let app = create_app!(&data, enable_frontend);
+            HttpService::build()
+                .keep_alive(keep_alive)
+                .client_timeout(client_timeout)
+                .h2(map_config(app, |_| AppConfig::default()))
+                .tcp()
+        })?
+        .run();
+    Ok(server)
+}
+
+fn keep_alive(opt: &Opt) -> KeepAlive {
+    match opt.http_keep_alive_sec {
+        0 => KeepAlive::Disabled,
+        sec => KeepAlive::Timeout(sec),
+    }
+}
+
+/// Resolves when the process receives SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate =
+            signal(SignalKind::terminate()).expect("Could not register the SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => (),
+            _ = terminate.recv() => (),
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Prints the effective configuration, without the secrets.
+fn print_config(opt: &Opt) {
+    let mut opt = opt.clone();
+    opt.master_key = opt.master_key.map(|_| "<redacted>".to_string());
+    opt.replication_api_key = opt.replication_api_key.map(|_| "<redacted>".to_string());
+    #[cfg(all(not(debug_assertions), feature = "sentry"))]
+    {
+        opt.sentry_dsn = "<redacted>".to_string();
+    }
+    println!("{:#?}", opt);
+}
+
 pub fn print_launch_resume(opt: &Opt, data: &Data) {
     let ascii_name = r#"
 888b     d888          d8b 888 d8b  .d8888b.                                    888
@@ -117,6 +235,7 @@ pub fn print_launch_resume(opt: &Opt, data: &Data) {
     eprintln!("Server listening on:\t{:?}", opt.http_addr);
     eprintln!("Environment:\t\t{:?}", opt.env);
     eprintln!("Commit SHA:\t\t{:?}", env!("VERGEN_SHA").to_string());
+    eprintln!("Commit date:\t\t{:?}", env!("VERGEN_COMMIT_DATE").to_string());
     eprintln!(
         "Build date:\t\t{:?}",
         env!("VERGEN_BUILD_TIMESTAMP").to_string()
@@ -137,9 +256,9 @@ pub fn print_launch_resume(opt: &Opt, data: &Data) {
     );
 
     eprintln!(
-        "Amplitude Analytics:\t{:?}",
+        "Analytics:\t\t{:?}",
         if !opt.no_analytics {
-            "Enabled"
+            opt.analytics_endpoint.as_str()
         } else {
             "Disabled"
         }
@@ -1,7 +1,10 @@
+use std::ffi::OsString;
 use std::io::{BufReader, Read};
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
-use std::{error, fs};
+use std::{env, error, fs};
 
 use byte_unit::Byte;
 use grenad::CompressionType;
@@ -10,8 +13,12 @@ use rustls::{
     AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, NoClientAuth,
     RootCertStore,
 };
+use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
+use crate::helpers::compression::ArchiveCodec;
+use crate::helpers::ip_allowlist::IpNetwork;
+
 #[derive(Debug, Clone, StructOpt)]
 pub struct IndexerOpts {
     /// The amount of documents to skip before printing
@@ -62,6 +69,82 @@ pub struct IndexerOpts {
     /// Number of parallel jobs for indexing, defaults to # of CPUs.
     #[structopt(long)]
     pub indexing_jobs: Option<usize>,
+
+    /// Tunes the indexer for machines with little memory: the Grenad buffers and the linked hash
+    /// map cache are reduced, and the documents are indexed by a single job unless
+    /// `--indexing-jobs` is set. It is enabled automatically when the memory of the process is
+    /// limited to 2 GiB or less by its cgroup.
+    #[structopt(long, env = "MEILI_LOW_MEMORY")]
+    pub low_memory: bool,
+
+    /// The maximum number of distinct fields an index can contain. The document additions that
+    /// would exceed it fail, so that sending values as field names can't exhaust the field ids
+    /// of an index. Milli can't identify more than 256 fields per index.
+    #[structopt(long, env = "MEILI_MAX_FIELDS_PER_INDEX", default_value = "256")]
+    pub max_fields_per_index: usize,
+}
+
+/// The memory limit under which the low-memory indexing mode is enabled automatically.
+const LOW_MEMORY_THRESHOLD: u64 = 2 * 1024 * 1024 * 1024;
+
+/// The Grenad buffers in low-memory mode, when the memory limit is unknown.
+const LOW_MEMORY_MAX_MEMORY: u64 = 256 * 1024 * 1024;
+
+const LOW_MEMORY_LINKED_HASH_MAP_SIZE: usize = 100;
+
+/// Parses the content of a cgroup memory limit file, `max` or a number of bytes. The very large
+/// values cgroup v1 uses to mean that there is no limit are ignored.
+fn parse_cgroup_memory_limit(content: &str) -> Option<u64> {
+    match content.trim().parse::<u64>() {
+        Ok(limit) if limit < 1 << 60 => Some(limit),
+        _ => None,
+    }
+}
+
+/// Returns the memory limit of the cgroup of the process, for cgroup v2 and v1.
+fn cgroup_memory_limit() -> Option<u64> {
+    [
+        "/sys/fs/cgroup/memory.max",
+        "/sys/fs/cgroup/memory/memory.limit_in_bytes",
+    ]
+    .iter()
+    .filter_map(|path| fs::read_to_string(path).ok())
+    .find_map(|content| parse_cgroup_memory_limit(&content))
+}
+
+impl IndexerOpts {
+    /// Returns the options to index with, tuned for the low-memory mode when it is enabled or
+    /// when the memory of the process is limited to `LOW_MEMORY_THRESHOLD`.
+    pub fn tuned_for_memory(&self) -> Self {
+        self.tuned_for_memory_limit(cgroup_memory_limit())
+    }
+
+    fn tuned_for_memory_limit(&self, memory_limit: Option<u64>) -> Self {
+        let low_memory =
+            self.low_memory || memory_limit.map_or(false, |limit| limit <= LOW_MEMORY_THRESHOLD);
+        if !low_memory {
+            return self.clone();
+        }
+
+        // A quarter of the memory is left to the buffers, the rest being needed by the indexes
+        // and the searches.
+        let max_memory = memory_limit.map_or(LOW_MEMORY_MAX_MEMORY, |limit| limit / 4);
+        let max_memory = max_memory.min(self.max_memory.get_bytes());
+        log::info!(
+            "Low-memory indexing mode enabled, the indexer uses up to {} of memory.",
+            Byte::from_bytes(max_memory).get_appropriate_unit(true)
+        );
+
+        Self {
+            max_memory: Byte::from_bytes(max_memory),
+            linked_hash_map_size: self
+                .linked_hash_map_size
+                .min(LOW_MEMORY_LINKED_HASH_MAP_SIZE),
+            indexing_jobs: self.indexing_jobs.or(Some(1)),
+            low_memory: true,
+            ..self.clone()
+        }
+    }
 }
 
 impl Default for IndexerOpts {
@@ -76,14 +159,89 @@ impl Default for IndexerOpts {
             chunk_fusing_shrink_size: Byte::from_str("4GiB").unwrap(),
             enable_chunk_fusing: false,
             indexing_jobs: None,
+            low_memory: false,
+            max_fields_per_index: 256,
         }
     }
 }
 
 const POSSIBLE_ENV: [&str; 2] = ["development", "production"];
 
+/// What to do with the inconsistencies found between the uuid resolver and the disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanRepair {
+    /// Only reports the inconsistencies.
+    Report,
+    /// Removes the indexes whose data is missing, and the data of the orphan directories.
+    Purge,
+    /// Removes the indexes whose data is missing, and makes the orphan index databases
+    /// available again as indexes named `orphan-{uuid}`. The orphan update stores without a
+    /// database are removed.
+    Relink,
+}
+
+impl Default for OrphanRepair {
+    fn default() -> Self {
+        OrphanRepair::Report
+    }
+}
+
+impl FromStr for OrphanRepair {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "report" => Ok(OrphanRepair::Report),
+            "purge" => Ok(OrphanRepair::Purge),
+            "relink" => Ok(OrphanRepair::Relink),
+            _ => Err(format!(
+                "Invalid orphan repair `{}`, expected `report`, `purge` or `relink`.",
+                s
+            )),
+        }
+    }
+}
+
+/// The commands operating on the database without starting the server. The database must not be
+/// used by a running instance meanwhile.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Command {
+    /// Creates a dump of the database in the dumps directory, and exits.
+    Dump,
+    /// Imports the dump at `path` in the database, waits for its documents to be indexed, and
+    /// exits.
+    Import {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Rebuilds the index `uid` from its documents and its current settings, and exits. The
+    /// index is only replaced once it is entirely rebuilt.
+    Reindex { uid: String },
+}
+
 #[derive(Debug, Clone, StructOpt)]
 pub struct Opt {
+    /// A TOML file holding the values of the options, named after the long command line flags
+    /// (e.g. `db_path = "./data.ms"`). The command line flags and the environment variables
+    /// take precedence over the values of this file.
+    #[structopt(long, env = "MEILI_CONFIG_FILE_PATH")]
+    pub config_file_path: Option<PathBuf>,
+
+    /// Print the effective configuration, once merged with the configuration file and the
+    /// environment, and exit.
+    #[structopt(long)]
+    pub print_config: bool,
+
+    /// Check the integrity of the database, without modifying it, and exit. The exit code isn't
+    /// zero if the database is corrupted.
+    #[structopt(long)]
+    pub verify: bool,
+
+    /// Run a command on the database instead of starting the server.
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+
     /// The destination where the database must be created.
     #[structopt(long, env = "MEILI_DB_PATH", default_value = "./data.ms")]
     pub db_path: PathBuf,
@@ -92,10 +250,42 @@ pub struct Opt {
     #[structopt(long, env = "MEILI_HTTP_ADDR", default_value = "127.0.0.1:7700")]
     pub http_addr: String,
 
+    /// Serves HTTP/2 with prior knowledge on the cleartext connections, for the clients that
+    /// multiplex their requests over h2c. The HTTP/1.1 requests are then refused. Over TLS,
+    /// HTTP/2 is always negotiated with ALPN, alongside HTTP/1.1.
+    #[structopt(long, env = "MEILI_HTTP2")]
+    pub http2: bool,
+
+    /// The number of threads handling the HTTP requests, independently from the indexing
+    /// threads. Defaults to the number of physical cores.
+    #[structopt(long, env = "MEILI_HTTP_WORKERS")]
+    pub http_workers: Option<NonZeroUsize>,
+
+    /// The number of seconds an idle connection is kept open, waiting for the next request.
+    /// `0` closes the connections after each request.
+    #[structopt(long, env = "MEILI_HTTP_KEEP_ALIVE_SEC", default_value = "5")]
+    pub http_keep_alive_sec: usize,
+
+    /// The number of milliseconds a client has to send the head of its request, after which the
+    /// connection is closed with a `408 Request Timeout`. The body of the request isn't subject
+    /// to it. `0` disables the timeout.
+    #[structopt(long, env = "MEILI_HTTP_CLIENT_TIMEOUT_MS", default_value = "5000")]
+    pub http_client_timeout_ms: u64,
+
+    /// The number of seconds the in-flight requests have to finish once the server is asked to
+    /// stop, after which their connections are closed.
+    #[structopt(long, env = "MEILI_HTTP_SHUTDOWN_TIMEOUT_SEC", default_value = "30")]
+    pub http_shutdown_timeout_sec: u64,
+
     /// The master key allowing you to do everything on the server.
     #[structopt(long, env = "MEILI_MASTER_KEY")]
     pub master_key: Option<String>,
 
+    /// The number of seconds during which the previous master key, and the keys derived from it,
+    /// are still accepted after the master key is rotated.
+    #[structopt(long, env = "MEILI_MASTER_KEY_GRACE_PERIOD_SEC", default_value = "300")]
+    pub master_key_grace_period_sec: u64,
+
     /// The Sentry DSN to use for error reporting. This defaults to the MeiliSearch Sentry project.
     /// You can disable sentry all together using the `--no-sentry` flag or `MEILI_NO_SENTRY` environment variable.
     #[cfg(all(not(debug_assertions), feature = "sentry"))]
@@ -112,28 +302,137 @@ pub struct Opt {
     pub no_sentry: bool,
 
     /// This environment variable must be set to `production` if you are running in production.
-    /// If the server is running in development mode more logs will be displayed,
-    /// and the master key can be avoided which implies that there is no security on the updates routes.
+    /// If the server is running in development mode more logs will be displayed, the internal
+    /// errors are detailed in the responses, any origin can make cross-origin requests, and the
+    /// master key can be avoided which implies that there is no security on the updates routes.
     /// This is useful to debug when integrating the engine with another service.
     #[structopt(long, env = "MEILI_ENV", default_value = "development", possible_values = &POSSIBLE_ENV)]
     pub env: String,
 
+    /// The origins allowed to make cross-origin requests, separated by commas. Any origin is
+    /// allowed in development when none is given, none is in production.
+    #[structopt(long, env = "MEILI_HTTP_ALLOWED_ORIGINS", use_delimiter = true)]
+    pub http_allowed_origins: Vec<String>,
+
+    /// The addresses allowed to call the administrative routes (index deletion, settings updates,
+    /// keys, dumps and replication), as IPs or CIDR ranges separated by commas. These routes can
+    /// be called from any address when none is given.
+    #[structopt(long, env = "MEILI_ADMIN_ALLOWED_IPS", use_delimiter = true)]
+    pub admin_allowed_ips: Vec<IpNetwork>,
+
     /// Do not send analytics to Meili.
+    ///
+    /// The analytics are enabled by default: once an hour, an anonymous identifier of the
+    /// instance, the size of the database, the number of indexes and of documents, and the
+    /// number of calls to each route are sent. No index name, document or query is ever sent.
     #[structopt(long, env = "MEILI_NO_ANALYTICS")]
     pub no_analytics: bool,
 
-    /// The maximum size, in bytes, of the main lmdb database directory
-    #[structopt(long, env = "MEILI_MAX_MDB_SIZE", default_value = "100 GiB")]
-    pub max_mdb_size: Byte,
+    /// The endpoint the anonymous usage analytics are sent to, unless `--no-analytics` is set.
+    /// The Amplitude API key of Meili is only sent to the default endpoint.
+    #[structopt(
+        long,
+        env = "MEILI_ANALYTICS_ENDPOINT",
+        default_value = "https://api2.amplitude.com/2/httpapi"
+    )]
+    pub analytics_endpoint: String,
+
+    /// The maximum size, in bytes, of the lmdb database of each index.
+    #[structopt(
+        long,
+        alias = "max-mdb-size",
+        env = "MEILI_MAX_INDEX_SIZE",
+        default_value = "100 GiB"
+    )]
+    pub max_index_size: Byte,
 
-    /// The maximum size, in bytes, of the update lmdb database directory
-    #[structopt(long, env = "MEILI_MAX_UDB_SIZE", default_value = "10 GiB")]
-    pub max_udb_size: Byte,
+    /// The maximum size, in bytes, of the lmdb database of each update store.
+    #[structopt(
+        long,
+        alias = "max-udb-size",
+        env = "MEILI_MAX_UPDATE_STORE_SIZE",
+        default_value = "10 GiB"
+    )]
+    pub max_update_store_size: Byte,
+
+    /// The maximum size, in bytes, of the lmdb database mapping the index uids to their uuids.
+    #[structopt(long, env = "MEILI_MAX_UUID_STORE_SIZE", default_value = "1 GiB")]
+    pub max_uuid_store_size: Byte,
+
+    /// When an lmdb database is full, it is automatically reopened with a bigger size, up to
+    /// this size in bytes.
+    #[structopt(long, env = "MEILI_MAX_MAP_SIZE", default_value = "1 TiB")]
+    pub max_map_size: Byte,
+
+    /// Makes the index uids case-insensitive: the uids are lowercased when the indexes are
+    /// created, fetched or deleted.
+    #[structopt(long, env = "MEILI_CASE_INSENSITIVE_INDEX_UIDS")]
+    pub case_insensitive_index_uids: bool,
+
+    /// The maximum number of processed updates kept in the update store of each index, the
+    /// oldest ones are pruned first. All the updates are kept if not specified.
+    #[structopt(long, env = "MEILI_MAX_UPDATE_HISTORY")]
+    pub max_update_history: Option<usize>,
+
+    /// The number of days after which the processed updates are pruned from the update stores.
+    /// The updates are kept forever if not specified.
+    #[structopt(long, env = "MEILI_UPDATE_RETENTION_DAYS")]
+    pub update_retention_days: Option<u64>,
+
+    /// The maximum number of updates processed at the same time. The updates of a given index
+    /// are always processed one after the other, only the updates of different indexes can be
+    /// processed concurrently. It can't be 0.
+    #[structopt(long, env = "MEILI_MAX_CONCURRENT_UPDATES", default_value = "4")]
+    pub max_concurrent_updates: NonZeroUsize,
+
+    /// How long, in milliseconds, the updates of an index are accumulated before being
+    /// processed, so that more document additions are indexed in a single batch. A longer delay
+    /// makes the indexing faster, at the cost of the latency of each update. `0` processes the
+    /// updates as soon as they are registered.
+    #[structopt(long, env = "MEILI_AUTOBATCH_DEBOUNCE_MS", default_value = "0")]
+    pub autobatch_debounce_ms: u64,
+
+    /// The maximum number of updates waiting to be processed in a single index. The new updates
+    /// are refused with a `429 Too Many Requests` once it is reached.
+    #[structopt(long, env = "MEILI_MAX_PENDING_UPDATES_PER_INDEX")]
+    pub max_pending_updates_per_index: Option<usize>,
+
+    /// The maximum number of updates waiting to be processed in all the indexes. The new updates
+    /// are refused with a `429 Too Many Requests` once it is reached.
+    #[structopt(long, env = "MEILI_MAX_PENDING_UPDATES")]
+    pub max_pending_updates: Option<usize>,
+
+    /// The free disk space under which the new updates are refused with a
+    /// `507 Insufficient Storage`, so that the databases never run out of disk while writing.
+    /// `0` disables the check.
+    #[structopt(long, env = "MEILI_MIN_FREE_DISK_SPACE", default_value = "100 MiB")]
+    pub min_free_disk_space: Byte,
 
     /// The maximum size, in bytes, of accepted JSON payloads
     #[structopt(long, env = "MEILI_HTTP_PAYLOAD_SIZE_LIMIT", default_value = "10 MiB")]
     pub http_payload_size_limit: Byte,
 
+    /// The default time budget of the searches, in milliseconds, after which the best results
+    /// found so far are returned. A search can set its own budget with `timeoutMs`.
+    #[structopt(long, env = "MEILI_SEARCH_TIMEOUT_MS")]
+    pub search_timeout_ms: Option<u64>,
+
+    /// The maximum number of searches executed at the same time. The other searches wait for
+    /// their turn.
+    #[structopt(long, env = "MEILI_MAX_CONCURRENT_SEARCHES", default_value = "10")]
+    pub max_concurrent_searches: usize,
+
+    /// The maximum number of searches waiting to be executed. The new searches are refused with
+    /// a `503 Service Unavailable` once it is reached.
+    #[structopt(long, env = "MEILI_SEARCH_QUEUE_SIZE", default_value = "100")]
+    pub search_queue_size: usize,
+
+    /// The number of search results kept in memory, to answer the identical searches without
+    /// searching the index again. The results of an index are discarded as soon as an update is
+    /// applied to it. `0` disables the cache.
+    #[structopt(long, env = "MEILI_SEARCH_CACHE_SIZE", default_value = "0")]
+    pub search_cache_size: usize,
+
     /// Read server certificates from CERTFILE.
     /// This should contain PEM-format certificates
     /// in the right order (the first certificate should
@@ -182,6 +481,12 @@ pub struct Opt {
     #[structopt(long, requires = "import-snapshot")]
     pub ignore_snapshot_if_db_exists: bool,
 
+    /// What to do, at startup, with the indexes whose data is missing from the disk and with the
+    /// data directories that no index references: `report` only logs them, `purge` removes
+    /// them, and `relink` makes the orphan indexes available again as `orphan-{uuid}`.
+    #[structopt(long, env = "MEILI_REPAIR_ORPHANS", default_value = "report", possible_values = &["report", "purge", "relink"])]
+    pub repair_orphans: OrphanRepair,
+
     /// Defines the directory path where meilisearch will create snapshot each snapshot_time_gap.
     #[structopt(long, env = "MEILI_SNAPSHOT_DIR", default_value = "snapshots/")]
     pub snapshot_dir: PathBuf,
@@ -194,23 +499,159 @@ pub struct Opt {
     #[structopt(long, env = "MEILI_SNAPSHOT_INTERVAL_SEC")]
     pub snapshot_interval_sec: Option<u64>,
 
+    /// The number of incremental snapshots created between two full snapshots, `0` to only create
+    /// full snapshots. An incremental snapshot is written at `{snapshot}.{n}` and only contains
+    /// the parts of the database files changed since the previous snapshot, it is applied when
+    /// the full snapshot is imported. The first snapshot after a start is always a full one.
+    #[structopt(long, env = "MEILI_SNAPSHOT_INCREMENTS", default_value = "0")]
+    pub snapshot_increments: u32,
+
     /// Folder where dumps are created when the dump route is called.
     #[structopt(long, env = "MEILI_DUMPS_DIR", default_value = "dumps/")]
     pub dumps_dir: PathBuf,
 
-    /// Import a dump from the specified path, must be a `.tar.gz` file.
+    /// Import a dump from the specified path, must be an archive created by the dump route.
     #[structopt(long, conflicts_with = "import-snapshot")]
     pub import_dump: Option<PathBuf>,
 
+    /// The compression algorithm of the snapshots and the dumps. `zstd` is much faster than
+    /// `gzip` for a similar size, and `none` only writes the archive. The snapshots and dumps
+    /// are imported whatever the algorithm they were compressed with.
+    #[structopt(long, env = "MEILI_ARCHIVE_COMPRESSION", default_value = "gzip", possible_values = &["none", "gzip", "zstd"])]
+    pub archive_compression: ArchiveCodec,
+
+    /// The compression level of the snapshots and the dumps, from 0 to 9 with `gzip` and from 1
+    /// to 22 with `zstd`. Defaults to the default level of the algorithm.
+    #[structopt(long, env = "MEILI_ARCHIVE_COMPRESSION_LEVEL")]
+    pub archive_compression_level: Option<u32>,
+
     /// The batch size used in the importation process, the bigger it is the faster the dump is created.
     #[structopt(long, env = "MEILI_DUMP_BATCH_SIZE", default_value = "1024")]
     pub dump_batch_size: usize,
 
+    /// Records the updates in a log that read replicas follow, see `--replicate-from`. The
+    /// imported dumps and the restored indexes are not replicated.
+    #[structopt(long, env = "MEILI_REPLICATION_PRIMARY", conflicts_with = "replicate-from")]
+    pub replication_primary: bool,
+
+    /// Runs as a read replica of the primary at this URL, e.g. `http://10.0.0.1:7700`. The
+    /// replica applies the updates of the primary, and refuses the updates sent to it.
+    #[structopt(long, env = "MEILI_REPLICATE_FROM")]
+    pub replicate_from: Option<String>,
+
+    /// The key sent by the replica to the primary, its private or master key.
+    #[structopt(long, env = "MEILI_REPLICATION_API_KEY", requires = "replicate-from")]
+    pub replication_api_key: Option<String>,
+
+    /// The interval, in seconds, between two polls of the primary once the replica is up to date.
+    #[structopt(long, env = "MEILI_REPLICATION_POLL_INTERVAL_SEC", default_value = "1")]
+    pub replication_poll_interval_sec: u64,
+
+    /// The number of entries kept in the replication log of a primary, the older entries are
+    /// removed. A replica lagging further behind can't catch up, and must be restarted from a
+    /// snapshot of the primary.
+    #[structopt(
+        long,
+        env = "MEILI_REPLICATION_LOG_RETENTION",
+        default_value = "100000"
+    )]
+    pub replication_log_retention: NonZeroU64,
+
     #[structopt(flatten)]
     pub indexer_options: IndexerOpts,
+
+    /// The warnings about the deprecated environment variables that are set, logged once the
+    /// logger is initialized.
+    #[structopt(skip)]
+    pub deprecation_warnings: Vec<String>,
 }
 
+/// The deprecated environment variables, and the options that replaced them. A deprecated
+/// variable is only used when the option replacing it isn't set.
+const DEPRECATED_ENV_VARS: &[(&str, &str)] = &[
+    ("MEILI_MAX_MDB_SIZE", "max-index-size"),
+    ("MEILI_MAX_UDB_SIZE", "max-update-store-size"),
+];
+
 impl Opt {
+    /// Parses the options from the command line and the environment, completed with the
+    /// configuration file given by `--config-file-path`, if any.
+    pub fn build() -> Result<Self, Box<dyn error::Error>> {
+        Self::build_from_args(env::args_os().collect())
+    }
+
+    /// An option set on the command line overrides the same option set in the environment, which
+    /// itself overrides the configuration file.
+    fn build_from_args(mut args: Vec<OsString>) -> Result<Self, Box<dyn error::Error>> {
+        let matches = Self::clap().get_matches_from(&args);
+        let opt = Self::from_clap(&matches);
+
+        // The values of the deprecated environment variables and of the configuration file are
+        // inserted before the arguments of the command line, which may end with a subcommand.
+        let mut extra_args = Vec::new();
+        let mut deprecation_warnings = Vec::new();
+        for (var, name) in DEPRECATED_ENV_VARS {
+            let value = match env::var_os(var) {
+                Some(value) => value,
+                None => continue,
+            };
+            deprecation_warnings.push(format!(
+                "The environment variable `{}` is deprecated, use `{}` instead.",
+                var,
+                env_var_name(name)
+            ));
+            if matches.occurrences_of(name) == 0 && env::var_os(env_var_name(name)).is_none() {
+                extra_args.extend(vec![OsString::from(format!("--{}", name)), value]);
+            }
+        }
+
+        if let Some(ref path) = opt.config_file_path {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("cannot read the configuration file {:?}: {}", path, e))?;
+            let options: toml::value::Table = toml::from_str(&content)
+                .map_err(|e| format!("invalid configuration file {:?}: {}", path, e))?;
+
+            for (key, value) in options {
+                let name = key.replace('_', "-");
+                let deprecated_var_is_set = DEPRECATED_ENV_VARS
+                    .iter()
+                    .any(|(var, option)| *option == name && env::var_os(var).is_some());
+                if name == "config-file-path"
+                    || matches.occurrences_of(&name) > 0
+                    || env::var_os(env_var_name(&key)).is_some()
+                    || deprecated_var_is_set
+                {
+                    continue;
+                }
+
+                let flag = OsString::from(format!("--{}", name));
+                match value {
+                    toml::Value::Boolean(true) => extra_args.push(flag),
+                    toml::Value::Boolean(false) => (),
+                    toml::Value::String(s) => extra_args.extend(vec![flag, s.into()]),
+                    toml::Value::Integer(i) => extra_args.extend(vec![flag, i.to_string().into()]),
+                    toml::Value::Float(f) => extra_args.extend(vec![flag, f.to_string().into()]),
+                    _ => {
+                        return Err(format!(
+                            "invalid value for `{}` in the configuration file: expected a string, a number or a boolean",
+                            key
+                        )
+                        .into())
+                    }
+                }
+            }
+        }
+
+        let mut opt = if extra_args.is_empty() {
+            opt
+        } else {
+            args.splice(1..1, extra_args);
+            Self::from_iter(args)
+        };
+        opt.deprecation_warnings = deprecation_warnings;
+        Ok(opt)
+    }
+
     pub fn get_ssl_config(&self) -> Result<Option<rustls::ServerConfig>, Box<dyn error::Error>> {
         if let (Some(cert_path), Some(key_path)) = (&self.ssl_cert_path, &self.ssl_key_path) {
             let client_auth = match &self.ssl_auth_path {
@@ -254,6 +695,14 @@ impl Opt {
     }
 }
 
+/// The environment variable setting the option named `key`.
+fn env_var_name(key: &str) -> String {
+    match key {
+        "sentry_dsn" => "SENTRY_DSN".to_string(),
+        key => format!("MEILI_{}", key.replace('-', "_").to_uppercase()),
+    }
+}
+
 fn load_certs(filename: PathBuf) -> Result<Vec<rustls::Certificate>, Box<dyn error::Error>> {
     let certfile = fs::File::open(filename).map_err(|_| "cannot open certificate file")?;
     let mut reader = BufReader::new(certfile);
@@ -296,3 +745,112 @@ fn load_ocsp(filename: &Option<PathBuf>) -> Result<Vec<u8>, Box<dyn error::Error
 
     Ok(ret)
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+
+    fn build(args: &[&str], config: &str) -> Opt {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+
+        let mut all_args = vec!["meilisearch", "--config-file-path"];
+        all_args.push(file.path().to_str().unwrap());
+        all_args.extend_from_slice(args);
+        let args = all_args.into_iter().map(OsString::from).collect();
+
+        Opt::build_from_args(args).unwrap()
+    }
+
+    #[test]
+    fn test_config_file_values_are_used() {
+        let opt = build(
+            &[],
+            "dump_batch_size = 42\ncase-insensitive-index-uids = true\nsnapshot_dir = \"snaps\"",
+        );
+        assert_eq!(opt.dump_batch_size, 42);
+        assert!(opt.case_insensitive_index_uids);
+        assert_eq!(opt.snapshot_dir, PathBuf::from("snaps"));
+    }
+
+    #[test]
+    fn test_command_line_overrides_config_file() {
+        let opt = build(&["--dump-batch-size", "12"], "dump_batch_size = 42");
+        assert_eq!(opt.dump_batch_size, 12);
+    }
+
+    #[test]
+    fn test_config_file_values_with_a_subcommand() {
+        let opt = build(&["import", "dump.tar.gz"], "dump_batch_size = 42");
+        assert_eq!(opt.dump_batch_size, 42);
+        assert!(matches!(
+            opt.command,
+            Some(Command::Import { ref path }) if *path == PathBuf::from("dump.tar.gz")
+        ));
+    }
+
+    #[test]
+    fn test_invalid_config_file_value() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"dump_batch_size = [1, 2]").unwrap();
+        let args = vec![
+            OsString::from("meilisearch"),
+            OsString::from("--config-file-path"),
+            file.path().into(),
+        ];
+        assert!(Opt::build_from_args(args).is_err());
+    }
+
+    #[test]
+    fn test_deprecated_env_vars() {
+        env::set_var("MEILI_MAX_MDB_SIZE", "12 GiB");
+        let opt = build(&[], "max_index_size = \"42 GiB\"");
+        let overridden = build(&["--max-index-size", "2 GiB"], "");
+        env::remove_var("MEILI_MAX_MDB_SIZE");
+
+        assert_eq!(opt.max_index_size, Byte::from_str("12 GiB").unwrap());
+        assert_eq!(opt.deprecation_warnings.len(), 1);
+        assert_eq!(overridden.max_index_size, Byte::from_str("2 GiB").unwrap());
+    }
+
+    #[test]
+    fn test_zero_concurrent_updates_is_refused() {
+        let args = ["meilisearch", "--max-concurrent-updates", "0"];
+        assert!(Opt::from_iter_safe(&args).is_err());
+        let args = ["meilisearch", "--max-concurrent-updates", "2"];
+        let opt = Opt::from_iter_safe(&args).unwrap();
+        assert_eq!(opt.max_concurrent_updates.get(), 2);
+    }
+
+    #[test]
+    fn test_parse_cgroup_memory_limit() {
+        assert_eq!(parse_cgroup_memory_limit("1073741824\n"), Some(1 << 30));
+        assert_eq!(parse_cgroup_memory_limit("max\n"), None);
+        assert_eq!(parse_cgroup_memory_limit("9223372036854771712"), None);
+    }
+
+    #[test]
+    fn test_low_memory_tuning() {
+        let opt = build(&["--max-memory", "4 GiB"], "");
+        let indexer = &opt.indexer_options;
+
+        let tuned = indexer.tuned_for_memory_limit(None);
+        assert!(!tuned.low_memory);
+        assert_eq!(tuned.max_memory, indexer.max_memory);
+
+        let tuned = indexer.tuned_for_memory_limit(Some(1 << 30));
+        assert!(tuned.low_memory);
+        assert_eq!(tuned.max_memory.get_bytes(), 1 << 28);
+        assert_eq!(tuned.indexing_jobs, Some(1));
+
+        let opt = build(
+            &["--low-memory", "--indexing-jobs=2", "--max-memory=32GiB"],
+            "",
+        );
+        let tuned = opt.indexer_options.tuned_for_memory_limit(Some(64 << 30));
+        assert_eq!(tuned.max_memory.get_bytes(), 16 << 30);
+        assert_eq!(tuned.indexing_jobs, Some(2));
+    }
+}
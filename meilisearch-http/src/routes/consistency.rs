@@ -0,0 +1,41 @@
+use actix_web::{get, post};
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::error::ResponseError;
+use crate::helpers::{AdminIpAllowlist, Authentication};
+use crate::option::OrphanRepair;
+use crate::Data;
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_consistency).service(repair_consistency);
+}
+
+/// Reports the indexes whose data is missing from the disk, and the data that no index
+/// references.
+#[get("/consistency", wrap = "Authentication::Private")]
+async fn get_consistency(data: web::Data<Data>) -> Result<HttpResponse, ResponseError> {
+    let report = data.check_consistency(OrphanRepair::Report).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RepairBody {
+    #[serde(default)]
+    repair: OrphanRepair,
+}
+
+/// Reports and repairs the inconsistencies between the indexes and their data on the disk.
+#[post(
+    "/consistency",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
+async fn repair_consistency(
+    data: web::Data<Data>,
+    body: web::Json<RepairBody>,
+) -> Result<HttpResponse, ResponseError> {
+    let report = data.check_consistency(body.repair).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
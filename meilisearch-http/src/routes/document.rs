@@ -1,19 +1,28 @@
+use actix_web::dev::Decompress;
+use actix_web::http::header::CONTENT_ENCODING;
 use actix_web::web::Payload;
 use actix_web::{delete, get, post, put};
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::TryStreamExt;
 use indexmap::IndexMap;
 use log::error;
 use milli::update::{IndexDocumentsMethod, UpdateFormat};
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{json, Value};
 
-use crate::error::ResponseError;
-use crate::helpers::Authentication;
-use crate::routes::IndexParam;
+use crate::error::{Error, ResponseError};
+use crate::helpers::checksum::{Checksum, VerifiedPayload};
+use crate::helpers::{AdminIpAllowlist, Authentication};
+use crate::index_controller::Priority;
+use crate::routes::{IndexParam, UpdateParam};
 use crate::Data;
 
 const DEFAULT_RETRIEVE_DOCUMENTS_OFFSET: usize = 0;
 const DEFAULT_RETRIEVE_DOCUMENTS_LIMIT: usize = 20;
+/// The maximum number of documents that can be fetched by id in a single request.
+const MAX_FETCH_DOCUMENTS: usize = 1000;
+/// The number of documents read from the index at once when exporting them.
+const EXPORT_CHUNK_SIZE: usize = 1000;
 
 macro_rules! guard_content_type {
     ($fn_name:ident, $guard_value:literal) => {
@@ -42,15 +51,82 @@ struct DocumentParam {
 }
 
 pub fn services(cfg: &mut web::ServiceConfig) {
-    cfg.service(get_document)
+    // The count and export routes must be registered before the document route, which would
+    // match them too.
+    cfg.service(count_documents)
+        .service(export_documents)
+        .service(get_document)
         .service(delete_document)
         .service(get_all_documents)
+        .service(fetch_documents)
         .service(add_documents)
         .service(update_documents)
         .service(delete_documents)
         .service(clear_all_documents);
 }
 
+#[get(
+    "/indexes/{index_uid}/documents/count",
+    wrap = "Authentication::Public"
+)]
+async fn count_documents(
+    data: web::Data<Data>,
+    path: web::Path<IndexParam>,
+) -> Result<HttpResponse, ResponseError> {
+    match data.number_of_documents(path.index_uid.clone()).await {
+        Ok(count) => Ok(HttpResponse::Ok().json(json!({ "numberOfDocuments": count }))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ExportQuery {
+    #[serde(alias = "fields")]
+    attributes_to_retrieve: Option<String>,
+}
+
+/// Streams all the documents of the index as newline-delimited JSON. An error happening once
+/// the streaming has started interrupts the response.
+#[get(
+    "/indexes/{index_uid}/documents/export",
+    wrap = "Authentication::Public"
+)]
+async fn export_documents(
+    data: web::Data<Data>,
+    path: web::Path<IndexParam>,
+    params: web::Query<ExportQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let attributes_to_retrieve = params
+        .attributes_to_retrieve
+        .as_ref()
+        .map(|attrs| attrs.split(',').map(String::from).collect::<Vec<_>>());
+
+    let documents = data
+        .export_documents(
+            path.index_uid.clone(),
+            EXPORT_CHUNK_SIZE,
+            attributes_to_retrieve,
+        )
+        .await?
+        .map_err(|e| {
+            error!("Error while exporting the documents: {}", e);
+            ResponseError::from(e)
+        });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(Box::pin(documents)))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct GetDocumentQuery {
+    /// The comma separated list of the attributes to return, all the displayed attributes are
+    /// returned when it is missing.
+    fields: Option<String>,
+}
+
 #[get(
     "/indexes/{index_uid}/documents/{document_id}",
     wrap = "Authentication::Public"
@@ -58,17 +134,20 @@ pub fn services(cfg: &mut web::ServiceConfig) {
 async fn get_document(
     data: web::Data<Data>,
     path: web::Path<DocumentParam>,
+    params: web::Query<GetDocumentQuery>,
 ) -> Result<HttpResponse, ResponseError> {
     let index = path.index_uid.clone();
     let id = path.document_id.clone();
-    match data
-        .retrieve_document(index, id, None as Option<Vec<String>>)
-        .await
-    {
+    let fields = params.fields.as_ref().map(|fields| {
+        fields
+            .split(',')
+            .map(|field| field.trim().to_string())
+            .collect::<Vec<_>>()
+    });
+
+    match data.retrieve_document(index, id, fields).await {
         Ok(document) => Ok(HttpResponse::Ok().json(document)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -79,15 +158,18 @@ async fn get_document(
 async fn delete_document(
     data: web::Data<Data>,
     path: web::Path<DocumentParam>,
+    params: web::Query<UpdateParam>,
 ) -> Result<HttpResponse, ResponseError> {
     match data
-        .delete_documents(path.index_uid.clone(), vec![path.document_id.clone()])
+        .delete_documents(
+            path.index_uid.clone(),
+            vec![path.document_id.clone()],
+            params.priority,
+        )
         .await
     {
         Ok(result) => Ok(HttpResponse::Ok().json(result)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -96,6 +178,7 @@ async fn delete_document(
 struct BrowseQuery {
     offset: Option<usize>,
     limit: Option<usize>,
+    #[serde(alias = "fields")]
     attributes_to_retrieve: Option<String>,
 }
 
@@ -120,9 +203,57 @@ async fn get_all_documents(
         .await
     {
         Ok(docs) => Ok(HttpResponse::Ok().json(docs)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FetchDocumentsBody {
+    ids: Vec<Value>,
+    #[serde(alias = "fields")]
+    attributes_to_retrieve: Option<Vec<String>>,
+}
+
+/// Returns the documents matching the given ids, in the order of the ids. The ids that don't
+/// match any document are ignored.
+#[post(
+    "/indexes/{index_uid}/documents/fetch",
+    wrap = "Authentication::Public"
+)]
+async fn fetch_documents(
+    data: web::Data<Data>,
+    path: web::Path<IndexParam>,
+    body: web::Json<FetchDocumentsBody>,
+) -> Result<HttpResponse, ResponseError> {
+    let FetchDocumentsBody {
+        ids,
+        attributes_to_retrieve,
+    } = body.into_inner();
+
+    if ids.len() > MAX_FETCH_DOCUMENTS {
+        return Err(Error::BadRequest(format!(
+            "Too many ids, at most {} documents can be fetched at once.",
+            MAX_FETCH_DOCUMENTS
+        ))
+        .into());
+    }
+
+    let ids = ids
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .map(String::from)
+                .unwrap_or_else(|| v.to_string())
+        })
+        .collect();
+
+    match data
+        .retrieve_documents_by_ids(path.index_uid.clone(), ids, attributes_to_retrieve)
+        .await
+    {
+        Ok(docs) => Ok(HttpResponse::Ok().json(docs)),
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -130,16 +261,64 @@ async fn get_all_documents(
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 struct UpdateDocumentsQuery {
     primary_key: Option<String>,
+    #[serde(default)]
+    priority: Priority,
+    /// Only validates the documents and returns the validation report, without registering an
+    /// update.
+    #[serde(default)]
+    dry_run: bool,
+    /// Skips the invalid documents, which are reported in the update result, instead of failing
+    /// the whole addition.
+    #[serde(default)]
+    skip_invalid_documents: bool,
+}
+
+/// Decompresses the payload according to its `Content-Encoding` header, so that the documents can
+/// be sent compressed. The payload is checked against the checksum sent with it, if any, before
+/// being decompressed.
+fn decompressed(req: &HttpRequest, body: Payload) -> Result<Payload, ResponseError> {
+    if let Some(encoding) = req.headers().get(CONTENT_ENCODING) {
+        let encoding = encoding
+            .to_str()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if !matches!(encoding.as_str(), "gzip" | "br" | "deflate" | "identity") {
+            return Err(Error::UnsupportedContentEncoding(encoding).into());
+        }
+    }
+    let body = match Checksum::from_headers(req.headers())? {
+        Some(checksum) => {
+            let stream = VerifiedPayload::new(body, checksum);
+            Payload(actix_web::dev::Payload::Stream(Box::pin(stream)))
+        }
+        None => body,
+    };
+    let stream = Decompress::from_headers(body, req.headers());
+    Ok(Payload(actix_web::dev::Payload::Stream(Box::pin(stream))))
 }
 
 /// Route used when the payload type is "application/json"
 #[post("/indexes/{index_uid}/documents", wrap = "Authentication::Private")]
 async fn add_documents(
+    req: HttpRequest,
     data: web::Data<Data>,
     path: web::Path<IndexParam>,
     params: web::Query<UpdateDocumentsQuery>,
     body: Payload,
 ) -> Result<HttpResponse, ResponseError> {
+    let body = decompressed(&req, body)?;
+    if params.dry_run {
+        let report = data
+            .validate_documents(
+                path.into_inner().index_uid,
+                body,
+                params.primary_key.clone(),
+            )
+            .await?;
+        return Ok(HttpResponse::Ok().json(report));
+    }
+
     let addition_result = data
         .add_documents(
             path.into_inner().index_uid,
@@ -147,14 +326,14 @@ async fn add_documents(
             UpdateFormat::Json,
             body,
             params.primary_key.clone(),
+            params.skip_invalid_documents,
+            params.priority,
         )
         .await;
 
     match addition_result {
         Ok(update) => Ok(HttpResponse::Ok().json(update)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -184,11 +363,24 @@ async fn update_documents_default(
 
 #[put("/indexes/{index_uid}/documents", wrap = "Authentication::Private")]
 async fn update_documents(
+    req: HttpRequest,
     data: web::Data<Data>,
     path: web::Path<IndexParam>,
     params: web::Query<UpdateDocumentsQuery>,
     body: web::Payload,
 ) -> Result<HttpResponse, ResponseError> {
+    let body = decompressed(&req, body)?;
+    if params.dry_run {
+        let report = data
+            .validate_documents(
+                path.into_inner().index_uid,
+                body,
+                params.primary_key.clone(),
+            )
+            .await?;
+        return Ok(HttpResponse::Ok().json(report));
+    }
+
     let addition_result = data
         .add_documents(
             path.into_inner().index_uid,
@@ -196,14 +388,14 @@ async fn update_documents(
             UpdateFormat::Json,
             body,
             params.primary_key.clone(),
+            params.skip_invalid_documents,
+            params.priority,
         )
         .await;
 
     match addition_result {
         Ok(update) => Ok(HttpResponse::Ok().json(update)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -214,6 +406,7 @@ async fn update_documents(
 async fn delete_documents(
     data: web::Data<Data>,
     path: web::Path<IndexParam>,
+    params: web::Query<UpdateParam>,
     body: web::Json<Vec<Value>>,
 ) -> Result<HttpResponse, ResponseError> {
     let ids = body
@@ -225,23 +418,30 @@ async fn delete_documents(
         })
         .collect();
 
-    match data.delete_documents(path.index_uid.clone(), ids).await {
+    match data
+        .delete_documents(path.index_uid.clone(), ids, params.priority)
+        .await
+    {
         Ok(result) => Ok(HttpResponse::Ok().json(result)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
-#[delete("/indexes/{index_uid}/documents", wrap = "Authentication::Private")]
+#[delete(
+    "/indexes/{index_uid}/documents",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
 async fn clear_all_documents(
     data: web::Data<Data>,
     path: web::Path<IndexParam>,
+    params: web::Query<UpdateParam>,
 ) -> Result<HttpResponse, ResponseError> {
-    match data.clear_documents(path.index_uid.clone()).await {
+    match data
+        .clear_documents(path.index_uid.clone(), params.priority)
+        .await
+    {
         Ok(update) => Ok(HttpResponse::Ok().json(update)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
@@ -1,31 +1,21 @@
-use std::fs::File;
-use std::path::Path;
-
 use actix_web::{get, post};
-use actix_web::{HttpResponse, web};
-use serde::{Deserialize, Serialize};
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
 
-use crate::dump::{DumpInfo, DumpStatus, compressed_dumps_dir, init_dump_process};
+use crate::error::ResponseError;
+use crate::helpers::{AdminIpAllowlist, Authentication};
 use crate::Data;
-use crate::error::{Error, ResponseError};
-use crate::helpers::Authentication;
 
 pub fn services(cfg: &mut web::ServiceConfig) {
     cfg.service(trigger_dump)
-        .service(get_dump_status);
+        .service(get_dump_status)
+        .service(restore_index);
 }
 
-#[post("/dumps", wrap = "Authentication::Private")]
-async fn trigger_dump(
-    data: web::Data<Data>,
-) -> Result<HttpResponse, ResponseError> {
-    todo!()
-}
-
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct DumpStatusResponse {
-    status: String,
+#[post("/dumps", wrap = "Authentication::Private", wrap = "AdminIpAllowlist")]
+async fn trigger_dump(data: web::Data<Data>) -> Result<HttpResponse, ResponseError> {
+    let info = data.create_dump()?;
+    Ok(HttpResponse::Accepted().json(info))
 }
 
 #[derive(Deserialize)]
@@ -33,10 +23,45 @@ struct DumpParam {
     dump_uid: String,
 }
 
-#[get("/dumps/{dump_uid}/status", wrap = "Authentication::Private")]
+#[get(
+    "/dumps/{dump_uid}/status",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
 async fn get_dump_status(
     data: web::Data<Data>,
     path: web::Path<DumpParam>,
 ) -> Result<HttpResponse, ResponseError> {
-    todo!()
+    let info = data.dump_info(path.into_inner().dump_uid)?;
+    Ok(HttpResponse::Ok().json(info))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RestoreIndexBody {
+    index_uid: String,
+    /// The uid the index is restored under, its uid in the dump by default.
+    target_uid: Option<String>,
+}
+
+/// Restores a single index of a dump, replacing the index with the same uid, if any. The
+/// documents are available once the updates registered by the restoration are processed.
+#[post(
+    "/dumps/{dump_uid}/restore",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
+async fn restore_index(
+    data: web::Data<Data>,
+    path: web::Path<DumpParam>,
+    body: web::Json<RestoreIndexBody>,
+) -> Result<HttpResponse, ResponseError> {
+    let RestoreIndexBody {
+        index_uid,
+        target_uid,
+    } = body.into_inner();
+    let meta = data
+        .restore_index(path.into_inner().dump_uid, index_uid, target_uid)
+        .await?;
+    Ok(HttpResponse::Accepted().json(meta))
 }
@@ -1,13 +1,37 @@
+use std::time::Duration;
+
 use actix_web::get;
 use actix_web::{web, HttpResponse};
 
 use crate::error::ResponseError;
+use crate::Data;
+
+/// Time given to the actors to answer the readiness probe.
+const READINESS_DEADLINE: Duration = Duration::from_secs(5);
 
 pub fn services(cfg: &mut web::ServiceConfig) {
-    cfg.service(get_health);
+    cfg.service(get_health)
+        .service(get_liveness)
+        .service(get_readiness);
 }
 
 #[get("/health")]
 async fn get_health() -> Result<HttpResponse, ResponseError> {
     Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "available" })))
 }
+
+/// Liveness probe, always succeeds as long as the process is able to answer http requests.
+#[get("/health/live")]
+async fn get_liveness() -> Result<HttpResponse, ResponseError> {
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "available" })))
+}
+
+/// Readiness probe, succeeds only if all the actors answer before the `READINESS_DEADLINE`.
+#[get("/health/ready")]
+async fn get_readiness(data: web::Data<Data>) -> Result<HttpResponse, ResponseError> {
+    match data.ready(READINESS_DEADLINE).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "available" }))),
+        Err(e) => Ok(HttpResponse::ServiceUnavailable()
+            .json(serde_json::json!({ "status": "unavailable", "error": e.to_string() }))),
+    }
+}
@@ -1,10 +1,17 @@
+use std::time::Duration;
+
+use actix_web::web::Bytes;
 use actix_web::{delete, get, post, put};
 use actix_web::{web, HttpResponse};
+use async_stream::stream;
 use chrono::{DateTime, Utc};
+use futures::{pin_mut, StreamExt};
 use serde::{Deserialize, Serialize};
 
-use crate::error::ResponseError;
-use crate::helpers::Authentication;
+use crate::error::{Error, ResponseError};
+use crate::helpers::{AdminIpAllowlist, Authentication};
+use crate::index::Settings;
+use crate::index_controller::WaitFor;
 use crate::routes::IndexParam;
 use crate::Data;
 
@@ -14,17 +21,35 @@ pub fn services(cfg: &mut web::ServiceConfig) {
         .service(create_index)
         .service(update_index)
         .service(delete_index)
+        .service(clone_index)
+        .service(reindex)
+        // Registered before `get_update_status`, whose pattern also matches its path.
+        .service(stream_updates)
         .service(get_update_status)
         .service(get_all_updates_status);
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ListIndexesQuery {
+    offset: Option<usize>,
+    limit: Option<usize>,
+    /// Only returns the indexes whose uid starts with this prefix.
+    uid: Option<String>,
+}
+
 #[get("/indexes", wrap = "Authentication::Private")]
-async fn list_indexes(data: web::Data<Data>) -> Result<HttpResponse, ResponseError> {
-    match data.list_indexes().await {
+async fn list_indexes(
+    data: web::Data<Data>,
+    params: web::Query<ListIndexesQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let params = params.into_inner();
+    match data
+        .list_indexes(params.uid, params.offset.unwrap_or_default(), params.limit)
+        .await
+    {
         Ok(indexes) => Ok(HttpResponse::Ok().json(indexes)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -35,9 +60,7 @@ async fn get_index(
 ) -> Result<HttpResponse, ResponseError> {
     match data.index(path.index_uid.clone()).await {
         Ok(meta) => Ok(HttpResponse::Ok().json(meta)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -56,9 +79,7 @@ async fn create_index(
     let body = body.into_inner();
     match data.create_index(body.uid, body.primary_key).await {
         Ok(meta) => Ok(HttpResponse::Ok().json(meta)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -91,31 +112,127 @@ async fn update_index(
         .await
     {
         Ok(meta) => Ok(HttpResponse::Ok().json(meta)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
-#[delete("/indexes/{index_uid}", wrap = "Authentication::Private")]
+#[delete(
+    "/indexes/{index_uid}",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
 async fn delete_index(
     data: web::Data<Data>,
     path: web::Path<IndexParam>,
 ) -> Result<HttpResponse, ResponseError> {
     match data.delete_index(path.index_uid.clone()).await {
         Ok(_) => Ok(HttpResponse::NoContent().finish()),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CloneIndexRequest {
+    uid: String,
+}
+
+#[post(
+    "/indexes/{index_uid}/clone",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
+async fn clone_index(
+    data: web::Data<Data>,
+    path: web::Path<IndexParam>,
+    body: web::Json<CloneIndexRequest>,
+) -> Result<HttpResponse, ResponseError> {
+    match data
+        .clone_index(path.into_inner().index_uid, body.into_inner().uid)
+        .await
+    {
+        Ok(update) => Ok(HttpResponse::Accepted().json(update)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[post(
+    "/indexes/{index_uid}/reindex",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
+async fn reindex(
+    data: web::Data<Data>,
+    path: web::Path<IndexParam>,
+    body: web::Json<Settings>,
+) -> Result<HttpResponse, ResponseError> {
+    match data
+        .reindex(path.into_inner().index_uid, body.into_inner())
+        .await
+    {
+        Ok(update) => Ok(HttpResponse::Accepted().json(update)),
+        Err(e) => Err(e.into()),
     }
 }
 
+/// The interval between two comments sent on a stream of update events, so that an idle stream
+/// isn't closed by the proxies.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Streams the new status of the updates of the index as server-sent events, each time an update
+/// changes state. The data of each event is the update, as returned by `get_update_status`.
+#[get(
+    "/indexes/{index_uid}/updates/stream",
+    wrap = "Authentication::Private"
+)]
+async fn stream_updates(
+    data: web::Data<Data>,
+    path: web::Path<IndexParam>,
+) -> Result<HttpResponse, ResponseError> {
+    let updates = data.update_events(path.into_inner().index_uid).await?;
+    let events = stream! {
+        pin_mut!(updates);
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+        loop {
+            tokio::select! {
+                update = updates.next() => match update {
+                    Some(update) => match serde_json::to_string(&update) {
+                        Ok(json) => yield Ok(Bytes::from(format!("data: {}\n\n", json))),
+                        Err(e) => {
+                            yield Err(ResponseError::from(Error::from(e)));
+                            break;
+                        }
+                    },
+                    None => break,
+                },
+                _ = keep_alive.tick() => yield Ok(Bytes::from_static(b": keep-alive\n\n")),
+            }
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(Box::pin(events)))
+}
+
 #[derive(Deserialize)]
 struct UpdateParam {
     index_uid: String,
     update_id: u64,
 }
 
+/// The time an update status is waited for when no timeout is given.
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateStatusQuery {
+    /// Waits for the update to reach this status before responding.
+    wait_for: Option<WaitFor>,
+    timeout_ms: Option<u64>,
+}
+
 #[get(
     "/indexes/{index_uid}/updates/{update_id}",
     wrap = "Authentication::Private"
@@ -123,16 +240,25 @@ struct UpdateParam {
 async fn get_update_status(
     data: web::Data<Data>,
     path: web::Path<UpdateParam>,
+    query: web::Query<UpdateStatusQuery>,
 ) -> Result<HttpResponse, ResponseError> {
     let params = path.into_inner();
-    let result = data
-        .get_update_status(params.index_uid, params.update_id)
-        .await;
+    let result = match query.wait_for {
+        Some(wait_for) => {
+            let duration = query
+                .timeout_ms
+                .map_or(DEFAULT_WAIT_TIMEOUT, Duration::from_millis);
+            data.wait_update_status(params.index_uid, params.update_id, wait_for, duration)
+                .await
+        }
+        None => {
+            data.get_update_status(params.index_uid, params.update_id)
+                .await
+        }
+    };
     match result {
         Ok(meta) => Ok(HttpResponse::Ok().json(meta)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -144,8 +270,6 @@ async fn get_all_updates_status(
     let result = data.get_updates_status(path.into_inner().index_uid).await;
     match result {
         Ok(metas) => Ok(HttpResponse::Ok().json(metas)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
@@ -1,13 +1,14 @@
-use actix_web::get;
 use actix_web::web;
 use actix_web::HttpResponse;
-use serde::Serialize;
+use actix_web::{get, post};
+use serde::{Deserialize, Serialize};
 
-use crate::helpers::Authentication;
+use crate::error::ResponseError;
+use crate::helpers::{AdminIpAllowlist, Authentication};
 use crate::Data;
 
 pub fn services(cfg: &mut web::ServiceConfig) {
-    cfg.service(list);
+    cfg.service(list).service(rotate);
 }
 
 #[derive(Serialize)]
@@ -16,11 +17,35 @@ struct KeysResponse {
     public: Option<String>,
 }
 
-#[get("/keys", wrap = "Authentication::Admin")]
+#[get("/keys", wrap = "Authentication::Admin", wrap = "AdminIpAllowlist")]
 async fn list(data: web::Data<Data>) -> HttpResponse {
-    let api_keys = data.api_keys.clone();
+    let api_keys = data.api_keys();
     HttpResponse::Ok().json(&KeysResponse {
         private: api_keys.private,
         public: api_keys.public,
     })
 }
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RotateKeyBody {
+    master_key: String,
+}
+
+#[post(
+    "/keys/rotate",
+    wrap = "Authentication::Admin",
+    wrap = "AdminIpAllowlist"
+)]
+async fn rotate(
+    data: web::Data<Data>,
+    body: web::Json<RotateKeyBody>,
+) -> Result<HttpResponse, ResponseError> {
+    match data.rotate_master_key(body.into_inner().master_key) {
+        Ok(api_keys) => Ok(HttpResponse::Ok().json(&KeysResponse {
+            private: api_keys.private,
+            public: api_keys.public,
+        })),
+        Err(e) => Err(e.into()),
+    }
+}
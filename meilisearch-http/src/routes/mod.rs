@@ -1,22 +1,35 @@
 use actix_web::{get, HttpResponse};
 use serde::{Deserialize, Serialize};
 
+use crate::index_controller::Priority;
+
+pub mod consistency;
 pub mod document;
+pub mod dump;
 pub mod health;
 pub mod index;
 pub mod key;
+pub mod replication;
 pub mod search;
 pub mod settings;
 pub mod stats;
 pub mod stop_words;
 pub mod synonym;
-//pub mod dump;
+pub mod tasks;
 
 #[derive(Deserialize)]
 pub struct IndexParam {
     index_uid: String,
 }
 
+/// The query parameters accepted by the routes registering an update.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct UpdateParam {
+    #[serde(default)]
+    pub priority: Priority,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexUpdateResponse {
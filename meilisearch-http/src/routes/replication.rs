@@ -0,0 +1,57 @@
+use actix_web::get;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::error::ResponseError;
+use crate::helpers::{AdminIpAllowlist, Authentication};
+use crate::Data;
+
+const DEFAULT_LOG_LIMIT: usize = 100;
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_log).service(get_payload);
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct LogQuery {
+    /// The sequence number of the last entry known by the replica, `0` when it knows none.
+    #[serde(default)]
+    after: u64,
+    limit: Option<usize>,
+}
+
+/// Lists the entries of the replication log of a primary, in the order they were recorded.
+#[get(
+    "/replication/log",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
+async fn get_log(
+    data: web::Data<Data>,
+    params: web::Query<LogQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let limit = params.limit.unwrap_or(DEFAULT_LOG_LIMIT);
+    let entries = data.replication_log(params.after, limit)?;
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[derive(Deserialize)]
+struct PayloadParam {
+    seq: u64,
+}
+
+#[get(
+    "/replication/log/{seq}/payload",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
+async fn get_payload(
+    data: web::Data<Data>,
+    path: web::Path<PayloadParam>,
+) -> Result<HttpResponse, ResponseError> {
+    let payload = data.replication_payload(path.seq)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .body(payload))
+}
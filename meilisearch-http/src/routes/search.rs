@@ -4,30 +4,63 @@ use std::convert::{TryFrom, TryInto};
 use actix_web::{get, post, web, HttpResponse};
 use serde::Deserialize;
 
-use crate::error::ResponseError;
+use crate::error::{Error, ResponseError};
 use crate::helpers::Authentication;
-use crate::index::{SearchQuery, DEFAULT_SEARCH_LIMIT};
+use crate::index::{FacetSearchQuery, MatchingStrategy, SearchQuery, DEFAULT_SEARCH_LIMIT};
 use crate::routes::IndexParam;
 use crate::Data;
 
 pub fn services(cfg: &mut web::ServiceConfig) {
-    cfg.service(search_with_post).service(search_with_url_query);
+    cfg.service(search_with_post)
+        .service(search_with_url_query)
+        .service(facet_search);
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct SearchQueryGet {
     q: Option<String>,
+    attributes_to_search_on: Option<String>,
     offset: Option<usize>,
     limit: Option<usize>,
     attributes_to_retrieve: Option<String>,
     attributes_to_crop: Option<String>,
     crop_length: Option<usize>,
     attributes_to_highlight: Option<String>,
+    #[serde(alias = "filter")]
     filters: Option<String>,
     matches: Option<bool>,
     facet_filters: Option<String>,
     facet_distributions: Option<String>,
+    facet_ranges: Option<String>,
+    page: Option<usize>,
+    hits_per_page: Option<usize>,
+    vector: Option<String>,
+    semantic_ratio: Option<f32>,
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    show_ranking_score: bool,
+    #[serde(default)]
+    show_ranking_score_details: bool,
+    ranking_score_threshold: Option<f64>,
+    #[serde(default)]
+    matching_strategy: MatchingStrategy,
+}
+
+/// Parses an array passed as a query parameter. Both a JSON array (`["title","overview"]`) and a
+/// comma-separated list (`title,overview`) are accepted.
+fn parse_array_param(param: &str) -> anyhow::Result<Vec<String>> {
+    let param = param.trim();
+    if param.starts_with('[') {
+        Ok(serde_json::from_str(param)?)
+    } else {
+        Ok(param
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect())
+    }
 }
 
 impl TryFrom<SearchQueryGet> for SearchQuery {
@@ -36,27 +69,54 @@ impl TryFrom<SearchQueryGet> for SearchQuery {
     fn try_from(other: SearchQueryGet) -> anyhow::Result<Self> {
         let attributes_to_retrieve = other
             .attributes_to_retrieve
-            .map(|attrs| attrs.split(',').map(String::from).collect::<Vec<_>>());
+            .as_deref()
+            .map(parse_array_param)
+            .transpose()?;
+
+        let attributes_to_search_on = other
+            .attributes_to_search_on
+            .as_deref()
+            .map(parse_array_param)
+            .transpose()?
+            .map(|attrs| attrs.into_iter().collect::<HashSet<_>>());
 
         let attributes_to_crop = other
             .attributes_to_crop
-            .map(|attrs| attrs.split(',').map(String::from).collect::<Vec<_>>());
+            .as_deref()
+            .map(parse_array_param)
+            .transpose()?;
 
         let attributes_to_highlight = other
             .attributes_to_highlight
-            .map(|attrs| attrs.split(',').map(String::from).collect::<HashSet<_>>());
+            .as_deref()
+            .map(parse_array_param)
+            .transpose()?
+            .map(|attrs| attrs.into_iter().collect::<HashSet<_>>());
 
         let facet_distributions = other
             .facet_distributions
-            .map(|attrs| attrs.split(',').map(String::from).collect::<Vec<_>>());
+            .as_deref()
+            .map(parse_array_param)
+            .transpose()?;
 
         let facet_filters = match other.facet_filters {
             Some(ref f) => Some(serde_json::from_str(f)?),
             None => None,
         };
 
+        let facet_ranges = match other.facet_ranges {
+            Some(ref r) => Some(serde_json::from_str(r)?),
+            None => None,
+        };
+
+        let vector = match other.vector {
+            Some(ref v) => Some(serde_json::from_str(v)?),
+            None => None,
+        };
+
         Ok(Self {
             q: other.q,
+            attributes_to_search_on,
             offset: other.offset,
             limit: other.limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
             attributes_to_retrieve,
@@ -67,6 +127,16 @@ impl TryFrom<SearchQueryGet> for SearchQuery {
             matches: other.matches,
             facet_filters,
             facet_distributions,
+            facet_ranges,
+            page: other.page,
+            hits_per_page: other.hits_per_page,
+            vector,
+            semantic_ratio: other.semantic_ratio,
+            timeout_ms: other.timeout_ms,
+            show_ranking_score: other.show_ranking_score,
+            show_ranking_score_details: other.show_ranking_score_details,
+            ranking_score_threshold: other.ranking_score_threshold,
+            matching_strategy: other.matching_strategy,
         })
     }
 }
@@ -79,18 +149,12 @@ async fn search_with_url_query(
 ) -> Result<HttpResponse, ResponseError> {
     let query: SearchQuery = match params.into_inner().try_into() {
         Ok(q) => q,
-        Err(e) => {
-            return Ok(
-                HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() }))
-            )
-        }
+        Err(e) => return Err(Error::bad_request(e).into()),
     };
     let search_result = data.search(path.into_inner().index_uid, query).await;
     match search_result {
         Ok(docs) => Ok(HttpResponse::Ok().json(docs)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -105,8 +169,21 @@ async fn search_with_post(
         .await;
     match search_result {
         Ok(docs) => Ok(HttpResponse::Ok().json(docs)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[post("/indexes/{index_uid}/facet-search", wrap = "Authentication::Public")]
+async fn facet_search(
+    data: web::Data<Data>,
+    path: web::Path<IndexParam>,
+    params: web::Json<FacetSearchQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let result = data
+        .facet_search(path.into_inner().index_uid, params.into_inner())
+        .await;
+    match result {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(e) => Err(e.into()),
     }
 }
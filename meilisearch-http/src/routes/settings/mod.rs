@@ -1,8 +1,9 @@
 use actix_web::{delete, get, post, web, HttpResponse};
 
 use crate::error::ResponseError;
-use crate::helpers::Authentication;
+use crate::helpers::{AdminIpAllowlist, Authentication};
 use crate::index::Settings;
+use crate::routes::UpdateParam;
 use crate::Data;
 
 #[macro_export]
@@ -13,46 +14,77 @@ macro_rules! make_setting_route {
 
             use crate::data;
             use crate::error::ResponseError;
-            use crate::helpers::Authentication;
+            use crate::helpers::{AdminIpAllowlist, Authentication};
             use crate::index::Settings;
+            use crate::index_controller::Priority;
+            use crate::routes::UpdateParam;
 
-            #[actix_web::delete($route, wrap = "Authentication::Private")]
+            #[actix_web::delete(
+                $route,
+                wrap = "Authentication::Private",
+                wrap = "AdminIpAllowlist"
+            )]
             pub async fn delete(
                 data: web::Data<data::Data>,
                 index_uid: web::Path<String>,
+                params: web::Query<UpdateParam>,
             ) -> Result<HttpResponse, ResponseError> {
                 use crate::index::Settings;
                 let settings = Settings {
                     $attr: Some(None),
                     ..Default::default()
                 };
-                match data.update_settings(index_uid.into_inner(), settings, false).await {
+                match data
+                    .update_settings(index_uid.into_inner(), settings, false, params.priority)
+                    .await
+                {
                     Ok(update_status) => Ok(HttpResponse::Ok().json(update_status)),
-                    Err(e) => {
-                        Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-                    }
+                    Err(e) => Err(e.into()),
                 }
             }
 
-            #[actix_web::post($route, wrap = "Authentication::Private")]
-            pub async fn update(
-                data: actix_web::web::Data<data::Data>,
-                index_uid: actix_web::web::Path<String>,
-                body: actix_web::web::Json<Option<$type>>,
+            async fn update_setting(
+                data: web::Data<data::Data>,
+                index_uid: String,
+                priority: Priority,
+                value: Option<$type>,
             ) -> std::result::Result<HttpResponse, ResponseError> {
                 let settings = Settings {
-                    $attr: Some(body.into_inner()),
+                    $attr: Some(value),
                     ..Default::default()
                 };
 
-                match data.update_settings(index_uid.into_inner(), settings, true).await {
+                match data
+                    .update_settings(index_uid, settings, true, priority)
+                    .await
+                {
                     Ok(update_status) => Ok(HttpResponse::Ok().json(update_status)),
-                    Err(e) => {
-                        Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-                    }
+                    Err(e) => Err(e.into()),
                 }
             }
 
+            #[actix_web::post($route, wrap = "Authentication::Private", wrap = "AdminIpAllowlist")]
+            pub async fn update(
+                data: actix_web::web::Data<data::Data>,
+                index_uid: actix_web::web::Path<String>,
+                params: actix_web::web::Query<UpdateParam>,
+                body: actix_web::web::Json<Option<$type>>,
+            ) -> std::result::Result<HttpResponse, ResponseError> {
+                update_setting(data, index_uid.into_inner(), params.priority, body.into_inner())
+                    .await
+            }
+
+            #[actix_web::put($route, wrap = "Authentication::Private", wrap = "AdminIpAllowlist")]
+            pub async fn replace(
+                data: actix_web::web::Data<data::Data>,
+                index_uid: actix_web::web::Path<String>,
+                params: actix_web::web::Query<UpdateParam>,
+                body: actix_web::web::Json<Option<$type>>,
+            ) -> std::result::Result<HttpResponse, ResponseError> {
+                update_setting(data, index_uid.into_inner(), params.priority, body.into_inner())
+                    .await
+            }
+
             #[actix_web::get($route, wrap = "Authentication::Private")]
             pub async fn get(
                 data: actix_web::web::Data<data::Data>,
@@ -60,9 +92,7 @@ macro_rules! make_setting_route {
             ) -> std::result::Result<HttpResponse, ResponseError> {
                 match data.settings(index_uid.into_inner()).await {
                     Ok(settings) => Ok(HttpResponse::Ok().json(settings.$attr)),
-                    Err(e) => {
-                        Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-                    }
+                    Err(e) => Err(e.into()),
                 }
             }
         }
@@ -87,17 +117,71 @@ make_setting_route!(
     searchable_attributes
 );
 
+make_setting_route!(
+    "/indexes/{index_uid}/settings/pagination",
+    crate::index::PaginationSettings,
+    pagination
+);
+
+make_setting_route!(
+    "/indexes/{index_uid}/settings/languages",
+    Vec<String>,
+    languages
+);
+
+make_setting_route!(
+    "/indexes/{index_uid}/settings/separator-tokens",
+    std::collections::BTreeSet<String>,
+    separator_tokens
+);
+
+make_setting_route!(
+    "/indexes/{index_uid}/settings/non-separator-tokens",
+    std::collections::BTreeSet<String>,
+    non_separator_tokens
+);
+
+make_setting_route!(
+    "/indexes/{index_uid}/settings/dictionary",
+    std::collections::BTreeSet<String>,
+    dictionary
+);
+
+make_setting_route!(
+    "/indexes/{index_uid}/settings/exact-words",
+    std::collections::BTreeSet<String>,
+    exact_words
+);
+
+make_setting_route!(
+    "/indexes/{index_uid}/settings/exact-attributes",
+    std::collections::BTreeSet<String>,
+    exact_attributes
+);
+
+make_setting_route!(
+    "/indexes/{index_uid}/settings/proximity-precision",
+    crate::index::ProximityPrecision,
+    proximity_precision
+);
+
+make_setting_route!(
+    "/indexes/{index_uid}/settings/prefix-search",
+    crate::index::PrefixSearch,
+    prefix_search
+);
+
 //make_setting_route!(
 //"/indexes/{index_uid}/settings/distinct-attribute",
 //String,
 //distinct_attribute
 //);
 
-//make_setting_route!(
-//"/indexes/{index_uid}/settings/ranking-rules",
-//Vec<String>,
-//ranking_rules
-//);
+make_setting_route!(
+    "/indexes/{index_uid}/settings/ranking-rules",
+    Vec<String>,
+    ranking_rules
+);
 
 macro_rules! create_services {
     ($($mod:ident),*) => {
@@ -109,6 +193,7 @@ macro_rules! create_services {
                 $(
                     .service($mod::get)
                     .service($mod::update)
+                    .service($mod::replace)
                     .service($mod::delete)
                 )*;
         }
@@ -118,23 +203,41 @@ macro_rules! create_services {
 create_services!(
     attributes_for_faceting,
     displayed_attributes,
-    searchable_attributes
+    searchable_attributes,
+    ranking_rules,
+    pagination,
+    languages,
+    separator_tokens,
+    non_separator_tokens,
+    dictionary,
+    exact_words,
+    exact_attributes,
+    proximity_precision,
+    prefix_search
 );
 
-#[post("/indexes/{index_uid}/settings", wrap = "Authentication::Private")]
+#[post(
+    "/indexes/{index_uid}/settings",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
 async fn update_all(
     data: web::Data<Data>,
     index_uid: web::Path<String>,
+    params: web::Query<UpdateParam>,
     body: web::Json<Settings>,
 ) -> Result<HttpResponse, ResponseError> {
     match data
-        .update_settings(index_uid.into_inner(), body.into_inner(), true)
+        .update_settings(
+            index_uid.into_inner(),
+            body.into_inner(),
+            true,
+            params.priority,
+        )
         .await
     {
         Ok(update_result) => Ok(HttpResponse::Accepted().json(update_result)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -145,25 +248,26 @@ async fn get_all(
 ) -> Result<HttpResponse, ResponseError> {
     match data.settings(index_uid.into_inner()).await {
         Ok(settings) => Ok(HttpResponse::Ok().json(settings)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
 
-#[delete("/indexes/{index_uid}/settings", wrap = "Authentication::Private")]
+#[delete(
+    "/indexes/{index_uid}/settings",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
 async fn delete_all(
     data: web::Data<Data>,
     index_uid: web::Path<String>,
+    params: web::Query<UpdateParam>,
 ) -> Result<HttpResponse, ResponseError> {
     let settings = Settings::cleared();
     match data
-        .update_settings(index_uid.into_inner(), settings, false)
+        .update_settings(index_uid.into_inner(), settings, false, params.priority)
         .await
     {
         Ok(update_result) => Ok(HttpResponse::Accepted().json(update_result)),
-        Err(e) => {
-            Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })))
-        }
+        Err(e) => Err(e.into()),
     }
 }
@@ -1,13 +1,14 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 
 use actix_web::get;
 use actix_web::web;
 use actix_web::HttpResponse;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::error::ResponseError;
 use crate::helpers::Authentication;
+use crate::index_controller::{IndexStats, Stats};
 use crate::routes::IndexParam;
 use crate::Data;
 
@@ -20,44 +21,94 @@ pub fn services(cfg: &mut web::ServiceConfig) {
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct IndexStatsResponse {
+    database_size: u64,
     number_of_documents: u64,
     is_indexing: bool,
-    fields_distribution: BTreeMap<String, usize>,
+    fields_distribution: BTreeMap<String, u64>,
+}
+
+impl From<IndexStats> for IndexStatsResponse {
+    fn from(stats: IndexStats) -> Self {
+        Self {
+            database_size: stats.size,
+            number_of_documents: stats.number_of_documents,
+            is_indexing: stats.is_indexing,
+            fields_distribution: stats.fields_distribution.into_iter().collect(),
+        }
+    }
 }
 
 #[get("/indexes/{index_uid}/stats", wrap = "Authentication::Private")]
 async fn index_stats(
-    _data: web::Data<Data>,
-    _path: web::Path<IndexParam>,
+    data: web::Data<Data>,
+    path: web::Path<IndexParam>,
 ) -> Result<HttpResponse, ResponseError> {
-    todo!()
+    match data.get_index_stats(path.into_inner().index_uid).await {
+        Ok(stats) => Ok(HttpResponse::Ok().json(IndexStatsResponse::from(stats))),
+        Err(e) => Err(e.into()),
+    }
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct StatsResult {
     database_size: u64,
+    free_disk_space: u64,
     last_update: Option<DateTime<Utc>>,
-    indexes: HashMap<String, IndexStatsResponse>,
+    indexes: BTreeMap<String, IndexStatsResponse>,
+}
+
+impl From<Stats> for StatsResult {
+    fn from(stats: Stats) -> Self {
+        Self {
+            database_size: stats.database_size,
+            free_disk_space: stats.free_disk_space,
+            last_update: stats.last_update,
+            indexes: stats
+                .indexes
+                .into_iter()
+                .map(|(uid, stats)| (uid, stats.into()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct StatsQuery {
+    /// Comma separated list of the indexes to report, all the indexes are reported when missing.
+    index_uids: Option<String>,
 }
 
 #[get("/stats", wrap = "Authentication::Private")]
-async fn get_stats(_data: web::Data<Data>) -> Result<HttpResponse, ResponseError> {
-    todo!()
+async fn get_stats(
+    data: web::Data<Data>,
+    params: web::Query<StatsQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let index_uids = params
+        .index_uids
+        .as_ref()
+        .map(|uids| uids.split(',').map(String::from).collect());
+    match data.get_stats(index_uids).await {
+        Ok(stats) => Ok(HttpResponse::Ok().json(StatsResult::from(stats))),
+        Err(e) => Err(e.into()),
+    }
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct VersionResponse {
     commit_sha: String,
+    commit_date: String,
     build_date: String,
     pkg_version: String,
 }
 
 #[get("/version", wrap = "Authentication::Private")]
 async fn get_version() -> HttpResponse {
-   HttpResponse::Ok().json(VersionResponse {
+    HttpResponse::Ok().json(VersionResponse {
         commit_sha: env!("VERGEN_SHA").to_string(),
+        commit_date: env!("VERGEN_COMMIT_DATE").to_string(),
         build_date: env!("VERGEN_BUILD_TIMESTAMP").to_string(),
         pkg_version: env!("CARGO_PKG_VERSION").to_string(),
     })
@@ -3,7 +3,7 @@ use actix_web::{web, HttpResponse};
 use std::collections::BTreeSet;
 
 use crate::error::ResponseError;
-use crate::helpers::Authentication;
+use crate::helpers::{AdminIpAllowlist, Authentication};
 use crate::routes::IndexParam;
 use crate::Data;
 
@@ -24,7 +24,8 @@ async fn get(
 
 #[post(
     "/indexes/{index_uid}/settings/stop-words",
-    wrap = "Authentication::Private"
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
 )]
 async fn update(
     _data: web::Data<Data>,
@@ -36,7 +37,8 @@ async fn update(
 
 #[delete(
     "/indexes/{index_uid}/settings/stop-words",
-    wrap = "Authentication::Private"
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
 )]
 async fn delete(
     _data: web::Data<Data>,
@@ -4,7 +4,7 @@ use actix_web::{delete, get, post};
 use actix_web::{web, HttpResponse};
 
 use crate::error::ResponseError;
-use crate::helpers::Authentication;
+use crate::helpers::{AdminIpAllowlist, Authentication};
 use crate::routes::IndexParam;
 use crate::Data;
 
@@ -25,7 +25,8 @@ async fn get(
 
 #[post(
     "/indexes/{index_uid}/settings/synonyms",
-    wrap = "Authentication::Private"
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
 )]
 async fn update(
     _data: web::Data<Data>,
@@ -37,7 +38,8 @@ async fn update(
 
 #[delete(
     "/indexes/{index_uid}/settings/synonyms",
-    wrap = "Authentication::Private"
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
 )]
 async fn delete(
     _data: web::Data<Data>,
@@ -0,0 +1,150 @@
+use std::str::FromStr;
+
+use actix_web::{delete, get, post};
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::error::{Error, ResponseError};
+use crate::helpers::{AdminIpAllowlist, Authentication};
+use crate::index_controller::TaskFilter;
+use crate::Data;
+
+const DEFAULT_TASKS_LIMIT: usize = 20;
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_tasks)
+        .service(delete_tasks)
+        .service(cancel_tasks)
+        .service(get_task);
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TasksQuery {
+    /// Comma separated lists of the accepted values, all the values are accepted when missing.
+    index_uids: Option<String>,
+    types: Option<String>,
+    statuses: Option<String>,
+    before_date: Option<DateTime<Utc>>,
+    /// The uid of the most recent task to return.
+    from: Option<u64>,
+    limit: Option<usize>,
+}
+
+/// Selects the tasks of a bulk operation, like the filters of `TasksQuery`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TaskSelectionQuery {
+    index_uids: Option<String>,
+    types: Option<String>,
+    statuses: Option<String>,
+    before_date: Option<DateTime<Utc>>,
+}
+
+fn parse_list<T: FromStr<Err = String>>(
+    param: &str,
+    list: &Option<String>,
+) -> Result<Option<Vec<T>>, Error> {
+    list.as_ref()
+        .map(|list| list.split(',').map(T::from_str).collect())
+        .transpose()
+        .map_err(|e| Error::BadParameter(param.to_string(), e))
+}
+
+fn task_filter(
+    index_uids: &Option<String>,
+    types: &Option<String>,
+    statuses: &Option<String>,
+    before_date: Option<DateTime<Utc>>,
+) -> Result<TaskFilter, Error> {
+    Ok(TaskFilter {
+        index_uids: index_uids
+            .as_ref()
+            .map(|uids| uids.split(',').map(String::from).collect()),
+        types: parse_list("types", types)?,
+        statuses: parse_list("statuses", statuses)?,
+        before_date,
+    })
+}
+
+/// Builds the filter of a bulk operation. At least one filter is required, so that a bare request
+/// can't delete or cancel all the tasks.
+fn task_selection(params: &TaskSelectionQuery) -> Result<TaskFilter, Error> {
+    if params.index_uids.is_none()
+        && params.types.is_none()
+        && params.statuses.is_none()
+        && params.before_date.is_none()
+    {
+        return Err(Error::BadRequest(
+            "At least one of `indexUids`, `types`, `statuses` or `beforeDate` is required to select the tasks".to_string(),
+        ));
+    }
+
+    task_filter(
+        &params.index_uids,
+        &params.types,
+        &params.statuses,
+        params.before_date,
+    )
+}
+
+/// Lists the tasks, the most recent first: the updates of the indexes, the creation and deletion
+/// of the indexes, the dumps and the snapshots.
+#[get("/tasks", wrap = "Authentication::Private")]
+async fn list_tasks(
+    data: web::Data<Data>,
+    params: web::Query<TasksQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let filter = task_filter(
+        &params.index_uids,
+        &params.types,
+        &params.statuses,
+        params.before_date,
+    )?;
+    let limit = params.limit.unwrap_or(DEFAULT_TASKS_LIMIT);
+    let tasks = data.list_tasks(&filter, params.from, limit).await?;
+    Ok(HttpResponse::Ok().json(tasks))
+}
+
+/// Deletes the selected tasks that are finished. The tasks that are enqueued or being processed
+/// are kept.
+#[delete("/tasks", wrap = "Authentication::Private", wrap = "AdminIpAllowlist")]
+async fn delete_tasks(
+    data: web::Data<Data>,
+    params: web::Query<TaskSelectionQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let filter = task_selection(&params)?;
+    let uids = data.delete_tasks(&filter).await?;
+    Ok(HttpResponse::Ok().json(json!({ "deletedTasks": uids })))
+}
+
+/// Cancels the selected tasks that are enqueued. The tasks being processed run until their end.
+#[post(
+    "/tasks/cancel",
+    wrap = "Authentication::Private",
+    wrap = "AdminIpAllowlist"
+)]
+async fn cancel_tasks(
+    data: web::Data<Data>,
+    params: web::Query<TaskSelectionQuery>,
+) -> Result<HttpResponse, ResponseError> {
+    let filter = task_selection(&params)?;
+    let uids = data.cancel_tasks(&filter).await?;
+    Ok(HttpResponse::Ok().json(json!({ "canceledTasks": uids })))
+}
+
+#[derive(Deserialize)]
+struct TaskParam {
+    task_uid: u64,
+}
+
+#[get("/tasks/{task_uid}", wrap = "Authentication::Private")]
+async fn get_task(
+    data: web::Data<Data>,
+    path: web::Path<TaskParam>,
+) -> Result<HttpResponse, ResponseError> {
+    let task = data.task(path.task_uid).await?;
+    Ok(HttpResponse::Ok().json(task))
+}
@@ -1,8 +1,6 @@
-use std::time::Duration;
-
 use actix_web::http::StatusCode;
 use serde_json::{json, Value};
-use tokio::time::sleep;
+use urlencoding::encode;
 
 use super::service::Service;
 
@@ -29,6 +27,17 @@ impl Index<'_> {
         update_id as u64
     }
 
+    /// Adds the documents, identified by their `id` field, and waits for them to be indexed.
+    /// Returns the id of the update.
+    pub async fn load_documents(&self, documents: Value) -> u64 {
+        let (response, code) = self.add_documents(documents, Some("id")).await;
+        assert_eq!(code, 200, "{}", response);
+        let update_id = response["updateId"].as_u64().unwrap();
+        let response = self.wait_update_id(update_id).await;
+        assert_eq!(response["status"], "processed", "{}", response);
+        update_id
+    }
+
     pub async fn create<'a>(&'a self, primary_key: Option<&str>) -> (Value, StatusCode) {
         let body = json!({
             "uid": self.uid,
@@ -46,6 +55,16 @@ impl Index<'_> {
         self.service.put(url, body).await
     }
 
+    pub async fn clone_to(&self, uid: &str) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/clone", self.uid);
+        self.service.post(url, json!({ "uid": uid })).await
+    }
+
+    pub async fn reindex(&self, settings: Value) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/reindex", self.uid);
+        self.service.post(url, settings).await
+    }
+
     pub async fn delete(&self) -> (Value, StatusCode) {
         let url = format!("/indexes/{}", self.uid);
         self.service.delete(url).await
@@ -76,21 +95,25 @@ impl Index<'_> {
     }
 
     pub async fn wait_update_id(&self, update_id: u64) -> Value {
-        // try 10 times to get status, or panic to not wait forever
-        let url = format!("/indexes/{}/updates/{}", self.uid, update_id);
-        for _ in 0..10 {
-            let (response, status_code) = self.service.get(&url).await;
-            assert_eq!(status_code, 200, "response: {}", response);
-
-            if response["status"] == "processed" || response["status"] == "failed" {
-                return response;
-            }
+        // wait 10 seconds at most for the update, or panic to not wait forever
+        let url = format!(
+            "/indexes/{}/updates/{}?waitFor=processed&timeoutMs=10000",
+            self.uid, update_id
+        );
+        let (response, status_code) = self.service.get(&url).await;
+        assert_eq!(status_code, 200, "response: {}", response);
 
-            sleep(Duration::from_secs(1)).await;
+        if response["status"] == "processed" || response["status"] == "failed" {
+            return response;
         }
         panic!("Timeout waiting for update id");
     }
 
+    pub async fn get_update_waiting_for(&self, update_id: u64, query: &str) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/updates/{}?{}", self.uid, update_id, query);
+        self.service.get(url).await
+    }
+
     pub async fn get_update(&self, update_id: u64) -> (Value, StatusCode) {
         let url = format!("/indexes/{}/updates/{}", self.uid, update_id);
         self.service.get(url).await
@@ -102,12 +125,22 @@ impl Index<'_> {
         self.service.get(url).await
     }
 
+    /// Returns the exported documents as newline-delimited JSON.
+    pub async fn export_documents(&self) -> (String, StatusCode) {
+        let url = format!("/indexes/{}/documents/export", self.uid);
+        let req = actix_web::test::TestRequest::get().uri(&url).to_request();
+        self.service.request_text(req).await
+    }
+
     pub async fn get_document(
         &self,
         id: u64,
-        _options: Option<GetDocumentOptions>,
+        options: Option<GetDocumentOptions>,
     ) -> (Value, StatusCode) {
-        let url = format!("/indexes/{}/documents/{}", self.uid, id);
+        let mut url = format!("/indexes/{}/documents/{}", self.uid, id);
+        if let Some(fields) = options.and_then(|options| options.fields) {
+            url.push_str(&format!("?fields={}", fields.join(",")));
+        }
         self.service.get(url).await
     }
 
@@ -128,6 +161,10 @@ impl Index<'_> {
             ));
         }
 
+        if let Some(fields) = options.fields {
+            url.push_str(&format!("fields={}&", fields.join(",")));
+        }
+
         self.service.get(url).await
     }
 
@@ -148,6 +185,16 @@ impl Index<'_> {
             .await
     }
 
+    pub async fn count_documents(&self) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/documents/count", self.uid);
+        self.service.get(url).await
+    }
+
+    pub async fn fetch_documents(&self, body: Value) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/documents/fetch", self.uid);
+        self.service.post(url, body).await
+    }
+
     pub async fn settings(&self) -> (Value, StatusCode) {
         let url = format!("/indexes/{}/settings", self.uid);
         self.service.get(url).await
@@ -162,13 +209,70 @@ impl Index<'_> {
         let url = format!("/indexes/{}/settings", self.uid);
         self.service.delete(url).await
     }
+
+    pub async fn stats(&self) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/stats", self.uid);
+        self.service.get(url).await
+    }
+
+    pub async fn search(&self, query: Value) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/search", self.uid);
+        self.service.post(url, query).await
+    }
+
+    pub async fn facet_search(&self, query: Value) -> (Value, StatusCode) {
+        let url = format!("/indexes/{}/facet-search", self.uid);
+        self.service.post(url, query).await
+    }
+
+    /// Performs a search on the GET route, the `query` object is turned into url parameters,
+    /// non-string values are passed as JSON.
+    pub async fn search_get(&self, query: Value) -> (Value, StatusCode) {
+        let params = query
+            .as_object()
+            .expect("search query must be an object")
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                format!("{}={}", key, encode(&value))
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        let url = format!("/indexes/{}/search?{}", self.uid, params);
+        self.service.get(url).await
+    }
+}
+
+/// Returns the ids of the hits of a search response, in the order of the hits.
+pub fn hits_ids(response: &Value) -> Vec<u64> {
+    response["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["id"].as_u64().unwrap())
+        .collect()
+}
+
+/// Returns the ids of the hits of a search response sorted, to check the hits regardless of
+/// their ranking.
+pub fn sorted_hits_ids(response: &Value) -> Vec<u64> {
+    let mut ids = hits_ids(response);
+    ids.sort_unstable();
+    ids
 }
 
-pub struct GetDocumentOptions;
+#[derive(Debug, Default)]
+pub struct GetDocumentOptions {
+    pub fields: Option<Vec<&'static str>>,
+}
 
 #[derive(Debug, Default)]
 pub struct GetAllDocumentsOptions {
     pub limit: Option<usize>,
     pub offset: Option<usize>,
     pub attributes_to_retrieve: Option<Vec<&'static str>>,
+    pub fields: Option<Vec<&'static str>>,
 }
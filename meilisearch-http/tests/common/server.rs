@@ -1,3 +1,6 @@
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::Path;
+
 use actix_web::http::StatusCode;
 use byte_unit::{Byte, ByteUnit};
 use serde_json::Value;
@@ -5,7 +8,8 @@ use tempdir::TempDir;
 use urlencoding::encode;
 
 use meilisearch_http::data::Data;
-use meilisearch_http::option::{IndexerOpts, Opt};
+use meilisearch_http::helpers::compression::ArchiveCodec;
+use meilisearch_http::option::{IndexerOpts, Opt, OrphanRepair};
 
 use super::index::Index;
 use super::service::Service;
@@ -13,49 +17,29 @@ use super::service::Service;
 pub struct Server {
     pub service: Service,
     // hod ownership to the tempdir while we use the server instance.
-    _dir: tempdir::TempDir,
+    _dir: Option<tempdir::TempDir>,
 }
 
 impl Server {
     pub async fn new() -> Self {
         let dir = TempDir::new("meilisearch").unwrap();
+        let opt = default_settings(dir.path());
 
-        let opt = Opt {
-            db_path: dir.path().join("db"),
-            dumps_dir: dir.path().join("dump"),
-            dump_batch_size: 16,
-            http_addr: "127.0.0.1:7700".to_owned(),
-            master_key: None,
-            env: "development".to_owned(),
-            no_analytics: true,
-            max_mdb_size: Byte::from_unit(4.0, ByteUnit::GiB).unwrap(),
-            max_udb_size: Byte::from_unit(4.0, ByteUnit::GiB).unwrap(),
-            http_payload_size_limit: Byte::from_unit(10.0, ByteUnit::MiB).unwrap(),
-            ssl_cert_path: None,
-            ssl_key_path: None,
-            ssl_auth_path: None,
-            ssl_ocsp_path: None,
-            ssl_require_auth: false,
-            ssl_resumption: false,
-            ssl_tickets: false,
-            import_snapshot: None,
-            ignore_missing_snapshot: false,
-            ignore_snapshot_if_db_exists: false,
-            snapshot_dir: ".".into(),
-            schedule_snapshot: false,
-            snapshot_interval_sec: None,
-            import_dump: None,
-            indexer_options: IndexerOpts::default(),
-            #[cfg(all(not(debug_assertions), feature = "sentry"))]
-            sentry_dsn: String::from(""),
-            #[cfg(all(not(debug_assertions), feature = "sentry"))]
-            no_sentry: true,
-        };
+        let mut server = Self::new_with_options(opt).await;
+        server._dir = Some(dir);
+        server
+    }
 
+    /// Starts a server with the given options. The directories the options point to must outlive
+    /// the server, `default_settings` returns options with all the directories in a given path.
+    pub async fn new_with_options(opt: Opt) -> Self {
         let data = Data::new(opt).unwrap();
-        let service = Service(data);
+        let service = Service::new(data).await;
 
-        Server { service, _dir: dir }
+        Server {
+            service,
+            _dir: None,
+        }
     }
 
     /// Returns a view to an index. There is no guarantee that the index exists.
@@ -70,7 +54,158 @@ impl Server {
         self.service.get("/indexes").await
     }
 
+    pub async fn stats(&self) -> (Value, StatusCode) {
+        self.service.get("/stats").await
+    }
+
     pub async fn version(&self) -> (Value, StatusCode) {
         self.service.get("/version").await
     }
+
+    pub async fn create_dump(&self) -> (Value, StatusCode) {
+        self.service.post("/dumps", serde_json::json!(null)).await
+    }
+
+    pub async fn dump_status(&self, uid: impl AsRef<str>) -> (Value, StatusCode) {
+        let url = format!("/dumps/{}/status", uid.as_ref());
+        self.service.get(url).await
+    }
+
+    pub async fn restore_index(
+        &self,
+        dump_uid: impl AsRef<str>,
+        body: Value,
+    ) -> (Value, StatusCode) {
+        let url = format!("/dumps/{}/restore", dump_uid.as_ref());
+        self.service.post(url, body).await
+    }
+
+    pub async fn consistency(&self) -> (Value, StatusCode) {
+        self.service.get("/consistency").await
+    }
+
+    pub async fn repair_consistency(&self, repair: &str) -> (Value, StatusCode) {
+        let body = serde_json::json!({ "repair": repair });
+        self.service.post("/consistency", body).await
+    }
+
+    pub async fn replication_log(&self, after: u64) -> (Value, StatusCode) {
+        let url = format!("/replication/log?after={}", after);
+        self.service.get(url).await
+    }
+
+    pub async fn replication_payload(&self, seq: u64) -> (Value, StatusCode) {
+        let url = format!("/replication/log/{}/payload", seq);
+        self.service.get(url).await
+    }
+
+    /// Lists the tasks, `query` being the query string of the request, e.g. `status=failed`.
+    pub async fn tasks(&self, query: &str) -> (Value, StatusCode) {
+        let url = format!("/tasks?{}", query);
+        self.service.get(url).await
+    }
+
+    pub async fn delete_tasks(&self, query: &str) -> (Value, StatusCode) {
+        let url = format!("/tasks?{}", query);
+        self.service.delete(url).await
+    }
+
+    pub async fn cancel_tasks(&self, query: &str) -> (Value, StatusCode) {
+        let url = format!("/tasks/cancel?{}", query);
+        self.service.post(url, serde_json::json!(null)).await
+    }
+
+    pub async fn task(&self, uid: u64) -> (Value, StatusCode) {
+        let url = format!("/tasks/{}", uid);
+        self.service.get(url).await
+    }
+
+    /// Waits for the dump `uid` to be created, and returns its final status.
+    pub async fn wait_dump(&self, uid: impl AsRef<str>) -> Value {
+        for _ in 0..10 {
+            let (response, code) = self.dump_status(uid.as_ref()).await;
+            assert_eq!(code, 200, "response: {}", response);
+
+            if response["status"] != "in_progress" {
+                return response;
+            }
+
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+        panic!("Timeout waiting for dump");
+    }
+}
+
+/// Returns the options used by `Server::new`, with all the directories in `dir`.
+pub fn default_settings(dir: impl AsRef<Path>) -> Opt {
+    let dir = dir.as_ref();
+
+    Opt {
+        config_file_path: None,
+        print_config: false,
+        verify: false,
+        command: None,
+        db_path: dir.join("db"),
+        dumps_dir: dir.join("dump"),
+        dump_batch_size: 16,
+        replication_primary: false,
+        replicate_from: None,
+        replication_api_key: None,
+        replication_poll_interval_sec: 1,
+        replication_log_retention: NonZeroU64::new(100_000).unwrap(),
+        http_addr: "127.0.0.1:7700".to_owned(),
+        http2: false,
+        http_workers: None,
+        http_keep_alive_sec: 5,
+        http_client_timeout_ms: 5000,
+        http_shutdown_timeout_sec: 30,
+        master_key: None,
+        master_key_grace_period_sec: 300,
+        env: "development".to_owned(),
+        http_allowed_origins: Vec::new(),
+        admin_allowed_ips: Vec::new(),
+        no_analytics: true,
+        analytics_endpoint: String::new(),
+        max_index_size: Byte::from_unit(4.0, ByteUnit::GiB).unwrap(),
+        max_update_store_size: Byte::from_unit(4.0, ByteUnit::GiB).unwrap(),
+        max_uuid_store_size: Byte::from_unit(100.0, ByteUnit::MiB).unwrap(),
+        max_map_size: Byte::from_unit(16.0, ByteUnit::GiB).unwrap(),
+        case_insensitive_index_uids: false,
+        max_update_history: None,
+        update_retention_days: None,
+        max_pending_updates_per_index: None,
+        max_pending_updates: None,
+        min_free_disk_space: Byte::from_bytes(0),
+        max_concurrent_updates: NonZeroUsize::new(4).unwrap(),
+        autobatch_debounce_ms: 0,
+        http_payload_size_limit: Byte::from_unit(10.0, ByteUnit::MiB).unwrap(),
+        search_timeout_ms: None,
+        max_concurrent_searches: 10,
+        search_queue_size: 100,
+        search_cache_size: 0,
+        ssl_cert_path: None,
+        ssl_key_path: None,
+        ssl_auth_path: None,
+        ssl_ocsp_path: None,
+        ssl_require_auth: false,
+        ssl_resumption: false,
+        ssl_tickets: false,
+        import_snapshot: None,
+        ignore_missing_snapshot: false,
+        ignore_snapshot_if_db_exists: false,
+        repair_orphans: OrphanRepair::Report,
+        snapshot_dir: dir.join("snapshots"),
+        schedule_snapshot: false,
+        snapshot_interval_sec: None,
+        snapshot_increments: 0,
+        import_dump: None,
+        archive_compression: ArchiveCodec::Gzip,
+        archive_compression_level: None,
+        indexer_options: IndexerOpts::default(),
+        deprecation_warnings: Vec::new(),
+        #[cfg(all(not(debug_assertions), feature = "sentry"))]
+        sentry_dsn: String::from(""),
+        #[cfg(all(not(debug_assertions), feature = "sentry"))]
+        no_sentry: true,
+    }
 }
@@ -1,25 +1,82 @@
-use actix_web::{http::StatusCode, test};
+use std::io::Write;
+use std::rc::Rc;
+
+use actix_http::Request;
+use actix_web::http::{Method, StatusCode};
+use actix_web::test;
+use actix_web::web::Bytes;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::future::LocalBoxFuture;
 use serde_json::Value;
 
 use meilisearch_http::create_app;
 use meilisearch_http::data::Data;
 
-pub struct Service(pub Data);
+type TestApp = Rc<dyn Fn(Request) -> LocalBoxFuture<'static, (Bytes, StatusCode)>>;
+
+pub struct Service {
+    pub data: Data,
+    app: TestApp,
+}
 
 impl Service {
-    pub async fn post(&self, url: impl AsRef<str>, body: Value) -> (Value, StatusCode) {
-        let mut app = test::init_service(create_app!(&self.0, true)).await;
+    /// Initializes the app once, all the requests are then sent to this same instance.
+    pub async fn new(data: Data) -> Self {
+        let app = Rc::new(test::init_service(create_app!(&data, true)).await);
+        let app: TestApp = Rc::new(move |req: Request| {
+            let app = app.clone();
+            Box::pin(async move {
+                let res = test::call_service(&*app, req).await;
+                let status_code = res.status();
+
+                let body = test::read_body(res).await;
+                (body, status_code)
+            })
+        });
+
+        Service { data, app }
+    }
+
+    /// Sends a request to the app. The requests don't borrow the service mutably, so several of
+    /// them can be sent concurrently, e.g. with `futures::future::join_all`.
+    pub async fn request(&self, req: Request) -> (Value, StatusCode) {
+        let (body, status_code) = (self.app)(req).await;
+        let response = serde_json::from_slice(&body).unwrap_or_default();
+        (response, status_code)
+    }
 
+    /// Sends a request to the app, and returns its body as text rather than as JSON.
+    pub async fn request_text(&self, req: Request) -> (String, StatusCode) {
+        let (body, status_code) = (self.app)(req).await;
+        (String::from_utf8(body.to_vec()).unwrap(), status_code)
+    }
+
+    pub async fn post(&self, url: impl AsRef<str>, body: Value) -> (Value, StatusCode) {
         let req = test::TestRequest::post()
             .uri(url.as_ref())
             .set_json(&body)
             .to_request();
-        let res = test::call_service(&mut app, req).await;
-        let status_code = res.status();
+        self.request(req).await
+    }
 
-        let body = test::read_body(res).await;
-        let response = serde_json::from_slice(&body).unwrap_or_default();
-        (response, status_code)
+    /// Sends a request with a raw body and the given headers. Unlike the other helpers, no
+    /// content type is set unless it is part of the headers.
+    pub async fn request_raw(
+        &self,
+        method: Method,
+        url: impl AsRef<str>,
+        body: impl Into<Vec<u8>>,
+        headers: &[(&str, &str)],
+    ) -> (Value, StatusCode) {
+        let mut req = test::TestRequest::default()
+            .method(method)
+            .uri(url.as_ref())
+            .set_payload(body.into());
+        for (name, value) in headers {
+            req = req.insert_header((*name, *value));
+        }
+        self.request(req.to_request()).await
     }
 
     /// Send a test post request from a text body, with a `content-type:application/json` header.
@@ -28,57 +85,86 @@ impl Service {
         url: impl AsRef<str>,
         body: impl AsRef<str>,
     ) -> (Value, StatusCode) {
-        let mut app = test::init_service(create_app!(&self.0, true)).await;
+        let headers = [("content-type", "application/json")];
+        self.request_raw(Method::POST, url, body.as_ref(), &headers)
+            .await
+    }
 
-        let req = test::TestRequest::post()
-            .uri(url.as_ref())
-            .set_payload(body.as_ref().to_string())
-            .insert_header(("content-type", "application/json"))
-            .to_request();
-        let res = test::call_service(&mut app, req).await;
-        let status_code = res.status();
+    /// Send a test put request from a text body, with a `content-type:application/json` header.
+    pub async fn put_str(
+        &self,
+        url: impl AsRef<str>,
+        body: impl AsRef<str>,
+    ) -> (Value, StatusCode) {
+        let headers = [("content-type", "application/json")];
+        self.request_raw(Method::PUT, url, body.as_ref(), &headers)
+            .await
+    }
 
-        let body = test::read_body(res).await;
-        let response = serde_json::from_slice(&body).unwrap_or_default();
-        (response, status_code)
+    /// Send a test post request from a raw body, with the given content type.
+    pub async fn post_bytes(
+        &self,
+        url: impl AsRef<str>,
+        body: impl Into<Vec<u8>>,
+        content_type: &str,
+    ) -> (Value, StatusCode) {
+        let headers = [("content-type", content_type)];
+        self.request_raw(Method::POST, url, body, &headers).await
     }
 
-    pub async fn get(&self, url: impl AsRef<str>) -> (Value, StatusCode) {
-        let mut app = test::init_service(create_app!(&self.0, true)).await;
+    /// Send a test put request from a raw body, with the given content type.
+    pub async fn put_bytes(
+        &self,
+        url: impl AsRef<str>,
+        body: impl Into<Vec<u8>>,
+        content_type: &str,
+    ) -> (Value, StatusCode) {
+        let headers = [("content-type", content_type)];
+        self.request_raw(Method::PUT, url, body, &headers).await
+    }
 
-        let req = test::TestRequest::get().uri(url.as_ref()).to_request();
-        let res = test::call_service(&mut app, req).await;
-        let status_code = res.status();
+    /// Send a test post request with a gzip-encoded json body.
+    pub async fn post_encoded(&self, url: impl AsRef<str>, body: Value) -> (Value, StatusCode) {
+        self.send_encoded(Method::POST, url, body).await
+    }
 
-        let body = test::read_body(res).await;
-        let response = serde_json::from_slice(&body).unwrap_or_default();
-        (response, status_code)
+    /// Send a test put request with a gzip-encoded json body.
+    pub async fn put_encoded(&self, url: impl AsRef<str>, body: Value) -> (Value, StatusCode) {
+        self.send_encoded(Method::PUT, url, body).await
     }
 
-    pub async fn put(&self, url: impl AsRef<str>, body: Value) -> (Value, StatusCode) {
-        let mut app = test::init_service(create_app!(&self.0, true)).await;
+    async fn send_encoded(
+        &self,
+        method: Method,
+        url: impl AsRef<str>,
+        body: Value,
+    ) -> (Value, StatusCode) {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body.to_string().as_bytes()).unwrap();
+        let body = encoder.finish().unwrap();
+
+        let headers = [
+            ("content-type", "application/json"),
+            ("content-encoding", "gzip"),
+        ];
+        self.request_raw(method, url, body, &headers).await
+    }
 
+    pub async fn get(&self, url: impl AsRef<str>) -> (Value, StatusCode) {
+        let req = test::TestRequest::get().uri(url.as_ref()).to_request();
+        self.request(req).await
+    }
+
+    pub async fn put(&self, url: impl AsRef<str>, body: Value) -> (Value, StatusCode) {
         let req = test::TestRequest::put()
             .uri(url.as_ref())
             .set_json(&body)
             .to_request();
-        let res = test::call_service(&mut app, req).await;
-        let status_code = res.status();
-
-        let body = test::read_body(res).await;
-        let response = serde_json::from_slice(&body).unwrap_or_default();
-        (response, status_code)
+        self.request(req).await
     }
 
     pub async fn delete(&self, url: impl AsRef<str>) -> (Value, StatusCode) {
-        let mut app = test::init_service(create_app!(&self.0, true)).await;
-
         let req = test::TestRequest::delete().uri(url.as_ref()).to_request();
-        let res = test::call_service(&mut app, req).await;
-        let status_code = res.status();
-
-        let body = test::read_body(res).await;
-        let response = serde_json::from_slice(&body).unwrap_or_default();
-        (response, status_code)
+        self.request(req).await
     }
 }
@@ -1,7 +1,9 @@
+use actix_web::http::Method;
 use chrono::DateTime;
 use serde_json::{json, Value};
+use tempdir::TempDir;
 
-use crate::common::{GetAllDocumentsOptions, Server};
+use crate::common::{default_settings, GetAllDocumentsOptions, Server};
 
 #[actix_rt::test]
 async fn add_documents_no_index_creation() {
@@ -30,7 +32,10 @@ async fn add_documents_no_index_creation() {
     assert_eq!(code, 200);
     assert_eq!(response["status"], "processed");
     assert_eq!(response["updateId"], 0);
-    assert_eq!(response["success"]["DocumentsAddition"]["nb_documents"], 1);
+    let result = &response["success"]["DocumentsAddition"];
+    assert_eq!(result["nb_documents"], 1);
+    assert_eq!(result["new_documents"], 1);
+    assert_eq!(result["total_documents"], 1);
 
     let processed_at =
         DateTime::parse_from_rfc3339(response["processedAt"].as_str().unwrap()).unwrap();
@@ -40,6 +45,7 @@ async fn add_documents_no_index_creation() {
         DateTime::parse_from_rfc3339(response["startedProcessingAt"].as_str().unwrap()).unwrap();
     assert!(processed_at > started_processing_at);
     assert!(started_processing_at > enqueued_at);
+    assert!(response["duration"].as_f64().unwrap() >= 0.0);
 
     // index was created, and primary key was infered.
     let (response, code) = index.get().await;
@@ -90,6 +96,26 @@ async fn document_addition_with_primary_key() {
     assert_eq!(response["primaryKey"], "primary");
 }
 
+#[actix_rt::test]
+async fn document_addition_replacing_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+
+    let documents = json!([{ "id": 1, "content": "foo" }]);
+    index.add_documents(documents, Some("id")).await;
+    index.wait_update_id(0).await;
+
+    let documents = json!([{ "id": 1, "content": "bar" }, { "id": 2, "content": "baz" }]);
+    index.add_documents(documents, None).await;
+    let response = index.wait_update_id(1).await;
+    assert_eq!(response["status"], "processed");
+    let result = &response["success"]["DocumentsAddition"];
+    assert_eq!(result["nb_documents"], 2);
+    // The first document replaced the existing one.
+    assert_eq!(result["new_documents"], 1);
+    assert_eq!(result["total_documents"], 2);
+}
+
 #[actix_rt::test]
 async fn document_update_with_primary_key() {
     let server = Server::new().await;
@@ -330,6 +356,8 @@ async fn add_documents_bad_primary_key() {
     let (response, code) = index.get_update(0).await;
     assert_eq!(code, 200);
     assert_eq!(response["status"], "failed");
+    assert!(response["failedAt"].is_string());
+    assert!(response["duration"].as_f64().unwrap() >= 0.0);
 }
 
 #[actix_rt::test]
@@ -349,3 +377,375 @@ async fn update_documents_bad_primary_key() {
     assert_eq!(code, 200);
     assert_eq!(response["status"], "failed");
 }
+
+#[actix_rt::test]
+async fn add_documents_in_batch() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // All the additions are registered before the processing starts.
+    options.autobatch_debounce_ms = 1000;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    for id in 0..10 {
+        let (_, code) = index.add_documents(json!([{ "id": id }]), None).await;
+        assert_eq!(code, 200);
+    }
+
+    let mut batched = false;
+    for id in 0..10 {
+        let response = index.wait_update_id(id).await;
+        assert_eq!(response["status"], "processed");
+        assert_eq!(response["success"]["DocumentsAddition"]["nb_documents"], 1);
+        if let Some(batch) = response["batch"].as_array() {
+            assert!(batch.contains(&json!(id)));
+            batched = true;
+        }
+    }
+    assert!(batched);
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response.as_array().unwrap().len(), 10);
+}
+
+#[actix_rt::test]
+async fn invalid_document_addition_does_not_fail_its_batch() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index
+        .add_documents(json!([{ "id": "foo & bar" }]), None)
+        .await;
+    index.add_documents(json!([{ "id": 2 }]), None).await;
+
+    assert_eq!(index.wait_update_id(0).await["status"], "processed");
+    assert_eq!(index.wait_update_id(1).await["status"], "failed");
+    assert_eq!(index.wait_update_id(2).await["status"], "processed");
+}
+
+#[actix_rt::test]
+async fn add_documents_raw_payload() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+
+    let url = "/indexes/test/documents";
+    let (_response, code) = server
+        .service
+        .post_bytes(url, r#"[{ "id": 1, "title": "a" }]"#, "text/plain")
+        .await;
+    assert_eq!(code, 200);
+    let (_response, code) = server
+        .service
+        .put_bytes(url, r#"[{ "id": 2, "title": "b" }]"#, "text/plain")
+        .await;
+    assert_eq!(code, 200);
+
+    index.wait_update_id(1).await;
+    let (response, code) = index.count_documents().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 2);
+}
+
+#[actix_rt::test]
+async fn add_documents_with_autobatch_debounce() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.autobatch_debounce_ms = 1000;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    for id in 0..5 {
+        let (_, code) = index.add_documents(json!([{ "id": id }]), None).await;
+        assert_eq!(code, 200);
+    }
+
+    // The additions registered while the update store waits are processed in a single batch.
+    let response = index.wait_update_id(4).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+    assert_eq!(response["batch"], json!([0, 1, 2, 3, 4]), "{}", response);
+}
+
+fn gzip(body: &str) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(body.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[actix_rt::test]
+async fn add_gzipped_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let headers = [
+        ("content-type", "application/json"),
+        ("content-encoding", "gzip"),
+    ];
+
+    let body = gzip(r#"[{ "id": 1, "title": "a" }, { "id": 2, "title": "b" }]"#);
+    let (response, code) = server
+        .service
+        .request_raw(Method::POST, "/indexes/test/documents", body, &headers)
+        .await;
+    assert_eq!(code, 200, "{}", response);
+
+    let body = gzip(r#"[{ "id": 2, "title": "c" }]"#);
+    let (response, code) = server
+        .service
+        .request_raw(Method::PUT, "/indexes/test/documents", body, &headers)
+        .await;
+    assert_eq!(code, 200, "{}", response);
+
+    let response = index.wait_update_id(1).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+    let (response, code) = index.get_document(2, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(response["title"], "c");
+    let (response, _code) = index.count_documents().await;
+    assert_eq!(response["numberOfDocuments"], 2);
+}
+
+#[actix_rt::test]
+async fn add_documents_with_unsupported_encoding() {
+    let server = Server::new().await;
+    let headers = [
+        ("content-type", "application/json"),
+        ("content-encoding", "lzma"),
+    ];
+    let (response, code) = server
+        .service
+        .request_raw(Method::POST, "/indexes/test/documents", "[]", &headers)
+        .await;
+    assert_eq!(code, 415, "{}", response);
+    assert_eq!(response["code"], "unsupported_media_type");
+}
+
+#[actix_rt::test]
+async fn add_documents_with_checksum() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let body = r#"[{ "id": 1, "title": "a" }]"#;
+    // The SHA-256 digest of the body, the header is case insensitive.
+    let headers = [
+        ("content-type", "application/json"),
+        (
+            "x-payload-checksum",
+            "644D3110CEF50EA9294D5F2DA2FA007B986EC20B99BD2270B548E175D8853BA6",
+        ),
+    ];
+    let (response, code) = server
+        .service
+        .request_raw(Method::POST, "/indexes/test/documents", body, &headers)
+        .await;
+    assert_eq!(code, 200, "{}", response);
+
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+}
+
+#[actix_rt::test]
+async fn add_documents_with_mismatched_checksum() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    // The MD5 digest of `[{ "id": 1, "title": "a" }]`, sent with a truncated payload.
+    let headers = [
+        ("content-type", "application/json"),
+        ("content-md5", "lIlNqY08E6//lvWPhH2avA=="),
+    ];
+    let (response, code) = server
+        .service
+        .request_raw(
+            Method::POST,
+            "/indexes/test/documents",
+            r#"[{ "id": 1, "ti"#,
+            &headers,
+        )
+        .await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "bad_request");
+
+    let (response, code) = index.list_updates().await;
+    assert_eq!(code, 200);
+    assert!(response.as_array().unwrap().is_empty(), "{}", response);
+}
+
+#[actix_rt::test]
+async fn add_documents_dry_run() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "a" },
+        { "title": "b" },
+        { "id": "not valid", "title": "c" },
+    ]);
+    let (response, code) = server
+        .service
+        .post("/indexes/test/documents?dryRun=true", documents)
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["valid"], false);
+    assert_eq!(response["primaryKey"], "id");
+    assert_eq!(response["numberOfDocuments"], 3);
+    assert_eq!(response["numberOfFields"], 2);
+    assert_eq!(response["errors"][0]["document"], 1);
+    assert_eq!(response["errors"][1]["document"], 2);
+
+    let (response, code) = server
+        .service
+        .put(
+            "/indexes/test/documents?dryRun=true",
+            json!([{ "id": 1, "title": "a" }]),
+        )
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["valid"], true);
+    assert!(response["errors"].as_array().unwrap().is_empty());
+
+    // Nothing was registered.
+    let (_response, code) = index.get().await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn add_documents_dry_run_uses_the_index_primary_key() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("uid")).await;
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes/test/documents?dryRun=true&primaryKey=id",
+            json!([{ "id": 1 }]),
+        )
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["valid"], false);
+    assert_eq!(response["primaryKey"], "uid");
+
+    let (response, _code) = index.list_updates().await;
+    assert!(response.as_array().unwrap().is_empty(), "{}", response);
+}
+
+#[actix_rt::test]
+async fn add_documents_no_primary_key_candidate() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([{ "title": "a", "content": "b" }]);
+    let (response, code) = index.add_documents(documents, None).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "missing_primary_key");
+    let message = response["message"].as_str().unwrap();
+    assert!(message.contains("`title`, `content`"), "{}", message);
+}
+
+#[actix_rt::test]
+async fn add_documents_ambiguous_primary_key() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([{ "movie_id": 1, "author_id": 2 }]);
+    let (response, code) = index.add_documents(documents, None).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "missing_primary_key");
+    let message = response["message"].as_str().unwrap();
+    assert!(message.contains("`movie_id`, `author_id`"), "{}", message);
+
+    // The primary key can be given explicitly.
+    let documents = json!([{ "movie_id": 1, "author_id": 2 }]);
+    let (response, code) = index.add_documents(documents, Some("movie_id")).await;
+    assert_eq!(code, 200, "{}", response);
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+}
+
+#[actix_rt::test]
+async fn add_documents_reports_the_invalid_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("docid")).await;
+    let documents = json!([
+        { "docid": 1, "content": "foo" },
+        { "docid": "foo & bar", "content": "bar" },
+        { "docid": 3, "content": "baz" },
+    ]);
+    index.add_documents(documents, None).await;
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+
+    let errors = response["documentErrors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1, "{}", response);
+    assert_eq!(errors[0]["document"], 1);
+    assert_eq!(errors[0]["id"], "foo & bar");
+    assert!(errors[0]["message"].is_string());
+}
+
+#[actix_rt::test]
+async fn add_documents_skipping_invalid_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("docid")).await;
+    let documents = json!([
+        { "docid": 1, "content": "foo" },
+        { "docid": "foo & bar", "content": "bar" },
+        "not a document",
+        { "docid": 4, "content": "baz" },
+    ]);
+    let (response, code) = server
+        .service
+        .post(
+            "/indexes/test/documents?skipInvalidDocuments=true",
+            documents,
+        )
+        .await;
+    assert_eq!(code, 200, "{}", response);
+
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+    let result = &response["success"]["DocumentsAddition"];
+    assert_eq!(result["nb_documents"], 4);
+    assert_eq!(result["total_documents"], 2);
+    assert_eq!(result["skipped_documents"], 2);
+    let errors = result["document_errors"].as_array().unwrap();
+    assert_eq!(errors[0]["document"], 1);
+    assert_eq!(errors[0]["id"], "foo & bar");
+    assert_eq!(errors[1]["document"], 2);
+
+    let (response, _code) = index.count_documents().await;
+    assert_eq!(response["numberOfDocuments"], 2);
+}
+
+#[actix_rt::test]
+async fn add_documents_exceeding_the_maximum_number_of_fields() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.indexer_options.max_fields_per_index = 3;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+
+    index
+        .add_documents(json!([{ "id": 1, "title": "a" }]), None)
+        .await;
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+
+    let documents = json!([
+        { "id": 2, "title": "b", "1622548800": "c" },
+        { "id": 3, "title": "d", "1622548801": "e" },
+    ]);
+    index.add_documents(documents, None).await;
+    let response = index.wait_update_id(1).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+    let error = response["error"].as_str().unwrap();
+    assert!(error.contains("4 distinct fields"), "{}", error);
+
+    // The failed addition left the index untouched.
+    let (response, _code) = index.count_documents().await;
+    assert_eq!(response["numberOfDocuments"], 1);
+}
@@ -6,7 +6,7 @@ use crate::common::{GetAllDocumentsOptions, Server};
 async fn delete_one_document_unexisting_index() {
     let server = Server::new().await;
     let (_response, code) = server.index("test").delete_document(0).await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
 
 #[actix_rt::test]
@@ -40,7 +40,7 @@ async fn delete_one_document() {
 async fn clear_all_documents_unexisting_index() {
     let server = Server::new().await;
     let (_response, code) = server.index("test").clear_all_documents().await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
 
 #[actix_rt::test]
@@ -57,7 +57,9 @@ async fn clear_all_documents() {
     let (_response, code) = index.clear_all_documents().await;
     assert_eq!(code, 200);
 
-    let _update = index.wait_update_id(1).await;
+    let update = index.wait_update_id(1).await;
+    assert_eq!(update["status"], "processed");
+    assert_eq!(update["success"]["ClearDocuments"]["deleted"], 2);
     let (response, code) = index
         .get_all_documents(GetAllDocumentsOptions::default())
         .await;
@@ -86,7 +88,7 @@ async fn clear_all_documents_empty_index() {
 async fn delete_batch_unexisting_index() {
     let server = Server::new().await;
     let (_response, code) = server.index("test").delete_batch(vec![]).await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
 
 #[actix_rt::test]
@@ -95,10 +97,14 @@ async fn delete_batch() {
     let index = server.index("test");
     index.add_documents(json!([{ "id": 1, "content": "foobar" }, { "id": 0, "content": "foobar" }, { "id": 3, "content": "foobar" }]), Some("id")).await;
     index.wait_update_id(0).await;
-    let (_response, code) = index.delete_batch(vec![1, 0]).await;
+    let (_response, code) = index.delete_batch(vec![1, 0, 42]).await;
     assert_eq!(code, 200);
 
-    let _update = index.wait_update_id(1).await;
+    let update = index.wait_update_id(1).await;
+    assert_eq!(update["status"], "processed");
+    // The unexisting document is ignored.
+    assert_eq!(update["success"]["DocumentDeletion"]["deleted"], 2);
+    assert_eq!(update["success"]["DocumentDeletion"]["requested"], 3);
     let (response, code) = index
         .get_all_documents(GetAllDocumentsOptions::default())
         .await;
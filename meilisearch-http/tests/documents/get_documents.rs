@@ -1,5 +1,5 @@
-use crate::common::GetAllDocumentsOptions;
 use crate::common::Server;
+use crate::common::{GetAllDocumentsOptions, GetDocumentOptions};
 
 use serde_json::json;
 
@@ -9,7 +9,7 @@ use serde_json::json;
 async fn get_unexisting_index_single_document() {
     let server = Server::new().await;
     let (_response, code) = server.index("test").get_document(1, None).await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
 
 #[actix_rt::test]
@@ -53,7 +53,7 @@ async fn get_unexisting_index_all_documents() {
         .index("test")
         .get_all_documents(GetAllDocumentsOptions::default())
         .await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
 
 #[actix_rt::test]
@@ -233,3 +233,159 @@ async fn get_documents_displayed_attributes() {
     assert_eq!(response.as_object().unwrap().keys().count(), 1);
     assert!(response.as_object().unwrap().get("gender").is_some());
 }
+
+#[actix_rt::test]
+async fn fetch_documents_by_ids() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .fetch_documents(json!({ "ids": [3, "1", 12345678] }))
+        .await;
+    assert_eq!(code, 200);
+    let documents = response.as_array().unwrap();
+    assert_eq!(documents.len(), 2);
+    assert_eq!(documents[0]["id"], 3);
+    assert_eq!(documents[1]["id"], 1);
+}
+
+#[actix_rt::test]
+async fn fetch_documents_by_ids_with_attributes_to_retrieve() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .fetch_documents(json!({ "ids": [1], "attributesToRetrieve": ["name"] }))
+        .await;
+    assert_eq!(code, 200);
+    let document = response[0].as_object().unwrap();
+    assert_eq!(document.len(), 1);
+    assert!(document.get("name").is_some());
+}
+
+#[actix_rt::test]
+async fn fetch_too_many_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let ids: Vec<_> = (0..1001).collect();
+    let (response, code) = index.fetch_documents(json!({ "ids": ids })).await;
+    assert_eq!(code, 400);
+    assert_eq!(response["code"], "bad_request");
+}
+
+#[actix_rt::test]
+async fn fetch_documents_unexisting_index() {
+    let server = Server::new().await;
+    let (_response, code) = server
+        .index("test")
+        .fetch_documents(json!({ "ids": [1] }))
+        .await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn get_document_with_fields() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .get_document(
+            0,
+            Some(GetDocumentOptions {
+                fields: Some(vec!["name", "age"]),
+            }),
+        )
+        .await;
+    assert_eq!(code, 200);
+    let document = response.as_object().unwrap();
+    assert_eq!(document.len(), 2);
+    assert_eq!(document["name"], "Lucas Hess");
+    assert_eq!(document["age"], 36);
+}
+
+#[actix_rt::test]
+async fn get_document_with_spaced_fields() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let url = format!("/indexes/{}/documents/0?fields=name,%20age%20", index.uid);
+    let (response, code) = server.service.get(url).await;
+    assert_eq!(code, 200);
+    let document = response.as_object().unwrap();
+    assert_eq!(document.len(), 2);
+    assert_eq!(document["name"], "Lucas Hess");
+    assert_eq!(document["age"], 36);
+}
+
+#[actix_rt::test]
+async fn get_all_documents_with_fields() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .get_all_documents(GetAllDocumentsOptions {
+            fields: Some(vec!["name"]),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(code, 200);
+    let documents = response.as_array().unwrap();
+    assert_eq!(documents.len(), 20);
+    for document in documents {
+        let keys: Vec<_> = document.as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["name"]);
+    }
+}
+
+#[actix_rt::test]
+async fn count_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let (response, code) = index.count_documents().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 0);
+
+    index.load_test_set().await;
+    let (response, code) = index.count_documents().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 77);
+}
+
+#[actix_rt::test]
+async fn count_documents_unexisting_index() {
+    let server = Server::new().await;
+    let (_response, code) = server.index("test").count_documents().await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn export_documents() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (body, code) = index.export_documents().await;
+    assert_eq!(code, 200);
+    let documents: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(documents.len(), 77);
+    assert_eq!(documents[0]["id"], 0);
+}
+
+#[actix_rt::test]
+async fn export_documents_unexisting_index() {
+    let server = Server::new().await;
+    let (_body, code) = server.index("test").export_documents().await;
+    assert_eq!(code, 404);
+}
@@ -0,0 +1,451 @@
+use std::ffi::OsStr;
+use std::fs::{read_dir, File};
+use std::path::Path;
+use std::process::Command;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use meilisearch_http::helpers::compression::ArchiveCodec;
+use meilisearch_http::Data;
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, GetAllDocumentsOptions, Server};
+
+#[actix_rt::test]
+async fn dump_status_unexisting_dump() {
+    let server = Server::new().await;
+    let (response, code) = server.dump_status("foo").await;
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "not_found");
+}
+
+#[actix_rt::test]
+async fn failed_dumps_keep_their_status() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // The dumps directory can't be created under a file.
+    std::fs::write(dir.path().join("file"), b"").unwrap();
+    options.dumps_dir = dir.path().join("file/dumps");
+    let server = Server::new_with_options(options).await;
+
+    let (response, code) = server.create_dump().await;
+    assert_eq!(code, 202, "response: {}", response);
+    let first = response["uid"].as_str().unwrap().to_string();
+    let response = server.wait_dump(&first).await;
+    assert_eq!(response["status"], "failed", "response: {}", response);
+    assert!(response["error"].is_string());
+
+    // The status of the first dump is still available once another dump is created. The uids
+    // are dated to the millisecond.
+    tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+    let (response, _code) = server.create_dump().await;
+    let second = response["uid"].as_str().unwrap().to_string();
+    assert_ne!(first, second);
+    server.wait_dump(&second).await;
+    let (response, code) = server.dump_status(&first).await;
+    assert_eq!(code, 200);
+    assert_eq!(response["uid"], first);
+    assert_eq!(response["status"], "failed", "response: {}", response);
+}
+
+#[actix_rt::test]
+async fn create_and_import_dump() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = Server::new_with_options(default_settings(dir.path())).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(
+            json!([{ "id": 1, "title": "foo" }, { "id": 2, "title": "bar" }]),
+            None,
+        )
+        .await;
+    index.wait_update_id(0).await;
+    index
+        .update_settings(json!({ "rankingRules": ["words", "typo"] }))
+        .await;
+    index.wait_update_id(1).await;
+
+    let (response, code) = server.create_dump().await;
+    assert_eq!(code, 202, "response: {}", response);
+    assert_eq!(response["status"], "in_progress");
+    let uid = response["uid"].as_str().unwrap().to_string();
+    let response = server.wait_dump(&uid).await;
+    assert_eq!(response["status"], "done", "response: {}", response);
+
+    let dump_path = dir.path().join("dump").join(format!("{}.dump", uid));
+    let server = Server::new().await;
+    server.service.data.import_dump(&dump_path).await.unwrap();
+
+    let index = server.index("test");
+    // The settings, then the documents, are imported as the first updates of the index.
+    index.wait_update_id(1).await;
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "id");
+    let (response, _code) = index.settings().await;
+    assert_eq!(response["rankingRules"], json!(["words", "typo"]));
+    assert_eq!(response["displayedAttributes"], json!(["*"]));
+    let (response, _code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(response.as_array().unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn create_and_import_zstd_dump() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.archive_compression = ArchiveCodec::Zstd;
+    options.archive_compression_level = Some(1);
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(json!([{ "id": 1, "title": "foo" }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
+    let (response, _code) = server.create_dump().await;
+    let uid = response["uid"].as_str().unwrap().to_string();
+    let response = server.wait_dump(&uid).await;
+    assert_eq!(response["status"], "done", "response: {}", response);
+
+    let dump_path = dir.path().join("dump").join(format!("{}.dump", uid));
+    let content = std::fs::read(&dump_path).unwrap();
+    assert!(content.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+
+    // The dump is imported whatever the compression of the importing server.
+    let server = Server::new().await;
+    server.service.data.import_dump(&dump_path).await.unwrap();
+    let index = server.index("test");
+    index.wait_update_id(1).await;
+    let (response, _code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(response, json!([{ "id": 1, "title": "foo" }]));
+}
+
+#[actix_rt::test]
+async fn invalid_compression_level() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.archive_compression_level = Some(10);
+    assert!(Data::new(options).is_err());
+}
+
+/// Archives the content of `src` in a dump at `dest`.
+fn archive_dump(src: &Path, dest: &Path) {
+    let encoder = GzEncoder::new(File::create(dest).unwrap(), Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.append_dir_all(".", src).unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+#[actix_rt::test]
+async fn import_dump_unsupported_version() {
+    let dir = TempDir::new("dump").unwrap();
+    let metadata = json!({ "dumpVersion": "V42", "dbVersion": "42.0.0", "indexes": [] });
+    std::fs::create_dir(dir.path().join("content")).unwrap();
+    std::fs::write(
+        dir.path().join("content/metadata.json"),
+        metadata.to_string(),
+    )
+    .unwrap();
+    let dump_path = dir.path().join("test.dump");
+    archive_dump(&dir.path().join("content"), &dump_path);
+
+    let server = Server::new().await;
+    let error = server
+        .service
+        .data
+        .import_dump(&dump_path)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("V42"), "error: {}", error);
+}
+
+#[actix_rt::test]
+async fn import_legacy_v1_dump() {
+    let dir = TempDir::new("dump").unwrap();
+    let dump_path = dir.path().join("v1.dump");
+    archive_dump(Path::new("tests/assets/dumps/v1"), &dump_path);
+
+    let server = Server::new().await;
+    server.service.data.import_dump(&dump_path).await.unwrap();
+
+    let index = server.index("test");
+    // The settings, then the 77 documents in batches of 16, are registered as updates.
+    index.wait_update_id(5).await;
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "id");
+    let (response, _code) = index.settings().await;
+    assert_eq!(response["searchableAttributes"][0], "balance");
+    assert_eq!(response["displayedAttributes"][0], "id");
+    assert_eq!(response["attributesForFaceting"]["gender"], "string");
+    let (response, _code) = index.stats().await;
+    assert_eq!(response["numberOfDocuments"], 77);
+
+    // The index without data in the dump is created empty.
+    let (response, code) = server.index("test2").get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "test2_id");
+}
+
+#[actix_rt::test]
+async fn import_legacy_dump_with_invalid_uid() {
+    let dir = TempDir::new("dump").unwrap();
+    let metadata = json!({
+        "indices": [{ "uid": "../escaped", "primaryKey": "id" }],
+        "dbVersion": "0.13.0",
+        "dumpVersion": "1",
+    });
+    std::fs::create_dir(dir.path().join("content")).unwrap();
+    std::fs::write(
+        dir.path().join("content/metadata.json"),
+        metadata.to_string(),
+    )
+    .unwrap();
+    let dump_path = dir.path().join("invalid.dump");
+    archive_dump(&dir.path().join("content"), &dump_path);
+
+    let server = Server::new().await;
+    let error = server
+        .service
+        .data
+        .import_dump(&dump_path)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("../escaped"), "error: {}", error);
+    let (response, _code) = server.list_indexes().await;
+    assert_eq!(response, json!([]));
+}
+
+#[actix_rt::test]
+async fn import_legacy_v2_dump() {
+    let dir = TempDir::new("dump").unwrap();
+    let content = dir.path().join("content");
+    let uuid = "2b1e0b5c-8f0a-4d4e-9a5e-3f1c2d3e4f5a";
+    let index_dir = content.join("indexes").join(format!("index-{}", uuid));
+    std::fs::create_dir_all(&index_dir).unwrap();
+    std::fs::create_dir_all(content.join("index_uuids")).unwrap();
+
+    let metadata = json!({
+        "dumpVersion": "V2",
+        "dbVersion": "0.21.0",
+        "indexDbSize": 1024,
+        "updateDbSize": 1024,
+        "dumpDate": "2021-06-01T12:00:00Z",
+    });
+    std::fs::write(content.join("metadata.json"), metadata.to_string()).unwrap();
+    let entry = json!({ "uid": "movies", "uuid": uuid });
+    std::fs::write(content.join("index_uuids/data.jsonl"), entry.to_string()).unwrap();
+    let meta = json!({
+        "settings": {
+            "rankingRules": ["words", "typo"],
+            "searchableAttributes": ["*"],
+            "filterableAttributes": ["genre"],
+            "stopWords": [],
+        },
+        "primary_key": "id",
+    });
+    std::fs::write(index_dir.join("meta.json"), meta.to_string()).unwrap();
+    let documents = "{\"id\":1,\"genre\":\"drama\"}\n{\"id\":2,\"genre\":\"comedy\"}\n";
+    std::fs::write(index_dir.join("documents.jsonl"), documents).unwrap();
+
+    let dump_path = dir.path().join("v2.dump");
+    archive_dump(&content, &dump_path);
+
+    let server = Server::new().await;
+    server.service.data.import_dump(&dump_path).await.unwrap();
+
+    let index = server.index("movies");
+    index.wait_update_id(1).await;
+    let (response, _code) = index.settings().await;
+    assert_eq!(response["rankingRules"], json!(["words", "typo"]));
+    assert_eq!(response["searchableAttributes"], json!(["*"]));
+    assert_eq!(
+        response["attributesForFaceting"],
+        json!({ "genre": "string" })
+    );
+    let (response, _code) = index.stats().await;
+    assert_eq!(response["numberOfDocuments"], 2);
+}
+
+#[actix_rt::test]
+async fn restore_single_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(json!([{ "id": 1 }, { "id": 2 }]), None)
+        .await;
+    index.wait_update_id(0).await;
+    server.index("other").create(None).await;
+
+    let (response, _code) = server.create_dump().await;
+    let uid = response["uid"].as_str().unwrap().to_string();
+    server.wait_dump(&uid).await;
+
+    // Restored under another uid.
+    let (response, code) = server
+        .restore_index(&uid, json!({ "indexUid": "test", "targetUid": "restored" }))
+        .await;
+    assert_eq!(code, 202, "response: {}", response);
+    assert_eq!(response["uid"], "restored");
+    assert_eq!(response["primaryKey"], "id");
+    let restored = server.index("restored");
+    restored.wait_update_id(1).await;
+    let (response, _code) = restored.stats().await;
+    assert_eq!(response["numberOfDocuments"], 2);
+
+    // Restored over the existing index, which is replaced.
+    index.clear_all_documents().await;
+    index.wait_update_id(1).await;
+    let (_response, code) = server
+        .restore_index(&uid, json!({ "indexUid": "test" }))
+        .await;
+    assert_eq!(code, 202);
+    index.wait_update_id(1).await;
+    let (response, _code) = index.stats().await;
+    assert_eq!(response["numberOfDocuments"], 2);
+
+    // The other indexes of the dump are left untouched.
+    let (response, _code) = server.list_indexes().await;
+    assert_eq!(response.as_array().unwrap().len(), 3);
+}
+
+#[actix_rt::test]
+async fn restore_index_not_in_dump() {
+    let server = Server::new().await;
+    let (response, _code) = server.create_dump().await;
+    let uid = response["uid"].as_str().unwrap().to_string();
+    server.wait_dump(&uid).await;
+
+    let (response, code) = server
+        .restore_index(&uid, json!({ "indexUid": "test" }))
+        .await;
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "index_not_found");
+
+    let (_response, code) = server
+        .restore_index("unexisting", json!({ "indexUid": "test" }))
+        .await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn restore_index_on_replication_primary() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.replication_primary = true;
+    let server = Server::new_with_options(options).await;
+    server.index("test").create(None).await;
+
+    let (response, _code) = server.create_dump().await;
+    let uid = response["uid"].as_str().unwrap().to_string();
+    server.wait_dump(&uid).await;
+
+    let (response, code) = server
+        .restore_index(&uid, json!({ "indexUid": "test", "targetUid": "restored" }))
+        .await;
+    assert_eq!(code, 400, "response: {}", response);
+    let (_response, code) = server.index("restored").get().await;
+    assert_eq!(code, 404);
+}
+
+/// Runs the `meilisearch` binary with the given arguments, without analytics.
+fn run_command(args: &[&OsStr]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_meilisearch"))
+        .arg("--no-analytics")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn offline_dump_and_import() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(dir.path());
+    let db_path = options.db_path.clone();
+    let dumps_dir = options.dumps_dir.clone();
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1 }, { "id": 2 }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
+    let output = run_command(&[
+        OsStr::new("--db-path"),
+        db_path.as_os_str(),
+        OsStr::new("--dumps-dir"),
+        dumps_dir.as_os_str(),
+        OsStr::new("dump"),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let dumps: Vec<_> = read_dir(&dumps_dir).unwrap().collect();
+    assert_eq!(dumps.len(), 1);
+    let dump_path = dumps[0].as_ref().unwrap().path();
+
+    let import_dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(import_dir.path());
+    let output = run_command(&[
+        OsStr::new("--db-path"),
+        options.db_path.as_os_str(),
+        OsStr::new("import"),
+        dump_path.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // The documents were indexed by the import.
+    let server = Server::new_with_options(options).await;
+    let (response, code) = server.index("test").stats().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["numberOfDocuments"], 2);
+}
+
+#[actix_rt::test]
+async fn failed_import_keeps_the_previous_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_update_id(0).await;
+
+    let dir = TempDir::new("dump").unwrap();
+    let content = dir.path().join("content");
+    let index_dir = content.join("indexes/test");
+    std::fs::create_dir_all(&index_dir).unwrap();
+    let metadata = json!({
+        "dumpVersion": "V3",
+        "dbVersion": "0.1.0",
+        "indexes": [{ "uid": "test", "primaryKey": "id" }],
+    });
+    std::fs::write(content.join("metadata.json"), metadata.to_string()).unwrap();
+    std::fs::write(index_dir.join("settings.json"), "{}").unwrap();
+    // The document id is invalid, the documents addition fails.
+    std::fs::write(
+        index_dir.join("documents.jsonl"),
+        "{\"id\":\"invalid id!\"}\n",
+    )
+    .unwrap();
+    let dump_path = dir.path().join("invalid.dump");
+    archive_dump(&content, &dump_path);
+
+    let error = server
+        .service
+        .data
+        .import_dump(&dump_path)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("test"), "error: {}", error);
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 1);
+    let (response, _code) = index.get_document(1, None).await;
+    assert_eq!(response["id"], 1);
+}
@@ -0,0 +1,97 @@
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
+
+#[actix_rt::test]
+async fn clone_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .update_settings(json!({ "displayedAttributes": ["id", "content"] }))
+        .await;
+    index.wait_update_id(0).await;
+    index
+        .add_documents(json!([{ "id": 1, "content": "foo", "other": "bar" }]), None)
+        .await;
+    index.wait_update_id(1).await;
+
+    let (response, code) = index.clone_to("test_copy").await;
+    assert_eq!(code, 202, "{}", response);
+
+    let copy = server.index("test_copy");
+    let response = copy.wait_update_id(response["updateId"].as_u64().unwrap()).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+
+    let (response, code) = copy.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "id");
+
+    let (response, code) = copy.get_document(1, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!({ "id": 1, "content": "foo" }));
+
+    // The source index is left untouched.
+    let (response, code) = index.get_document(1, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!({ "id": 1, "content": "foo" }));
+}
+
+#[actix_rt::test]
+async fn clone_unexisting_index() {
+    let server = Server::new().await;
+    let (_response, code) = server.index("test").clone_to("test_copy").await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn clone_into_existing_index() {
+    let server = Server::new().await;
+    server.index("test").create(None).await;
+    server.index("test_copy").create(None).await;
+    let (_response, code) = server.index("test").clone_to("test_copy").await;
+    assert_eq!(code, 400);
+}
+
+#[actix_rt::test]
+async fn concurrent_clones_into_same_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let (first, second) = futures::join!(index.clone_to("test_copy"), index.clone_to("test_copy"));
+    let mut codes = vec![first.1, second.1];
+    codes.sort();
+    assert_eq!(codes, [202, 400]);
+}
+
+#[actix_rt::test]
+async fn failed_clone_is_removed() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // The clone waits to be processed long enough for its source to be deleted.
+    options.autobatch_debounce_ms = 1000;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let (response, code) = index.clone_to("test_copy").await;
+    assert_eq!(code, 202, "{}", response);
+    let update_id = response["updateId"].as_u64().unwrap();
+    index.delete().await;
+
+    let copy = server.index("test_copy");
+    let (response, code) = copy.get_update(update_id).await;
+    assert_eq!(code, 200, "{}", response);
+
+    // The index is removed in the background once the clone failed.
+    for _ in 0..50 {
+        let (_response, code) = copy.get_update(update_id).await;
+        if code == 404 {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("The failed clone wasn't removed");
+}
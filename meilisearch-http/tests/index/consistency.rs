@@ -0,0 +1,152 @@
+use std::fs::{copy, create_dir_all, read_dir, remove_dir_all};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::json;
+use tempdir::TempDir;
+use tokio::time::sleep;
+use uuid::Uuid;
+
+use crate::common::{default_settings, Server};
+
+/// The paths of the directories of `dir`.
+fn dirs(dir: &Path) -> Vec<PathBuf> {
+    read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn consistent_database() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_update_id(0).await;
+
+    let (response, code) = server.consistency().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["missingIndexes"], json!([]));
+    assert_eq!(response["orphans"], json!([]));
+    assert_eq!(response["repair"], "report");
+}
+
+#[actix_rt::test]
+async fn report_and_purge_orphans() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(dir.path());
+    let db_path = options.db_path.clone();
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_update_id(0).await;
+
+    let orphan_index = Uuid::new_v4();
+    let orphan_updates = Uuid::new_v4();
+    let orphan_index_path = db_path
+        .join("indexes")
+        .join(format!("index-{}", orphan_index));
+    let orphan_updates_path = db_path
+        .join("updates")
+        .join(format!("updates-{}", orphan_updates));
+    create_dir_all(&orphan_index_path).unwrap();
+    create_dir_all(&orphan_updates_path).unwrap();
+
+    let (response, code) = server.consistency().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["missingIndexes"], json!([]));
+    let orphans = response["orphans"].as_array().unwrap();
+    assert_eq!(orphans.len(), 2);
+    assert!(orphans.contains(&json!(orphan_index.to_string())));
+    assert!(orphans.contains(&json!(orphan_updates.to_string())));
+    // Reporting doesn't remove anything.
+    assert!(orphan_index_path.exists());
+
+    let (response, code) = server.repair_consistency("purge").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["repair"], "purge");
+    assert_eq!(response["orphans"].as_array().unwrap().len(), 2);
+
+    // The data is removed in the background.
+    for _ in 0..50 {
+        if !orphan_index_path.exists() && !orphan_updates_path.exists() {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(!orphan_index_path.exists());
+    assert!(!orphan_updates_path.exists());
+
+    let (response, _code) = server.consistency().await;
+    assert_eq!(response["orphans"], json!([]));
+    assert_eq!(index.get().await.1, 200);
+}
+
+#[actix_rt::test]
+async fn purge_index_with_missing_data() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(dir.path());
+    let db_path = options.db_path.clone();
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    let (_response, code) = index.create(None).await;
+    assert_eq!(code, 200);
+
+    let index_paths = dirs(&db_path.join("indexes"));
+    assert_eq!(index_paths.len(), 1);
+    remove_dir_all(&index_paths[0]).unwrap();
+
+    let (response, code) = server.consistency().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["missingIndexes"][0]["uid"], "test");
+
+    let (response, code) = server.repair_consistency("purge").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(index.get().await.1, 404);
+
+    let (response, _code) = server.consistency().await;
+    assert_eq!(response["missingIndexes"], json!([]));
+}
+
+#[actix_rt::test]
+async fn relink_orphan_index() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(dir.path());
+    let db_path = options.db_path.clone();
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_update_id(0).await;
+
+    // A copy of the database of the index, that no index references.
+    let index_paths = dirs(&db_path.join("indexes"));
+    assert_eq!(index_paths.len(), 1);
+    let orphan = Uuid::new_v4();
+    let orphan_path = db_path.join("indexes").join(format!("index-{}", orphan));
+    create_dir_all(&orphan_path).unwrap();
+    copy(
+        index_paths[0].join("data.mdb"),
+        orphan_path.join("data.mdb"),
+    )
+    .unwrap();
+
+    let (response, code) = server.repair_consistency("relink").await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["orphans"], json!([orphan.to_string()]));
+
+    let relinked = server.index(format!("orphan-{}", orphan));
+    let (response, code) = relinked.stats().await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["numberOfDocuments"], 1);
+
+    let (response, _code) = server.consistency().await;
+    assert_eq!(response["orphans"], json!([]));
+}
+
+#[actix_rt::test]
+async fn invalid_repair() {
+    let server = Server::new().await;
+    let (_response, code) = server.repair_consistency("fix").await;
+    assert_eq!(code, 400);
+}
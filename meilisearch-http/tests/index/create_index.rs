@@ -1,5 +1,8 @@
-use crate::common::Server;
-use serde_json::Value;
+use byte_unit::Byte;
+use serde_json::{json, Value};
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
 
 #[actix_rt::test]
 async fn create_index_no_primary_key() {
@@ -70,5 +73,68 @@ async fn test_create_multiple_indexes() {
     assert_eq!(index1.get().await.1, 200);
     assert_eq!(index2.get().await.1, 200);
     assert_eq!(index3.get().await.1, 200);
-    assert_eq!(index4.get().await.1, 400);
+    assert_eq!(index4.get().await.1, 404);
+}
+
+#[actix_rt::test]
+async fn create_with_too_long_index_uid() {
+    let server = Server::new().await;
+    let index = server.index("a".repeat(401));
+    let (response, code) = index.create(None).await;
+    assert_eq!(code, 400);
+    assert_eq!(response["code"], "index_uid_too_long");
+    assert!(response["message"].as_str().unwrap().contains("too long"));
+
+    let index = server.index("a".repeat(400));
+    let (_, code) = index.create(None).await;
+    assert_eq!(code, 200);
+}
+
+#[actix_rt::test]
+async fn create_with_invalid_index_uid_reports_pattern() {
+    let server = Server::new().await;
+    let index = server.index("test.test");
+    let (response, code) = index.create(None).await;
+    assert_eq!(code, 400);
+    assert_eq!(response["code"], "invalid_index_uid");
+    assert!(response["message"]
+        .as_str()
+        .unwrap()
+        .contains("^[a-zA-Z0-9_-]+$"));
+}
+
+#[actix_rt::test]
+async fn create_indexes_concurrently() {
+    let server = Server::new().await;
+    let uids: Vec<String> = (0..10).map(|i| format!("test{}", i)).collect();
+
+    let server = &server;
+    let responses = futures::future::join_all(uids.iter().map(|uid| async move {
+        server.index(uid).create(None).await
+    }))
+    .await;
+
+    for (_response, code) in responses {
+        assert_eq!(code, 200);
+    }
+
+    let (response, code) = server.list_indexes().await;
+    assert_eq!(code, 200);
+    assert_eq!(response.as_array().unwrap().len(), 10);
+}
+
+#[actix_rt::test]
+async fn create_index_payload_too_large() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.http_payload_size_limit = Byte::from_bytes(100);
+    let server = Server::new_with_options(options).await;
+
+    let body = json!({ "uid": "test", "primaryKey": "a".repeat(200) });
+    let (response, code) = server.service.post("/indexes", body).await;
+    assert_eq!(code, 413);
+    assert_eq!(response["code"], "payload_too_large");
+
+    let (_response, code) = server.service.post("/indexes", json!({ "uid": "test" })).await;
+    assert_eq!(code, 200);
 }
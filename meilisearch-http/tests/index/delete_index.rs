@@ -1,4 +1,12 @@
-use crate::common::Server;
+use std::fs::read_dir;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde_json::json;
+use tempdir::TempDir;
+use tokio::time::sleep;
+
+use crate::common::{default_settings, Server};
 
 #[actix_rt::test]
 async fn create_and_delete_index() {
@@ -12,7 +20,7 @@ async fn create_and_delete_index() {
 
     assert_eq!(code, 204);
 
-    assert_eq!(index.get().await.1, 400);
+    assert_eq!(index.get().await.1, 404);
 }
 
 #[actix_rt::test]
@@ -21,5 +29,36 @@ async fn delete_unexisting_index() {
     let index = server.index("test");
     let (_response, code) = index.delete().await;
 
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn delete_index_removes_its_data() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // The second addition is still pending when the index is deleted.
+    options.autobatch_debounce_ms = 2000;
+    let db_path = options.db_path.clone();
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_update_id(0).await;
+    index.add_documents(json!([{ "id": 2 }]), None).await;
+
+    let (_response, code) = index.delete().await;
+    assert_eq!(code, 204);
+
+    let count = |path: PathBuf| read_dir(path).map_or(0, |entries| entries.count());
+    // The data is removed in the background.
+    for _ in 0..50 {
+        let removed = count(db_path.join("indexes")) == 0
+            // Only the directory of the update files is left.
+            && count(db_path.join("updates")) == 1
+            && count(db_path.join("updates").join("update_files")) == 0;
+        if removed {
+            return;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    panic!("The data of the deleted index was not removed.");
 }
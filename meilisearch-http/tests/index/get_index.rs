@@ -21,16 +21,19 @@ async fn create_and_get_index() {
     assert_eq!(response.as_object().unwrap().len(), 5);
 }
 
-// TODO: partial test since we are testing error, amd error is not yet fully implemented in
-// transplant
 #[actix_rt::test]
 async fn get_unexisting_index() {
     let server = Server::new().await;
     let index = server.index("test");
 
-    let (_response, code) = index.get().await;
+    let (response, code) = index.get().await;
 
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "index_not_found");
+    assert_eq!(response["type"], "invalid_request_error");
+    assert!(response["message"].is_string());
+    assert!(response["link"].is_string());
+    assert_eq!(response.as_object().unwrap().len(), 4);
 }
 
 #[actix_rt::test]
@@ -62,3 +65,39 @@ async fn list_multiple_indexes() {
         .find(|entry| entry["uid"] == "test1" && entry["primaryKey"] == "key")
         .is_some());
 }
+
+#[actix_rt::test]
+async fn list_indexes_with_offset_and_limit() {
+    let server = Server::new().await;
+    for uid in &["a", "b", "c", "d"] {
+        server.index(uid).create(None).await;
+    }
+
+    let (response, code) = server.service.get("/indexes?offset=1&limit=2").await;
+    assert_eq!(code, 200);
+    let uids: Vec<_> = response
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["uid"].as_str().unwrap())
+        .collect();
+    assert_eq!(uids, ["b", "c"]);
+}
+
+#[actix_rt::test]
+async fn list_indexes_filtered_by_uid_prefix() {
+    let server = Server::new().await;
+    for uid in &["movies", "movies_fr", "books"] {
+        server.index(uid).create(None).await;
+    }
+
+    let (response, code) = server.service.get("/indexes?uid=movies").await;
+    assert_eq!(code, 200);
+    let uids: Vec<_> = response
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["uid"].as_str().unwrap())
+        .collect();
+    assert_eq!(uids, ["movies", "movies_fr"]);
+}
@@ -1,4 +1,7 @@
+mod clone_index;
+mod consistency;
 mod create_index;
 mod delete_index;
 mod get_index;
+mod reindex;
 mod update_index;
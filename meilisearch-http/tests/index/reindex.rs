@@ -0,0 +1,149 @@
+use std::fs::read_dir;
+
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Index, Server};
+
+/// Creates the index with two documents, whose `other` field isn't displayed.
+async fn create_index(index: &Index<'_>) {
+    index.create(Some("id")).await;
+    index
+        .update_settings(json!({ "displayedAttributes": ["id", "content", "author"] }))
+        .await;
+    index.wait_update_id(0).await;
+    index
+        .add_documents(
+            json!([
+                { "id": 1, "content": "foo", "author": { "name": "kero" }, "other": "bar" },
+                { "id": 2, "content": "baz", "author": { "name": "pom" }, "other": "qux" },
+            ]),
+            None,
+        )
+        .await;
+    index.wait_update_id(1).await;
+}
+
+#[actix_rt::test]
+async fn reindex() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    create_index(&index).await;
+    let (settings, _) = index.settings().await;
+
+    server
+        .service
+        .data
+        .reindex_and_wait("test".to_string())
+        .await
+        .unwrap();
+
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "id");
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200);
+    assert_eq!(response, settings);
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 2);
+
+    let (response, code) = index.get_document(1, None).await;
+    assert_eq!(code, 200);
+    assert_eq!(
+        response,
+        json!({ "id": 1, "content": "foo", "author": { "name": "kero" } })
+    );
+
+    // The fields that aren't displayed are kept.
+    let (response, code) = index.search(json!({ "q": "qux" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["hits"],
+        json!([{ "id": 2, "content": "baz", "author": { "name": "pom" } }])
+    );
+
+    // The update history is kept.
+    let (response, code) = index.list_updates().await;
+    assert_eq!(code, 200);
+    let updates = response.as_array().unwrap();
+    assert_eq!(updates.len(), 3);
+    assert_eq!(updates[2]["meta"]["type"], "Reindex");
+    assert_eq!(updates[2]["status"], "processed");
+}
+
+#[actix_rt::test]
+async fn reindex_with_new_settings() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(dir.path());
+    let indexes_path = options.db_path.join("indexes");
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    create_index(&index).await;
+
+    let (response, code) = index
+        .reindex(json!({ "searchableAttributes": ["content"] }))
+        .await;
+    assert_eq!(code, 202, "{}", response);
+    assert_eq!(response["updateId"], 2);
+    // The updates registered during the rebuild are applied to the rebuilt index.
+    index
+        .add_documents(json!([{ "id": 3, "content": "bar" }]), None)
+        .await;
+
+    let response = index.wait_update_id(2).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+    let response = index.wait_update_id(3).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["searchableAttributes"], json!(["content"]));
+    assert_eq!(
+        response["displayedAttributes"],
+        json!(["id", "content", "author"])
+    );
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 3);
+
+    // The `other` field isn't searchable anymore.
+    let (response, code) = index.search(json!({ "q": "bar" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"], json!([{ "id": 3, "content": "bar" }]));
+
+    // The shadow index replaced the index.
+    assert_eq!(read_dir(&indexes_path).unwrap().count(), 1);
+}
+
+#[actix_rt::test]
+async fn failed_reindex_keeps_the_index() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(dir.path());
+    let indexes_path = options.db_path.join("indexes");
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    create_index(&index).await;
+
+    let (response, code) = index.reindex(json!({ "languages": ["invalid"] })).await;
+    assert_eq!(code, 202, "{}", response);
+    let response = index.wait_update_id(2).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 2);
+
+    // The shadow index is removed.
+    assert_eq!(read_dir(&indexes_path).unwrap().count(), 1);
+}
+
+#[actix_rt::test]
+async fn reindex_unexisting_index() {
+    let server = Server::new().await;
+    let (_response, code) = server.index("test").reindex(json!({})).await;
+    assert_eq!(code, 404);
+}
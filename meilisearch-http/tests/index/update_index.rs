@@ -39,6 +39,20 @@ async fn update_nothing() {
     assert_eq!(response, update);
 }
 
+#[actix_rt::test]
+async fn update_existing_primary_key_on_empty_index() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let (_response, code) = index.create(Some("primary")).await;
+
+    assert_eq!(code, 200);
+
+    let (response, code) = index.update(Some("primary2")).await;
+
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "primary2");
+}
+
 // TODO: partial test since we are testing error, amd error is not yet fully implemented in
 // transplant
 #[actix_rt::test]
@@ -49,6 +63,11 @@ async fn update_existing_primary_key() {
 
     assert_eq!(code, 200);
 
+    index
+        .add_documents(serde_json::json!([{ "primary": 1 }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
     let (_update, code) = index.update(Some("primary2")).await;
 
     assert_eq!(code, 400);
@@ -60,5 +79,5 @@ async fn update_existing_primary_key() {
 async fn test_unexisting_index() {
     let server = Server::new().await;
     let (_response, code) = server.index("test").update(None).await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
@@ -1,10 +1,17 @@
 mod common;
 mod documents;
+mod dumps;
 mod index;
+mod keys;
+mod production;
+mod replication;
 mod search;
 mod settings;
+mod snapshot;
 mod updates;
 mod stats;
+mod tasks;
+mod verify;
 
 // Tests are isolated by features in different modules to allow better readability, test
 // targetability, and improved incremental compilation times.
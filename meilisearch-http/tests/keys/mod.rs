@@ -0,0 +1,132 @@
+use actix_web::test;
+use serde_json::json;
+use sha2::Digest;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
+
+async fn server_with_master_key(dir: &TempDir, grace_period_sec: u64) -> Server {
+    let mut options = default_settings(dir.path());
+    options.master_key = Some("master".to_string());
+    options.master_key_grace_period_sec = grace_period_sec;
+    Server::new_with_options(options).await
+}
+
+async fn get_indexes_with_key(server: &Server, key: &str) -> u16 {
+    let req = test::TestRequest::get()
+        .uri("/indexes")
+        .insert_header(("X-Meili-API-Key", key))
+        .to_request();
+    let (_response, code) = server.service.request(req).await;
+    code.as_u16()
+}
+
+#[actix_rt::test]
+async fn master_key_protects_routes() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = server_with_master_key(&dir, 300).await;
+
+    let (_response, code) = server.list_indexes().await;
+    assert_eq!(code, 401);
+    assert_eq!(get_indexes_with_key(&server, "wrong").await, 403);
+    assert_eq!(get_indexes_with_key(&server, "master").await, 200);
+}
+
+#[actix_rt::test]
+async fn rotate_master_key() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = server_with_master_key(&dir, 300).await;
+
+    let req = test::TestRequest::post()
+        .uri("/keys/rotate")
+        .insert_header(("X-Meili-API-Key", "master"))
+        .set_json(&json!({ "masterKey": "new-master" }))
+        .to_request();
+    let (response, code) = server.service.request(req).await;
+    assert_eq!(code, 200);
+    let private_key = response["private"].as_str().unwrap().to_string();
+
+    assert_eq!(get_indexes_with_key(&server, "new-master").await, 200);
+    assert_eq!(get_indexes_with_key(&server, &private_key).await, 200);
+    // The previous master key is still accepted during the grace period.
+    assert_eq!(get_indexes_with_key(&server, "master").await, 200);
+}
+
+#[actix_rt::test]
+async fn rotate_master_key_without_grace_period() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = server_with_master_key(&dir, 0).await;
+
+    let req = test::TestRequest::post()
+        .uri("/keys/rotate")
+        .insert_header(("X-Meili-API-Key", "master"))
+        .set_json(&json!({ "masterKey": "new-master" }))
+        .to_request();
+    let (_response, code) = server.service.request(req).await;
+    assert_eq!(code, 200);
+
+    assert_eq!(get_indexes_with_key(&server, "new-master").await, 200);
+    assert_eq!(get_indexes_with_key(&server, "master").await, 403);
+}
+
+#[actix_rt::test]
+async fn rotate_master_key_without_master_key() {
+    let server = Server::new().await;
+    let (_response, code) = server
+        .service
+        .post("/keys/rotate", json!({ "masterKey": "new-master-key" }))
+        .await;
+    assert_eq!(code, 400);
+
+    let (response, code) = server.service.get("/keys").await;
+    assert_eq!(code, 200);
+    assert!(response["private"].is_null());
+    assert!(response["public"].is_null());
+}
+
+#[actix_rt::test]
+async fn rotate_master_key_with_empty_key() {
+    let server = Server::new().await;
+    let (_response, code) = server
+        .service
+        .post("/keys/rotate", json!({ "masterKey": "" }))
+        .await;
+    assert_eq!(code, 400);
+}
+
+async fn rotate(server: &Server, key: &str, new_key: &str) {
+    let req = test::TestRequest::post()
+        .uri("/keys/rotate")
+        .insert_header(("X-Meili-API-Key", key))
+        .set_json(&json!({ "masterKey": new_key }))
+        .to_request();
+    let (response, code) = server.service.request(req).await;
+    assert_eq!(code, 200, "response: {}", response);
+}
+
+#[actix_rt::test]
+async fn all_the_previous_keys_are_accepted_during_their_grace_period() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = server_with_master_key(&dir, 300).await;
+
+    rotate(&server, "master", "second").await;
+    rotate(&server, "second", "third").await;
+
+    assert_eq!(get_indexes_with_key(&server, "third").await, 200);
+    assert_eq!(get_indexes_with_key(&server, "second").await, 200);
+    assert_eq!(get_indexes_with_key(&server, "master").await, 200);
+}
+
+#[actix_rt::test]
+async fn rotated_master_key_is_persisted() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = server_with_master_key(&dir, 300).await;
+    rotate(&server, "master", "second").await;
+
+    let persisted = std::fs::read_to_string(dir.path().join("db/rotated-master-key.json")).unwrap();
+    let persisted: serde_json::Value = serde_json::from_str(&persisted).unwrap();
+    assert_eq!(persisted["masterKey"], "second");
+    // Only the hashes of the previous keys are persisted.
+    let hash = format!("{:x}", sha2::Sha256::digest(b"master"));
+    assert_eq!(persisted["previousKeys"][0][0]["master"], hash);
+}
@@ -0,0 +1,140 @@
+use actix_web::test;
+use meilisearch_http::create_app;
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
+
+async fn production_server(dir: &TempDir, allowed_origins: &[&str]) -> Server {
+    let mut options = default_settings(dir.path());
+    options.env = "production".to_string();
+    options.master_key = Some("master".to_string());
+    options.http_allowed_origins = allowed_origins.iter().map(|o| o.to_string()).collect();
+    Server::new_with_options(options).await
+}
+
+/// Returns the `Access-Control-Allow-Origin` header of the response to a request from `origin`.
+async fn allowed_origin(server: &Server, origin: &str) -> Option<String> {
+    let app = test::init_service(create_app!(&server.service.data, true)).await;
+    let req = test::TestRequest::get()
+        .uri("/health")
+        .insert_header(("Origin", origin))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    res.headers()
+        .get("access-control-allow-origin")
+        .map(|value| value.to_str().unwrap().to_string())
+}
+
+#[actix_rt::test]
+async fn any_origin_is_allowed_in_development() {
+    let server = Server::new().await;
+    let origin = allowed_origin(&server, "https://example.com").await;
+    assert_eq!(origin.as_deref(), Some("*"));
+}
+
+#[actix_rt::test]
+async fn only_the_given_origins_are_allowed_in_production() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = production_server(&dir, &["https://example.com"]).await;
+
+    let origin = allowed_origin(&server, "https://example.com").await;
+    assert_eq!(origin.as_deref(), Some("https://example.com"));
+    let origin = allowed_origin(&server, "https://other.com").await;
+    assert_eq!(origin, None);
+}
+
+#[actix_rt::test]
+async fn no_origin_is_allowed_by_default_in_production() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = production_server(&dir, &[]).await;
+
+    let origin = allowed_origin(&server, "https://example.com").await;
+    assert_eq!(origin, None);
+}
+
+async fn add_documents_after_shutdown(server: &Server) -> serde_json::Value {
+    server.service.data.shutdown().await.unwrap();
+    let req = test::TestRequest::post()
+        .uri("/indexes/test/documents")
+        .insert_header(("X-Meili-API-Key", "master"))
+        .set_json(&json!([{ "id": 1 }]))
+        .to_request();
+    let (response, code) = server.service.request(req).await;
+    assert_eq!(code, 503);
+    assert_eq!(response["code"], "shutting_down");
+    response
+}
+
+#[actix_rt::test]
+async fn internal_errors_are_detailed_in_development() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.master_key = Some("master".to_string());
+    let server = Server::new_with_options(options).await;
+
+    let response = add_documents_after_shutdown(&server).await;
+    assert_ne!(response["message"], "An internal error occurred");
+}
+
+#[actix_rt::test]
+async fn internal_errors_are_hidden_in_production() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = production_server(&dir, &[]).await;
+
+    let response = add_documents_after_shutdown(&server).await;
+    assert_eq!(response["message"], "An internal error occurred");
+}
+
+async fn request_from(
+    server: &Server,
+    req: test::TestRequest,
+    ip: &str,
+) -> (serde_json::Value, actix_web::http::StatusCode) {
+    let req = req.peer_addr(format!("{}:1234", ip).parse().unwrap());
+    server.service.request(req.to_request()).await
+}
+
+#[actix_rt::test]
+async fn administrative_routes_are_restricted_to_the_allowed_ips() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.admin_allowed_ips = vec!["10.0.0.0/24".parse().unwrap()];
+    let server = Server::new_with_options(options).await;
+
+    let admin_routes = vec![
+        test::TestRequest::delete().uri("/indexes/test/documents"),
+        test::TestRequest::delete().uri("/indexes/test"),
+        test::TestRequest::post().uri("/indexes/test/reindex"),
+        test::TestRequest::post().uri("/consistency"),
+        test::TestRequest::post().uri("/dumps"),
+        test::TestRequest::get().uri("/keys"),
+        test::TestRequest::delete().uri("/tasks?statuses=succeeded"),
+        test::TestRequest::post().uri("/tasks/cancel?statuses=enqueued"),
+    ];
+    for req in admin_routes {
+        let (response, code) = request_from(&server, req, "127.0.0.1").await;
+        assert_eq!(code, 403);
+        assert_eq!(response["code"], "ip_not_allowed");
+    }
+
+    let (_response, code) = request_from(
+        &server,
+        test::TestRequest::get().uri("/indexes"),
+        "127.0.0.1",
+    )
+    .await;
+    assert_eq!(code, 200);
+
+    let (_response, code) =
+        request_from(&server, test::TestRequest::get().uri("/keys"), "10.0.0.1").await;
+    assert_eq!(code, 200);
+}
+
+#[actix_rt::test]
+async fn administrative_routes_are_open_without_allowlist() {
+    let server = Server::new().await;
+    let (_response, code) =
+        request_from(&server, test::TestRequest::get().uri("/keys"), "127.0.0.1").await;
+    assert_eq!(code, 200);
+}
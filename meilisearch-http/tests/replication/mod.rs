@@ -0,0 +1,201 @@
+use std::net::TcpListener;
+use std::num::NonZeroU64;
+use std::time::Duration;
+
+use actix_web::HttpServer;
+use meilisearch_http::create_app;
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
+
+#[actix_rt::test]
+async fn primary_records_updates() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.replication_primary = true;
+    let server = Server::new_with_options(options).await;
+
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(json!([{ "id": 1, "title": "foo" }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
+    let (response, code) = server.replication_log(0).await;
+    assert_eq!(code, 200, "response: {}", response);
+    let entries = response.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["seq"], 1);
+    assert_eq!(entries[0]["op"]["type"], "createIndex");
+    assert_eq!(entries[0]["op"]["uid"], "test");
+    assert_eq!(entries[0]["op"]["primaryKey"], "id");
+    assert_eq!(entries[1]["seq"], 2);
+    assert_eq!(entries[1]["op"]["type"], "update");
+    assert_eq!(entries[1]["op"]["meta"]["type"], "DocumentsAddition");
+    assert_eq!(entries[1]["hasPayload"], true);
+
+    let (response, code) = server.replication_payload(2).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!([{ "id": 1, "title": "foo" }]));
+
+    let (response, code) = server.replication_log(2).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!([]));
+
+    let (response, code) = server.replication_payload(1).await;
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "not_found");
+}
+
+#[actix_rt::test]
+async fn primary_records_task_deletions_and_refuses_cancellations() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.replication_primary = true;
+    let server = Server::new_with_options(options).await;
+
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_update_id(0).await;
+
+    let (response, code) = server.cancel_tasks("indexUids=test").await;
+    assert_eq!(code, 400, "response: {}", response);
+
+    let (response, code) = server.delete_tasks("indexUids=test&statuses=succeeded").await;
+    assert_eq!(code, 200, "response: {}", response);
+
+    let (response, code) = server.replication_log(2).await;
+    assert_eq!(code, 200, "response: {}", response);
+    let entries = response.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["op"]["type"], "deleteTasks");
+    assert_eq!(entries[0]["op"]["filter"]["indexUids"], json!(["test"]));
+    assert_eq!(entries[0]["op"]["filter"]["statuses"], json!(["succeeded"]));
+}
+
+#[actix_rt::test]
+async fn replication_disabled() {
+    let server = Server::new().await;
+    let (response, code) = server.replication_log(0).await;
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "replication_disabled");
+}
+
+#[actix_rt::test]
+async fn replica_rejects_updates() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // Nothing listens on this port, the replica keeps retrying in the background.
+    options.replicate_from = Some("http://127.0.0.1:1".to_string());
+    let server = Server::new_with_options(options).await;
+
+    let index = server.index("test");
+    let (response, code) = index.create(None).await;
+    assert_eq!(code, 403);
+    assert_eq!(response["code"], "read_only_replica");
+
+    let (response, code) = index
+        .add_documents(json!([{ "id": 1, "title": "foo" }]), None)
+        .await;
+    assert_eq!(code, 403);
+    assert_eq!(response["code"], "read_only_replica");
+
+    let (response, code) = index.delete_batch(vec![1]).await;
+    assert_eq!(code, 403);
+    assert_eq!(response["code"], "read_only_replica");
+
+    // The searches are served by the replica.
+    let (response, code) = index.search(json!({ "q": "foo" })).await;
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "index_not_found");
+
+    let (response, code) = index
+        .facet_search(json!({ "facetName": "title", "facetQuery": "foo" }))
+        .await;
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "index_not_found");
+}
+
+#[actix_rt::test]
+async fn primary_prunes_the_oldest_entries() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.replication_primary = true;
+    options.replication_log_retention = NonZeroU64::new(1).unwrap();
+    let server = Server::new_with_options(options).await;
+
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(json!([{ "id": 1, "title": "foo" }]), None)
+        .await;
+
+    let (response, code) = server.replication_log(1).await;
+    assert_eq!(code, 200, "response: {}", response);
+    assert_eq!(response.as_array().unwrap().len(), 1);
+    assert_eq!(response[0]["seq"], 2);
+
+    // The replicas that didn't apply the first entry can't catch up.
+    let (response, code) = server.replication_log(0).await;
+    assert_eq!(code, 410);
+    assert_eq!(response["code"], "replication_log_truncated");
+
+    // The payloads are removed with their entries.
+    index
+        .add_documents(json!([{ "id": 2, "title": "bar" }]), None)
+        .await;
+    let (response, code) = server.replication_payload(2).await;
+    assert_eq!(code, 404, "response: {}", response);
+    let (response, code) = server.replication_payload(3).await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!([{ "id": 2, "title": "bar" }]));
+    let payloads = std::fs::read_dir(dir.path().join("db/replication/payloads")).unwrap();
+    assert_eq!(payloads.count(), 1);
+}
+
+#[actix_rt::test]
+async fn replica_applies_the_updates_of_its_primary() {
+    let primary_dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(primary_dir.path());
+    options.replication_primary = true;
+    let primary = Server::new_with_options(options).await;
+
+    // The replica fetches the log over HTTP, the primary must listen on a port.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let data = primary.service.data.clone();
+    let http_server = HttpServer::new(move || create_app!(&data, true))
+        .workers(1)
+        .disable_signals()
+        .listen(listener)
+        .unwrap()
+        .run();
+    actix_rt::spawn(http_server);
+
+    let index = primary.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(json!([{ "id": 1, "title": "foo" }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
+    let replica_dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(replica_dir.path());
+    options.replicate_from = Some(format!("http://{}", addr));
+    let replica = Server::new_with_options(options).await;
+
+    let replicated = replica.index("test");
+    for _ in 0..50 {
+        let (response, code) = replicated.stats().await;
+        if code == 200 && response["numberOfDocuments"] == 1 {
+            let (response, _code) = replicated.get_document(1, None).await;
+            assert_eq!(response["title"], "foo");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    panic!("The replica didn't apply the updates of its primary.");
+}
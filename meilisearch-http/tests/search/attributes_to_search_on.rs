@@ -0,0 +1,78 @@
+use crate::common::{sorted_hits_ids, Index, Server};
+use crate::test_post_get_search;
+use serde_json::json;
+
+async fn load_vehicles(index: &Index<'_>) {
+    let documents = json!([
+        { "id": 1, "title": "red car", "description": "fast and red" },
+        { "id": 2, "title": "blue boat", "description": "not a car" },
+        { "id": 3, "title": "green bike", "description": "nothing like a boat" },
+    ]);
+    index.load_documents(documents).await;
+}
+
+#[actix_rt::test]
+async fn search_on_attributes() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_vehicles(&index).await;
+
+    let (response, code) = index.search(json!({ "q": "car" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2]);
+
+    let query = json!({ "q": "car", "attributesToSearchOn": ["title"] });
+    test_post_get_search!(index, query, |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(sorted_hits_ids(&response), vec![1]);
+        assert_eq!(response["nbHits"], 1);
+    });
+
+    let (response, code) = index
+        .search(json!({ "q": "boat", "attributesToSearchOn": ["description"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![3]);
+}
+
+#[actix_rt::test]
+async fn search_on_attribute_not_searchable() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_vehicles(&index).await;
+
+    let (_, code) = index
+        .update_settings(json!({ "searchableAttributes": ["title"] }))
+        .await;
+    assert_eq!(code, 202);
+    index.wait_update_id(1).await;
+
+    let (response, code) = index
+        .search(json!({ "q": "car", "attributesToSearchOn": ["description"] }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+}
+
+#[actix_rt::test]
+async fn paginate_hits_searched_on_attributes() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents: Vec<_> = (0..20)
+        .map(|id| {
+            if id % 2 == 0 {
+                json!({ "id": id, "title": "car", "description": "red" })
+            } else {
+                json!({ "id": id, "title": "boat", "description": "car" })
+            }
+        })
+        .collect();
+    index.load_documents(json!(documents)).await;
+
+    let query = json!({ "q": "car", "attributesToSearchOn": ["title"], "offset": 5, "limit": 10 });
+    test_post_get_search!(index, query, |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(response["hits"].as_array().unwrap().len(), 5);
+        assert!(sorted_hits_ids(&response).iter().all(|id| id % 2 == 0));
+        assert_eq!(response["nbHits"], 10);
+    });
+}
@@ -0,0 +1,81 @@
+use crate::common::{default_settings, Server};
+use serde_json::json;
+use tempdir::TempDir;
+
+async fn server_with_cache() -> Server {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.search_cache_size = 10;
+    Server::new_with_options(options).await
+}
+
+#[actix_rt::test]
+async fn cached_search_returns_the_same_results() {
+    let server = server_with_cache().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let query = json!({ "q": "exercitation", "attributesToHighlight": ["about"] });
+    let (first, code) = index.search(query.clone()).await;
+    assert_eq!(code, 200, "{}", first);
+    let (second, code) = index.search(query).await;
+    assert_eq!(code, 200, "{}", second);
+    assert_eq!(first["hits"], second["hits"]);
+    assert_eq!(first["nbHits"], second["nbHits"]);
+
+    // The queries differing by their parameters don't share their results.
+    let (response, code) = index
+        .search(json!({ "q": "exercitation", "limit": 1 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 1);
+}
+
+#[actix_rt::test]
+async fn cached_search_is_invalidated_by_updates() {
+    let server = server_with_cache().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "red car" }]), Some("id"))
+        .await;
+    index.wait_update_id(0).await;
+
+    let (response, code) = index.search(json!({ "q": "car" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["nbHits"], 1);
+
+    index
+        .add_documents(json!([{ "id": 2, "title": "blue car" }]), None)
+        .await;
+    index.wait_update_id(1).await;
+
+    let (response, code) = index.search(json!({ "q": "car" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["nbHits"], 2);
+
+    index.delete_document(1).await;
+    index.wait_update_id(2).await;
+
+    let (response, code) = index.search(json!({ "q": "car" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["nbHits"], 1);
+}
+
+#[actix_rt::test]
+async fn cached_search_is_invalidated_by_index_deletion() {
+    let server = server_with_cache().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "red car" }]), Some("id"))
+        .await;
+    index.wait_update_id(0).await;
+
+    let (response, code) = index.search(json!({ "q": "car" })).await;
+    assert_eq!(code, 200, "{}", response);
+
+    let (_, code) = index.delete().await;
+    assert_eq!(code, 204);
+
+    let (response, code) = index.search(json!({ "q": "car" })).await;
+    assert_eq!(code, 404, "{}", response);
+}
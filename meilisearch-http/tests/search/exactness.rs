@@ -0,0 +1,86 @@
+use crate::common::{sorted_hits_ids, Index, Server};
+use serde_json::json;
+
+async fn load_codes(index: &Index<'_>) {
+    let documents = json!([
+        { "id": 1, "code": "A0211", "label": "salmonella sepsis" },
+        { "id": 2, "code": "A0212", "label": "salmonella pneumonia" },
+        { "id": 3, "code": "B0211", "label": "herpes zoster" },
+    ]);
+    index.load_documents(documents).await;
+}
+
+#[actix_rt::test]
+async fn search_without_exactness_rules_matches_typos() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_codes(&index).await;
+
+    let (response, code) = index.search(json!({ "q": "a0211 " })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2, 3]);
+}
+
+#[actix_rt::test]
+async fn search_exact_attribute() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_codes(&index).await;
+    index
+        .update_settings(json!({ "exactAttributes": ["code"] }))
+        .await;
+    index.wait_update_id(1).await;
+
+    let (response, code) = index.search(json!({ "q": "a0211 " })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1]);
+    assert_eq!(response["nbHits"], 1);
+
+    // The other attributes still match with typos.
+    let (response, code) = index.search(json!({ "q": "salmonela " })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2]);
+}
+
+#[actix_rt::test]
+async fn search_exact_word() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_codes(&index).await;
+    index
+        .update_settings(json!({ "exactWords": ["A0211"] }))
+        .await;
+    index.wait_update_id(1).await;
+
+    let (response, code) = index.search(json!({ "q": "a0211 " })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1]);
+
+    // The words that are not exact still match with typos.
+    let (response, code) = index.search(json!({ "q": "a0212 " })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2]);
+}
+
+#[actix_rt::test]
+async fn paginate_exact_hits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents: Vec<_> = (0..20)
+        .map(|id| json!({ "id": id, "code": if id % 2 == 0 { "A0211" } else { "A0212" } }))
+        .collect();
+    index.load_documents(json!(documents)).await;
+    index
+        .update_settings(json!({ "exactAttributes": ["code"] }))
+        .await;
+    index.wait_update_id(1).await;
+
+    // The hits matching with a typo are removed before paginating.
+    let (response, code) = index
+        .search(json!({ "q": "a0211 ", "offset": 5, "limit": 10 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 5);
+    assert_eq!(response["nbHits"], 10);
+    assert!(sorted_hits_ids(&response).iter().all(|id| id % 2 == 0));
+}
@@ -0,0 +1,95 @@
+use crate::common::Server;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn search_returns_range_facets() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "name": "phone", "price": 499.5 },
+        { "id": 2, "name": "phone", "price": 1099 },
+        { "id": 3, "name": "laptop", "price": 2000 },
+        { "id": 4, "name": "phone", "price": 25 },
+        { "id": 5, "name": "phone", "price": 100 },
+    ]);
+    index.load_documents(documents).await;
+    let (_, code) = index
+        .update_settings(json!({ "attributesForFaceting": { "price": "number" } }))
+        .await;
+    assert_eq!(code, 202);
+    index.wait_update_id(1).await;
+
+    let ranges = json!({ "price": [{ "to": 100 }, { "from": 100, "to": 1000 }, { "from": 1000 }] });
+    let (response, code) = index
+        .search(json!({ "q": "phone", "facetRanges": ranges }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["facetRanges"],
+        json!({ "price": [
+            { "from": null, "to": 100.0, "count": 1 },
+            { "from": 100.0, "to": 1000.0, "count": 2 },
+            { "from": 1000.0, "to": null, "count": 1 },
+        ] })
+    );
+
+    let (response, code) = index
+        .search_get(json!({ "q": "phone", "facetRanges": ranges }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["facetRanges"]["price"][1]["count"], 2);
+}
+
+#[actix_rt::test]
+async fn invalid_range_facets() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "attributesForFaceting": { "price": "number" } }))
+        .await;
+    index.wait_update_id(0).await;
+
+    let (response, code) = index
+        .search(json!({ "facetRanges": { "name": [{ "from": 0 }] } }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+
+    let (response, code) = index
+        .search(json!({ "facetRanges": { "price": [{ "from": 10, "to": 0 }] } }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+}
+
+#[actix_rt::test]
+async fn range_facets_follow_the_hits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "attributesForFaceting": { "price": "number" } }))
+        .await;
+    index.wait_update_id(0).await;
+    let documents = json!([
+        { "id": 1, "name": "phone", "price": 499.5, "brand": "Samsung" },
+        { "id": 2, "name": "phone", "price": 1099, "brand": "Apple" },
+        { "id": 3, "name": "laptop", "price": 2000, "brand": "Phone Depot" },
+    ]);
+    index.load_documents(documents).await;
+
+    // The third document only matches outside of the attributes to search on.
+    let query = json!({
+        "q": "phone",
+        "attributesToSearchOn": ["name"],
+        "facetRanges": { "price": [{ "to": 1000 }, { "from": 1000 }] },
+        "limit": 1,
+    });
+    let (response, code) = index.search(query).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["nbHits"], 2);
+    assert_eq!(
+        response["facetRanges"],
+        json!({ "price": [
+            { "from": null, "to": 1000.0, "count": 1 },
+            { "from": 1000.0, "to": null, "count": 1 },
+        ] })
+    );
+}
@@ -0,0 +1,89 @@
+use crate::common::{Index, Server};
+use serde_json::json;
+
+async fn load_products(index: &Index<'_>) {
+    let documents = json!([
+        { "id": 1, "name": "phone", "brand": "Samsung" },
+        { "id": 2, "name": "tablet", "brand": "Samsung" },
+        { "id": 3, "name": "phone", "brand": "Apple" },
+        { "id": 4, "name": "laptop", "brand": "Sony" },
+        { "id": 5, "name": "phone", "brand": "Sony Ericsson" },
+    ]);
+    index.load_documents(documents).await;
+
+    let (_, code) = index
+        .update_settings(json!({ "attributesForFaceting": { "brand": "string" } }))
+        .await;
+    assert_eq!(code, 202);
+    index.wait_update_id(1).await;
+}
+
+fn facet_hits(response: &serde_json::Value) -> Vec<(String, u64)> {
+    response["facetHits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| {
+            let value = hit["value"].as_str().unwrap().to_lowercase();
+            (value, hit["count"].as_u64().unwrap())
+        })
+        .collect()
+}
+
+#[actix_rt::test]
+async fn facet_search_with_prefix_and_typo() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_products(&index).await;
+
+    let (response, code) = index
+        .facet_search(json!({ "facetName": "brand", "facetQuery": "sams" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(facet_hits(&response), vec![("samsung".to_string(), 2)]);
+    assert_eq!(response["facetQuery"], "sams");
+
+    let (response, code) = index
+        .facet_search(json!({ "facetName": "brand", "facetQuery": "sonny eric" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        facet_hits(&response),
+        vec![("sony ericsson".to_string(), 1)]
+    );
+}
+
+#[actix_rt::test]
+async fn facet_search_among_search_hits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_products(&index).await;
+
+    let (response, code) = index
+        .facet_search(json!({ "facetName": "brand", "facetQuery": "so", "q": "phone" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        facet_hits(&response),
+        vec![("sony ericsson".to_string(), 1)]
+    );
+
+    // Without facet query, all the values are returned, the most frequent first.
+    let (response, code) = index
+        .facet_search(json!({ "facetName": "brand", "q": "phone", "limit": 1 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(facet_hits(&response).len(), 1);
+}
+
+#[actix_rt::test]
+async fn facet_search_on_attribute_not_faceted() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_products(&index).await;
+
+    let (response, code) = index
+        .facet_search(json!({ "facetName": "name", "facetQuery": "phone" }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+}
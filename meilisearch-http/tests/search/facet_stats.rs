@@ -0,0 +1,71 @@
+use crate::common::Server;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn search_returns_numeric_facet_stats() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "name": "phone", "price": 499.5, "brand": "Samsung" },
+        { "id": 2, "name": "phone", "price": 1099, "brand": "Apple" },
+        { "id": 3, "name": "laptop", "price": 2000, "brand": "Apple" },
+        { "id": 4, "name": "phone", "price": 25, "brand": "Nokia" },
+    ]);
+    index.load_documents(documents).await;
+    let (_, code) = index
+        .update_settings(json!({
+            "attributesForFaceting": { "price": "number", "brand": "string" }
+        }))
+        .await;
+    assert_eq!(code, 202);
+    index.wait_update_id(1).await;
+
+    let (response, code) = index
+        .search(json!({ "q": "phone", "facetDistributions": ["price", "brand"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["facetStats"]["price"]["min"], 25.0);
+    assert_eq!(response["facetStats"]["price"]["max"], 1099.0);
+    // The facets without numeric values have no stats.
+    assert!(response["facetStats"].get("brand").is_none());
+
+    let (response, code) = index.search(json!({ "q": "phone" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(response.get("facetStats").is_none());
+}
+
+#[actix_rt::test]
+async fn facet_stats_follow_the_hits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "attributesForFaceting": { "price": "number" } }))
+        .await;
+    index.wait_update_id(0).await;
+    let documents = json!([
+        { "id": 1, "name": "phone", "price": 499.5, "brand": "Samsung" },
+        { "id": 2, "name": "phone", "price": 1099, "brand": "Apple" },
+        { "id": 3, "name": "laptop", "price": 2000, "brand": "Phone Depot" },
+    ]);
+    index.load_documents(documents).await;
+
+    // The third document only matches outside of the attributes to search on.
+    let query = json!({
+        "q": "phone",
+        "attributesToSearchOn": ["name"],
+        "facetDistributions": ["price"],
+        "limit": 1,
+    });
+    let (response, code) = index.search(query).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["nbHits"], 2);
+    assert_eq!(response["facetStats"]["price"]["min"], 499.5);
+    assert_eq!(response["facetStats"]["price"]["max"], 1099.0);
+    assert_eq!(
+        response["facetDistributions"]["price"]
+            .as_object()
+            .unwrap()
+            .len(),
+        2
+    );
+}
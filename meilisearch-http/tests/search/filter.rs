@@ -0,0 +1,323 @@
+use crate::common::{sorted_hits_ids, Index, Server};
+use crate::test_post_get_search;
+use serde_json::json;
+
+async fn load_users(index: &Index<'_>) {
+    index
+        .update_settings(json!({ "attributesForFaceting": { "user": "string" } }))
+        .await;
+    index.wait_update_id(0).await;
+
+    let documents = json!([
+        { "id": 1, "user": { "name": "kero", "age": 22, "address": { "city": "Paris" } } },
+        { "id": 2, "user": { "name": "tamo", "age": 34, "address": { "city": "Lyon" } } },
+        { "id": 3, "user": { "name": "many", "age": 41, "address": { "city": "Paris" } } },
+    ]);
+    index.load_documents(documents).await;
+}
+
+#[actix_rt::test]
+async fn filter_on_nested_fields() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_users(&index).await;
+
+    test_post_get_search!(
+        index,
+        json!({ "filter": "user.address.city = \"Paris\"" }),
+        |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(sorted_hits_ids(&response), vec![1, 3]);
+            assert_eq!(response["nbHits"], 2);
+        }
+    );
+
+    let (response, code) = index
+        .search(json!({ "filter": "user.address.city = paris AND NOT user.age > 40" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1]);
+
+    let (response, code) = index
+        .search(json!({ "filter": "user.age 30 TO 50 OR user.name = kero" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2, 3]);
+}
+
+#[actix_rt::test]
+async fn paginate_filtered_hits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_users(&index).await;
+
+    test_post_get_search!(
+        index,
+        json!({ "filter": "user.address.city = paris", "offset": 1, "limit": 1 }),
+        |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(sorted_hits_ids(&response), vec![3]);
+            assert_eq!(response["nbHits"], 2);
+        }
+    );
+}
+
+#[actix_rt::test]
+async fn filter_with_in() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_users(&index).await;
+
+    test_post_get_search!(
+        index,
+        json!({ "filter": "user.name IN [kero, \"many\", unknown]" }),
+        |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(sorted_hits_ids(&response), vec![1, 3]);
+        }
+    );
+
+    let (response, code) = index
+        .search(json!({ "filter": "user.age NOT IN [22, 41] OR user.address.city IN []" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![2]);
+}
+
+#[actix_rt::test]
+async fn filter_on_missing_and_empty_values() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "attributesForFaceting": { "price": "number", "tags": "string" } }))
+        .await;
+    index.wait_update_id(0).await;
+    let documents = json!([
+        { "id": 1, "price": 12.5, "tags": ["new"] },
+        { "id": 2, "price": null, "tags": [] },
+        { "id": 3 },
+    ]);
+    index.load_documents(documents).await;
+
+    test_post_get_search!(
+        index,
+        json!({ "filter": "price NOT EXISTS" }),
+        |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(sorted_hits_ids(&response), vec![3]);
+        }
+    );
+
+    let (response, code) = index.search(json!({ "filter": "price IS NULL" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![2]);
+
+    let (response, code) = index
+        .search(json!({ "filter": "price EXISTS AND price IS NOT NULL" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1]);
+
+    let (response, code) = index.search(json!({ "filter": "tags IS EMPTY" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![2]);
+}
+
+#[actix_rt::test]
+async fn filter_on_all_the_values() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let facets = json!({
+        "user": "string",
+        "tags": "string",
+        "price": "number",
+        "description": "string",
+        "publishedAt": "string",
+        "unknown": "string",
+    });
+    index
+        .update_settings(json!({ "attributesForFaceting": facets }))
+        .await;
+    index.wait_update_id(0).await;
+    let documents = json!([
+        {
+            "id": 1,
+            "user": { "address": { "city": "Paris" }, "age": 32 },
+            "tags": ["a", "b"],
+            "price": null,
+            "description": "",
+            "publishedAt": "2021-03-01T12:00:00+01:00",
+        },
+        { "id": 2, "tags": "c", "price": 10 },
+    ]);
+    index.load_documents(documents).await;
+
+    let matching = [
+        "user.address.city = paris",
+        "user.age > 30 AND user.age <= 32",
+        "tags = b",
+        "user = paris",
+        "user.address.city IN [Lyon, PARIS]",
+        "user.age IN [32.0, 18]",
+        "NOT tags IN [c] AND user.age NOT IN [31]",
+        "user EXISTS AND price EXISTS AND unknown NOT EXISTS AND price IS NULL",
+        "description IS EMPTY AND tags IS NOT EMPTY",
+        "publishedAt >= \"2021-01-01T00:00:00Z\"",
+        "publishedAt 2021-03-01T00:00:00Z TO 2021-03-01T11:00:00Z",
+        "publishedAt = 2021-03-01T11:00:00Z",
+        "publishedAt IN [2021-03-01T11:00:00Z]",
+    ];
+    for filter in matching.iter() {
+        let (response, code) = index.search(json!({ "filter": filter })).await;
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(sorted_hits_ids(&response), vec![1], "{}", filter);
+    }
+
+    let not_matching = [
+        "tags != b AND tags != c",
+        "user.address.city = Lyon OR user.age 0 TO 10",
+        "tags IN []",
+        "tags NOT IN [c, a]",
+        "tags IS EMPTY OR unknown IS EMPTY OR unknown IS NULL",
+        "publishedAt > 2021-03-01T11:00:00Z",
+    ];
+    for filter in not_matching.iter() {
+        let (response, code) = index.search(json!({ "filter": filter })).await;
+        assert_eq!(code, 200, "{}", response);
+        assert!(sorted_hits_ids(&response).is_empty(), "{}", filter);
+    }
+}
+
+#[actix_rt::test]
+async fn filter_follows_the_updates() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "attributesForFaceting": { "price": "number" } }))
+        .await;
+    index.wait_update_id(0).await;
+    let documents = json!([
+        { "id": 1, "price": 12.5 },
+        { "id": 2, "price": null },
+        { "id": 3, "tags": [] },
+    ]);
+    index.load_documents(documents).await;
+
+    // The replaced documents are indexed again.
+    index
+        .load_documents(json!([{ "id": 1 }, { "id": 3, "price": 3, "tags": [] }]))
+        .await;
+    let (response, code) = index.search(json!({ "filter": "price EXISTS" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![2, 3]);
+
+    // The deleted documents are removed.
+    let (response, code) = index.delete_document(2).await;
+    assert_eq!(code, 200, "{}", response);
+    index.wait_update_id(3).await;
+    let (response, code) = index.search(json!({ "filter": "price NOT EXISTS" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1]);
+
+    // The new filterable fields are indexed.
+    index
+        .update_settings(json!({ "attributesForFaceting": { "tags": "string" } }))
+        .await;
+    index.wait_update_id(4).await;
+    let (response, code) = index.search(json!({ "filter": "tags IS EMPTY" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![3]);
+
+    // All the documents are removed.
+    let (response, code) = index.clear_all_documents().await;
+    assert_eq!(code, 200, "{}", response);
+    index.wait_update_id(5).await;
+    index.load_documents(json!([{ "id": 4 }])).await;
+    let (response, code) = index.search(json!({ "filter": "tags NOT EXISTS" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![4]);
+}
+
+#[actix_rt::test]
+async fn filter_on_booleans_ignores_the_case() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "attributesForFaceting": { "available": "string" } }))
+        .await;
+    index.wait_update_id(0).await;
+    let documents = json!([
+        { "id": 1, "available": true },
+        { "id": 2, "available": false },
+    ]);
+    index.load_documents(documents).await;
+
+    let filters = [
+        "available = TRUE",
+        "available = True",
+        "available IN [TRUE, maybe]",
+        "available != False",
+    ];
+    for filter in filters.iter() {
+        let (response, code) = index.search(json!({ "filter": filter })).await;
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(sorted_hits_ids(&response), vec![1], "{}", filter);
+    }
+}
+
+#[actix_rt::test]
+async fn filter_on_dates() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "attributesForFaceting": { "publishedAt": "string" } }))
+        .await;
+    index.wait_update_id(0).await;
+    let documents = json!([
+        { "id": 1, "publishedAt": "2020-12-31T23:59:59Z" },
+        { "id": 2, "publishedAt": "2021-01-01T00:30:00+01:00" },
+        { "id": 3, "publishedAt": "2021-06-15T08:00:00Z" },
+    ]);
+    index.load_documents(documents).await;
+
+    test_post_get_search!(
+        index,
+        json!({ "filter": "publishedAt >= \"2021-01-01T00:00:00Z\"" }),
+        |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(sorted_hits_ids(&response), vec![3]);
+        }
+    );
+
+    let (response, code) = index
+        .search(json!({ "filter": "publishedAt 2020-12-31T00:00:00Z TO 2021-01-01T00:00:00Z" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2]);
+
+    // The dates can be compared with timestamps.
+    let (response, code) = index
+        .search(json!({ "filter": "publishedAt < 1609459200" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2]);
+}
+
+#[actix_rt::test]
+async fn invalid_filter() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_users(&index).await;
+
+    let (response, code) = index.search(json!({ "filter": "id = 1" })).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_filter");
+
+    let (response, code) = index.search(json!({ "filter": "user.age > young" })).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_filter");
+
+    let (response, code) = index.search_get(json!({ "filter": "user.age >" })).await;
+    assert_eq!(code, 400, "{}", response);
+    assert_eq!(response["code"], "invalid_filter");
+}
@@ -0,0 +1,53 @@
+use crate::common::Server;
+use crate::test_post_get_search;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn search_get_and_post_return_the_same_hits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    test_post_get_search!(
+        index,
+        json!({
+            "limit": 3,
+            "offset": 2,
+            "attributesToRetrieve": ["id", "name"],
+            "matches": false,
+        }),
+        |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            let hits = response["hits"].as_array().unwrap();
+            assert_eq!(hits.len(), 3);
+            assert_eq!(hits[0].as_object().unwrap().len(), 2);
+            assert_eq!(response["offset"], 2);
+        }
+    );
+}
+
+#[actix_rt::test]
+async fn search_get_comma_separated_attributes() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let url = format!("/indexes/{}/search?attributesToRetrieve=id,age&limit=1", index.uid);
+    let (response, code) = server.service.get(url).await;
+    assert_eq!(code, 200, "{}", response);
+    let hit = response["hits"][0].as_object().unwrap();
+    assert_eq!(hit.len(), 2);
+    assert!(hit.contains_key("age"));
+}
+
+#[actix_rt::test]
+async fn search_get_invalid_array_param() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    // `["id"` url-encoded: the JSON array is never closed.
+    let url = format!("/indexes/{}/search?attributesToRetrieve=%5B%22id%22", index.uid);
+    let (_response, code) = server.service.get(url).await;
+    assert_eq!(code, 400);
+}
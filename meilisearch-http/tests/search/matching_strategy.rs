@@ -0,0 +1,61 @@
+use crate::common::Server;
+use crate::test_post_get_search;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn search_with_matching_strategy() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "the red planet" },
+        { "id": 2, "title": "red dwarf" },
+        { "id": 3, "title": "blue planet" },
+    ]);
+    index.load_documents(documents).await;
+
+    let query = json!({ "q": "red planet", "matchingStrategy": "all" });
+    test_post_get_search!(index, query, |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        let hits = response["hits"].as_array().unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0]["id"], 1);
+        assert_eq!(response["nbHits"], 1);
+    });
+
+    // The last words are dropped by default.
+    let (response, code) = index
+        .search(json!({ "q": "red planet", "matchingStrategy": "last" }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(response["hits"].as_array().unwrap().len() > 1);
+
+    let (response, code) = index
+        .search(json!({ "q": "red planet", "matchingStrategy": "any" }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+}
+
+#[actix_rt::test]
+async fn paginate_hits_matching_all_words() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents: Vec<_> = (0..20)
+        .map(|id| {
+            if id % 2 == 0 {
+                json!({ "id": id, "title": "red planet" })
+            } else {
+                json!({ "id": id, "title": "red dwarf" })
+            }
+        })
+        .collect();
+    index.load_documents(json!(documents)).await;
+
+    let query = json!({ "q": "red planet", "matchingStrategy": "all", "offset": 5, "limit": 10 });
+    test_post_get_search!(index, query, |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        let hits = response["hits"].as_array().unwrap();
+        assert_eq!(hits.len(), 5);
+        assert!(hits.iter().all(|hit| hit["title"] == "red planet"));
+        assert_eq!(response["nbHits"], 10);
+    });
+}
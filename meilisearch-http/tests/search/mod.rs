@@ -1,2 +1,18 @@
 // This modules contains all the test concerning search. Each particular feture of the search
 // should be tested in its own module to isolate tests and keep the tests readable.
+mod attributes_to_search_on;
+mod cache;
+mod exactness;
+mod facet_ranges;
+mod facet_search;
+mod facet_stats;
+mod filter;
+mod get_route;
+mod matching_strategy;
+mod nested;
+mod pagination;
+mod phrase;
+mod prefix_search;
+mod ranking_score;
+mod timeout;
+mod vector;
@@ -0,0 +1,48 @@
+use crate::common::{GetDocumentOptions, Server};
+use serde_json::json;
+
+#[actix_rt::test]
+async fn search_nested_fields() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "Carol", "author": { "name": "Patricia Highsmith", "country": "US" } },
+        { "id": 2, "title": "Dune", "author": { "name": "Frank Herbert", "country": "US" } },
+    ]);
+    index.load_documents(documents).await;
+
+    // The fields of the nested objects are searchable, and the hits keep their nesting.
+    let (response, code) = index.search(json!({ "q": "herbert" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["hits"],
+        json!([{ "id": 2, "title": "Dune", "author": { "name": "Frank Herbert", "country": "US" } }])
+    );
+
+    let (response, code) = index.get_document(1, None).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response,
+        json!({ "id": 1, "title": "Carol", "author": { "name": "Patricia Highsmith", "country": "US" } })
+    );
+
+    // Retrieving a nested object retrieves all its fields.
+    let options = GetDocumentOptions {
+        fields: Some(vec!["author"]),
+    };
+    let (response, code) = index.get_document(1, Some(options)).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response,
+        json!({ "author": { "name": "Patricia Highsmith", "country": "US" } })
+    );
+
+    let (response, code) = index
+        .search(json!({ "q": "carol", "attributesToRetrieve": ["author.name"] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(
+        response["hits"],
+        json!([{ "author": { "name": "Patricia Highsmith" } }])
+    );
+}
@@ -0,0 +1,76 @@
+use crate::common::Server;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn search_with_page_and_hits_per_page() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .search(json!({ "page": 2, "hitsPerPage": 10 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 10);
+    assert_eq!(response["page"], 2);
+    assert_eq!(response["hitsPerPage"], 10);
+    assert_eq!(response["totalHits"], 77);
+    assert_eq!(response["totalPages"], 8);
+}
+
+#[actix_rt::test]
+async fn search_last_page_is_partial() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .search(json!({ "page": 8, "hitsPerPage": 10 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 7);
+}
+
+#[actix_rt::test]
+async fn search_without_page_has_no_page_fields() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index.search(json!({ "limit": 5 })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 5);
+    assert!(response.get("totalPages").is_none());
+    assert!(response.get("totalHits").is_none());
+}
+
+#[actix_rt::test]
+async fn search_deep_pagination_without_max_total_hits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index.search(json!({ "offset": 2000, "limit": 20 })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(response["hits"].as_array().unwrap().is_empty());
+    assert_eq!(response["nbHits"], 77);
+}
+
+#[actix_rt::test]
+async fn search_beyond_max_total_hits() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (_, code) = index
+        .update_settings(json!({ "pagination": { "maxTotalHits": 10 } }))
+        .await;
+    assert_eq!(code, 202);
+    index.wait_update_id(1).await;
+
+    let (response, code) = index.search(json!({ "offset": 5, "limit": 5 })).await;
+    assert_eq!(code, 200, "{}", response);
+
+    let (_response, code) = index.search(json!({ "offset": 5, "limit": 6 })).await;
+    assert_eq!(code, 400);
+}
@@ -0,0 +1,89 @@
+use crate::common::{sorted_hits_ids, Index, Server};
+use crate::test_post_get_search;
+use serde_json::json;
+
+async fn load_restaurants(index: &Index<'_>) {
+    let documents = json!([
+        { "id": 1, "name": "New York pizza" },
+        { "id": 2, "name": "York pizza, new recipe" },
+        { "id": 3, "name": "New York bagels" },
+        { "id": 4, "name": "Pizza from New Yorkshire" },
+    ]);
+    index.load_documents(documents).await;
+}
+
+#[actix_rt::test]
+async fn search_with_phrase() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_restaurants(&index).await;
+
+    let (response, code) = index.search(json!({ "q": "new york pizza" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(sorted_hits_ids(&response).len() > 1);
+
+    // The words of the phrase must be next to each other, and can't match as prefixes.
+    test_post_get_search!(
+        index,
+        json!({ "q": "\"new york\" pizza" }),
+        |response, code| {
+            assert_eq!(code, 200, "{}", response);
+            assert_eq!(sorted_hits_ids(&response), vec![1]);
+            assert_eq!(response["nbHits"], 1);
+        }
+    );
+
+    let (response, code) = index.search(json!({ "q": "\"new york\"" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 3]);
+
+    // The hits not containing the phrase are removed before paginating.
+    let (response, code) = index
+        .search(json!({ "q": "\"new york\"", "offset": 1, "limit": 1 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 1);
+    assert_eq!(response["nbHits"], 2);
+}
+
+#[actix_rt::test]
+async fn search_with_unclosed_quote() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_restaurants(&index).await;
+
+    let (response, code) = index.search(json!({ "q": "\"york pizza" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(sorted_hits_ids(&response).contains(&2));
+}
+
+#[actix_rt::test]
+async fn search_with_negative_term() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_restaurants(&index).await;
+
+    test_post_get_search!(index, json!({ "q": "pizza -york" }), |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(sorted_hits_ids(&response), vec![4]);
+        assert_eq!(response["nbHits"], 1);
+        // The query is returned as it was sent.
+        assert_eq!(response["query"], "pizza -york");
+    });
+
+    // Without any other word, the negative terms exclude documents from all the documents.
+    let (response, code) = index.search(json!({ "q": "-pizza" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![3]);
+
+    // The candidates containing the negative terms are removed before paginating.
+    let (response, code) = index.search(json!({ "q": "-bagels", "limit": 1 })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 1);
+    assert_eq!(response["nbHits"], 3);
+
+    // Inside a phrase, the `-` is not an operator.
+    let (response, code) = index.search(json!({ "q": "\"york -pizza\"" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2]);
+}
@@ -0,0 +1,34 @@
+use crate::common::{sorted_hits_ids, Server};
+use serde_json::json;
+
+#[actix_rt::test]
+async fn search_without_prefix_search() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "hello" },
+        { "id": 2, "title": "help" },
+        { "id": 3, "title": "hel" },
+    ]);
+    index.load_documents(documents).await;
+
+    let (response, code) = index.search(json!({ "q": "hel" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1, 2, 3]);
+
+    index
+        .update_settings(json!({ "prefixSearch": "disabled" }))
+        .await;
+    let response = index.wait_update_id(1).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+
+    // The last word only matches whole words.
+    let (response, code) = index.search(json!({ "q": "hel" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![3]);
+    assert_eq!(response["nbHits"], 1);
+
+    let (response, code) = index.search(json!({ "q": "hello" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(sorted_hits_ids(&response), vec![1]);
+}
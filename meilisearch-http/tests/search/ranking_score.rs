@@ -0,0 +1,102 @@
+use crate::common::Server;
+use crate::test_post_get_search;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn search_with_ranking_score() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "the red planet" },
+        { "id": 2, "title": "a red planel" },
+        { "id": 3, "title": "red dwarf" },
+    ]);
+    index.load_documents(documents).await;
+
+    let query = json!({ "q": "red planet ", "showRankingScore": true });
+    test_post_get_search!(index, query, |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        let hits = response["hits"].as_array().unwrap();
+        assert_eq!(hits[0]["id"], 1);
+        assert_eq!(hits[0]["_rankingScore"], 1.0);
+        let scores: Vec<_> = hits
+            .iter()
+            .map(|hit| hit["_rankingScore"].as_f64().unwrap())
+            .collect();
+        assert!(scores.iter().all(|score| (0.0..=1.0).contains(score)));
+        assert!(hits
+            .iter()
+            .all(|hit| hit.get("_rankingScoreDetails").is_none()));
+    });
+
+    let (response, code) = index
+        .search(json!({ "q": "red planet ", "showRankingScoreDetails": true }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    let hit = &response["hits"][0];
+    assert!(hit.get("_rankingScore").is_none());
+    assert_eq!(hit["_rankingScoreDetails"]["words"]["matchingWords"], 2);
+    assert_eq!(hit["_rankingScoreDetails"]["words"]["order"], 0);
+
+    let (response, code) = index.search(json!({ "q": "red planet " })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(response["hits"][0].get("_rankingScore").is_none());
+}
+
+#[actix_rt::test]
+async fn search_with_ranking_score_threshold() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "the red planet" },
+        { "id": 2, "title": "a red planel" },
+        { "id": 3, "title": "red dwarf" },
+    ]);
+    index.load_documents(documents).await;
+
+    let query = json!({ "q": "red planet ", "rankingScoreThreshold": 1.0 });
+    test_post_get_search!(index, query, |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        let hits = response["hits"].as_array().unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0]["id"], 1);
+        assert_eq!(response["nbHits"], 1);
+    });
+
+    let (response, code) = index
+        .search(json!({ "q": "red planet ", "rankingScoreThreshold": 0.0 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 3);
+
+    let (response, code) = index
+        .search(json!({ "q": "red", "rankingScoreThreshold": 1.5 }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+}
+
+#[actix_rt::test]
+async fn paginate_hits_above_ranking_score_threshold() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents: Vec<_> = (0..20)
+        .map(|id| {
+            if id % 2 == 0 {
+                json!({ "id": id, "title": "the red planet" })
+            } else {
+                json!({ "id": id, "title": "red dwarf" })
+            }
+        })
+        .collect();
+    index.load_documents(json!(documents)).await;
+
+    let query =
+        json!({ "q": "red planet ", "rankingScoreThreshold": 1.0, "offset": 5, "limit": 10 });
+    test_post_get_search!(index, query, |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        let hits = response["hits"].as_array().unwrap();
+        assert_eq!(hits.len(), 5);
+        assert!(hits.iter().all(|hit| hit["title"] == "the red planet"));
+        assert_eq!(response["nbHits"], 10);
+    });
+}
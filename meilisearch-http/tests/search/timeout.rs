@@ -0,0 +1,68 @@
+use crate::common::{default_settings, Server};
+use crate::test_post_get_search;
+use serde_json::json;
+use tempdir::TempDir;
+
+#[actix_rt::test]
+async fn search_within_time_budget_is_not_degraded() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index
+        .search(json!({ "limit": 5, "timeoutMs": 60000 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"].as_array().unwrap().len(), 5);
+    assert!(response.get("degraded").is_none());
+}
+
+#[actix_rt::test]
+async fn search_exhausting_time_budget_is_degraded() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([
+        { "id": 1, "title": "red car" },
+        { "id": 2, "title": "blue car" },
+    ]);
+    index.load_documents(documents).await;
+
+    let query = json!({
+        "q": "car",
+        "attributesToHighlight": ["title"],
+        "timeoutMs": 0,
+    });
+    test_post_get_search!(index, query, |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        assert_eq!(response["degraded"], true);
+        // The hits found by the search are returned, without highlighting.
+        let hits = response["hits"].as_array().unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits
+            .iter()
+            .all(|hit| !hit["title"].as_str().unwrap().contains("<mark>")));
+    });
+}
+
+#[actix_rt::test]
+async fn search_with_default_time_budget() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.search_timeout_ms = Some(0);
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.load_test_set().await;
+
+    let (response, code) = index.search(json!({ "facetDistributions": ["*"] })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["degraded"], true);
+    assert!(response.get("facetDistributions").is_none());
+
+    // The budget of the query overrides the default one.
+    let (response, code) = index
+        .search(json!({ "facetDistributions": ["*"], "timeoutMs": 60000 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert!(response.get("degraded").is_none());
+    assert!(response.get("facetDistributions").is_some());
+}
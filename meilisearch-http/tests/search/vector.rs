@@ -0,0 +1,99 @@
+use crate::common::{hits_ids, Index, Server};
+use crate::test_post_get_search;
+use serde_json::json;
+
+async fn load_vectors(index: &Index<'_>) {
+    let documents = json!([
+        { "id": 1, "title": "red car", "_vectors": [1.0, 0.0] },
+        { "id": 2, "title": "blue car", "_vectors": [0.8, 0.6] },
+        { "id": 3, "title": "blue boat", "_vectors": [[0.0, 1.0], [-1.0, 0.0]] },
+        { "id": 4, "title": "red boat" },
+    ]);
+    index.load_documents(documents).await;
+}
+
+#[actix_rt::test]
+async fn search_with_vector() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_vectors(&index).await;
+
+    test_post_get_search!(index, json!({ "vector": [1.0, 0.1] }), |response, code| {
+        assert_eq!(code, 200, "{}", response);
+        // The document without vector is not a hit.
+        assert_eq!(hits_ids(&response), vec![1, 2, 3]);
+        assert_eq!(response["nbHits"], 3);
+    });
+}
+
+#[actix_rt::test]
+async fn search_with_vector_and_keywords() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_vectors(&index).await;
+
+    let (response, code) = index
+        .search(json!({ "q": "blue", "vector": [1.0, 0.0], "semanticRatio": 1.0 }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(hits_ids(&response), vec![2, 3]);
+
+    let (response, code) = index
+        .search(json!({ "q": "boat", "vector": [1.0, 0.0] }))
+        .await;
+    assert_eq!(code, 200, "{}", response);
+    // The document without vector still matches the keywords.
+    let ids = hits_ids(&response);
+    assert_eq!(ids.len(), 2);
+    assert!(ids.contains(&3) && ids.contains(&4));
+}
+
+#[actix_rt::test]
+async fn search_with_invalid_vector() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    load_vectors(&index).await;
+
+    let (response, code) = index.search(json!({ "vector": [] })).await;
+    assert_eq!(code, 400, "{}", response);
+
+    let (response, code) = index
+        .search(json!({ "vector": [1.0, 0.0], "semanticRatio": 1.5 }))
+        .await;
+    assert_eq!(code, 400, "{}", response);
+
+    let (response, code) = index.search(json!({ "semanticRatio": 0.5 })).await;
+    assert_eq!(code, 400, "{}", response);
+
+    // The documents with a vector of another dimension are ignored.
+    let (response, code) = index.search(json!({ "vector": [1.0, 0.0, 0.0] })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["nbHits"], 0);
+}
+
+#[actix_rt::test]
+async fn vectors_are_neither_searchable_nor_displayed() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let documents = json!([{ "id": 1, "title": "car", "_vectors": [42, 7] }]);
+    index.load_documents(documents).await;
+
+    let (response, _code) = index.search(json!({ "q": "42" })).await;
+    assert_eq!(response["nbHits"], 0, "{}", response);
+
+    let (response, _code) = index.search(json!({ "q": "car" })).await;
+    assert_eq!(response["hits"], json!([{ "id": 1, "title": "car" }]));
+    let (response, _code) = index
+        .search(json!({ "q": "car", "attributesToRetrieve": ["id", "_vectors"] }))
+        .await;
+    assert_eq!(response["hits"], json!([{ "id": 1, "_vectors": [42, 7] }]));
+
+    // The searchable attributes are still the wildcard, the new fields are searchable.
+    let (response, _code) = index.settings().await;
+    assert_eq!(response["searchableAttributes"], json!(["*"]));
+    let documents = json!([{ "id": 2, "color": "red", "_vectors": [1, 2] }]);
+    index.add_documents(documents, None).await;
+    index.wait_update_id(1).await;
+    let (response, _code) = index.search(json!({ "q": "red" })).await;
+    assert_eq!(response["nbHits"], 1, "{}", response);
+}
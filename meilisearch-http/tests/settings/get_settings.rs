@@ -5,7 +5,7 @@ use serde_json::json;
 async fn get_settings_unexisting_index() {
     let server = Server::new().await;
     let (_response, code) = server.index("test").settings().await;
-    assert_eq!(code, 400)
+    assert_eq!(code, 404)
 }
 
 #[actix_rt::test]
@@ -16,7 +16,7 @@ async fn get_settings() {
     let (response, code) = index.settings().await;
     assert_eq!(code, 200);
     let settings = response.as_object().unwrap();
-    assert_eq!(settings.keys().len(), 4);
+    assert_eq!(settings.keys().len(), 13);
     assert_eq!(settings["displayedAttributes"], json!(["*"]));
     assert_eq!(settings["searchableAttributes"], json!(["*"]));
     assert_eq!(settings["attributesForFaceting"], json!({}));
@@ -31,6 +31,15 @@ async fn get_settings() {
             "exactness"
         ])
     );
+    assert_eq!(settings["pagination"], json!({ "maxTotalHits": null }));
+    assert_eq!(settings["languages"], json!([]));
+    assert_eq!(settings["separatorTokens"], json!([]));
+    assert_eq!(settings["nonSeparatorTokens"], json!([]));
+    assert_eq!(settings["dictionary"], json!([]));
+    assert_eq!(settings["exactWords"], json!([]));
+    assert_eq!(settings["exactAttributes"], json!([]));
+    assert_eq!(settings["proximityPrecision"], json!("byWord"));
+    assert_eq!(settings["prefixSearch"], json!("indexingTime"));
 }
 
 #[actix_rt::test]
@@ -70,7 +79,7 @@ async fn delete_settings_unexisting_index() {
     let server = Server::new().await;
     let index = server.index("test");
     let (_response, code) = index.delete_settings().await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
 
 #[actix_rt::test]
@@ -130,7 +139,7 @@ macro_rules! test_setting_routes {
                         .map(|c| if c == '_' { '-' } else { c })
                         .collect::<String>());
                     let (_response, code) = server.service.get(url).await;
-                    assert_eq!(code, 400);
+                    assert_eq!(code, 404);
                 }
 
                 #[actix_rt::test]
@@ -147,6 +156,20 @@ macro_rules! test_setting_routes {
                     assert_eq!(code, 200, "{}", response);
                 }
 
+                #[actix_rt::test]
+                async fn replace_unexisting_index() {
+                    let server = Server::new().await;
+                    let url = format!("/indexes/test/settings/{}",
+                        stringify!($setting)
+                        .chars()
+                        .map(|c| if c == '_' { '-' } else { c })
+                        .collect::<String>());
+                    let (response, code) = server.service.put(url, serde_json::Value::Null).await;
+                    assert_eq!(code, 200, "{}", response);
+                    let (response, code) = server.index("test").get().await;
+                    assert_eq!(code, 200, "{}", response);
+                }
+
                 #[actix_rt::test]
                 async fn delete_unexisting_index() {
                     let server = Server::new().await;
@@ -156,7 +179,7 @@ macro_rules! test_setting_routes {
                         .map(|c| if c == '_' { '-' } else { c })
                         .collect::<String>());
                     let (_response, code) = server.service.delete(url).await;
-                    assert_eq!(code, 400);
+                    assert_eq!(code, 404);
                 }
             }
         )*
@@ -166,5 +189,329 @@ macro_rules! test_setting_routes {
 test_setting_routes!(
     attributes_for_faceting,
     displayed_attributes,
-    searchable_attributes
+    searchable_attributes,
+    ranking_rules,
+    languages,
+    separator_tokens,
+    non_separator_tokens,
+    dictionary,
+    exact_words,
+    exact_attributes,
+    proximity_precision,
+    prefix_search
 );
+
+#[actix_rt::test]
+async fn update_displayed_and_searchable_attributes_separately() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let url = "/indexes/test/settings/displayed-attributes";
+    let (response, code) = server.service.put(url, json!(["title"])).await;
+    assert_eq!(code, 200);
+    let displayed_update = response["updateId"].as_u64().unwrap();
+
+    let url = "/indexes/test/settings/searchable-attributes";
+    let (response, code) = server.service.put(url, json!(["description"])).await;
+    assert_eq!(code, 200);
+    let searchable_update = response["updateId"].as_u64().unwrap();
+
+    assert_ne!(displayed_update, searchable_update);
+    index.wait_update_id(searchable_update).await;
+
+    let (response, code) = server
+        .service
+        .get("/indexes/test/settings/displayed-attributes")
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!(["title"]));
+
+    let (response, code) = server
+        .service
+        .get("/indexes/test/settings/searchable-attributes")
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!(["description"]));
+
+    let (_response, code) = server
+        .service
+        .delete("/indexes/test/settings/displayed-attributes")
+        .await;
+    assert_eq!(code, 200);
+}
+
+#[actix_rt::test]
+async fn reset_single_setting() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({
+            "displayedAttributes": ["title"],
+            "searchableAttributes": ["description"],
+            "rankingRules": ["typo", "words"],
+        }))
+        .await;
+    index.wait_update_id(0).await;
+
+    for setting in &["displayed-attributes", "ranking-rules"] {
+        let url = format!("/indexes/test/settings/{}", setting);
+        let (_response, code) = server.service.delete(url).await;
+        assert_eq!(code, 200);
+    }
+    index.wait_update_id(2).await;
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["displayedAttributes"], json!(["*"]));
+    assert_eq!(response["searchableAttributes"], json!(["description"]));
+    assert_ne!(response["rankingRules"], json!(["typo", "words"]));
+}
+
+#[actix_rt::test]
+async fn settings_update_reports_changes() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    index
+        .update_settings(json!({
+            "displayedAttributes": ["title"],
+            "searchableAttributes": ["*"],
+        }))
+        .await;
+
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "processed");
+    let changes = &response["success"]["Settings"]["changes"];
+    assert_eq!(changes["displayedAttributes"]["old"], json!(["*"]));
+    assert_eq!(changes["displayedAttributes"]["new"], json!(["title"]));
+    assert!(changes.get("searchableAttributes").is_none());
+}
+
+#[actix_rt::test]
+async fn settings_update_reindexes_only_when_needed() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "kero", "body": "pom" }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
+    let settings = [
+        json!({ "displayedAttributes": ["id", "title"] }),
+        json!({ "searchableAttributes": ["title"] }),
+        // Setting the searchable attributes to their current value changes nothing.
+        json!({ "searchableAttributes": ["title"], "rankingRules": ["words", "typo"] }),
+        json!({ "attributesForFaceting": { "title": "string" } }),
+    ];
+    let reindexed = [false, true, false, true];
+    for (update_id, (settings, reindexed)) in settings.iter().zip(&reindexed).enumerate() {
+        index.update_settings(settings.clone()).await;
+        let response = index.wait_update_id(update_id as u64 + 1).await;
+        assert_eq!(response["status"], "processed", "{}", response);
+        assert_eq!(response["success"]["Settings"]["reindexed"], *reindexed);
+    }
+
+    let (response, code) = index.search(json!({ "q": "kero" })).await;
+    assert_eq!(code, 200, "{}", response);
+    assert_eq!(response["hits"], json!([{ "id": 1, "title": "kero" }]));
+}
+
+#[actix_rt::test]
+async fn update_settings_gzip_payload() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let url = "/indexes/test/settings";
+    let body = json!({ "displayedAttributes": ["title"] });
+    let (response, code) = server.service.post_encoded(url, body).await;
+    assert_eq!(code, 200);
+    index
+        .wait_update_id(response["updateId"].as_u64().unwrap())
+        .await;
+
+    let url = "/indexes/test/settings/searchable-attributes";
+    let (response, code) = server.service.put_encoded(url, json!(["title"])).await;
+    assert_eq!(code, 200);
+    index
+        .wait_update_id(response["updateId"].as_u64().unwrap())
+        .await;
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["displayedAttributes"], json!(["title"]));
+    assert_eq!(response["searchableAttributes"], json!(["title"]));
+}
+
+#[actix_rt::test]
+async fn update_setting_malformed_payload() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let url = "/indexes/test/settings/displayed-attributes";
+    let (response, code) = server.service.put_str(url, r#"["title""#).await;
+    assert_eq!(code, 400);
+    assert_eq!(response["code"], "bad_request");
+}
+
+#[actix_rt::test]
+async fn update_languages() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    // The tokenizer doesn't accept a language hint, the languages are refused instead of being
+    // ignored.
+    index
+        .update_settings(json!({ "languages": ["jpn", "eng"] }))
+        .await;
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+
+    let (response, code) = server.service.get("/indexes/test/settings/languages").await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!([]));
+
+    index.update_settings(json!({ "languages": [] })).await;
+    let response = index.wait_update_id(1).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+
+    let (_response, code) = server
+        .service
+        .delete("/indexes/test/settings/languages")
+        .await;
+    assert_eq!(code, 200);
+    let response = index.wait_update_id(2).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+    let (response, _code) = index.settings().await;
+    assert_eq!(response["languages"], json!([]));
+}
+
+#[actix_rt::test]
+async fn update_invalid_languages() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({ "languages": ["japanese"] }))
+        .await;
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+}
+
+#[actix_rt::test]
+async fn update_separator_tokens() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    // The tokenizer doesn't accept custom separators, the tokens are refused instead of being
+    // ignored.
+    index
+        .update_settings(json!({ "separatorTokens": ["|", "&"] }))
+        .await;
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+
+    index
+        .update_settings(json!({ "nonSeparatorTokens": ["#", "-"] }))
+        .await;
+    let response = index.wait_update_id(1).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+
+    index
+        .update_settings(json!({ "separatorTokens": [], "nonSeparatorTokens": null }))
+        .await;
+    let response = index.wait_update_id(2).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["separatorTokens"], json!([]));
+    assert_eq!(response["nonSeparatorTokens"], json!([]));
+}
+
+#[actix_rt::test]
+async fn update_conflicting_separator_tokens() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .update_settings(json!({
+            "separatorTokens": ["#"],
+            "nonSeparatorTokens": ["#"],
+        }))
+        .await;
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+}
+
+#[actix_rt::test]
+async fn update_dictionary() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    // The tokenizer can't be given a dictionary, the words are refused instead of being split
+    // as before.
+    index
+        .update_settings(json!({ "dictionary": ["J. R. R.", "Tolkien"] }))
+        .await;
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+
+    index.update_settings(json!({ "dictionary": [] })).await;
+    let response = index.wait_update_id(1).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+
+    let (response, code) = server
+        .service
+        .get("/indexes/test/settings/dictionary")
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response, json!([]));
+}
+
+#[actix_rt::test]
+async fn update_indexing_precision() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    // milli always measures the proximity by word, the coarser precision is refused instead of
+    // being ignored.
+    index
+        .update_settings(json!({
+            "proximityPrecision": "byAttribute",
+            "prefixSearch": "disabled",
+        }))
+        .await;
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "failed", "{}", response);
+
+    index
+        .update_settings(json!({
+            "proximityPrecision": "byWord",
+            "prefixSearch": "disabled",
+        }))
+        .await;
+    let response = index.wait_update_id(1).await;
+    assert_eq!(response["status"], "processed", "{}", response);
+
+    let (response, code) = index.settings().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["proximityPrecision"], json!("byWord"));
+    assert_eq!(response["prefixSearch"], json!("disabled"));
+
+    let (_response, code) = server
+        .service
+        .delete("/indexes/test/settings/prefix-search")
+        .await;
+    assert_eq!(code, 200);
+    index.wait_update_id(2).await;
+    let (response, _code) = index.settings().await;
+    assert_eq!(response["proximityPrecision"], json!("byWord"));
+    assert_eq!(response["prefixSearch"], json!("indexingTime"));
+}
+
+#[actix_rt::test]
+async fn update_invalid_proximity_precision() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    let (_response, code) = index
+        .update_settings(json!({ "proximityPrecision": "byLetter" }))
+        .await;
+    assert_eq!(code, 400);
+}
@@ -0,0 +1,129 @@
+use meilisearch_http::helpers::compression::ArchiveCodec;
+use meilisearch_http::Data;
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, GetAllDocumentsOptions, Server};
+
+#[actix_rt::test]
+async fn create_and_import_snapshot() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let server = Server::new_with_options(default_settings(dir.path())).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(
+            json!([{ "id": 1, "title": "foo" }, { "id": 2, "title": "bar" }]),
+            None,
+        )
+        .await;
+    index.wait_update_id(0).await;
+    server.index("empty").create(None).await;
+
+    let snapshot_path = server.service.data.create_snapshot().await.unwrap();
+    assert!(snapshot_path.exists());
+
+    let restore_dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(restore_dir.path());
+    options.import_snapshot = Some(snapshot_path);
+    let server = Server::new_with_options(options).await;
+
+    let (response, code) = server.list_indexes().await;
+    assert_eq!(code, 200);
+    assert_eq!(response.as_array().unwrap().len(), 2);
+
+    let index = server.index("test");
+    let (response, code) = index.get().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["primaryKey"], "id");
+    let (response, code) = index.get_update(0).await;
+    assert_eq!(code, 200);
+    assert_eq!(response["status"], "processed");
+    let (response, _code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(response.as_array().unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn create_and_import_uncompressed_snapshot() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.archive_compression = ArchiveCodec::None;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(json!([{ "id": 1, "title": "foo" }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
+    let snapshot_path = server.service.data.create_snapshot().await.unwrap();
+    let mut archive = tar::Archive::new(std::fs::File::open(&snapshot_path).unwrap());
+    assert!(archive.entries().unwrap().count() > 0);
+
+    let restore_dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(restore_dir.path());
+    options.import_snapshot = Some(snapshot_path);
+    let server = Server::new_with_options(options).await;
+    let (response, _code) = server
+        .index("test")
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(response, json!([{ "id": 1, "title": "foo" }]));
+}
+
+#[actix_rt::test]
+async fn create_and_import_incremental_snapshots() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.snapshot_increments = 2;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index
+        .add_documents(json!([{ "id": 1, "title": "foo" }]), None)
+        .await;
+    index.wait_update_id(0).await;
+    let snapshot_path = server.service.data.create_snapshot().await.unwrap();
+
+    index
+        .add_documents(json!([{ "id": 2, "title": "bar" }]), None)
+        .await;
+    index.wait_update_id(1).await;
+    server.index("other").create(None).await;
+    server.service.data.create_snapshot().await.unwrap();
+    let increment_path = dir.path().join("snapshots/db.snapshot.1");
+    assert!(increment_path.exists());
+
+    let restore_dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(restore_dir.path());
+    options.import_snapshot = Some(snapshot_path);
+    let server = Server::new_with_options(options).await;
+
+    let (response, _code) = server.list_indexes().await;
+    assert_eq!(response.as_array().unwrap().len(), 2);
+    let index = server.index("test");
+    let (response, code) = index.get_update(1).await;
+    assert_eq!(code, 200);
+    assert_eq!(response["status"], "processed");
+    let (response, _code) = index
+        .get_all_documents(GetAllDocumentsOptions::default())
+        .await;
+    assert_eq!(response.as_array().unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn import_snapshot_errors() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.import_snapshot = Some(dir.path().join("missing.snapshot"));
+    assert!(Data::new(options.clone()).is_err());
+
+    std::fs::create_dir_all(&options.db_path).unwrap();
+    assert!(Data::new(options.clone()).is_err());
+
+    // The database is kept, but the snapshot is still missing.
+    options.ignore_snapshot_if_db_exists = true;
+    assert!(Data::new(options).is_err());
+}
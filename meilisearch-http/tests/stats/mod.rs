@@ -1,4 +1,8 @@
-use crate::common::Server;
+use byte_unit::{Byte, ByteUnit};
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
 
 #[actix_rt::test]
 async fn get_settings_unexisting_index() {
@@ -11,6 +15,14 @@ async fn get_settings_unexisting_index() {
     assert!(version.get("pkgVersion").is_some());
 }
 
+#[actix_rt::test]
+async fn version_commit_date() {
+    let server = Server::new().await;
+    let (response, code) = server.version().await;
+    assert_eq!(code, 200);
+    assert!(response["commitDate"].is_string());
+}
+
 #[actix_rt::test]
 async fn test_healthyness() {
     let server = Server::new().await;
@@ -18,4 +30,124 @@ async fn test_healthyness() {
     let (response, status_code) = server.service.get("/health").await;
     assert_eq!(status_code, 200);
     assert_eq!(response["status"], "available");
- }
+}
+
+#[actix_rt::test]
+async fn test_liveness_and_readiness() {
+    let server = Server::new().await;
+
+    let (response, status_code) = server.service.get("/health/live").await;
+    assert_eq!(status_code, 200);
+    assert_eq!(response["status"], "available");
+
+    let (response, status_code) = server.service.get("/health/ready").await;
+    assert_eq!(status_code, 200);
+    assert_eq!(response["status"], "available");
+}
+
+#[actix_rt::test]
+async fn stats_unexisting_index() {
+    let server = Server::new().await;
+    let (_response, code) = server.index("test").stats().await;
+    assert_eq!(code, 404);
+}
+
+#[actix_rt::test]
+async fn index_stats() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 0);
+    assert_eq!(response["isIndexing"], false);
+    assert!(response["fieldsDistribution"].as_object().unwrap().is_empty());
+
+    let documents = json!([
+        {
+            "id": 1,
+            "name": "Alexey",
+        },
+        {
+            "id": 2,
+            "age": 45,
+        }
+    ]);
+    index.add_documents(documents, None).await;
+    index.wait_update_id(0).await;
+
+    let (response, code) = index.stats().await;
+    assert_eq!(code, 200);
+    assert_eq!(response["numberOfDocuments"], 2);
+    assert_eq!(response["isIndexing"], false);
+    assert_eq!(response["fieldsDistribution"]["id"], 2);
+    assert_eq!(response["fieldsDistribution"]["name"], 1);
+    assert_eq!(response["fieldsDistribution"]["age"], 1);
+}
+
+#[actix_rt::test]
+async fn global_stats() {
+    let server = Server::new().await;
+
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200);
+    assert!(response["indexes"].as_object().unwrap().is_empty());
+    assert_eq!(response["lastUpdate"], serde_json::Value::Null);
+
+    let index = server.index("test");
+    index.create(None).await;
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_update_id(0).await;
+    server.index("test2").create(None).await;
+
+    let (response, code) = server.stats().await;
+    assert_eq!(code, 200);
+    assert!(response["databaseSize"].as_u64().unwrap() > 0);
+    assert!(response["lastUpdate"].is_string());
+    assert_eq!(response["indexes"].as_object().unwrap().len(), 2);
+    assert_eq!(response["indexes"]["test"]["numberOfDocuments"], 1);
+    assert_eq!(response["indexes"]["test2"]["numberOfDocuments"], 0);
+    assert!(response["freeDiskSpace"].as_u64().unwrap() > 0);
+    let index_size = response["indexes"]["test"]["databaseSize"]
+        .as_u64()
+        .unwrap();
+    assert!(index_size > 0);
+    assert!(index_size <= response["databaseSize"].as_u64().unwrap());
+}
+
+#[actix_rt::test]
+async fn global_stats_of_some_indexes() {
+    let server = Server::new().await;
+    server.index("test").create(None).await;
+    server.index("test2").create(None).await;
+    server.index("test3").create(None).await;
+
+    let (response, code) = server.service.get("/stats?indexUids=test,test3").await;
+    assert_eq!(code, 200, "{}", response);
+    let indexes = response["indexes"].as_object().unwrap();
+    assert_eq!(indexes.len(), 2);
+    assert!(indexes.contains_key("test"));
+    assert!(indexes.contains_key("test3"));
+
+    let (response, code) = server.service.get("/stats?indexUids=test,unknown").await;
+    assert_eq!(code, 404, "{}", response);
+    assert_eq!(response["code"], "index_not_found");
+}
+
+#[actix_rt::test]
+async fn updates_are_refused_without_enough_disk_space() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.min_free_disk_space = Byte::from_unit(1.0, ByteUnit::PiB).unwrap();
+    let server = Server::new_with_options(options).await;
+
+    let index = server.index("test");
+    let (response, code) = index.add_documents(json!([{ "id": 1 }]), None).await;
+    assert_eq!(code, 507, "{}", response);
+    assert_eq!(response["code"], "not_enough_disk_space");
+
+    // The other routes still work.
+    let (_, code) = server.stats().await;
+    assert_eq!(code, 200);
+}
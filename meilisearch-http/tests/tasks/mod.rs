@@ -0,0 +1,186 @@
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
+
+#[actix_rt::test]
+async fn list_tasks_of_all_kinds() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    let (response, _code) = index.add_documents(json!([{ "id": 1 }]), None).await;
+    index
+        .wait_update_id(response["updateId"].as_u64().unwrap())
+        .await;
+    server.index("other").create(None).await;
+    server.index("other").delete().await;
+    let (response, _code) = server.create_dump().await;
+    server.wait_dump(response["uid"].as_str().unwrap()).await;
+
+    let (response, code) = server.tasks("").await;
+    assert_eq!(code, 200, "response: {}", response);
+    let types: Vec<_> = response
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["type"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "dumpCreation",
+            "indexDeletion",
+            "indexCreation",
+            "documentsAddition",
+            "indexCreation"
+        ]
+    );
+    let uids: Vec<_> = response
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["uid"].as_u64().unwrap())
+        .collect();
+    assert_eq!(uids, [4, 3, 2, 1, 0]);
+    assert!(response
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|task| task["status"] == "succeeded"));
+    assert_eq!(response[3]["indexUid"], "test");
+    assert_eq!(response[3]["details"]["updateId"], 0);
+    assert!(response[0]["indexUid"].is_null());
+}
+
+#[actix_rt::test]
+async fn filter_tasks() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    let (response, _code) = index.add_documents(json!([{ "id": 1 }]), None).await;
+    index
+        .wait_update_id(response["updateId"].as_u64().unwrap())
+        .await;
+    server.index("other").create(None).await;
+
+    let (response, code) = server.tasks("indexUids=test").await;
+    assert_eq!(code, 200, "response: {}", response);
+    assert_eq!(response.as_array().unwrap().len(), 2);
+
+    let (response, _code) = server
+        .tasks("indexUids=test,other&types=indexCreation")
+        .await;
+    assert_eq!(response.as_array().unwrap().len(), 2);
+
+    let (response, _code) = server
+        .tasks("types=documentsAddition&statuses=succeeded")
+        .await;
+    assert_eq!(response.as_array().unwrap().len(), 1);
+    assert_eq!(response[0]["uid"], 1);
+
+    let (response, _code) = server.tasks("from=1&limit=1").await;
+    assert_eq!(response.as_array().unwrap().len(), 1);
+    assert_eq!(response[0]["uid"], 1);
+
+    let (response, code) = server.tasks("types=foo").await;
+    assert_eq!(code, 400, "response: {}", response);
+    assert_eq!(response["code"], "bad_parameter");
+}
+
+#[actix_rt::test]
+async fn get_task() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    let (response, _code) = index.add_documents(json!([{ "id": 1 }]), None).await;
+    index
+        .wait_update_id(response["updateId"].as_u64().unwrap())
+        .await;
+
+    let (response, code) = server.task(0).await;
+    assert_eq!(code, 200, "response: {}", response);
+    assert_eq!(response["type"], "indexCreation");
+    let (response, code) = server.task(1).await;
+    assert_eq!(code, 200, "response: {}", response);
+    assert_eq!(response["type"], "documentsAddition");
+    assert_eq!(response["status"], "succeeded");
+    assert!(response["finishedAt"].is_string());
+
+    let (response, code) = server.task(2).await;
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "task_not_found");
+}
+
+#[actix_rt::test]
+async fn delete_finished_tasks() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    for _ in 0..2 {
+        let (response, _code) = index.add_documents(json!([{ "id": 1 }]), None).await;
+        index
+            .wait_update_id(response["updateId"].as_u64().unwrap())
+            .await;
+    }
+
+    // The most recent update of the index is kept.
+    let (response, code) = server
+        .delete_tasks("types=documentsAddition&statuses=succeeded")
+        .await;
+    assert_eq!(code, 200, "response: {}", response);
+    assert_eq!(response["deletedTasks"], json!([1]));
+    let (response, _code) = server.task(1).await;
+    assert_eq!(response["code"], "task_not_found");
+    let (response, _code) = index.get_update(0).await;
+    assert_eq!(response["code"], "update_not_found");
+
+    let (response, _code) = server.delete_tasks("beforeDate=2000-01-01T00:00:00Z").await;
+    assert_eq!(response["deletedTasks"], json!([]));
+    let (response, _code) = server.delete_tasks("types=indexCreation").await;
+    assert_eq!(response["deletedTasks"], json!([0]));
+    let (response, _code) = server.tasks("").await;
+    assert_eq!(response.as_array().unwrap().len(), 1);
+    assert_eq!(response[0]["uid"], 2);
+}
+
+#[actix_rt::test]
+async fn cancel_enqueued_tasks() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // The updates wait to be processed long enough to be canceled.
+    options.autobatch_debounce_ms = 5000;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.add_documents(json!([{ "id": 2 }]), None).await;
+
+    let (response, code) = server.cancel_tasks("indexUids=other,test").await;
+    assert_eq!(code, 200, "response: {}", response);
+    assert_eq!(response["canceledTasks"], json!([2, 1]));
+    let (response, _code) = server.task(1).await;
+    assert_eq!(response["status"], "canceled", "response: {}", response);
+    let (response, _code) = index.get_update(0).await;
+    assert_eq!(response["status"], "aborted");
+
+    // The finished tasks can't be canceled.
+    let (response, _code) = server.cancel_tasks("indexUids=test").await;
+    assert_eq!(response["canceledTasks"], json!([]));
+}
+
+#[actix_rt::test]
+async fn bulk_operations_require_a_filter() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+
+    let (response, code) = server.delete_tasks("").await;
+    assert_eq!(code, 400, "response: {}", response);
+    assert_eq!(response["code"], "bad_request");
+    let (response, code) = server.cancel_tasks("").await;
+    assert_eq!(code, 400, "response: {}", response);
+    assert_eq!(response["code"], "bad_request");
+
+    let (response, _code) = server.tasks("").await;
+    assert_eq!(response.as_array().unwrap().len(), 1);
+}
@@ -1,10 +1,17 @@
-use crate::common::Server;
+use std::num::NonZeroUsize;
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
 
 #[actix_rt::test]
 async fn get_update_unexisting_index() {
     let server = Server::new().await;
     let (_response, code) = server.index("test").get_update(0).await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
 
 #[actix_rt::test]
@@ -12,8 +19,9 @@ async fn get_unexisting_update_status() {
     let server = Server::new().await;
     let index = server.index("test");
     index.create(None).await;
-    let (_response, code) = index.get_update(0).await;
-    assert_eq!(code, 400);
+    let (response, code) = index.get_update(0).await;
+    assert_eq!(code, 404);
+    assert_eq!(response["code"], "update_not_found");
 }
 
 #[actix_rt::test]
@@ -39,7 +47,7 @@ async fn get_update_status() {
 async fn list_updates_unexisting_index() {
     let server = Server::new().await;
     let (_response, code) = server.index("test").list_updates().await;
-    assert_eq!(code, 400);
+    assert_eq!(code, 404);
 }
 
 #[actix_rt::test]
@@ -67,3 +75,262 @@ async fn list_updates() {
     assert_eq!(code, 200);
     assert_eq!(response.as_array().unwrap().len(), 1);
 }
+
+#[actix_rt::test]
+async fn updates_are_refused_after_shutdown() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index
+        .add_documents(serde_json::json!([{ "id": 1 }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
+    server.service.data.shutdown().await.unwrap();
+
+    let (response, code) = index
+        .add_documents(serde_json::json!([{ "id": 2 }]), None)
+        .await;
+    assert_eq!(code, 503);
+    assert_eq!(response["code"], "shutting_down");
+}
+
+#[actix_rt::test]
+async fn high_priority_update_status() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    let url = format!("/indexes/{}/settings?priority=high", index.uid);
+    let (response, code) = server
+        .service
+        .post(url, serde_json::json!({ "displayedAttributes": ["id"] }))
+        .await;
+    assert_eq!(code, 202);
+    assert_eq!(response["priority"], "high");
+
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "processed");
+    assert_eq!(response["priority"], "high");
+}
+
+#[actix_rt::test]
+async fn high_priority_update_is_processed_first() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // All the updates are registered before the processing starts.
+    options.autobatch_debounce_ms = 1000;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+
+    let (response, code) = index.add_documents(json!([{ "id": 1 }]), None).await;
+    assert_eq!(code, 200, "response: {}", response);
+    let (response, code) = index
+        .update_settings(json!({ "searchableAttributes": ["id"] }))
+        .await;
+    assert_eq!(code, 202, "response: {}", response);
+    let url = format!("/indexes/{}/settings?priority=high", index.uid);
+    let (response, code) = server
+        .service
+        .post(url, json!({ "displayedAttributes": ["id"] }))
+        .await;
+    assert_eq!(code, 202, "response: {}", response);
+    assert_eq!(response["updateId"], 2);
+
+    let high = index.wait_update_id(2).await;
+    assert_eq!(high["status"], "processed", "response: {}", high);
+    let high_processed: DateTime<Utc> = high["processedAt"].as_str().unwrap().parse().unwrap();
+    for update_id in 0..2 {
+        let normal = index.wait_update_id(update_id).await;
+        assert_eq!(normal["status"], "processed", "response: {}", normal);
+        let normal_started: DateTime<Utc> = normal["startedProcessingAt"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(high_processed <= normal_started);
+    }
+}
+
+#[actix_rt::test]
+async fn default_update_priority_is_normal() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+    let (response, code) = index
+        .add_documents(serde_json::json!([{ "id": 1 }]), None)
+        .await;
+    assert_eq!(code, 200);
+    assert_eq!(response["priority"], "normal");
+}
+
+#[actix_rt::test]
+async fn update_carries_request_id() {
+    use actix_web::test;
+    use meilisearch_http::create_app;
+
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(None).await;
+
+    let mut app = test::init_service(create_app!(&server.service.data, true)).await;
+    let req = test::TestRequest::post()
+        .uri("/indexes/test/documents")
+        .set_json(&serde_json::json!([{ "id": 1 }]))
+        .insert_header(("X-Request-Id", "my-request"))
+        .to_request();
+    let res = test::call_service(&mut app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("x-request-id").unwrap(), "my-request");
+
+    let response = index.wait_update_id(0).await;
+    assert_eq!(response["status"], "processed");
+    assert_eq!(response["requestId"], "my-request");
+}
+
+#[actix_rt::test]
+async fn request_id_is_generated() {
+    use actix_web::test;
+    use meilisearch_http::create_app;
+
+    let server = Server::new().await;
+    let mut app = test::init_service(create_app!(&server.service.data, true)).await;
+    let req = test::TestRequest::get().uri("/indexes/test").to_request();
+    let res = test::call_service(&mut app, req).await;
+    assert_eq!(res.status(), 404);
+    assert!(!res.headers().get("x-request-id").unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn stream_update_events() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    let events = server
+        .service
+        .data
+        .update_events("test".to_string())
+        .await
+        .unwrap();
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+
+    let statuses: Vec<_> = events
+        .take(3)
+        .map(|update| serde_json::to_value(update).unwrap()["status"].clone())
+        .collect()
+        .await;
+    assert_eq!(
+        statuses,
+        [json!("pending"), json!("processing"), json!("processed")]
+    );
+}
+
+#[actix_rt::test]
+async fn stream_update_events_unexisting_index() {
+    let server = Server::new().await;
+    let (response, code) = server.service.get("/indexes/test/updates/stream").await;
+    assert_eq!(code, 404, "response: {}", response);
+}
+
+#[actix_rt::test]
+async fn wait_for_update_status() {
+    let server = Server::new().await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+
+    let (response, code) = index
+        .get_update_waiting_for(0, "waitFor=processed&timeoutMs=10000")
+        .await;
+    assert_eq!(code, 200, "response: {}", response);
+    assert_eq!(response["status"], "processed");
+
+    // The status is returned right away once it is reached.
+    let (response, _code) = index
+        .get_update_waiting_for(0, "waitFor=processing&timeoutMs=0")
+        .await;
+    assert_eq!(response["status"], "processed");
+}
+
+#[actix_rt::test]
+async fn wait_for_update_status_timeout() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // The update waits to be processed longer than the timeout.
+    options.autobatch_debounce_ms = 5000;
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.create(Some("id")).await;
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+
+    let (response, code) = index
+        .get_update_waiting_for(0, "waitFor=processed&timeoutMs=100")
+        .await;
+    assert_eq!(code, 200, "response: {}", response);
+    assert_eq!(response["status"], "pending");
+
+    let (_response, code) = index.get_update_waiting_for(0, "waitFor=foo").await;
+    assert_eq!(code, 400);
+}
+
+#[actix_rt::test]
+async fn concurrent_updates_are_bounded() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    options.max_concurrent_updates = NonZeroUsize::new(1).unwrap();
+    let server = Server::new_with_options(options).await;
+
+    let mut updates = Vec::new();
+    for uid in &["first", "second", "third"] {
+        let index = server.index(uid);
+        index.create(None).await;
+        let documents: Vec<_> = (0..100).map(|id| json!({ "id": id })).collect();
+        let (response, code) = index.add_documents(json!(documents), Some("id")).await;
+        assert_eq!(code, 200, "response: {}", response);
+        updates.push((index, response["updateId"].as_u64().unwrap()));
+    }
+
+    let mut intervals = Vec::new();
+    for (index, update_id) in &updates {
+        let response = index.wait_update_id(*update_id).await;
+        assert_eq!(response["status"], "processed", "response: {}", response);
+        let started: DateTime<Utc> = response["startedProcessingAt"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let processed: DateTime<Utc> = response["processedAt"].as_str().unwrap().parse().unwrap();
+        intervals.push((started, processed));
+    }
+
+    // With a single update processed at a time, the updates of different indexes don't overlap.
+    intervals.sort();
+    for pair in intervals.windows(2) {
+        assert!(pair[0].1 <= pair[1].0, "overlapping updates: {:?}", pair);
+    }
+}
+
+#[actix_rt::test]
+async fn too_many_pending_updates() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let mut options = default_settings(dir.path());
+    // The updates stay pending long enough to fill the queue.
+    options.autobatch_debounce_ms = 5000;
+    options.max_pending_updates_per_index = Some(2);
+    options.max_pending_updates = Some(3);
+    let server = Server::new_with_options(options).await;
+
+    let first = server.index("first");
+    for id in 0..2 {
+        let (response, code) = first.add_documents(json!([{ "id": id }]), None).await;
+        assert_eq!(code, 200, "response: {}", response);
+    }
+    let (response, code) = first.add_documents(json!([{ "id": 2 }]), None).await;
+    assert_eq!(code, 429, "response: {}", response);
+    assert_eq!(response["code"], "too_many_pending_updates");
+
+    let second = server.index("second");
+    let (response, code) = second.add_documents(json!([{ "id": 0 }]), None).await;
+    assert_eq!(code, 200, "response: {}", response);
+    let (response, code) = second.add_documents(json!([{ "id": 1 }]), None).await;
+    assert_eq!(code, 429, "response: {}", response);
+    assert_eq!(response["code"], "too_many_pending_updates");
+}
@@ -0,0 +1,72 @@
+use std::fs::{read_dir, remove_dir_all};
+use std::path::Path;
+use std::process::{Command, Output};
+
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::common::{default_settings, Server};
+
+/// Runs the verification of the database at `db_path` in a separate process, as it would be
+/// from a restore runbook.
+fn verify(db_path: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_meilisearch"))
+        .arg("--verify")
+        .arg("--db-path")
+        .arg(db_path)
+        .output()
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn verify_sane_database() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(dir.path());
+    let db_path = options.db_path.clone();
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index
+        .add_documents(json!([{ "id": 1, "title": "kero" }]), None)
+        .await;
+    index.wait_update_id(0).await;
+
+    let output = verify(&db_path);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("1 indexes checked, 0 errors"), "{}", stdout);
+}
+
+#[actix_rt::test]
+async fn verify_missing_index_database() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let options = default_settings(dir.path());
+    let db_path = options.db_path.clone();
+    let server = Server::new_with_options(options).await;
+    let index = server.index("test");
+    index.add_documents(json!([{ "id": 1 }]), None).await;
+    index.wait_update_id(0).await;
+
+    let index_path = read_dir(db_path.join("indexes"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    remove_dir_all(index_path).unwrap();
+
+    let output = verify(&db_path);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("the database of the index test"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn verify_unexisting_database() {
+    let dir = TempDir::new("meilisearch").unwrap();
+    let output = verify(&dir.path().join("db"));
+    assert!(!output.status.success());
+}